@@ -0,0 +1,299 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::import::exceptions::ImportException;
+use crate::import::ntriples_parser::NTriplesParser;
+use crate::import::statement_encoder::{StatementEncoder, StatementTerm, STRINGS_FILE};
+use crate::import::turtle_parser::TurtleParser;
+use crate::query::object_id::{ObjectId, TermEncoding};
+use crate::query::rdf_terms::{RdfQuad, RdfTerm};
+use crate::storage::bplus_tree::BPlusTree;
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::catalog::{Catalog, DataModel};
+use crate::storage::iri_prefixes::{IriPrefixes, PrefixDiscovery};
+use crate::storage::quad_indexes::{permutation_file, QUAD_PERMUTATIONS, TRIPLE_PERMUTATIONS};
+use crate::storage::string_manager::{StringManager, STRINGS_HASH_FILE};
+
+pub type LoadError = Box<dyn Error + Send + Sync>;
+
+pub const TEMP_FOLDER: &str = "tmp_import";
+
+// Tables of the statement encoder
+const TRIPLES: usize = 0;
+const QUADS: usize = 1;
+
+// Pages of the buffer used to write the indexes
+pub const LOAD_BUFFER_PAGES: usize = 1024;
+
+// Statements kept in memory to discover the IRI prefixes before encoding them
+pub const PREFIX_SAMPLE_SIZE: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    NTriples,
+    NQuads,
+    Turtle,
+    TriG,
+}
+
+impl InputFormat {
+    pub fn from_path(path: &Path) -> Option<InputFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "nt" => Some(InputFormat::NTriples),
+            "nq" => Some(InputFormat::NQuads),
+            "ttl" => Some(InputFormat::Turtle),
+            "trig" => Some(InputFormat::TriG),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoadStatistics {
+    pub statements_read: u64,
+    // Terms stored in the dictionary, inlined terms are not counted
+    pub dictionary_terms: u64,
+    pub iri_prefixes: usize,
+    pub default_graph_triples: u64,
+    pub named_graph_quads: u64,
+}
+
+impl fmt::Display for LoadStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} statements read, {} terms in the dictionary, {} IRI prefixes, {} triples in the default graph, {} quads in named graphs",
+            self.statements_read,
+            self.dictionary_terms,
+            self.iri_prefixes,
+            self.default_graph_triples,
+            self.named_graph_quads
+        )
+    }
+}
+
+// Builds a new database from RDF files. Memory use is bounded by `memory_budget`:
+// terms are encoded by sorting their occurrences on disk (no in-memory dictionary),
+// and every permutation is produced by an external sort of the encoded statements.
+//
+// IRIs are compressed with a prefix table made of the configured prefixes and the ones
+// discovered in the first `PREFIX_SAMPLE_SIZE` statements, which wait in memory until
+// the table is ready.
+pub struct BulkLoader {
+    db_folder: PathBuf,
+    temp_dir: PathBuf,
+    // Taken when the load is finished
+    encoder: Option<StatementEncoder>,
+    statistics: LoadStatistics,
+    memory_budget: usize,
+    files: Vec<PathBuf>,
+    configured_prefixes: Vec<String>,
+    discovery: PrefixDiscovery,
+    sample: Vec<RdfQuad>,
+    // Set once the sample is complete
+    prefixes: Option<IriPrefixes>,
+}
+
+impl BulkLoader {
+    pub fn new(db_folder: &Path, memory_budget: usize) -> Result<Self, LoadError> {
+        if db_folder.join(STRINGS_FILE).exists() {
+            return Err(format!("{} already contains a database", db_folder.display()).into());
+        }
+        let temp_dir = db_folder.join(TEMP_FOLDER);
+        fs::create_dir_all(&temp_dir)?;
+
+        Ok(Self {
+            db_folder: db_folder.to_path_buf(),
+            encoder: Some(StatementEncoder::new(&temp_dir, memory_budget, 2)),
+            temp_dir,
+            statistics: LoadStatistics::default(),
+            memory_budget,
+            files: Vec::new(),
+            configured_prefixes: Vec::new(),
+            discovery: PrefixDiscovery::default(),
+            sample: Vec::new(),
+            prefixes: None,
+        })
+    }
+
+    // Prefixes that are always part of the prefix table, before the discovered ones
+    pub fn with_prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.configured_prefixes = prefixes;
+        self
+    }
+
+    // Parses a file and adds its statements. Blank nodes are local to each file.
+    pub fn load_file(&mut self, path: &Path) -> Result<u64, LoadError> {
+        let format = InputFormat::from_path(path)
+            .ok_or_else(|| format!("Unknown format for {}, expected .nt, .nq, .ttl or .trig", path.display()))?;
+        let reader = BufReader::with_capacity(1024 * 1024, File::open(path)?);
+        let file_name = path.display().to_string();
+        let base = file_iri(&fs::canonicalize(path)?);
+
+        let parser: Box<dyn Iterator<Item = Result<RdfQuad, ImportException>>> = match format {
+            InputFormat::NTriples => Box::new(NTriplesParser::ntriples(reader, &file_name)),
+            InputFormat::NQuads => Box::new(NTriplesParser::nquads(reader, &file_name)),
+            InputFormat::Turtle => Box::new(TurtleParser::turtle(reader, &file_name, Some(&base))),
+            InputFormat::TriG => Box::new(TurtleParser::trig(reader, &file_name, Some(&base))),
+        };
+
+        self.files.push(path.to_path_buf());
+        let mut count = 0;
+        for quad in parser {
+            self.add_quad(&quad?)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn add_quad(&mut self, quad: &RdfQuad) -> Result<(), LoadError> {
+        if self.encoder.is_none() {
+            return Err("The load was already finished".into());
+        }
+        self.statistics.statements_read += 1;
+        let quad = self.scope_blank_nodes(quad);
+        if self.prefixes.is_some() {
+            return self.encode_quad(&quad);
+        }
+
+        for term in [&quad.subject, &quad.predicate, &quad.object].into_iter().chain(&quad.graph) {
+            if let RdfTerm::Iri(iri) = term {
+                self.discovery.add(iri);
+            }
+        }
+        self.sample.push(quad);
+        if self.sample.len() >= PREFIX_SAMPLE_SIZE {
+            self.fix_prefixes()?;
+        }
+        Ok(())
+    }
+
+    // Builds the prefix table from the sample and encodes the statements of the sample
+    fn fix_prefixes(&mut self) -> Result<(), LoadError> {
+        let discovery = std::mem::take(&mut self.discovery);
+        self.prefixes = Some(discovery.finish(&self.configured_prefixes));
+        for quad in std::mem::take(&mut self.sample) {
+            self.encode_quad(&quad)?;
+        }
+        Ok(())
+    }
+
+    fn encode_quad(&mut self, quad: &RdfQuad) -> Result<(), LoadError> {
+        let prefixes = self.prefixes.as_ref().ok_or("The IRI prefixes are not known yet")?;
+        let mut terms: Vec<StatementTerm> =
+            [&quad.subject, &quad.predicate, &quad.object].map(|term| encode_term(term, prefixes)).into();
+        let table = match &quad.graph {
+            Some(graph) => {
+                terms.push(encode_term(graph, prefixes));
+                QUADS
+            }
+            None => TRIPLES,
+        };
+        match &mut self.encoder {
+            Some(encoder) => encoder.add(table, terms)?,
+            None => return Err("The load was already finished".into()),
+        }
+        Ok(())
+    }
+
+    // Blank nodes are local to their file, those of the second file and later get a prefix
+    fn scope_blank_nodes(&self, quad: &RdfQuad) -> RdfQuad {
+        let scope = |term: &RdfTerm| match term {
+            RdfTerm::BlankNode(label) if self.files.len() > 1 => {
+                RdfTerm::BlankNode(format!("f{}_{}", self.files.len(), label))
+            }
+            _ => term.clone(),
+        };
+        RdfQuad {
+            subject: scope(&quad.subject),
+            predicate: scope(&quad.predicate),
+            object: scope(&quad.object),
+            graph: quad.graph.as_ref().map(scope),
+        }
+    }
+
+    pub fn finish(mut self) -> Result<LoadStatistics, LoadError> {
+        if self.prefixes.is_none() {
+            self.fix_prefixes()?;
+        }
+        let prefixes = self.prefixes.take().unwrap_or_default();
+        self.statistics.iri_prefixes = prefixes.len();
+
+        let encoder = match self.encoder.take() {
+            Some(encoder) => encoder,
+            None => return Err("The load was already finished".into()),
+        };
+        let encoded = encoder.finish(&self.db_folder.join(STRINGS_FILE))?;
+        self.statistics.dictionary_terms = encoded.distinct_terms();
+
+        // Every permutation is a B+tree bulk loaded from the sorted statements
+        let buffer = Arc::new(BufferManager::new(&self.db_folder, LOAD_BUFFER_PAGES, 0, 0));
+        for (name, order) in TRIPLE_PERMUTATIONS {
+            let tuples = encoded.sorted_permutation(TRIPLES, &order, name)?;
+            self.statistics.default_graph_triples = BPlusTree::bulk_load(&buffer, &permutation_file(name), tuples)?.len();
+        }
+        for (name, order) in QUAD_PERMUTATIONS {
+            let tuples = encoded.sorted_permutation(QUADS, &order, name)?;
+            self.statistics.named_graph_quads = BPlusTree::bulk_load(&buffer, &permutation_file(name), tuples)?.len();
+        }
+        buffer.flush()?;
+
+        // Builds the hash index of the dictionary, so it is ready when the database is opened
+        StringManager::open(&buffer, 0)?.flush()?;
+
+        drop(encoded);
+        fs::remove_dir_all(&self.temp_dir)?;
+        self.catalog(prefixes).save(&self.db_folder)?;
+        Ok(self.statistics.clone())
+    }
+
+    fn catalog(&self, prefixes: IriPrefixes) -> Catalog {
+        let mut catalog = Catalog::new(DataModel::Rdf);
+        catalog.indexes = TRIPLE_PERMUTATIONS
+            .iter()
+            .map(|(name, _)| permutation_file(name))
+            .chain(QUAD_PERMUTATIONS.iter().map(|(name, _)| permutation_file(name)))
+            .chain([STRINGS_HASH_FILE.to_string()])
+            .collect();
+        catalog.counts.insert("default_graph_triples".to_string(), self.statistics.default_graph_triples);
+        catalog.counts.insert("named_graph_quads".to_string(), self.statistics.named_graph_quads);
+        catalog.counts.insert("dictionary_terms".to_string(), self.statistics.dictionary_terms);
+        catalog.prefixes = prefixes;
+        catalog.parameters.insert("memory_budget".to_string(), self.memory_budget.to_string());
+        catalog.parameters.insert("configured_prefixes".to_string(), self.configured_prefixes.len().to_string());
+        let files: Vec<String> = self.files.iter().map(|file| file.display().to_string()).collect();
+        catalog.parameters.insert("files".to_string(), files.join("\n"));
+        catalog
+    }
+}
+
+// `file://` IRI of an absolute path, used as base IRI of the documents
+fn file_iri(path: &Path) -> String {
+    let mut iri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            iri.push(byte as char);
+        } else {
+            iri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    iri
+}
+
+// ObjectId of a term, or its dictionary key when it can not be inlined
+fn encode_term(term: &RdfTerm, prefixes: &IriPrefixes) -> StatementTerm {
+    match ObjectId::encode(term, prefixes) {
+        TermEncoding::Inlined(id) => StatementTerm::Id(id.raw()),
+        TermEncoding::External { mask, key } => StatementTerm::Key { key, mask },
+    }
+}
+
+impl Drop for BulkLoader {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.temp_dir);
+    }
+}
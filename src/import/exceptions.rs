@@ -0,0 +1,44 @@
+use std::error::Error;
+use std::fmt;
+
+// Used when an input file can not be parsed. Points to the position of the error
+// so it can be found in files too big to open in an editor.
+#[derive(Debug)]
+pub struct ImportException {
+    file: String,
+    line: u64,
+    column: u64,
+    message: String,
+}
+
+impl ImportException {
+    // Constructor for ImportException, `line` and `column` start at 1
+    pub fn new(file: &str, line: u64, column: u64, message: &str) -> Self {
+        ImportException {
+            file: file.to_string(),
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    pub fn column(&self) -> u64 {
+        self.column
+    }
+}
+
+impl fmt::Display for ImportException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+impl Error for ImportException {}
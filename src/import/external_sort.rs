@@ -0,0 +1,234 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Maximum number of runs merged at once, to stay well below the open files limit
+const MAX_FAN_IN: usize = 128;
+
+const IO_BUFFER_SIZE: usize = 256 * 1024;
+
+// A record that can be sorted on disk
+pub trait SortRecord: Ord + Sized {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()>;
+
+    // Returns `None` at the end of the input
+    fn read_from(input: &mut dyn BufRead) -> io::Result<Option<Self>>;
+
+    // Approximated number of bytes the record uses in memory
+    fn memory_size(&self) -> usize;
+}
+
+// Sorts more records than fit in memory: records are buffered until `memory_budget`
+// is reached, then the buffer is sorted and written to a run file. Runs are merged
+// when the records are read back.
+pub struct ExternalSorter<T: SortRecord> {
+    temp_dir: PathBuf,
+    name: String,
+    memory_budget: usize,
+    buffer: Vec<T>,
+    buffer_size: usize,
+    runs: Vec<PathBuf>,
+    run_counter: usize,
+}
+
+impl<T: SortRecord> ExternalSorter<T> {
+    pub fn new(temp_dir: &Path, name: &str, memory_budget: usize) -> Self {
+        Self {
+            temp_dir: temp_dir.to_path_buf(),
+            name: name.to_string(),
+            memory_budget,
+            buffer: Vec::new(),
+            buffer_size: 0,
+            runs: Vec::new(),
+            run_counter: 0,
+        }
+    }
+
+    pub fn push(&mut self, record: T) -> io::Result<()> {
+        self.buffer_size += record.memory_size();
+        self.buffer.push(record);
+        if self.buffer_size >= self.memory_budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    // Returns the records in order. Duplicated records are returned only once.
+    pub fn finish(mut self) -> io::Result<SortedRecords<T>> {
+        if self.runs.is_empty() {
+            self.buffer.sort_unstable();
+            self.buffer.dedup();
+            let buffer = std::mem::take(&mut self.buffer);
+            return Ok(SortedRecords {
+                source: Source::Memory(buffer.into_iter()),
+                last: None,
+            });
+        }
+
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        while self.runs.len() > MAX_FAN_IN {
+            let group: Vec<PathBuf> = self.runs.drain(..MAX_FAN_IN).collect();
+            let merged_path = self.next_run_path();
+            let mut out = BufWriter::with_capacity(IO_BUFFER_SIZE, File::create(&merged_path)?);
+            for record in Merger::<T>::open(group)? {
+                record?.write_to(&mut out)?;
+            }
+            out.flush()?;
+            self.runs.push(merged_path);
+        }
+        let runs = std::mem::take(&mut self.runs);
+        Ok(SortedRecords {
+            source: Source::Runs(Merger::open(runs)?),
+            last: None,
+        })
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.buffer.sort_unstable();
+        self.buffer.dedup();
+        let path = self.next_run_path();
+        let mut out = BufWriter::with_capacity(IO_BUFFER_SIZE, File::create(&path)?);
+        for record in self.buffer.drain(..) {
+            record.write_to(&mut out)?;
+        }
+        out.flush()?;
+        self.runs.push(path);
+        self.buffer_size = 0;
+        Ok(())
+    }
+
+    fn next_run_path(&mut self) -> PathBuf {
+        self.run_counter += 1;
+        self.temp_dir.join(format!("{}.run{}", self.name, self.run_counter))
+    }
+}
+
+impl<T: SortRecord> Drop for ExternalSorter<T> {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+enum Source<T: SortRecord> {
+    Memory(std::vec::IntoIter<T>),
+    Runs(Merger<T>),
+}
+
+pub struct SortedRecords<T: SortRecord> {
+    source: Source<T>,
+    last: Option<T>,
+}
+
+impl<T: SortRecord + Clone> Iterator for SortedRecords<T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match &mut self.source {
+                Source::Memory(records) => return records.next().map(Ok),
+                Source::Runs(merger) => match merger.next()? {
+                    Ok(record) => record,
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+            // Runs are deduplicated, but the same record may appear in different runs
+            if self.last.as_ref() == Some(&record) {
+                continue;
+            }
+            self.last = Some(record.clone());
+            return Some(Ok(record));
+        }
+    }
+}
+
+// K-way merge of sorted run files, deletes the files once merged
+struct Merger<T: SortRecord> {
+    readers: Vec<BufReader<File>>,
+    paths: Vec<PathBuf>,
+    heap: BinaryHeap<Reverse<(T, usize)>>,
+}
+
+impl<T: SortRecord> Merger<T> {
+    fn open(paths: Vec<PathBuf>) -> io::Result<Self> {
+        let mut readers = Vec::with_capacity(paths.len());
+        let mut heap = BinaryHeap::with_capacity(paths.len());
+        for (i, path) in paths.iter().enumerate() {
+            let mut reader = BufReader::with_capacity(IO_BUFFER_SIZE, File::open(path)?);
+            if let Some(record) = T::read_from(&mut reader)? {
+                heap.push(Reverse((record, i)));
+            }
+            readers.push(reader);
+        }
+        Ok(Self { readers, paths, heap })
+    }
+}
+
+impl<T: SortRecord> Iterator for Merger<T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((record, i)) = self.heap.pop()?;
+        match T::read_from(&mut self.readers[i]) {
+            Ok(Some(next)) => self.heap.push(Reverse((next, i))),
+            Ok(None) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(record))
+    }
+}
+
+impl<T: SortRecord> Drop for Merger<T> {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Reads exactly `buffer.len()` bytes, `false` if the input ended before the first byte
+pub fn read_exact_or_eof(input: &mut dyn BufRead, buffer: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match input.read(&mut buffer[read..])? {
+            0 if read == 0 => return Ok(false),
+            0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated sort run")),
+            n => read += n,
+        }
+    }
+    Ok(true)
+}
+
+// Fixed-width tuples of ids, e.g. the triples of a permutation
+impl<const N: usize> SortRecord for [u64; N] {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        for value in self {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read_from(input: &mut dyn BufRead) -> io::Result<Option<Self>> {
+        let mut tuple = [0; N];
+        let mut bytes = [0; 8];
+        for (i, value) in tuple.iter_mut().enumerate() {
+            if !read_exact_or_eof(input, &mut bytes)? {
+                if i == 0 {
+                    return Ok(None);
+                }
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated tuple"));
+            }
+            *value = u64::from_le_bytes(bytes);
+        }
+        Ok(Some(tuple))
+    }
+
+    fn memory_size(&self) -> usize {
+        N * 8
+    }
+}
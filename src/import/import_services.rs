@@ -1,4 +1,69 @@
-pub async fn load_data_into_database() {
-    // Implement the logic to load data into the graph database
-    // TODO: Add data loading logic here
-}
\ No newline at end of file
+use std::path::PathBuf;
+
+use crate::import::bulk_loader::{BulkLoader, LoadError, LoadStatistics};
+use crate::import::quad_model_loader::{QuadModelLoader, QuadModelStatistics};
+use crate::storage::iri_prefixes::IriPrefixes;
+
+// Memory used by each external sort of the bulk load
+pub const DEFAULT_IMPORT_MEMORY_BUDGET: usize = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub db_folder: PathBuf,
+    pub files: Vec<PathBuf>,
+    pub memory_budget: usize,
+    // Text file with IRI prefixes (one per line) always used to compress IRIs
+    pub prefix_file: Option<PathBuf>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            db_folder: PathBuf::from("."),
+            files: Vec::new(),
+            memory_budget: DEFAULT_IMPORT_MEMORY_BUDGET,
+            prefix_file: None,
+        }
+    }
+}
+
+// Creates the database in `options.db_folder` from the given files.
+// Does nothing (and returns `None`) when there are no files to import.
+pub async fn load_data_into_database(options: ImportOptions) -> Result<Option<LoadStatistics>, LoadError> {
+    if options.files.is_empty() {
+        return Ok(None);
+    }
+
+    // Parsing and sorting are blocking I/O, keep them away from the async workers
+    tokio::task::spawn_blocking(move || {
+        let prefixes = match &options.prefix_file {
+            Some(path) => IriPrefixes::read_prefix_list(path)?,
+            None => Vec::new(),
+        };
+        let mut loader = BulkLoader::new(&options.db_folder, options.memory_budget)?.with_prefixes(prefixes);
+        for file in &options.files {
+            let count = loader.load_file(file)?;
+            println!("Read {} statements from {}", count, file.display());
+        }
+        loader.finish().map(Some)
+    })
+    .await?
+}
+
+// Creates a property graph database in `options.db_folder` from QuadModel text files.
+// Does nothing (and returns `None`) when there are no files to import.
+pub async fn load_quad_model_into_database(options: ImportOptions) -> Result<Option<QuadModelStatistics>, LoadError> {
+    if options.files.is_empty() {
+        return Ok(None);
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let mut loader = QuadModelLoader::new(&options.db_folder, options.memory_budget)?;
+        for file in &options.files {
+            let count = loader.load_file(file)?;
+            println!("Read {} nodes and edges from {}", count, file.display());
+        }
+        loader.finish().map(Some)
+    })
+    .await?
+}
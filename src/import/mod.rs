@@ -1 +1,9 @@
-pub mod import_services;
\ No newline at end of file
+pub mod import_services;
+pub mod exceptions;
+pub mod ntriples_parser;
+pub mod external_sort;
+pub mod bulk_loader;
+pub mod turtle_parser;
+pub mod statement_encoder;
+pub mod quad_model_parser;
+pub mod quad_model_loader;
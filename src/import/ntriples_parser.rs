@@ -0,0 +1,395 @@
+use std::io::BufRead;
+
+use crate::import::exceptions::ImportException;
+use crate::query::rdf_terms::{RdfQuad, RdfTerm};
+
+// Streaming parser for N-Triples and N-Quads. Reads one line at a time, so memory use
+// does not depend on the size of the file.
+pub struct NTriplesParser<R> {
+    reader: R,
+    file_name: String,
+    // When false, a graph label is a syntax error (N-Triples)
+    allow_graphs: bool,
+    line_number: u64,
+    line: String,
+    finished: bool,
+}
+
+impl<R: BufRead> NTriplesParser<R> {
+    pub fn ntriples(reader: R, file_name: &str) -> Self {
+        Self::new(reader, file_name, false)
+    }
+
+    pub fn nquads(reader: R, file_name: &str) -> Self {
+        Self::new(reader, file_name, true)
+    }
+
+    fn new(reader: R, file_name: &str, allow_graphs: bool) -> Self {
+        Self {
+            reader,
+            file_name: file_name.to_string(),
+            allow_graphs,
+            line_number: 0,
+            line: String::new(),
+            finished: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NTriplesParser<R> {
+    type Item = Result<RdfQuad, ImportException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            self.line.clear();
+            self.line_number += 1;
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => self.finished = true,
+                Ok(_) => {
+                    let mut cursor = Cursor::new(&self.line, &self.file_name, self.line_number);
+                    match parse_statement(&mut cursor, self.allow_graphs) {
+                        Ok(Some(quad)) => return Some(Ok(quad)),
+                        Ok(None) => continue,
+                        Err(e) => {
+                            self.finished = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(ImportException::new(&self.file_name, self.line_number, 1, &e.to_string())));
+                }
+            }
+        }
+        None
+    }
+}
+
+// Parses a line; `None` for blank and comment lines
+fn parse_statement(cursor: &mut Cursor, allow_graphs: bool) -> Result<Option<RdfQuad>, ImportException> {
+    cursor.skip_whitespace();
+    if cursor.at_end_of_line() {
+        return Ok(None);
+    }
+
+    let subject = match cursor.peek() {
+        Some(b'<') => RdfTerm::Iri(cursor.parse_iri()?),
+        Some(b'_') => RdfTerm::BlankNode(cursor.parse_blank_node_label()?),
+        _ => return Err(cursor.error("Expected an IRI or a blank node as subject")),
+    };
+    cursor.skip_whitespace();
+
+    let predicate = match cursor.peek() {
+        Some(b'<') => RdfTerm::Iri(cursor.parse_iri()?),
+        _ => return Err(cursor.error("Expected an IRI as predicate")),
+    };
+    cursor.skip_whitespace();
+
+    let object = match cursor.peek() {
+        Some(b'<') => RdfTerm::Iri(cursor.parse_iri()?),
+        Some(b'_') => RdfTerm::BlankNode(cursor.parse_blank_node_label()?),
+        Some(b'"') => cursor.parse_literal()?,
+        _ => return Err(cursor.error("Expected an IRI, a blank node or a literal as object")),
+    };
+    cursor.skip_whitespace();
+
+    let graph = match cursor.peek() {
+        Some(b'<') | Some(b'_') if !allow_graphs => {
+            return Err(cursor.error("Graph labels are only allowed in N-Quads"));
+        }
+        Some(b'<') => Some(RdfTerm::Iri(cursor.parse_iri()?)),
+        Some(b'_') => Some(RdfTerm::BlankNode(cursor.parse_blank_node_label()?)),
+        _ => None,
+    };
+    cursor.skip_whitespace();
+
+    if cursor.peek() != Some(b'.') {
+        return Err(cursor.error("Expected '.' at the end of the statement"));
+    }
+    cursor.advance(1);
+    cursor.skip_whitespace();
+    if !cursor.at_end_of_line() {
+        return Err(cursor.error("Unexpected content after '.'"));
+    }
+
+    Ok(Some(RdfQuad {
+        subject,
+        predicate,
+        object,
+        graph,
+    }))
+}
+
+// Parses a single term in N-Triples syntax, such as a key of the dictionary
+pub fn parse_term(text: &str) -> Result<RdfTerm, ImportException> {
+    let mut cursor = Cursor::new(text, "term", 1);
+    let term = match cursor.peek() {
+        Some(b'<') => RdfTerm::Iri(cursor.parse_iri()?),
+        Some(b'_') => RdfTerm::BlankNode(cursor.parse_blank_node_label()?),
+        Some(b'"') => cursor.parse_literal()?,
+        _ => return Err(cursor.error("Expected an IRI, a blank node or a literal")),
+    };
+    if !cursor.rest().is_empty() {
+        return Err(cursor.error("Unexpected content after the term"));
+    }
+    Ok(term)
+}
+
+// Position inside a line, used to parse terms and to report errors with their column
+pub struct Cursor<'a> {
+    line: &'a str,
+    position: usize,
+    file_name: &'a str,
+    line_number: u64,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(line: &'a str, file_name: &'a str, line_number: u64) -> Self {
+        Self {
+            line,
+            position: 0,
+            file_name,
+            line_number,
+        }
+    }
+
+    pub fn peek(&self) -> Option<u8> {
+        self.line.as_bytes().get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.line.as_bytes().get(self.position + offset).copied()
+    }
+
+    pub fn peek_char(&self) -> Option<char> {
+        self.line[self.position..].chars().next()
+    }
+
+    // Unparsed part of the line
+    pub fn rest(&self) -> &'a str {
+        &self.line[self.position..]
+    }
+
+    pub fn advance(&mut self, bytes: usize) {
+        self.position += bytes;
+    }
+
+    pub fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ') | Some(b'\t')) {
+            self.position += 1;
+        }
+    }
+
+    // True at the end of the line or at the start of a comment
+    pub fn at_end_of_line(&self) -> bool {
+        matches!(self.peek(), None | Some(b'\n') | Some(b'\r') | Some(b'#'))
+    }
+
+    // Column (in characters, starting at 1) of the current position
+    pub fn column(&self) -> u64 {
+        self.line[..self.position].chars().count() as u64 + 1
+    }
+
+    pub fn error(&self, message: &str) -> ImportException {
+        ImportException::new(self.file_name, self.line_number, self.column(), message)
+    }
+
+    // IRIREF: '<' ([^#x00-#x20<>"{}|^`\] | UCHAR)* '>', must be an absolute IRI
+    pub fn parse_iri(&mut self) -> Result<String, ImportException> {
+        let start = self.position;
+        self.advance(1);
+        let mut iri = String::new();
+        loop {
+            match self.peek_char() {
+                None | Some('\n') | Some('\r') => {
+                    self.position = start;
+                    return Err(self.error("Unterminated IRI"));
+                }
+                Some('>') => {
+                    self.advance(1);
+                    break;
+                }
+                Some('\\') => iri.push(self.parse_uchar()?),
+                Some(c) if c <= ' ' || "<\"{}|^`".contains(c) => {
+                    return Err(self.error(&format!("Invalid character {:?} in IRI", c)));
+                }
+                Some(c) => {
+                    iri.push(c);
+                    self.advance(c.len_utf8());
+                }
+            }
+        }
+        if !has_scheme(&iri) {
+            self.position = start;
+            return Err(self.error(&format!("Relative IRI <{}> is not allowed", iri)));
+        }
+        Ok(iri)
+    }
+
+    // BLANK_NODE_LABEL: '_:' (PN_CHARS_U | [0-9]) ((PN_CHARS | '.')* PN_CHARS)?
+    pub fn parse_blank_node_label(&mut self) -> Result<String, ImportException> {
+        if self.peek_at(1) != Some(b':') {
+            return Err(self.error("Expected '_:' to start a blank node"));
+        }
+        self.advance(2);
+        let start = self.position;
+        match self.peek_char() {
+            Some(c) if is_pn_chars_u(c) || c.is_ascii_digit() => self.advance(c.len_utf8()),
+            _ => return Err(self.error("Invalid blank node label")),
+        }
+        while let Some(c) = self.peek_char() {
+            if is_pn_chars(c) || c == '.' {
+                self.advance(c.len_utf8());
+            } else {
+                break;
+            }
+        }
+        // The label can not end with '.', it belongs to the statement
+        while self.position > start + 1 && self.line.as_bytes()[self.position - 1] == b'.' {
+            self.position -= 1;
+        }
+        Ok(self.line[start..self.position].to_string())
+    }
+
+    // STRING_LITERAL_QUOTE followed by an optional LANGTAG or '^^' IRIREF
+    pub fn parse_literal(&mut self) -> Result<RdfTerm, ImportException> {
+        let value = self.parse_quoted_string()?;
+
+        match self.peek() {
+            Some(b'@') => {
+                self.advance(1);
+                let language = self.parse_language_tag()?;
+                Ok(RdfTerm::lang_literal(&value, &language))
+            }
+            Some(b'^') if self.peek_at(1) == Some(b'^') => {
+                self.advance(2);
+                if self.peek() != Some(b'<') {
+                    return Err(self.error("Expected a datatype IRI after '^^'"));
+                }
+                let datatype = self.parse_iri()?;
+                Ok(RdfTerm::typed_literal(&value, &datatype))
+            }
+            _ => Ok(RdfTerm::simple_literal(&value)),
+        }
+    }
+
+    // STRING_LITERAL_QUOTE, the cursor is at the opening quote
+    pub fn parse_quoted_string(&mut self) -> Result<String, ImportException> {
+        let start = self.position;
+        self.advance(1);
+        let mut value = String::new();
+        loop {
+            match self.peek_char() {
+                None | Some('\n') | Some('\r') => {
+                    self.position = start;
+                    return Err(self.error("Unterminated string literal"));
+                }
+                Some('"') => {
+                    self.advance(1);
+                    return Ok(value);
+                }
+                Some('\\') => value.push(self.parse_escape()?),
+                Some(c) => {
+                    value.push(c);
+                    self.advance(c.len_utf8());
+                }
+            }
+        }
+    }
+
+    // LANGTAG: '@' [a-zA-Z]+ ('-' [a-zA-Z0-9]+)*   (the '@' was already consumed)
+    pub fn parse_language_tag(&mut self) -> Result<String, ImportException> {
+        let start = self.position;
+        while matches!(self.peek(), Some(b) if b.is_ascii_alphabetic()) {
+            self.advance(1);
+        }
+        if self.position == start {
+            return Err(self.error("Invalid language tag"));
+        }
+        while self.peek() == Some(b'-') {
+            self.advance(1);
+            let subtag_start = self.position;
+            while matches!(self.peek(), Some(b) if b.is_ascii_alphanumeric()) {
+                self.advance(1);
+            }
+            if self.position == subtag_start {
+                return Err(self.error("Invalid language tag"));
+            }
+        }
+        Ok(self.line[start..self.position].to_string())
+    }
+
+    // ECHAR or UCHAR, the cursor is at the backslash
+    pub fn parse_escape(&mut self) -> Result<char, ImportException> {
+        let escaped = match self.peek_at(1) {
+            Some(b't') => '\t',
+            Some(b'b') => '\u{8}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b'f') => '\u{c}',
+            Some(b'"') => '"',
+            Some(b'\'') => '\'',
+            Some(b'\\') => '\\',
+            Some(b'u') | Some(b'U') => return self.parse_uchar(),
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        self.advance(2);
+        Ok(escaped)
+    }
+
+    // UCHAR: '\u' HEX{4} | '\U' HEX{8}, the cursor is at the backslash
+    pub fn parse_uchar(&mut self) -> Result<char, ImportException> {
+        let digits = match self.peek_at(1) {
+            Some(b'u') => 4,
+            Some(b'U') => 8,
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        let hex = self.line.get(self.position + 2..self.position + 2 + digits).unwrap_or("");
+        if hex.len() != digits || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(self.error("Invalid unicode escape"));
+        }
+        let c = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("Invalid unicode code point"))?;
+        self.advance(2 + digits);
+        Ok(c)
+    }
+}
+
+// scheme ":" with scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+pub fn has_scheme(iri: &str) -> bool {
+    match iri.split_once(':') {
+        Some((scheme, _)) => is_scheme(scheme),
+        None => false,
+    }
+}
+
+pub fn is_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+pub fn is_pn_chars_base(c: char) -> bool {
+    matches!(c,
+        'A'..='Z' | 'a'..='z'
+        | '\u{00C0}'..='\u{00D6}' | '\u{00D8}'..='\u{00F6}' | '\u{00F8}'..='\u{02FF}'
+        | '\u{0370}'..='\u{037D}' | '\u{037F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+}
+
+pub fn is_pn_chars_u(c: char) -> bool {
+    is_pn_chars_base(c) || c == '_'
+}
+
+pub fn is_pn_chars(c: char) -> bool {
+    is_pn_chars_u(c)
+        || c == '-'
+        || c.is_ascii_digit()
+        || c == '\u{00B7}'
+        || ('\u{0300}'..='\u{036F}').contains(&c)
+        || ('\u{203F}'..='\u{2040}').contains(&c)
+}
@@ -0,0 +1,226 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::import::bulk_loader::{LoadError, LOAD_BUFFER_PAGES, TEMP_FOLDER};
+use crate::import::quad_model_parser::{Properties, QuadModelElement, QuadModelParser};
+use crate::import::statement_encoder::{StatementEncoder, STRINGS_FILE};
+use crate::storage::bplus_tree::BPlusTree;
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::catalog::{Catalog, DataModel};
+use crate::storage::quad_indexes::permutation_file;
+use crate::storage::string_manager::{StringManager, STRINGS_HASH_FILE};
+
+// Tables of the statement encoder
+const NODES: usize = 0;
+const LABELS: usize = 1;
+const PROPERTIES: usize = 2;
+const EDGES: usize = 3;
+
+// Positions in the statements of each table
+const NODE: usize = 0;
+const LABEL: usize = 1;
+const OBJECT: usize = 0;
+const KEY: usize = 1;
+const VALUE: usize = 2;
+const FROM: usize = 0;
+const TO: usize = 1;
+const TYPE: usize = 2;
+const EDGE: usize = 3;
+
+// Every node of the graph, declared or used by an edge
+pub const NODES_FILE: &str = "nodes";
+pub const NODE_LABEL_FILE: &str = "node_label";
+pub const LABEL_NODE_FILE: &str = "label_node";
+// Properties of nodes and edges
+pub const OBJECT_KEY_VALUE_FILE: &str = "object_key_value";
+pub const KEY_VALUE_OBJECT_FILE: &str = "key_value_object";
+// Edges are quads: the edge has an id, so it can have properties
+pub const FROM_TO_TYPE_EDGE_FILE: &str = "from_to_type_edge";
+pub const TO_TYPE_FROM_EDGE_FILE: &str = "to_type_from_edge";
+pub const TYPE_FROM_TO_EDGE_FILE: &str = "type_from_to_edge";
+pub const EDGE_FROM_TO_TYPE_FILE: &str = "edge_from_to_type";
+
+pub const LABEL_PERMUTATIONS: [(&str, [usize; 2]); 2] =
+    [(NODE_LABEL_FILE, [NODE, LABEL]), (LABEL_NODE_FILE, [LABEL, NODE])];
+
+pub const PROPERTY_PERMUTATIONS: [(&str, [usize; 3]); 2] = [
+    (OBJECT_KEY_VALUE_FILE, [OBJECT, KEY, VALUE]),
+    (KEY_VALUE_OBJECT_FILE, [KEY, VALUE, OBJECT]),
+];
+
+pub const EDGE_PERMUTATIONS: [(&str, [usize; 4]); 4] = [
+    (FROM_TO_TYPE_EDGE_FILE, [FROM, TO, TYPE, EDGE]),
+    (TO_TYPE_FROM_EDGE_FILE, [TO, TYPE, FROM, EDGE]),
+    (TYPE_FROM_TO_EDGE_FILE, [TYPE, FROM, TO, EDGE]),
+    (EDGE_FROM_TO_TYPE_FILE, [EDGE, FROM, TO, TYPE]),
+];
+
+// Prefix of the ids given to edges, node identifiers can not start with '_'
+pub const EDGE_ID_PREFIX: &str = "_e";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuadModelStatistics {
+    pub elements_read: u64,
+    pub distinct_terms: u64,
+    pub nodes: u64,
+    pub labels: u64,
+    pub properties: u64,
+    pub edges: u64,
+}
+
+impl fmt::Display for QuadModelStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} elements read, {} distinct terms, {} nodes, {} labels, {} properties, {} edges",
+            self.elements_read, self.distinct_terms, self.nodes, self.labels, self.properties, self.edges
+        )
+    }
+}
+
+// Builds a new property graph database from QuadModel text files. Node identifiers,
+// labels, keys and edge types are stored by name and values in the syntax of the text
+// format, in the same dictionary and with the same bounded memory use as the RDF loader.
+pub struct QuadModelLoader {
+    db_folder: PathBuf,
+    temp_dir: PathBuf,
+    // Taken when the load is finished
+    encoder: Option<StatementEncoder>,
+    statistics: QuadModelStatistics,
+    memory_budget: usize,
+    files: Vec<PathBuf>,
+}
+
+impl QuadModelLoader {
+    pub fn new(db_folder: &Path, memory_budget: usize) -> Result<Self, LoadError> {
+        if db_folder.join(STRINGS_FILE).exists() {
+            return Err(format!("{} already contains a database", db_folder.display()).into());
+        }
+        let temp_dir = db_folder.join(TEMP_FOLDER);
+        fs::create_dir_all(&temp_dir)?;
+
+        Ok(Self {
+            db_folder: db_folder.to_path_buf(),
+            encoder: Some(StatementEncoder::new(&temp_dir, memory_budget, 4)),
+            temp_dir,
+            statistics: QuadModelStatistics::default(),
+            memory_budget,
+            files: Vec::new(),
+        })
+    }
+
+    pub fn load_file(&mut self, path: &Path) -> Result<u64, LoadError> {
+        let reader = BufReader::with_capacity(1024 * 1024, File::open(path)?);
+        let parser = QuadModelParser::new(reader, &path.display().to_string());
+        self.files.push(path.to_path_buf());
+
+        let mut count = 0;
+        for element in parser {
+            self.add_element(&element?)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn add_element(&mut self, element: &QuadModelElement) -> Result<(), LoadError> {
+        let encoder = match &mut self.encoder {
+            Some(encoder) => encoder,
+            None => return Err("The load was already finished".into()),
+        };
+        match element {
+            QuadModelElement::Node(node) => {
+                encoder.add(NODES, [node.id.clone()])?;
+                for label in &node.labels {
+                    encoder.add(LABELS, [node.id.clone(), label.clone()])?;
+                }
+                add_properties(encoder, &node.id, &node.properties)?;
+            }
+            QuadModelElement::Edge(edge) => {
+                // Every edge is a new object, even when another edge has the same nodes and type
+                let edge_id = format!("{}{}", EDGE_ID_PREFIX, self.statistics.edges + 1);
+                encoder.add(NODES, [edge.from.clone()])?;
+                encoder.add(NODES, [edge.to.clone()])?;
+                encoder.add(
+                    EDGES,
+                    [edge.from.clone(), edge.to.clone(), edge.edge_type.clone(), edge_id.clone()],
+                )?;
+                add_properties(encoder, &edge_id, &edge.properties)?;
+                self.statistics.edges += 1;
+            }
+        }
+        self.statistics.elements_read += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<QuadModelStatistics, LoadError> {
+        let encoder = match self.encoder.take() {
+            Some(encoder) => encoder,
+            None => return Err("The load was already finished".into()),
+        };
+        let encoded = encoder.finish(&self.db_folder.join(STRINGS_FILE))?;
+        self.statistics.distinct_terms = encoded.distinct_terms();
+
+        // Every table is a B+tree bulk loaded from the sorted statements, as the RDF permutations
+        let buffer = Arc::new(BufferManager::new(&self.db_folder, LOAD_BUFFER_PAGES, 0, 0));
+        let tuples = encoded.sorted_permutation(NODES, &[NODE], NODES_FILE)?;
+        self.statistics.nodes = BPlusTree::bulk_load(&buffer, &permutation_file(NODES_FILE), tuples)?.len();
+        for (name, order) in LABEL_PERMUTATIONS {
+            let tuples = encoded.sorted_permutation(LABELS, &order, name)?;
+            self.statistics.labels = BPlusTree::bulk_load(&buffer, &permutation_file(name), tuples)?.len();
+        }
+        for (name, order) in PROPERTY_PERMUTATIONS {
+            let tuples = encoded.sorted_permutation(PROPERTIES, &order, name)?;
+            self.statistics.properties = BPlusTree::bulk_load(&buffer, &permutation_file(name), tuples)?.len();
+        }
+        for (name, order) in EDGE_PERMUTATIONS {
+            let tuples = encoded.sorted_permutation(EDGES, &order, name)?;
+            BPlusTree::bulk_load(&buffer, &permutation_file(name), tuples)?;
+        }
+        buffer.flush()?;
+
+        // Builds the hash index of the dictionary, so it is ready when the graph is opened
+        StringManager::open(&buffer, 0)?.flush()?;
+
+        drop(encoded);
+        fs::remove_dir_all(&self.temp_dir)?;
+        self.catalog().save(&self.db_folder)?;
+        Ok(self.statistics.clone())
+    }
+
+    fn catalog(&self) -> Catalog {
+        let mut catalog = Catalog::new(DataModel::QuadModel);
+        catalog.indexes = [NODES_FILE]
+            .into_iter()
+            .chain(LABEL_PERMUTATIONS.iter().map(|(name, _)| *name))
+            .chain(PROPERTY_PERMUTATIONS.iter().map(|(name, _)| *name))
+            .chain(EDGE_PERMUTATIONS.iter().map(|(name, _)| *name))
+            .map(permutation_file)
+            .chain([STRINGS_HASH_FILE.to_string()])
+            .collect();
+        catalog.counts.insert("nodes".to_string(), self.statistics.nodes);
+        catalog.counts.insert("labels".to_string(), self.statistics.labels);
+        catalog.counts.insert("properties".to_string(), self.statistics.properties);
+        catalog.counts.insert("edges".to_string(), self.statistics.edges);
+        catalog.counts.insert("dictionary_terms".to_string(), self.statistics.distinct_terms);
+        catalog.parameters.insert("memory_budget".to_string(), self.memory_budget.to_string());
+        let files: Vec<String> = self.files.iter().map(|file| file.display().to_string()).collect();
+        catalog.parameters.insert("files".to_string(), files.join("\n"));
+        catalog
+    }
+}
+
+fn add_properties(encoder: &mut StatementEncoder, object: &str, properties: &Properties) -> Result<(), LoadError> {
+    for (key, value) in properties {
+        encoder.add(PROPERTIES, [object.to_string(), key.clone(), value.to_string()])?;
+    }
+    Ok(())
+}
+
+impl Drop for QuadModelLoader {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.temp_dir);
+    }
+}
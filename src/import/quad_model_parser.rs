@@ -0,0 +1,290 @@
+use std::fmt;
+use std::io::BufRead;
+
+use crate::import::exceptions::ImportException;
+use crate::import::ntriples_parser::{is_pn_chars_base, Cursor};
+use crate::query::rdf_terms::write_quoted_string;
+
+// Value of a property of a node or an edge
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuadModelValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+// Writes the value in the syntax of the text format
+impl fmt::Display for QuadModelValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuadModelValue::String(value) => write_quoted_string(f, value),
+            QuadModelValue::Integer(value) => write!(f, "{}", value),
+            // The debug format always has a '.' or an exponent, so it is read back as a float
+            QuadModelValue::Float(value) => write!(f, "{:?}", value),
+            QuadModelValue::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+pub type Properties = Vec<(String, QuadModelValue)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeDeclaration {
+    pub id: String,
+    pub labels: Vec<String>,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeDeclaration {
+    pub from: String,
+    pub to: String,
+    pub edge_type: String,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuadModelElement {
+    Node(NodeDeclaration),
+    Edge(EdgeDeclaration),
+}
+
+// Streaming parser for the text format of the property graph model (QuadModel).
+// Every line declares a node, with any number of labels, or an edge, with exactly one type:
+//   (alice) :Person :Employee {name: "Alice", age: 42}
+//   (alice)->(bob) :knows {since: 2010}
+//   (carol)<-(bob) :knows
+// Nodes used by an edge do not need to be declared.
+pub struct QuadModelParser<R> {
+    reader: R,
+    file_name: String,
+    line_number: u64,
+    line: String,
+    finished: bool,
+}
+
+impl<R: BufRead> QuadModelParser<R> {
+    pub fn new(reader: R, file_name: &str) -> Self {
+        Self {
+            reader,
+            file_name: file_name.to_string(),
+            line_number: 0,
+            line: String::new(),
+            finished: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for QuadModelParser<R> {
+    type Item = Result<QuadModelElement, ImportException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            self.line.clear();
+            self.line_number += 1;
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => self.finished = true,
+                Ok(_) => {
+                    let mut cursor = Cursor::new(&self.line, &self.file_name, self.line_number);
+                    match parse_element(&mut cursor) {
+                        Ok(Some(element)) => return Some(Ok(element)),
+                        Ok(None) => continue,
+                        Err(e) => {
+                            self.finished = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(ImportException::new(&self.file_name, self.line_number, 1, &e.to_string())));
+                }
+            }
+        }
+        None
+    }
+}
+
+// Parses a line; `None` for blank and comment lines
+fn parse_element(cursor: &mut Cursor) -> Result<Option<QuadModelElement>, ImportException> {
+    cursor.skip_whitespace();
+    if cursor.at_end_of_line() {
+        return Ok(None);
+    }
+
+    let first = parse_node_reference(cursor)?;
+    cursor.skip_whitespace();
+
+    // `None` for a node declaration, otherwise whether the edge goes from `first`
+    let direction = match cursor.peek() {
+        Some(b'-') => {
+            cursor.advance(1);
+            expect(cursor, b'>')?;
+            Some(true)
+        }
+        Some(b'<') => {
+            cursor.advance(1);
+            expect(cursor, b'-')?;
+            Some(false)
+        }
+        _ => None,
+    };
+
+    let element = match direction {
+        None => {
+            let mut labels = Vec::new();
+            while cursor.peek() == Some(b':') {
+                cursor.advance(1);
+                labels.push(parse_identifier(cursor, "label")?);
+                cursor.skip_whitespace();
+            }
+            let properties = parse_optional_properties(cursor)?;
+            QuadModelElement::Node(NodeDeclaration {
+                id: first,
+                labels,
+                properties,
+            })
+        }
+        Some(outgoing) => {
+            cursor.skip_whitespace();
+            let second = parse_node_reference(cursor)?;
+            cursor.skip_whitespace();
+            if cursor.peek() != Some(b':') {
+                return Err(cursor.error("Expected the type of the edge"));
+            }
+            cursor.advance(1);
+            let edge_type = parse_identifier(cursor, "edge type")?;
+            cursor.skip_whitespace();
+            if cursor.peek() == Some(b':') {
+                return Err(cursor.error("An edge has exactly one type"));
+            }
+            let properties = parse_optional_properties(cursor)?;
+            let (from, to) = if outgoing { (first, second) } else { (second, first) };
+            QuadModelElement::Edge(EdgeDeclaration {
+                from,
+                to,
+                edge_type,
+                properties,
+            })
+        }
+    };
+
+    cursor.skip_whitespace();
+    if !cursor.at_end_of_line() {
+        return Err(cursor.error("Unexpected content at the end of the line"));
+    }
+    Ok(Some(element))
+}
+
+fn expect(cursor: &mut Cursor, expected: u8) -> Result<(), ImportException> {
+    if cursor.peek() != Some(expected) {
+        return Err(cursor.error(&format!("Expected '{}'", expected as char)));
+    }
+    cursor.advance(1);
+    Ok(())
+}
+
+// '(' identifier ')'
+fn parse_node_reference(cursor: &mut Cursor) -> Result<String, ImportException> {
+    expect(cursor, b'(')?;
+    cursor.skip_whitespace();
+    let id = parse_identifier(cursor, "node identifier")?;
+    cursor.skip_whitespace();
+    expect(cursor, b')')?;
+    Ok(id)
+}
+
+// A letter followed by letters, digits and '_'
+fn parse_identifier(cursor: &mut Cursor, what: &str) -> Result<String, ImportException> {
+    let rest = cursor.rest();
+    let length = rest
+        .char_indices()
+        .find(|&(i, c)| !(is_pn_chars_base(c) || (i > 0 && (c.is_ascii_digit() || c == '_'))))
+        .map_or(rest.len(), |(i, _)| i);
+    if length == 0 {
+        return Err(cursor.error(&format!("Expected a {}", what)));
+    }
+    cursor.advance(length);
+    Ok(rest[..length].to_string())
+}
+
+// '{' (key ':' value (',' key ':' value)*)? '}', when present
+fn parse_optional_properties(cursor: &mut Cursor) -> Result<Properties, ImportException> {
+    let mut properties = Vec::new();
+    if cursor.peek() != Some(b'{') {
+        return Ok(properties);
+    }
+    cursor.advance(1);
+    cursor.skip_whitespace();
+    if cursor.peek() == Some(b'}') {
+        cursor.advance(1);
+        return Ok(properties);
+    }
+    loop {
+        cursor.skip_whitespace();
+        let key = parse_identifier(cursor, "property key")?;
+        cursor.skip_whitespace();
+        expect(cursor, b':')?;
+        cursor.skip_whitespace();
+        properties.push((key, parse_value(cursor)?));
+        cursor.skip_whitespace();
+        match cursor.peek() {
+            Some(b',') => cursor.advance(1),
+            Some(b'}') => {
+                cursor.advance(1);
+                return Ok(properties);
+            }
+            _ => return Err(cursor.error("Expected ',' or '}'")),
+        }
+    }
+}
+
+fn parse_value(cursor: &mut Cursor) -> Result<QuadModelValue, ImportException> {
+    match cursor.peek() {
+        Some(b'"') => Ok(QuadModelValue::String(cursor.parse_quoted_string()?)),
+        Some(b) if b.is_ascii_digit() || b == b'-' || b == b'+' || b == b'.' => parse_number(cursor),
+        Some(b) if b.is_ascii_alphabetic() => {
+            let rest = cursor.rest();
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            let value = match &rest[..length] {
+                "true" => true,
+                "false" => false,
+                _ => return Err(cursor.error("Expected a string, a number, true or false")),
+            };
+            cursor.advance(length);
+            Ok(QuadModelValue::Boolean(value))
+        }
+        _ => Err(cursor.error("Expected a string, a number, true or false")),
+    }
+}
+
+// An integer, or a float when there is a '.' or an exponent
+fn parse_number(cursor: &mut Cursor) -> Result<QuadModelValue, ImportException> {
+    let rest = cursor.rest();
+    let length = rest
+        .char_indices()
+        .find(|&(i, c)| {
+            let after_exponent = i > 0 && matches!(rest.as_bytes()[i - 1], b'e' | b'E');
+            !(c.is_ascii_digit() || "eE.".contains(c) || (matches!(c, '+' | '-') && (i == 0 || after_exponent)))
+        })
+        .map_or(rest.len(), |(i, _)| i);
+    let number = &rest[..length];
+
+    let value = if number.contains(['.', 'e', 'E']) {
+        number
+            .parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .map(QuadModelValue::Float)
+    } else {
+        number.parse().ok().map(QuadModelValue::Integer)
+    };
+    match value {
+        Some(value) => {
+            cursor.advance(length);
+            Ok(value)
+        }
+        None => Err(cursor.error(&format!("Invalid number {}", number))),
+    }
+}
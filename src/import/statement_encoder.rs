@@ -0,0 +1,208 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::import::external_sort::{read_exact_or_eof, ExternalSorter, SortRecord, SortedRecords};
+
+pub use crate::storage::string_manager::{STRINGS_FILE, STRINGS_FILE_MAGIC};
+
+// Statements have at most this many terms, missing positions are encoded as 0
+pub const MAX_STATEMENT_TERMS: usize = 4;
+
+// The table of an occurrence is stored in the highest bits of its slot
+const TABLE_SHIFT: u32 = 56;
+const STATEMENT_MASK: u64 = (1 << TABLE_SHIFT) - 1;
+
+// A term of a statement: a key of the dictionary, whose id is its offset combined with
+// `mask`, or a value that already is an id (e.g. an inlined ObjectId)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatementTerm {
+    Key { key: String, mask: u64 },
+    Id(u64),
+}
+
+impl From<String> for StatementTerm {
+    fn from(key: String) -> Self {
+        StatementTerm::Key { key, mask: 0 }
+    }
+}
+
+// One occurrence of a term: `slot` is table << 56 | statement number * 4 + position
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct TermOccurrence {
+    term: String,
+    slot: u64,
+    mask: u64,
+}
+
+impl SortRecord for TermOccurrence {
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&(self.term.len() as u32).to_le_bytes())?;
+        out.write_all(self.term.as_bytes())?;
+        out.write_all(&self.slot.to_le_bytes())?;
+        out.write_all(&self.mask.to_le_bytes())
+    }
+
+    fn read_from(input: &mut dyn BufRead) -> io::Result<Option<Self>> {
+        let mut length = [0; 4];
+        if !read_exact_or_eof(input, &mut length)? {
+            return Ok(None);
+        }
+        let mut term = vec![0; u32::from_le_bytes(length) as usize];
+        input.read_exact(&mut term)?;
+        let mut slot = [0; 8];
+        input.read_exact(&mut slot)?;
+        let mut mask = [0; 8];
+        input.read_exact(&mut mask)?;
+        let term = String::from_utf8(term).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some(Self {
+            term,
+            slot: u64::from_le_bytes(slot),
+            mask: u64::from_le_bytes(mask),
+        }))
+    }
+
+    fn memory_size(&self) -> usize {
+        self.term.len() + std::mem::size_of::<Self>()
+    }
+}
+
+// Replaces the terms of the statements of a database by ids, with memory use bounded by
+// `memory_budget`: there is no in-memory dictionary, the occurrences of the terms are
+// sorted on disk instead. Statements belong to tables (e.g. triples and quads), every
+// table is encoded to its own file so its permutations can be written later.
+pub struct StatementEncoder {
+    temp_dir: PathBuf,
+    memory_budget: usize,
+    occurrences: ExternalSorter<TermOccurrence>,
+    // (slot, id) of the terms given as ids, and later of every term
+    slot_ids: ExternalSorter<[u64; 2]>,
+    // Number of statements added to each table
+    statements: Vec<u64>,
+}
+
+impl StatementEncoder {
+    pub fn new(temp_dir: &Path, memory_budget: usize, tables: usize) -> Self {
+        Self {
+            temp_dir: temp_dir.to_path_buf(),
+            memory_budget,
+            occurrences: ExternalSorter::new(temp_dir, "terms", memory_budget),
+            slot_ids: ExternalSorter::new(temp_dir, "slots", memory_budget),
+            statements: vec![0; tables],
+        }
+    }
+
+    // Adds a statement to `table`, the terms are given by their dictionary key or their id
+    pub fn add<T: Into<StatementTerm>>(&mut self, table: usize, terms: impl IntoIterator<Item = T>) -> io::Result<()> {
+        let statement = self.statements[table];
+        self.statements[table] += 1;
+        let base_slot = ((table as u64) << TABLE_SHIFT) | (statement * MAX_STATEMENT_TERMS as u64);
+        for (position, term) in terms.into_iter().enumerate() {
+            debug_assert!(position < MAX_STATEMENT_TERMS);
+            let slot = base_slot + position as u64;
+            match term.into() {
+                StatementTerm::Key { key, mask } => self.occurrences.push(TermOccurrence { term: key, slot, mask })?,
+                StatementTerm::Id(id) => self.slot_ids.push([slot, id])?,
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the dictionary to `strings_path`, assigning ids in term order, and encodes the
+    // statements of every table
+    pub fn finish(self, strings_path: &Path) -> io::Result<EncodedStatements> {
+        let StatementEncoder { temp_dir, memory_budget, occurrences, mut slot_ids, statements } = self;
+        let mut distinct_terms = 0;
+        let mut strings = BufWriter::new(File::create(strings_path)?);
+        strings.write_all(STRINGS_FILE_MAGIC)?;
+        let mut offset = STRINGS_FILE_MAGIC.len() as u64;
+        let mut last_term: Option<String> = None;
+        let mut current_id = 0;
+        for occurrence in occurrences.finish()? {
+            let occurrence = occurrence?;
+            if last_term.as_deref() != Some(occurrence.term.as_str()) {
+                current_id = offset;
+                strings.write_all(&(occurrence.term.len() as u32).to_le_bytes())?;
+                strings.write_all(occurrence.term.as_bytes())?;
+                offset += 4 + occurrence.term.len() as u64;
+                distinct_terms += 1;
+                last_term = Some(occurrence.term);
+            }
+            slot_ids.push([occurrence.slot, occurrence.mask | current_id])?;
+        }
+        strings.flush()?;
+
+        // Rebuild the statements with ids, in input order
+        let tables: Vec<PathBuf> = (0..statements.len())
+            .map(|table| temp_dir.join(format!("table{}", table)))
+            .collect();
+        let mut writers = Vec::with_capacity(tables.len());
+        for path in &tables {
+            writers.push(BufWriter::new(File::create(path)?));
+        }
+        let mut current = [0u64; MAX_STATEMENT_TERMS];
+        let mut current_statement = None;
+        for slot_id in slot_ids.finish()? {
+            let [slot, id] = slot_id?;
+            let table = (slot >> TABLE_SHIFT) as usize;
+            let statement = (slot & STATEMENT_MASK) / MAX_STATEMENT_TERMS as u64;
+            if current_statement != Some((table, statement)) {
+                if let Some((previous_table, _)) = current_statement {
+                    current.write_to(&mut writers[previous_table])?;
+                }
+                current = [0; MAX_STATEMENT_TERMS];
+                current_statement = Some((table, statement));
+            }
+            current[(slot % MAX_STATEMENT_TERMS as u64) as usize] = id;
+        }
+        if let Some((previous_table, _)) = current_statement {
+            current.write_to(&mut writers[previous_table])?;
+        }
+        for writer in &mut writers {
+            writer.flush()?;
+        }
+
+        Ok(EncodedStatements {
+            temp_dir,
+            memory_budget,
+            tables,
+            distinct_terms,
+        })
+    }
+}
+
+// The statements of every table with their terms replaced by ids
+pub struct EncodedStatements {
+    temp_dir: PathBuf,
+    memory_budget: usize,
+    tables: Vec<PathBuf>,
+    distinct_terms: u64,
+}
+
+impl EncodedStatements {
+    pub fn distinct_terms(&self) -> u64 {
+        self.distinct_terms
+    }
+
+    // The statements of `table` with their terms in the given order, sorted and without duplicates
+    pub fn sorted_permutation<const N: usize>(&self, table: usize, order: &[usize; N], name: &str) -> io::Result<SortedRecords<[u64; N]>> {
+        let mut sorter: ExternalSorter<[u64; N]> = ExternalSorter::new(&self.temp_dir, name, self.memory_budget);
+        let mut encoded = BufReader::new(File::open(&self.tables[table])?);
+        while let Some(statement) = <[u64; MAX_STATEMENT_TERMS]>::read_from(&mut encoded)? {
+            let mut tuple = [0; N];
+            for (value, &position) in tuple.iter_mut().zip(order) {
+                *value = statement[position];
+            }
+            sorter.push(tuple)?;
+        }
+        sorter.finish()
+    }
+}
+
+impl Drop for EncodedStatements {
+    fn drop(&mut self) {
+        for table in &self.tables {
+            let _ = fs::remove_file(table);
+        }
+    }
+}
@@ -0,0 +1,881 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead};
+
+use crate::import::exceptions::ImportException;
+use crate::import::ntriples_parser::{has_scheme, is_pn_chars, is_pn_chars_base, is_pn_chars_u, is_scheme};
+use crate::query::rdf_terms::{RdfQuad, RdfTerm, RDF_FIRST, RDF_NIL, RDF_REST, RDF_TYPE, XSD_PREFIX};
+
+// Prefix of the labels given to anonymous blank nodes. Labels of the document starting
+// with it get the prefix repeated, so they never clash with the generated ones.
+const GENERATED_BLANK_NODE_PREFIX: &str = "genid";
+
+// Streaming parser for Turtle 1.1 and TriG. Statements may span several lines, so the
+// input is read one line at a time and the unconsumed characters are kept until they are
+// parsed. Memory use depends on the size of a statement, not on the size of the file.
+pub struct TurtleParser<R> {
+    reader: R,
+    file_name: String,
+    // When false, graph blocks are a syntax error (Turtle)
+    allow_graphs: bool,
+    base: Option<String>,
+    prefixes: HashMap<String, String>,
+    chars: Vec<char>,
+    position: usize,
+    // Position of `chars[position]` in the file, starting at 1
+    line: u64,
+    column: u64,
+    end_of_input: bool,
+    io_error: Option<io::Error>,
+    // Graph of the block being parsed, `None` for the default graph
+    graph: Option<RdfTerm>,
+    // Inside a graph block, statements are the triples of the block until its '}'
+    in_graph_block: bool,
+    // Statements parsed but not returned yet, a statement may produce many of them
+    pending: VecDeque<RdfQuad>,
+    blank_node_counter: u64,
+    finished: bool,
+}
+
+impl<R: BufRead> TurtleParser<R> {
+    // `base` is used to resolve relative IRIs until the document declares its own base
+    pub fn turtle(reader: R, file_name: &str, base: Option<&str>) -> Self {
+        Self::new(reader, file_name, base, false)
+    }
+
+    pub fn trig(reader: R, file_name: &str, base: Option<&str>) -> Self {
+        Self::new(reader, file_name, base, true)
+    }
+
+    fn new(reader: R, file_name: &str, base: Option<&str>, allow_graphs: bool) -> Self {
+        Self {
+            reader,
+            file_name: file_name.to_string(),
+            allow_graphs,
+            base: base.map(|base| base.to_string()),
+            prefixes: HashMap::new(),
+            chars: Vec::new(),
+            position: 0,
+            line: 1,
+            column: 1,
+            end_of_input: false,
+            io_error: None,
+            graph: None,
+            in_graph_block: false,
+            pending: VecDeque::new(),
+            blank_node_counter: 0,
+            finished: false,
+        }
+    }
+
+    // Reads the next line, keeping the characters not consumed yet
+    fn read_line(&mut self) -> bool {
+        if self.end_of_input {
+            return false;
+        }
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => {
+                self.end_of_input = true;
+                false
+            }
+            Ok(_) => {
+                self.chars.drain(..self.position);
+                self.position = 0;
+                self.chars.extend(line.chars());
+                true
+            }
+            Err(e) => {
+                self.io_error = Some(e);
+                self.end_of_input = true;
+                false
+            }
+        }
+    }
+
+    fn peek_at(&mut self, offset: usize) -> Option<char> {
+        while self.position + offset >= self.chars.len() {
+            if !self.read_line() {
+                return None;
+            }
+        }
+        Some(self.chars[self.position + offset])
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn consume(&mut self, count: usize) {
+        for _ in 0..count {
+            self.bump();
+        }
+    }
+
+    fn error(&self, message: &str) -> ImportException {
+        self.error_at(self.line, self.column, message)
+    }
+
+    fn error_at(&self, line: u64, column: u64, message: &str) -> ImportException {
+        ImportException::new(&self.file_name, line, column, message)
+    }
+
+    // Skips white space and comments
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' => {
+                    self.bump();
+                }
+                '#' => while !matches!(self.bump(), None | Some('\n')) {},
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ImportException> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", expected)))
+        }
+    }
+
+    // True if the next characters are `keyword` and it is not the start of a prefixed name
+    fn at_keyword(&mut self, keyword: &str, ignore_case: bool) -> bool {
+        for (i, expected) in keyword.chars().enumerate() {
+            match self.peek_at(i) {
+                Some(c) if c == expected || (ignore_case && c.eq_ignore_ascii_case(&expected)) => {}
+                _ => return false,
+            }
+        }
+        match self.peek_at(keyword.len()) {
+            Some(c) if is_pn_chars(c) || c == ':' => false,
+            Some('.') => !matches!(self.peek_at(keyword.len() + 1), Some(c) if is_pn_chars(c) || c == ':'),
+            _ => true,
+        }
+    }
+
+    // True if after the '.' at the current position (and any '.' following it) there is
+    // a character accepted by `is_name_char`, so the '.' belongs to the name
+    fn name_continues_after_dots(&mut self, is_name_char: fn(char) -> bool) -> bool {
+        let mut offset = 1;
+        while self.peek_at(offset) == Some('.') {
+            offset += 1;
+        }
+        matches!(self.peek_at(offset), Some(c) if is_name_char(c))
+    }
+
+    fn new_blank_node(&mut self) -> RdfTerm {
+        self.blank_node_counter += 1;
+        RdfTerm::BlankNode(format!("{}{}", GENERATED_BLANK_NODE_PREFIX, self.blank_node_counter))
+    }
+
+    fn emit(&mut self, subject: RdfTerm, predicate: RdfTerm, object: RdfTerm) {
+        self.pending.push_back(RdfQuad {
+            subject,
+            predicate,
+            object,
+            graph: self.graph.clone(),
+        });
+    }
+
+    // statement: directive | triples '.' | (TriG) graph block. `false` at the end of the input
+    fn parse_statement(&mut self) -> Result<bool, ImportException> {
+        self.skip_whitespace();
+        if self.in_graph_block {
+            self.parse_graph_block_statement()?;
+            return Ok(true);
+        }
+        match self.peek() {
+            None => return Ok(false),
+            Some('@') => {
+                self.bump();
+                if self.at_keyword("prefix", false) {
+                    self.consume(6);
+                    self.parse_prefix_declaration()?;
+                } else if self.at_keyword("base", false) {
+                    self.consume(4);
+                    self.parse_base_declaration()?;
+                } else {
+                    return Err(self.error("Expected @prefix or @base"));
+                }
+                self.expect('.')?;
+            }
+            Some(_) if self.at_keyword("PREFIX", true) => {
+                self.consume(6);
+                self.parse_prefix_declaration()?;
+            }
+            Some(_) if self.at_keyword("BASE", true) => {
+                self.consume(4);
+                self.parse_base_declaration()?;
+            }
+            Some(_) if self.allow_graphs && self.at_keyword("GRAPH", true) => {
+                self.consume(5);
+                self.skip_whitespace();
+                let label = self.parse_graph_label()?;
+                self.parse_graph_block(Some(label))?;
+            }
+            Some('{') if self.allow_graphs => self.parse_graph_block(None)?,
+            Some(_) => {
+                if !self.parse_triples(true)? {
+                    self.expect('.')?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn parse_prefix_declaration(&mut self) -> Result<(), ImportException> {
+        self.skip_whitespace();
+        let prefix = self.parse_pname_ns()?;
+        self.skip_whitespace();
+        if self.peek() != Some('<') {
+            return Err(self.error("Expected an IRI after the prefix"));
+        }
+        let namespace = self.parse_iri_ref()?;
+        self.prefixes.insert(prefix, namespace);
+        Ok(())
+    }
+
+    fn parse_base_declaration(&mut self) -> Result<(), ImportException> {
+        self.skip_whitespace();
+        if self.peek() != Some('<') {
+            return Err(self.error("Expected an IRI after base"));
+        }
+        self.base = Some(self.parse_iri_ref()?);
+        Ok(())
+    }
+
+    // triples: subject predicateObjectList | blankNodePropertyList predicateObjectList?
+    // At the top level of TriG the subject may be the label of a graph block instead,
+    // returns true when a graph block was parsed.
+    fn parse_triples(&mut self, top_level: bool) -> Result<bool, ImportException> {
+        let (subject, predicates_required, can_be_label) = match self.peek() {
+            Some('[') => {
+                let (node, has_properties) = self.parse_blank_node_property_list()?;
+                (node, !has_properties, !has_properties)
+            }
+            Some('(') => (self.parse_collection()?, true, false),
+            _ => (self.parse_subject()?, true, true),
+        };
+        self.skip_whitespace();
+
+        if top_level && self.allow_graphs && can_be_label && self.peek() == Some('{') {
+            self.parse_graph_block(Some(subject))?;
+            return Ok(true);
+        }
+        if predicates_required || !matches!(self.peek(), None | Some('.') | Some('}')) {
+            self.parse_predicate_object_list(&subject)?;
+        }
+        Ok(false)
+    }
+
+    fn parse_graph_label(&mut self) -> Result<RdfTerm, ImportException> {
+        if self.peek() == Some('[') {
+            self.bump();
+            self.expect(']')?;
+            return Ok(self.new_blank_node());
+        }
+        self.parse_subject()
+    }
+
+    // '{' (triples ('.' triples)* '.'?)? '}'
+    // Only the '{' is parsed here, the triples of the block are then parsed one statement
+    // at a time like the ones of the default graph.
+    fn parse_graph_block(&mut self, graph: Option<RdfTerm>) -> Result<(), ImportException> {
+        self.expect('{')?;
+        self.graph = graph;
+        self.in_graph_block = true;
+        Ok(())
+    }
+
+    // The triples of a graph block up to the next '.', or the '}' ending the block
+    fn parse_graph_block_statement(&mut self) -> Result<(), ImportException> {
+        if self.peek() != Some('}') {
+            self.parse_triples(false)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some('.') => {
+                    self.bump();
+                    return Ok(());
+                }
+                Some('}') => {}
+                _ => return Err(self.error("Expected '.' or '}'")),
+            }
+        }
+        self.bump();
+        self.graph = None;
+        self.in_graph_block = false;
+        Ok(())
+    }
+
+    fn parse_subject(&mut self) -> Result<RdfTerm, ImportException> {
+        match self.peek() {
+            Some('<') => Ok(RdfTerm::Iri(self.parse_iri_ref()?)),
+            Some('_') if self.peek_at(1) == Some(':') => self.parse_blank_node_label(),
+            Some('"') | Some('\'') => Err(self.error("A literal can not be a subject")),
+            None => Err(self.error("Unexpected end of file")),
+            Some(_) => Ok(RdfTerm::Iri(self.parse_prefixed_name()?)),
+        }
+    }
+
+    // verb objectList (';' (verb objectList)?)*
+    fn parse_predicate_object_list(&mut self, subject: &RdfTerm) -> Result<(), ImportException> {
+        loop {
+            self.skip_whitespace();
+            let predicate = self.parse_verb()?;
+            self.parse_object_list(subject, &predicate)?;
+            self.skip_whitespace();
+            if self.peek() != Some(';') {
+                return Ok(());
+            }
+            while self.peek() == Some(';') {
+                self.bump();
+                self.skip_whitespace();
+            }
+            if matches!(self.peek(), None | Some('.') | Some(']') | Some('}')) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn parse_verb(&mut self) -> Result<RdfTerm, ImportException> {
+        match self.peek() {
+            Some('a') if self.at_keyword("a", false) => {
+                self.bump();
+                Ok(RdfTerm::iri(RDF_TYPE))
+            }
+            Some('<') => Ok(RdfTerm::Iri(self.parse_iri_ref()?)),
+            Some('_') | Some('[') | Some('(') | Some('"') | Some('\'') => {
+                Err(self.error("Expected an IRI as predicate"))
+            }
+            None => Err(self.error("Unexpected end of file")),
+            Some(_) => Ok(RdfTerm::Iri(self.parse_prefixed_name()?)),
+        }
+    }
+
+    fn parse_object_list(&mut self, subject: &RdfTerm, predicate: &RdfTerm) -> Result<(), ImportException> {
+        loop {
+            self.skip_whitespace();
+            let object = self.parse_object()?;
+            self.emit(subject.clone(), predicate.clone(), object);
+            self.skip_whitespace();
+            if self.peek() != Some(',') {
+                return Ok(());
+            }
+            self.bump();
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<RdfTerm, ImportException> {
+        match self.peek() {
+            Some('<') => Ok(RdfTerm::Iri(self.parse_iri_ref()?)),
+            Some('_') if self.peek_at(1) == Some(':') => self.parse_blank_node_label(),
+            Some('[') => Ok(self.parse_blank_node_property_list()?.0),
+            Some('(') => self.parse_collection(),
+            Some(quote @ ('"' | '\'')) => self.parse_rdf_literal(quote),
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' || c == '.' => self.parse_numeric_literal(),
+            Some(_) if self.at_keyword("true", false) || self.at_keyword("false", false) => {
+                let value = if self.peek() == Some('t') { "true" } else { "false" };
+                self.consume(value.len());
+                Ok(RdfTerm::typed_literal(value, &format!("{}boolean", XSD_PREFIX)))
+            }
+            None => Err(self.error("Unexpected end of file")),
+            Some(_) => Ok(RdfTerm::Iri(self.parse_prefixed_name()?)),
+        }
+    }
+
+    // '[' predicateObjectList? ']', returns the node and whether it has properties
+    fn parse_blank_node_property_list(&mut self) -> Result<(RdfTerm, bool), ImportException> {
+        self.bump();
+        self.skip_whitespace();
+        let node = self.new_blank_node();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok((node, false));
+        }
+        self.parse_predicate_object_list(&node)?;
+        self.expect(']')?;
+        Ok((node, true))
+    }
+
+    // '(' object* ')', encoded with rdf:first/rdf:rest. The empty collection is rdf:nil.
+    fn parse_collection(&mut self) -> Result<RdfTerm, ImportException> {
+        let (line, column) = (self.line, self.column);
+        self.bump();
+        let mut head = None;
+        let mut last: Option<RdfTerm> = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(')') => {
+                    self.bump();
+                    break;
+                }
+                None => return Err(self.error_at(line, column, "Unterminated collection")),
+                Some(_) => {}
+            }
+            let item = self.parse_object()?;
+            let node = self.new_blank_node();
+            match last {
+                Some(previous) => self.emit(previous, RdfTerm::iri(RDF_REST), node.clone()),
+                None => head = Some(node.clone()),
+            }
+            self.emit(node.clone(), RdfTerm::iri(RDF_FIRST), item);
+            last = Some(node);
+        }
+        if let Some(last) = last {
+            self.emit(last, RdfTerm::iri(RDF_REST), RdfTerm::iri(RDF_NIL));
+        }
+        Ok(head.unwrap_or_else(|| RdfTerm::iri(RDF_NIL)))
+    }
+
+    // IRIREF, resolved against the base IRI
+    fn parse_iri_ref(&mut self) -> Result<String, ImportException> {
+        let (line, column) = (self.line, self.column);
+        self.bump();
+        let mut iri = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error_at(line, column, "Unterminated IRI")),
+                Some('>') => break,
+                Some('\\') => iri.push(self.parse_uchar()?),
+                Some(c) if c <= ' ' || "<\"{}|^`".contains(c) => {
+                    return Err(self.error(&format!("Invalid character {:?} in IRI", c)));
+                }
+                Some(c) => iri.push(c),
+            }
+        }
+        if has_scheme(&iri) {
+            return Ok(iri);
+        }
+        match &self.base {
+            Some(base) => Ok(resolve_iri(base, &iri)),
+            None => Err(self.error_at(line, column, &format!("Relative IRI <{}> without a base IRI", iri))),
+        }
+    }
+
+    // PN_PREFIX? ':', returns the prefix without the ':'
+    fn parse_pname_ns(&mut self) -> Result<String, ImportException> {
+        let mut prefix = String::new();
+        if matches!(self.peek(), Some(c) if is_pn_chars_base(c)) {
+            while let Some(c) = self.peek() {
+                if is_pn_chars(c) || (c == '.' && self.name_continues_after_dots(is_pn_chars)) {
+                    prefix.push(c);
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+        }
+        if self.peek() != Some(':') {
+            return Err(self.error("Expected a prefixed name"));
+        }
+        self.bump();
+        Ok(prefix)
+    }
+
+    // PNAME_LN or PNAME_NS, returns the expanded IRI
+    fn parse_prefixed_name(&mut self) -> Result<String, ImportException> {
+        let (line, column) = (self.line, self.column);
+        let prefix = self.parse_pname_ns()?;
+        let namespace = match self.prefixes.get(&prefix) {
+            Some(namespace) => namespace.clone(),
+            None => return Err(self.error_at(line, column, &format!("Undefined prefix '{}:'", prefix))),
+        };
+        Ok(namespace + &self.parse_local_name()?)
+    }
+
+    // PN_LOCAL, with the escapes (PN_LOCAL_ESC) removed and the percent encodings kept
+    fn parse_local_name(&mut self) -> Result<String, ImportException> {
+        let mut local = String::new();
+        match self.peek() {
+            Some(c) if is_pn_chars_u(c) || c.is_ascii_digit() || c == ':' || c == '%' || c == '\\' => {}
+            _ => return Ok(local),
+        }
+        while let Some(c) = self.peek() {
+            match c {
+                '%' => {
+                    let hex = (self.peek_at(1), self.peek_at(2));
+                    match hex {
+                        (Some(a), Some(b)) if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+                            local.extend(['%', a, b]);
+                            self.consume(3);
+                        }
+                        _ => return Err(self.error("Invalid percent encoding in local name")),
+                    }
+                }
+                '\\' => match self.peek_at(1) {
+                    Some(escaped) if "_~.-!$&'()*+,;=/?#@%".contains(escaped) => {
+                        local.push(escaped);
+                        self.consume(2);
+                    }
+                    _ => return Err(self.error("Invalid escape in local name")),
+                },
+                '.' if self.name_continues_after_dots(is_local_name_char) => {
+                    local.push('.');
+                    self.bump();
+                }
+                c if is_pn_chars(c) || c == ':' => {
+                    local.push(c);
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+        Ok(local)
+    }
+
+    // BLANK_NODE_LABEL: '_:' (PN_CHARS_U | [0-9]) ((PN_CHARS | '.')* PN_CHARS)?
+    fn parse_blank_node_label(&mut self) -> Result<RdfTerm, ImportException> {
+        self.consume(2);
+        let mut label = String::new();
+        match self.peek() {
+            Some(c) if is_pn_chars_u(c) || c.is_ascii_digit() => {
+                label.push(c);
+                self.bump();
+            }
+            _ => return Err(self.error("Invalid blank node label")),
+        }
+        while let Some(c) = self.peek() {
+            if is_pn_chars(c) || (c == '.' && self.name_continues_after_dots(is_pn_chars)) {
+                label.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        if label.starts_with(GENERATED_BLANK_NODE_PREFIX) {
+            label.insert_str(0, GENERATED_BLANK_NODE_PREFIX);
+        }
+        Ok(RdfTerm::BlankNode(label))
+    }
+
+    // String (short or long, with either quote) followed by an optional LANGTAG or '^^' iri
+    fn parse_rdf_literal(&mut self, quote: char) -> Result<RdfTerm, ImportException> {
+        let value = self.parse_string(quote)?;
+        match self.peek() {
+            Some('@') => {
+                self.bump();
+                let language = self.parse_language_tag()?;
+                Ok(RdfTerm::lang_literal(&value, &language))
+            }
+            Some('^') if self.peek_at(1) == Some('^') => {
+                self.consume(2);
+                let datatype = match self.peek() {
+                    Some('<') => self.parse_iri_ref()?,
+                    _ => self.parse_prefixed_name()?,
+                };
+                Ok(RdfTerm::typed_literal(&value, &datatype))
+            }
+            _ => Ok(RdfTerm::simple_literal(&value)),
+        }
+    }
+
+    fn parse_string(&mut self, quote: char) -> Result<String, ImportException> {
+        let (line, column) = (self.line, self.column);
+        self.bump();
+        let long = self.peek() == Some(quote) && self.peek_at(1) == Some(quote);
+        if long {
+            self.consume(2);
+        }
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error_at(line, column, "Unterminated string literal")),
+                Some(c) if c == quote => {
+                    if !long {
+                        return Ok(value);
+                    }
+                    if self.peek() == Some(quote) && self.peek_at(1) == Some(quote) {
+                        self.consume(2);
+                        return Ok(value);
+                    }
+                    value.push(c);
+                }
+                Some('\\') => value.push(self.parse_escape()?),
+                Some('\n') | Some('\r') if !long => {
+                    return Err(self.error_at(line, column, "Unterminated string literal"));
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    // LANGTAG: '@' [a-zA-Z]+ ('-' [a-zA-Z0-9]+)*   (the '@' was already consumed)
+    fn parse_language_tag(&mut self) -> Result<String, ImportException> {
+        let mut language = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            language.push(c);
+            self.bump();
+        }
+        if language.is_empty() {
+            return Err(self.error("Invalid language tag"));
+        }
+        while self.peek() == Some('-') {
+            language.push('-');
+            self.bump();
+            let subtag_length = language.len();
+            while let Some(c) = self.peek().filter(char::is_ascii_alphanumeric) {
+                language.push(c);
+                self.bump();
+            }
+            if language.len() == subtag_length {
+                return Err(self.error("Invalid language tag"));
+            }
+        }
+        Ok(language)
+    }
+
+    // ECHAR or UCHAR, the backslash was already consumed
+    fn parse_escape(&mut self) -> Result<char, ImportException> {
+        let escaped = match self.peek() {
+            Some('t') => '\t',
+            Some('b') => '\u{8}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('f') => '\u{c}',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('\\') => '\\',
+            Some('u') | Some('U') => return self.parse_uchar(),
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        self.bump();
+        Ok(escaped)
+    }
+
+    // UCHAR: '\u' HEX{4} | '\U' HEX{8}, the backslash was already consumed
+    fn parse_uchar(&mut self) -> Result<char, ImportException> {
+        let digits = match self.bump() {
+            Some('u') => 4,
+            Some('U') => 8,
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        let mut code_point = 0;
+        for _ in 0..digits {
+            let digit = self
+                .bump()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("Invalid unicode escape"))?;
+            code_point = code_point * 16 + digit;
+        }
+        char::from_u32(code_point).ok_or_else(|| self.error("Invalid unicode code point"))
+    }
+
+    // INTEGER, DECIMAL or DOUBLE
+    fn parse_numeric_literal(&mut self) -> Result<RdfTerm, ImportException> {
+        let (line, column) = (self.line, self.column);
+        let mut lexical = String::new();
+        if let Some(sign @ ('+' | '-')) = self.peek() {
+            lexical.push(sign);
+            self.bump();
+        }
+        let mut digits = self.take_digits(&mut lexical);
+        let mut datatype = "integer";
+
+        // A '.' not followed by a digit (or an exponent) ends the statement
+        let exponent_follows = |c: Option<char>| matches!(c, Some('e') | Some('E'));
+        if self.peek() == Some('.')
+            && (matches!(self.peek_at(1), Some(c) if c.is_ascii_digit())
+                || (digits > 0 && exponent_follows(self.peek_at(1))))
+        {
+            lexical.push('.');
+            self.bump();
+            digits += self.take_digits(&mut lexical);
+            datatype = "decimal";
+        }
+        if digits == 0 {
+            return Err(self.error_at(line, column, "Invalid number"));
+        }
+        if exponent_follows(self.peek()) {
+            lexical.push('e');
+            self.bump();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                lexical.push(sign);
+                self.bump();
+            }
+            if self.take_digits(&mut lexical) == 0 {
+                return Err(self.error_at(line, column, "Invalid exponent"));
+            }
+            datatype = "double";
+        }
+        Ok(RdfTerm::typed_literal(&lexical, &format!("{}{}", XSD_PREFIX, datatype)))
+    }
+
+    fn take_digits(&mut self, lexical: &mut String) -> usize {
+        let mut count = 0;
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            lexical.push(c);
+            self.bump();
+            count += 1;
+        }
+        count
+    }
+}
+
+impl<R: BufRead> Iterator for TurtleParser<R> {
+    type Item = Result<RdfQuad, ImportException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(quad) = self.pending.pop_front() {
+                return Some(Ok(quad));
+            }
+            if self.finished {
+                return None;
+            }
+            let result = self.parse_statement();
+            if matches!(result, Ok(true)) {
+                continue;
+            }
+            self.finished = true;
+            // A read error looks like the end of the input to the parser
+            if let Some(e) = self.io_error.take() {
+                return Some(Err(self.error(&e.to_string())));
+            }
+            if let Err(e) = result {
+                self.pending.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+fn is_local_name_char(c: char) -> bool {
+    is_pn_chars(c) || c == ':' || c == '%' || c == '\\'
+}
+
+// Resolves an IRI reference against an absolute base IRI (RFC 3986, section 5.2)
+pub fn resolve_iri(base: &str, reference: &str) -> String {
+    let reference = IriParts::split(reference);
+    let base = IriParts::split(base);
+
+    let (scheme, authority, path, query) = if reference.scheme.is_some() {
+        (reference.scheme, reference.authority, remove_dot_segments(reference.path), reference.query)
+    } else if reference.authority.is_some() {
+        (base.scheme, reference.authority, remove_dot_segments(reference.path), reference.query)
+    } else if reference.path.is_empty() {
+        (base.scheme, base.authority, base.path.to_string(), reference.query.or(base.query))
+    } else if reference.path.starts_with('/') {
+        (base.scheme, base.authority, remove_dot_segments(reference.path), reference.query)
+    } else {
+        let merged = if base.authority.is_some() && base.path.is_empty() {
+            format!("/{}", reference.path)
+        } else {
+            let directory = base.path.rfind('/').map_or("", |i| &base.path[..=i]);
+            format!("{}{}", directory, reference.path)
+        };
+        (base.scheme, base.authority, remove_dot_segments(&merged), reference.query)
+    };
+
+    let mut iri = String::new();
+    if let Some(scheme) = scheme {
+        iri.push_str(scheme);
+        iri.push(':');
+    }
+    if let Some(authority) = authority {
+        iri.push_str("//");
+        iri.push_str(authority);
+    }
+    iri.push_str(&path);
+    if let Some(query) = query {
+        iri.push('?');
+        iri.push_str(query);
+    }
+    if let Some(fragment) = reference.fragment {
+        iri.push('#');
+        iri.push_str(fragment);
+    }
+    iri
+}
+
+struct IriParts<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> IriParts<'a> {
+    fn split(iri: &'a str) -> Self {
+        let (rest, fragment) = match iri.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (iri, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+        let (scheme, rest) = match rest.split_once(':') {
+            Some((scheme, rest)) if is_scheme(scheme) => (Some(scheme), rest),
+            _ => (None, rest),
+        };
+        let (authority, path) = match rest.strip_prefix("//") {
+            Some(rest) => {
+                let end = rest.find('/').unwrap_or(rest.len());
+                (Some(&rest[..end]), &rest[end..])
+            }
+            None => (None, rest),
+        };
+        Self {
+            scheme,
+            authority,
+            path,
+            query,
+            fragment,
+        }
+    }
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output = String::with_capacity(path.len());
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("../") {
+            input = rest;
+        } else if let Some(rest) = input.strip_prefix("./") {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            remove_last_segment(&mut output);
+        } else if input == "/.." {
+            input = "/";
+            remove_last_segment(&mut output);
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let start = usize::from(input.starts_with('/'));
+            let end = input[start..].find('/').map_or(input.len(), |i| i + start);
+            output.push_str(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output
+}
+
+fn remove_last_segment(output: &mut String) {
+    match output.rfind('/') {
+        Some(i) => output.truncate(i),
+        None => output.clear(),
+    }
+}
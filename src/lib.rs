@@ -2,4 +2,5 @@ pub mod server;
 pub mod query;
 pub mod import;
 pub mod network;
+pub mod storage;
 // Other module exports or code...
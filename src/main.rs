@@ -1,16 +1,15 @@
-mod server;
-mod query;
-mod import;
-mod network;
-
 use clap::{Parser, ValueHint, Error};
 use clap::error::ErrorKind; 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
 
-use tokio::runtime::Builder;
-
-use server::sparql_server_orchestrator::startup_server;
+use milleniumdb_rs::import::import_services::ImportOptions;
+use milleniumdb_rs::server::{graph_server_orchestrator, sparql_server_orchestrator::startup_server};
+use milleniumdb_rs::storage::buffer_manager::{pages_in_megabytes, BufferManager};
+use milleniumdb_rs::storage::catalog::{Catalog, DataModel, CATALOG_FILE};
+use milleniumdb_rs::storage::string_manager::GIGABYTE;
+use milleniumdb_rs::storage::wal::WalSyncPolicy;
 
 #[derive(Parser, Debug)]
 #[command(about = "MillenniumDB server", long_about = None)]
@@ -18,24 +17,44 @@ struct ServerConfig {
     #[arg(short, long, value_hint = ValueHint::DirPath)]
     db_folder: PathBuf,
 
+    // RDF files (.nt, .nq, .ttl, .trig) or property graph files (.qm) used to create the
+    // database before starting the server
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    import: Vec<PathBuf>,
+
+    // IRI prefixes (one per line) used to compress IRIs when creating the database
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    prefixes: Option<PathBuf>,
+
     #[arg(short, long, default_value_t = 8080, value_parser = parse_positive_number::<u16>)]
     port: u16,
 
     #[arg(short = 't', long, default_value_t = 60, value_parser = parse_positive_number::<u64>)]
     timeout: u64,
 
+    // Size in GiB of the start of the dictionary loaded in memory at startup
     #[arg(long, default_value_t = 2, value_parser = parse_positive_number::<u64>)]
     string_initial_populate_size: u64,
 
+    // Size in MiB of the buffer shared by all the workers
     #[arg(long, default_value_t = 1024, value_parser = parse_positive_number::<u64>)]
     buffer_size: u64,
 
+    // Size in MiB of the buffer for the temporary pages of each worker
     #[arg(long, default_value_t = 256, value_parser = parse_positive_number::<u64>)]
     private_buffer_size: u64,
 
     #[arg(long, default_value_t = 4, value_parser = parse_positive_number::<u8>)]
     threads: u8,
 
+    // When the write-ahead log is forced to disk: "commit", "never" or an interval such as "100ms"
+    #[arg(long, default_value = "commit")]
+    wal_sync: WalSyncPolicy,
+
+    // Size in MiB of the write-ahead log that triggers a checkpoint
+    #[arg(long, default_value_t = 64, value_parser = parse_positive_number::<u64>)]
+    checkpoint_size: u64,
+
     #[arg(short, long, default_value_t = 0, value_parser = parse_positive_number::<u64>)]
     limit: u64,
 }
@@ -56,12 +75,52 @@ async fn main() {
 
     let config = ServerConfig::parse();
 
-    if let Err(e) = validate_db_folder(&config.db_folder) {
-        eprintln!("Error: {}", e);
-        process::exit(1);
-    }
+    let data_model = match validate_db_folder(&config.db_folder, &config.import) {
+        Ok(data_model) => data_model,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+
+        let import_options = ImportOptions {
+            db_folder: config.db_folder.clone(),
+            files: config.import.clone(),
+            prefix_file: config.prefixes.clone(),
+            ..ImportOptions::default()
+        };
+
+        // Redoes the transactions committed before a crash, if any
+        let buffer_manager = match BufferManager::with_wal(
+            &config.db_folder,
+            pages_in_megabytes(config.buffer_size),
+            pages_in_megabytes(config.private_buffer_size),
+            config.threads as usize,
+            config.wal_sync,
+            config.checkpoint_size * 1024 * 1024,
+        ) {
+            Ok(buffer_manager) => Arc::new(buffer_manager),
+            Err(e) => {
+                eprintln!("Failed to open the database: {}", e);
+                process::exit(1);
+            }
+        };
+        if let Some(wal) = buffer_manager.wal().filter(|wal| wal.recovered_transactions() > 0) {
+            println!("Recovered {} transactions from the write-ahead log", wal.recovered_transactions());
+        }
+
+        let string_populate_size = config.string_initial_populate_size * GIGABYTE;
+
+        // Property graphs are served by the graph server, on the same port option
+        if data_model == DataModel::QuadModel {
+            if let Err(e) = graph_server_orchestrator::startup_server(import_options, buffer_manager, string_populate_size, config.port).await {
+                eprintln!("Failed to start server: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
 
-        match startup_server().await {
+        match startup_server(import_options, buffer_manager, string_populate_size, config.port, std::time::Duration::from_secs(config.timeout)).await {
             Ok(_) => {
                 println!("Server started successfully.");
                 // Continue with your server logic here
@@ -74,12 +133,26 @@ async fn main() {
 
 }
 
-fn validate_db_folder(path: &PathBuf) -> Result<(), String> {
+// The folder must hold a database of a format this server reads, unless the database is
+// being created from `import` (then it must not hold one yet). Returns the data model
+// of the database, property graphs are created from .qm files.
+fn validate_db_folder(path: &Path, import: &[PathBuf]) -> Result<DataModel, String> {
     if !path.exists() {
         Err(String::from("Database folder does not exist"))
     } else if !path.is_dir() {
         Err(String::from("Database folder is not a directory"))
+    } else if !import.is_empty() {
+        let is_quad_model = |file: &PathBuf| file.extension().is_some_and(|e| e.eq_ignore_ascii_case("qm"));
+        if path.join(CATALOG_FILE).exists() {
+            Err(String::from("Database folder already contains a database"))
+        } else if import.iter().all(is_quad_model) {
+            Ok(DataModel::QuadModel)
+        } else if import.iter().any(is_quad_model) {
+            Err(String::from("Property graph files cannot be imported together with RDF files"))
+        } else {
+            Ok(DataModel::Rdf)
+        }
     } else {
-        Ok(()) // This indicates that the validation passed successfully
+        Catalog::load(path).map(|catalog| catalog.data_model).map_err(|e| e.to_string())
     }
 }
@@ -0,0 +1,126 @@
+use crate::network::exceptions::HttpException;
+use crate::network::response_type::ResponseType;
+use crate::query::query_forms::QueryForm;
+
+// A media range of an Accept header, e.g. `text/*;q=0.5`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    pub main_type: String,
+    pub sub_type: String,
+    pub quality: f32,
+}
+
+impl MediaRange {
+    // How specific the range is: 2 for `type/subtype`, 1 for `type/*` and 0 for `*/*`
+    fn specificity(&self) -> u8 {
+        match (self.main_type.as_str(), self.sub_type.as_str()) {
+            ("*", _) => 0,
+            (_, "*") => 1,
+            _ => 2,
+        }
+    }
+
+    fn matches(&self, media_type: &str) -> bool {
+        let (main_type, sub_type) = media_type.split_once('/').unwrap_or((media_type, ""));
+        (self.main_type == "*" || self.main_type == main_type) && (self.sub_type == "*" || self.sub_type == sub_type)
+    }
+}
+
+// Parses an Accept header. Invalid elements are ignored, as most servers do.
+pub fn parse_accept(accept: &str) -> Vec<MediaRange> {
+    let mut ranges = Vec::new();
+    for element in accept.split(',') {
+        let mut parts = element.split(';');
+        let media_range = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let (main_type, sub_type) = match media_range.split_once('/') {
+            Some((main_type, sub_type)) if !main_type.is_empty() && !sub_type.is_empty() => (main_type, sub_type),
+            _ => continue,
+        };
+        if main_type == "*" && sub_type != "*" {
+            continue;
+        }
+
+        let mut quality = 1.0;
+        for parameter in parts {
+            if let Some((name, value)) = parameter.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    quality = value.trim().parse::<f32>().unwrap_or(0.0).clamp(0.0, 1.0);
+                }
+            }
+        }
+
+        ranges.push(MediaRange {
+            main_type: main_type.to_string(),
+            sub_type: sub_type.to_string(),
+            quality,
+        });
+    }
+    ranges
+}
+
+// Formats that can serialize the results of each query form, the first one is the default
+pub fn supported_response_types(query_form: QueryForm) -> &'static [ResponseType] {
+    match query_form {
+        QueryForm::Select => &[ResponseType::JSON, ResponseType::XML, ResponseType::CSV, ResponseType::TSV],
+        QueryForm::Ask => &[ResponseType::JSON, ResponseType::XML],
+        QueryForm::Construct | QueryForm::Describe => {
+            &[ResponseType::TURTLE, ResponseType::NTRIPLES, ResponseType::NQUADS]
+        }
+    }
+}
+
+// Chooses the response format. An explicit `format` parameter takes precedence over the
+// Accept header; without both, the default format of the query form is used.
+pub fn negotiate_response_type(
+    query_form: QueryForm,
+    accept: Option<&str>,
+    format: Option<&str>,
+) -> Result<ResponseType, HttpException> {
+    let supported = supported_response_types(query_form);
+
+    if let Some(format) = format {
+        return match format.parse::<ResponseType>() {
+            Ok(response_type) if supported.contains(&response_type) => Ok(response_type),
+            _ => Err(not_acceptable(query_form)),
+        };
+    }
+
+    let ranges = match accept {
+        Some(accept) if !accept.trim().is_empty() => parse_accept(accept),
+        _ => return Ok(supported[0]),
+    };
+
+    // For each candidate the quality comes from the most specific range matching it,
+    // on equal quality the server preference order decides.
+    let mut best: Option<(ResponseType, f32)> = None;
+    for response_type in supported {
+        let quality = response_type
+            .media_types()
+            .iter()
+            .filter_map(|media_type| {
+                ranges
+                    .iter()
+                    .filter(|range| range.matches(media_type))
+                    .max_by_key(|range| range.specificity())
+                    .map(|range| range.quality)
+            })
+            .fold(0.0, f32::max);
+
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((*response_type, quality));
+        }
+    }
+
+    best.map(|(response_type, _)| response_type).ok_or_else(|| not_acceptable(query_form))
+}
+
+fn not_acceptable(query_form: QueryForm) -> HttpException {
+    let available: Vec<&str> = supported_response_types(query_form)
+        .iter()
+        .map(|response_type| response_type.media_types()[0])
+        .collect();
+    HttpException::new(
+        406,
+        &format!("No acceptable response format, available: {}", available.join(", ")),
+    )
+}
@@ -25,3 +25,56 @@ impl fmt::Display for ConnectionException {
 
 // Implement Error trait for ConnectionException
 impl Error for ConnectionException {}
+
+// Used when an HTTP request is malformed or exceeds the configured limits.
+// Carries the status code that has to be sent back to the client.
+#[derive(Debug)]
+pub struct HttpException {
+    status: u16,
+    message: String,
+}
+
+impl HttpException {
+    // Constructor for HttpException
+    pub fn new(status: u16, message: &str) -> Self {
+        HttpException {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+}
+
+impl fmt::Display for HttpException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for HttpException {}
+
+// Used when a response format named by the client (e.g. in the `format` parameter) is unknown
+#[derive(Debug)]
+pub struct ResponseTypeException {
+    message: String,
+}
+
+impl ResponseTypeException {
+    // Constructor for ResponseTypeException
+    pub fn new(message: &str) -> Self {
+        ResponseTypeException {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ResponseTypeException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ResponseTypeException {}
@@ -0,0 +1,657 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::network::exceptions::HttpException;
+
+// Maximum size of the request line plus all the header fields (and chunked trailers)
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 16 * 1024;
+
+// Maximum size of a request body, after removing the chunked transfer coding
+pub const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+impl HttpVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFraming {
+    Empty,
+    ContentLength(usize),
+    Chunked,
+}
+
+// Request line and header fields of a request. The body is read separately so the
+// session can answer `Expect: 100-continue` before the client sends it.
+#[derive(Debug, Clone)]
+pub struct RequestHead {
+    pub method: String,
+    pub target: String,
+    pub version: HttpVersion,
+    pub headers: Vec<(String, String)>,
+    framing: BodyFraming,
+}
+
+impl RequestHead {
+    // Returns the first value of the header `name` (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Returns all the comma separated elements of every `name` header
+    pub fn header_list(&self, name: &str) -> Vec<String> {
+        self.headers
+            .iter()
+            .filter(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .flat_map(|(_, value)| value.split(','))
+            .map(|element| element.trim().to_ascii_lowercase())
+            .filter(|element| !element.is_empty())
+            .collect()
+    }
+
+    // Path of the request target, without the query string
+    pub fn path(&self) -> &str {
+        match self.target.find('?') {
+            Some(pos) => &self.target[..pos],
+            None => &self.target,
+        }
+    }
+
+    // Raw (still percent-encoded) query string of the request target
+    pub fn query_string(&self) -> Option<&str> {
+        self.target.find('?').map(|pos| &self.target[pos + 1..])
+    }
+
+    pub fn has_body(&self) -> bool {
+        self.framing != BodyFraming::Empty
+    }
+
+    pub fn content_length(&self) -> Option<usize> {
+        match self.framing {
+            BodyFraming::ContentLength(length) => Some(length),
+            _ => None,
+        }
+    }
+
+    pub fn expects_continue(&self) -> bool {
+        self.version == HttpVersion::Http11
+            && self.has_body()
+            && self.header("Expect").is_some_and(|value| value.eq_ignore_ascii_case("100-continue"))
+    }
+
+    // HTTP/1.1 connections are persistent unless the client asks otherwise,
+    // HTTP/1.0 connections are closed unless the client asks for keep-alive.
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.header_list("Connection");
+        match self.version {
+            HttpVersion::Http11 => !connection.iter().any(|option| option == "close"),
+            HttpVersion::Http10 => connection.iter().any(|option| option == "keep-alive"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub head: RequestHead,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.header(name)
+    }
+
+    pub fn method(&self) -> &str {
+        &self.head.method
+    }
+
+    pub fn path(&self) -> &str {
+        self.head.path()
+    }
+
+    pub fn query_string(&self) -> Option<&str> {
+        self.head.query_string()
+    }
+}
+
+// Reads HTTP/1.1 requests from a byte stream. Bytes received after the end of a request
+// are kept in the internal buffer, so pipelined requests on a persistent connection are
+// not lost.
+pub struct RequestReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    start: usize,
+    max_header_size: usize,
+    max_body_size: usize,
+}
+
+impl<R: AsyncRead + Unpin> RequestReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, DEFAULT_MAX_HEADER_SIZE, DEFAULT_MAX_BODY_SIZE)
+    }
+
+    pub fn with_limits(reader: R, max_header_size: usize, max_body_size: usize) -> Self {
+        Self {
+            reader,
+            buffer: Vec::with_capacity(READ_CHUNK_SIZE),
+            start: 0,
+            max_header_size,
+            max_body_size,
+        }
+    }
+
+    // Reads a complete request (head and body).
+    // Returns `Ok(None)` if the client closed the connection between requests.
+    pub async fn read_request(&mut self) -> Result<Option<HttpRequest>, HttpException> {
+        let head = match self.read_head().await? {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        let body = self.read_body(&head).await?;
+        Ok(Some(HttpRequest { head, body }))
+    }
+
+    // Reads the request line and the header fields.
+    // Returns `Ok(None)` if the client closed the connection between requests.
+    pub async fn read_head(&mut self) -> Result<Option<RequestHead>, HttpException> {
+        self.compact();
+
+        // RFC 9112 section 2.2: ignore empty lines received before the request line
+        loop {
+            while self.available().starts_with(b"\r\n") {
+                self.start += 2;
+            }
+            while self.available().starts_with(b"\n") {
+                self.start += 1;
+            }
+            if self.available().is_empty() || self.available() == b"\r" {
+                if self.fill().await? == 0 {
+                    if self.available().is_empty() || self.available() == b"\r" {
+                        return Ok(None);
+                    }
+                    return Err(HttpException::new(400, "Connection closed in the middle of a request"));
+                }
+                continue;
+            }
+            break;
+        }
+
+        let header_end = self.find_section_end().await?;
+        if header_end - self.start > self.max_header_size {
+            return Err(HttpException::new(431, "Request header fields too large"));
+        }
+        let section = String::from_utf8(self.buffer[self.start..header_end].to_vec())
+            .map_err(|_| HttpException::new(400, "Request head is not valid UTF-8"))?;
+        self.start = header_end;
+
+        let mut lines = section.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line));
+        let request_line = lines.next().unwrap_or("");
+        let (method, target, version) = parse_request_line(request_line)?;
+        let headers = parse_header_fields(lines)?;
+
+        let mut head = RequestHead {
+            method,
+            target,
+            version,
+            headers,
+            framing: BodyFraming::Empty,
+        };
+        head.framing = self.body_framing(&head)?;
+
+        if head.version == HttpVersion::Http11 && head.header("Host").is_none() {
+            return Err(HttpException::new(400, "Missing Host header"));
+        }
+        Ok(Some(head))
+    }
+
+    // Reads the body announced by `head`, decoding the chunked transfer coding if needed
+    pub async fn read_body(&mut self, head: &RequestHead) -> Result<Vec<u8>, HttpException> {
+        match head.framing {
+            BodyFraming::Empty => Ok(Vec::new()),
+            BodyFraming::ContentLength(length) => self.read_exact(length).await,
+            BodyFraming::Chunked => self.read_chunked().await,
+        }
+    }
+
+    fn body_framing(&self, head: &RequestHead) -> Result<BodyFraming, HttpException> {
+        let transfer_encoding = head.header_list("Transfer-Encoding");
+        let content_lengths: Vec<&str> = head
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+            .flat_map(|(_, value)| value.split(','))
+            .map(str::trim)
+            .collect();
+
+        if !transfer_encoding.is_empty() {
+            // A request with both framings is a request smuggling vector, refuse it
+            if !content_lengths.is_empty() {
+                return Err(HttpException::new(400, "Both Transfer-Encoding and Content-Length present"));
+            }
+            if head.version == HttpVersion::Http10 {
+                return Err(HttpException::new(400, "Transfer-Encoding is not allowed in HTTP/1.0"));
+            }
+            if transfer_encoding.last().map(String::as_str) != Some("chunked") {
+                return Err(HttpException::new(400, "Final transfer coding is not chunked"));
+            }
+            if transfer_encoding.len() > 1 {
+                return Err(HttpException::new(501, "Unsupported transfer coding"));
+            }
+            return Ok(BodyFraming::Chunked);
+        }
+
+        let mut length: Option<usize> = None;
+        for value in content_lengths {
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(HttpException::new(400, "Invalid Content-Length"));
+            }
+            // Numbers too big for usize are certainly too big for the body limit
+            let parsed = value.parse::<usize>().unwrap_or(usize::MAX);
+            match length {
+                Some(previous) if previous != parsed => {
+                    return Err(HttpException::new(400, "Conflicting Content-Length values"));
+                }
+                _ => length = Some(parsed),
+            }
+        }
+
+        match length {
+            None | Some(0) => Ok(BodyFraming::Empty),
+            Some(length) if length > self.max_body_size => {
+                Err(HttpException::new(413, "Request body too large"))
+            }
+            Some(length) => Ok(BodyFraming::ContentLength(length)),
+        }
+    }
+
+    async fn read_chunked(&mut self) -> Result<Vec<u8>, HttpException> {
+        let mut body = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            let size_str = line.split(';').next().unwrap_or("").trim();
+            if size_str.is_empty() || !size_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(HttpException::new(400, "Invalid chunk size"));
+            }
+            let size = usize::from_str_radix(size_str, 16).unwrap_or(usize::MAX);
+            if size == 0 {
+                break;
+            }
+            if size > self.max_body_size - body.len() {
+                return Err(HttpException::new(413, "Request body too large"));
+            }
+            body.extend_from_slice(&self.read_exact(size).await?);
+            let terminator = self.read_line().await?;
+            if !terminator.is_empty() {
+                return Err(HttpException::new(400, "Chunk data not terminated by CRLF"));
+            }
+        }
+
+        // Trailer fields are read and discarded, they count against the header limit
+        let mut trailer_size = 0;
+        loop {
+            let line = self.read_line().await?;
+            if line.is_empty() {
+                break;
+            }
+            trailer_size += line.len() + 2;
+            if trailer_size > self.max_header_size {
+                return Err(HttpException::new(431, "Trailer fields too large"));
+            }
+        }
+        Ok(body)
+    }
+
+    // Reads a single line (used for chunk sizes and trailers), without the line terminator
+    async fn read_line(&mut self) -> Result<String, HttpException> {
+        loop {
+            if let Some(pos) = self.available().iter().position(|&b| b == b'\n') {
+                let raw = &self.buffer[self.start..self.start + pos];
+                let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+                let line = String::from_utf8(raw.to_vec())
+                    .map_err(|_| HttpException::new(400, "Invalid UTF-8 in chunked body"))?;
+                self.start += pos + 1;
+                return Ok(line);
+            }
+            if self.available().len() > self.max_header_size {
+                return Err(HttpException::new(431, "Chunk line too long"));
+            }
+            if self.fill().await? == 0 {
+                return Err(HttpException::new(400, "Connection closed in the middle of a request"));
+            }
+        }
+    }
+
+    async fn read_exact(&mut self, length: usize) -> Result<Vec<u8>, HttpException> {
+        let mut data = Vec::with_capacity(length);
+        let buffered = length.min(self.available().len());
+        data.extend_from_slice(&self.buffer[self.start..self.start + buffered]);
+        self.start += buffered;
+
+        while data.len() < length {
+            let missing = length - data.len();
+            let mut chunk = vec![0; missing.min(READ_CHUNK_SIZE * 8)];
+            let read = self
+                .reader
+                .read(&mut chunk)
+                .await
+                .map_err(|e| HttpException::new(400, &format!("Error reading request body: {}", e)))?;
+            if read == 0 {
+                return Err(HttpException::new(400, "Connection closed in the middle of a request body"));
+            }
+            data.extend_from_slice(&chunk[..read]);
+        }
+        Ok(data)
+    }
+
+    // Returns the position in `buffer` just after the empty line that ends the header section
+    async fn find_section_end(&mut self) -> Result<usize, HttpException> {
+        let mut searched = 0;
+        loop {
+            let available = self.available();
+            let mut pos = searched;
+            while let Some(offset) = available[pos..].iter().position(|&b| b == b'\n') {
+                let newline = pos + offset;
+                let rest = &available[newline + 1..];
+                if rest.starts_with(b"\n") {
+                    return Ok(self.start + newline + 2);
+                }
+                if rest.starts_with(b"\r\n") {
+                    return Ok(self.start + newline + 3);
+                }
+                if rest.is_empty() || rest == b"\r" {
+                    break;
+                }
+                pos = newline + 1;
+            }
+            searched = pos;
+
+            if available.len() > self.max_header_size {
+                return Err(HttpException::new(431, "Request header fields too large"));
+            }
+            if self.fill().await? == 0 {
+                return Err(HttpException::new(400, "Connection closed in the middle of a request"));
+            }
+        }
+    }
+
+    fn available(&self) -> &[u8] {
+        &self.buffer[self.start..]
+    }
+
+    fn compact(&mut self) {
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+    }
+
+    async fn fill(&mut self) -> Result<usize, HttpException> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        let read = self
+            .reader
+            .read(&mut chunk)
+            .await
+            .map_err(|e| HttpException::new(400, &format!("Error reading request: {}", e)))?;
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(read)
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+fn parse_request_line(line: &str) -> Result<(String, String, HttpVersion), HttpException> {
+    let mut parts = line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None) => (method, target, version),
+        _ => return Err(HttpException::new(400, "Malformed request line")),
+    };
+
+    if method.is_empty() || !method.chars().all(is_token_char) {
+        return Err(HttpException::new(400, "Invalid request method"));
+    }
+    if target.is_empty() || target.chars().any(|c| c.is_ascii_control() || c.is_whitespace()) {
+        return Err(HttpException::new(400, "Invalid request target"));
+    }
+
+    let version = match version {
+        "HTTP/1.1" => HttpVersion::Http11,
+        "HTTP/1.0" => HttpVersion::Http10,
+        v if v.starts_with("HTTP/") && v.len() == 8 => {
+            return Err(HttpException::new(505, "HTTP version not supported"));
+        }
+        _ => return Err(HttpException::new(400, "Malformed HTTP version")),
+    };
+
+    Ok((method.to_string(), target.to_string(), version))
+}
+
+fn parse_header_fields<'a>(lines: impl Iterator<Item = &'a str>) -> Result<Vec<(String, String)>, HttpException> {
+    let mut headers = Vec::new();
+    for line in lines {
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with(' ') || line.starts_with('\t') {
+            return Err(HttpException::new(400, "Obsolete line folding is not allowed"));
+        }
+        let (name, value) = match line.split_once(':') {
+            Some(field) => field,
+            None => return Err(HttpException::new(400, "Malformed header field")),
+        };
+        if name.is_empty() || !name.chars().all(is_token_char) {
+            return Err(HttpException::new(400, "Invalid header field name"));
+        }
+        let value = value.trim_matches(|c| c == ' ' || c == '\t');
+        if value.chars().any(|c| c.is_ascii_control() && c != '\t') {
+            return Err(HttpException::new(400, "Invalid header field value"));
+        }
+        headers.push((name.to_string(), value.to_string()));
+    }
+    Ok(headers)
+}
+
+pub fn status_reason(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_body(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        let mut response = Self::new(status);
+        response.set_header("Content-Type", content_type);
+        response.body = body.into();
+        response
+    }
+
+    // Plain text response used to report errors to the client
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::with_body(status, "text/plain; charset=utf-8", format!("{}\n", message))
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(header_name, _)| !header_name.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Serializes status line, headers and body. `Content-Length` is always computed here.
+    pub fn to_bytes(&self, keep_alive: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.body.len() + 256);
+        bytes.extend_from_slice(format!("HTTP/1.1 {} {}\r\n", self.status, status_reason(self.status)).as_bytes());
+        for (name, value) in &self.headers {
+            if name.eq_ignore_ascii_case("Content-Length") || name.eq_ignore_ascii_case("Connection") {
+                continue;
+            }
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        bytes.extend_from_slice(format!("Content-Length: {}\r\n", self.body.len()).as_bytes());
+        if !keep_alive {
+            bytes.extend_from_slice(b"Connection: close\r\n");
+        }
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W, keep_alive: bool) -> std::io::Result<()> {
+        writer.write_all(&self.to_bytes(keep_alive)).await?;
+        writer.flush().await
+    }
+}
+
+pub async fn write_continue<W: AsyncWrite + Unpin>(writer: &mut W) -> std::io::Result<()> {
+    writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+    writer.flush().await
+}
+
+// Decodes an `application/x-www-form-urlencoded` string (also used for query strings)
+pub fn parse_form_urlencoded(input: &str) -> Result<Vec<(String, String)>, HttpException> {
+    input
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(name)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+// Decodes `%XX` escapes and `+` as space, the result must be valid UTF-8
+pub fn percent_decode(input: &str) -> Result<String, HttpException> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => decoded.push(byte),
+                    None => return Err(HttpException::new(400, "Invalid percent-encoding")),
+                }
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| HttpException::new(400, "Percent-encoded data is not valid UTF-8"))
+}
+
+// Media type of a `Content-Type` value, lowercased and without parameters
+pub fn media_type(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+// Writes a response whose body is sent with the chunked transfer coding,
+// used when the length of the body is not known in advance. HTTP/1.0 clients do not
+// know that coding, their body is sent as is and ends when the connection is closed.
+pub struct ChunkedResponseWriter<'a, W> {
+    writer: &'a mut W,
+    chunked: bool,
+}
+
+impl<'a, W: AsyncWrite + Unpin> ChunkedResponseWriter<'a, W> {
+    // Sends the status line and headers
+    pub async fn start(
+        writer: &'a mut W,
+        status: u16,
+        headers: &[(String, String)],
+        version: HttpVersion,
+        keep_alive: bool,
+    ) -> std::io::Result<ChunkedResponseWriter<'a, W>> {
+        let chunked = version == HttpVersion::Http11;
+        let mut head = format!("HTTP/1.1 {} {}\r\n", status, status_reason(status));
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if chunked {
+            head.push_str("Transfer-Encoding: chunked\r\n");
+        }
+        if !keep_alive || !chunked {
+            head.push_str("Connection: close\r\n");
+        }
+        head.push_str("\r\n");
+        writer.write_all(head.as_bytes()).await?;
+        Ok(Self { writer, chunked })
+    }
+
+    pub async fn write_chunk(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if data.is_empty() {
+            // An empty chunk would terminate the body
+            return Ok(());
+        }
+        if !self.chunked {
+            return self.writer.write_all(data).await;
+        }
+        self.writer.write_all(format!("{:x}\r\n", data.len()).as_bytes()).await?;
+        self.writer.write_all(data).await?;
+        self.writer.write_all(b"\r\n").await
+    }
+
+    pub async fn finish(self) -> std::io::Result<()> {
+        if self.chunked {
+            self.writer.write_all(b"0\r\n\r\n").await?;
+        }
+        self.writer.flush().await
+    }
+}
@@ -4,13 +4,11 @@ use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Mutex;
 
-use crate::query::query_contexts::QueryContext;
 use crate::network::sparql_servers::Server;
 use crate::network::session::Session;
 
 pub struct Listener {
     server: Weak<Mutex<Server>>,
-    io_context: Arc<tokio::runtime::Runtime>,
     acceptor: Arc<Mutex<tokio::net::TcpListener>>,
     timeout: Duration,
 }
@@ -18,7 +16,6 @@ pub struct Listener {
 impl Listener {
     pub async fn new(
         server: Weak<Mutex<Server>>,
-        io_context: Arc<tokio::runtime::Runtime>,
         endpoint: std::net::SocketAddr,
         timeout: Duration,
     ) -> Result<Self, Box<dyn Error>> {
@@ -27,7 +24,6 @@ impl Listener {
 
         Ok(Self {
             server,
-            io_context,
             acceptor: Arc::new(Mutex::new(listener)),
             timeout,
         })
@@ -69,18 +65,13 @@ impl Listener {
 
     async fn handle_connection(&self, socket: TcpStream) {
         
-        let server = match self.server.upgrade() {
-            Some(server) => server,
-            None => {
-                eprintln!("Error: Server no longer exists");
-                return;
-            }
-        };
+        if self.server.upgrade().is_none() {
+            eprintln!("Error: Server no longer exists");
+            return;
+        }
 
         let timeout = self.timeout;
 
-        let query_ctx = Arc::new(Mutex::new(QueryContext::new()));
-
         let server_weak = self.server.clone();
 
         tokio::spawn(async move {
@@ -1,5 +1,9 @@
 pub mod sparql_servers;
 pub mod listener;
 pub mod session;
+pub mod http;
+pub mod sparql_protocol;
 pub mod response_type;
+pub mod content_negotiation;
+pub mod result_streaming;
 pub mod exceptions;
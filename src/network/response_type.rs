@@ -1,15 +1,27 @@
-use crate::query::exceptions::LogicException;
+use crate::network::exceptions::ResponseTypeException;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ResponseType {
     JSON,
     XML,
     TSV,
     CSV,
     TURTLE,
+    NTRIPLES,
+    NQUADS,
 }
 
 impl ResponseType {
+    pub const ALL: [ResponseType; 7] = [
+        ResponseType::JSON,
+        ResponseType::XML,
+        ResponseType::TSV,
+        ResponseType::CSV,
+        ResponseType::TURTLE,
+        ResponseType::NTRIPLES,
+        ResponseType::NQUADS,
+    ];
+
     pub fn response_type_to_string(response_type: ResponseType) -> &'static str {
         match response_type {
             ResponseType::JSON => "JSON",
@@ -17,6 +29,47 @@ impl ResponseType {
             ResponseType::TSV => "TSV",
             ResponseType::CSV => "CSV",
             ResponseType::TURTLE => "TURTLE",
+            ResponseType::NTRIPLES => "NTRIPLES",
+            ResponseType::NQUADS => "NQUADS",
+        }
+    }
+
+    // Value of the Content-Type header of a response in this format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseType::JSON => "application/sparql-results+json; charset=utf-8",
+            ResponseType::XML => "application/sparql-results+xml; charset=utf-8",
+            ResponseType::TSV => "text/tab-separated-values; charset=utf-8",
+            ResponseType::CSV => "text/csv; charset=utf-8",
+            ResponseType::TURTLE => "text/turtle; charset=utf-8",
+            ResponseType::NTRIPLES => "application/n-triples; charset=utf-8",
+            ResponseType::NQUADS => "application/n-quads; charset=utf-8",
+        }
+    }
+
+    // Media types (as they may appear in an Accept header) served by this format
+    pub fn media_types(&self) -> &'static [&'static str] {
+        match self {
+            ResponseType::JSON => &["application/sparql-results+json", "application/json"],
+            ResponseType::XML => &["application/sparql-results+xml", "application/xml", "text/xml"],
+            ResponseType::TSV => &["text/tab-separated-values"],
+            ResponseType::CSV => &["text/csv"],
+            ResponseType::TURTLE => &["text/turtle", "application/x-turtle"],
+            ResponseType::NTRIPLES => &["application/n-triples"],
+            ResponseType::NQUADS => &["application/n-quads"],
+        }
+    }
+
+    // Short names accepted in the `format` request parameter
+    pub fn format_names(&self) -> &'static [&'static str] {
+        match self {
+            ResponseType::JSON => &["json", "srj"],
+            ResponseType::XML => &["xml", "srx"],
+            ResponseType::TSV => &["tsv"],
+            ResponseType::CSV => &["csv"],
+            ResponseType::TURTLE => &["turtle", "ttl"],
+            ResponseType::NTRIPLES => &["ntriples", "nt"],
+            ResponseType::NQUADS => &["nquads", "nq"],
         }
     }
 }
@@ -27,28 +80,21 @@ impl std::fmt::Display for ResponseType {
     }
 }
 
-impl From<&str> for ResponseType {
-    fn from(s: &str) -> Self {
-        match s {
-            "JSON" => ResponseType::JSON,
-            "XML" => ResponseType::XML,
-            "TSV" => ResponseType::TSV,
-            "CSV" => ResponseType::CSV,
-            "TURTLE" => ResponseType::TURTLE,
-            _ => panic!("Invalid response type string"),
-        }
-    }
-}
+// A format as a client names it: a short name of the `format` parameter, a media type or
+// the name of the variant, ignoring case and media type parameters
+impl std::str::FromStr for ResponseType {
+    type Err = ResponseTypeException;
 
-impl ResponseType {
-    pub fn from_str(s: &str) -> Result<ResponseType, LogicException> {
-        match s {
-            "JSON" => Ok(ResponseType::JSON),
-            "XML" => Ok(ResponseType::XML),
-            "TSV" => Ok(ResponseType::TSV),
-            "CSV" => Ok(ResponseType::CSV),
-            "TURTLE" => Ok(ResponseType::TURTLE),
-            _ => Err(LogicException::new("Unmanaged ResposeType in response_type_to_string")),
-        }
+    fn from_str(s: &str) -> Result<ResponseType, ResponseTypeException> {
+        let name = s.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        ResponseType::ALL
+            .iter()
+            .find(|response_type| {
+                response_type.format_names().contains(&name.as_str())
+                    || response_type.media_types().contains(&name.as_str())
+                    || ResponseType::response_type_to_string(**response_type).eq_ignore_ascii_case(&name)
+            })
+            .copied()
+            .ok_or_else(|| ResponseTypeException::new(&format!("Unknown response format `{}`", s.trim())))
     }
 }
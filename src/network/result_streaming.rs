@@ -0,0 +1,109 @@
+use std::error::Error;
+use futures::{Stream, StreamExt};
+use tokio::io::AsyncWrite;
+
+use crate::network::http::{ChunkedResponseWriter, HttpResponse, HttpVersion};
+use crate::network::response_type::ResponseType;
+use crate::query::exceptions::LogicException;
+use crate::query::rdf_terms::{RdfQuad, Solution};
+use crate::query::result_writers::{graph_writer, solution_writer};
+
+// Serialized results are sent to the socket every time this many bytes are buffered
+pub const FLUSH_THRESHOLD: usize = 64 * 1024;
+
+pub type StreamError = Box<dyn Error + Send + Sync>;
+
+// Streams the solutions of a SELECT query as a chunked 200 response (unframed for an
+// HTTP/1.0 request, closing the connection), pulling them as the chunks are written. The first solution is computed before sending anything, so
+// errors found early (parsing, timeouts, ...) are returned to the caller and can still
+// get a proper status. Errors after the response started are reported by closing the
+// connection.
+pub async fn stream_solutions<W, S>(
+    writer: &mut W,
+    response_type: ResponseType,
+    variables: &[String],
+    mut solutions: S,
+    version: HttpVersion,
+    keep_alive: bool,
+) -> Result<(), StreamError>
+where
+    W: AsyncWrite + Unpin,
+    S: Stream<Item = Result<Solution, StreamError>> + Unpin,
+{
+    let mut serializer = solution_writer(response_type).ok_or_else(|| {
+        LogicException::new(&format!("{} can not serialize solutions", response_type))
+    })?;
+
+    let first = solutions.next().await.transpose()?;
+
+    let headers = vec![("Content-Type".to_string(), response_type.content_type().to_string())];
+    let mut chunked = ChunkedResponseWriter::start(writer, 200, &headers, version, keep_alive).await?;
+
+    let mut buffer = Vec::with_capacity(FLUSH_THRESHOLD * 2);
+    serializer.write_head(&mut buffer, variables)?;
+    if let Some(first) = first {
+        serializer.write_solution(&mut buffer, &first)?;
+        while let Some(solution) = solutions.next().await {
+            serializer.write_solution(&mut buffer, &solution?)?;
+            if buffer.len() >= FLUSH_THRESHOLD {
+                chunked.write_chunk(&buffer).await?;
+                buffer.clear();
+            }
+        }
+    }
+    serializer.finish(&mut buffer)?;
+    chunked.write_chunk(&buffer).await?;
+    chunked.finish().await?;
+    Ok(())
+}
+
+// Streams the statements of a CONSTRUCT or DESCRIBE query as a chunked 200 response,
+// with the same error handling as `stream_solutions`.
+pub async fn stream_graph<W, S>(
+    writer: &mut W,
+    response_type: ResponseType,
+    prefixes: &[(String, String)],
+    mut quads: S,
+    version: HttpVersion,
+    keep_alive: bool,
+) -> Result<(), StreamError>
+where
+    W: AsyncWrite + Unpin,
+    S: Stream<Item = Result<RdfQuad, StreamError>> + Unpin,
+{
+    let mut serializer = graph_writer(response_type, prefixes).ok_or_else(|| {
+        LogicException::new(&format!("{} can not serialize a graph", response_type))
+    })?;
+
+    let first = quads.next().await.transpose()?;
+
+    let headers = vec![("Content-Type".to_string(), response_type.content_type().to_string())];
+    let mut chunked = ChunkedResponseWriter::start(writer, 200, &headers, version, keep_alive).await?;
+
+    let mut buffer = Vec::with_capacity(FLUSH_THRESHOLD * 2);
+    serializer.write_head(&mut buffer)?;
+    if let Some(first) = first {
+        serializer.write_quad(&mut buffer, &first)?;
+        while let Some(quad) = quads.next().await {
+            serializer.write_quad(&mut buffer, &quad?)?;
+            if buffer.len() >= FLUSH_THRESHOLD {
+                chunked.write_chunk(&buffer).await?;
+                buffer.clear();
+            }
+        }
+    }
+    serializer.finish(&mut buffer)?;
+    chunked.write_chunk(&buffer).await?;
+    chunked.finish().await?;
+    Ok(())
+}
+
+// Builds the complete response of an ASK query
+pub fn boolean_response(response_type: ResponseType, value: bool) -> Result<HttpResponse, StreamError> {
+    let mut serializer = solution_writer(response_type).ok_or_else(|| {
+        LogicException::new(&format!("{} can not serialize a boolean result", response_type))
+    })?;
+    let mut body = Vec::new();
+    serializer.write_boolean(&mut body, value)?;
+    Ok(HttpResponse::with_body(200, response_type.content_type(), body))
+}
@@ -1,18 +1,137 @@
 use std::sync::{Arc, Weak};
 use std::time::Duration;
+use futures::stream::{self, StreamExt};
 use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::runtime::Handle;
 use tokio::time::timeout;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, oneshot, Mutex};
 
-use crate::query::query_contexts::QueryContext;
-//use crate::query::executor::query_executor::QueryExecutor;
-//use crate::network::response_type::ResponseType;
+use crate::network::content_negotiation::negotiate_response_type;
+use crate::network::http::{write_continue, HttpRequest, HttpResponse, HttpVersion, RequestReader};
+use crate::network::result_streaming::{boolean_response, stream_graph, stream_solutions, StreamError};
+use crate::network::sparql_protocol::{error_response, parse_sparql_request, SparqlQueryRequest, SPARQL_ENDPOINT_PATH};
 use crate::network::sparql_servers::Server;
+use crate::query::algebra::{Dataset, Query};
+use crate::query::exceptions::LogicException;
+use crate::query::executor::{Interruption, TemporaryPages};
+use crate::query::query_contexts::QueryContext;
+use crate::query::query_executor::{execute_query, QueryResults, QueryTerms};
+use crate::query::rdf_terms::{RdfQuad, Solution};
+use crate::query::sparql_parser::parse_query;
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::iri_prefixes::IriPrefixes;
+use crate::storage::quad_indexes::QuadIndexes;
+use crate::storage::string_manager::StringManager;
+
+// Results computed ahead of the ones sent to the client, at most
+const RESULTS_CHANNEL_CAPACITY: usize = 1024;
+
+// What answering a request produced: a response still to be written, or results that were
+// streamed to the client. A stream that failed half way leaves the connection unusable.
+enum Answer {
+    Response(HttpResponse),
+    Streamed { completed: bool },
+}
+
+// What a query produced, its solutions or statements follow through the channels of
+// `RunningQuery`
+enum ResultsHead {
+    Boolean(bool),
+    Solutions(Vec<String>),
+    Graph,
+}
+
+// A query running in a blocking thread, its results received as they are computed
+struct RunningQuery {
+    head: oneshot::Receiver<Result<ResultsHead, StreamError>>,
+    solutions: mpsc::Receiver<Result<Solution, StreamError>>,
+    quads: mpsc::Receiver<Result<RdfQuad, StreamError>>,
+}
+
+// Where the thread running a query sends its results. A closed channel means the session
+// stopped reading them, the query ends then.
+struct ResultSenders {
+    head: oneshot::Sender<Result<ResultsHead, StreamError>>,
+    solutions: mpsc::Sender<Result<Solution, StreamError>>,
+    quads: mpsc::Sender<Result<RdfQuad, StreamError>>,
+}
+
+impl ResultSenders {
+    fn send(self, results: QueryResults) {
+        match results {
+            QueryResults::Boolean(value) => {
+                let _ = self.head.send(Ok(ResultsHead::Boolean(value)));
+            }
+            QueryResults::Solutions { variables, solutions } => {
+                if self.head.send(Ok(ResultsHead::Solutions(variables))).is_ok() {
+                    for solution in solutions {
+                        if self.solutions.blocking_send(solution).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            QueryResults::Graph(quads) => {
+                if self.head.send(Ok(ResultsHead::Graph)).is_ok() {
+                    for quad in quads {
+                        if self.quads.blocking_send(quad).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn fail(self, error: StreamError) {
+        let _ = self.head.send(Err(error));
+    }
+}
+
+// Finishes the context of a query when the thread running it ends, however it ends
+struct QueryContextGuard {
+    runtime: Handle,
+    server: Arc<Mutex<Server>>,
+    query_ctx: Arc<Mutex<QueryContext>>,
+}
+
+impl Drop for QueryContextGuard {
+    fn drop(&mut self) {
+        let server = Arc::clone(&self.server);
+        let query_ctx = Arc::clone(&self.query_ctx);
+        self.runtime.spawn(async move { server.lock().await.finish_query_context(&query_ctx).await });
+    }
+}
+
+type Database = (Arc<BufferManager>, Arc<StringManager>, Arc<IriPrefixes>);
+
+// Body of the blocking thread of a query
+fn run_query(query: Query, database: Database, interruption: Interruption, worker_index: usize, senders: ResultSenders) {
+    let (buffer_manager, string_manager, iri_prefixes) = database;
+    let indexes = match QuadIndexes::open(&buffer_manager) {
+        Ok(indexes) => indexes,
+        Err(e) => return senders.fail(e),
+    };
+    let terms = QueryTerms::new(&string_manager, &iri_prefixes);
+    let pages = TemporaryPages::new(&buffer_manager, worker_index);
+    let results = execute_query(&query, &indexes, &terms, interruption, Some(pages));
+    match results {
+        Ok(results) => senders.send(results),
+        Err(e) => senders.fail(e),
+    }
+}
+
+// The first result, received before the response starts so an error computing it (a
+// timeout...) still gets an error status
+async fn first_result<T>(receiver: &mut mpsc::Receiver<Result<T, StreamError>>) -> Result<Option<T>, StreamError> {
+    receiver.recv().await.transpose()
+}
 
 pub struct Session {
     server: Weak<Mutex<Server>>,
-    stream: TcpStream,
+    reader: RequestReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
     timeout: Duration,
 }
 
@@ -22,45 +141,192 @@ impl Session {
         stream: TcpStream,
         timeout: Duration,
     ) -> Self {
+        let (read_half, write_half) = stream.into_split();
         Self {
             server,
-            stream,
+            reader: RequestReader::new(read_half),
+            writer: write_half,
             timeout,
         }
     }
 
+    // Serves requests until the client closes the connection, asks to close it,
+    // sends an invalid request or stays idle longer than the timeout.
     pub async fn run(mut self) {
         loop {
-            let result = timeout(self.timeout, self.do_read()).await;
-            match result {
-                Ok(_) => {}
-                Err(_) => {
-                    eprintln!("Session timed out");
-                    break;
-                }
+            if self.server_is_shutting_down().await {
+                break;
+            }
+
+            let keep_alive = self.do_read().await;
+
+            if !keep_alive {
+                break;
             }
         }
     }
 
-    async fn do_read(&mut self) {
-        let mut buffer = vec![0; 1024];
-        match self.stream.read(&mut buffer).await {
-            Ok(bytes_read) => {
-                if bytes_read == 0 {
-                    return;
-                }
-                let request = String::from_utf8_lossy(&buffer[..bytes_read]);
-                // Process the request
-                println!("Received request: {}", request);
-                // Write response
-                let response = "HTTP/1.1 200 OK\r\n\r\nHello, World!";
-                if let Err(e) = self.stream.write_all(response.as_bytes()).await {
-                    eprintln!("Error writing response: {}", e);
-                }
+    // Reads and answers one request. Returns whether the connection should be kept open.
+    // The timeout only applies to reading the request, a running query is interrupted by
+    // the timeout task of the server instead.
+    async fn do_read(&mut self) -> bool {
+        // An idle connection is closed without an error
+        let head = match timeout(self.timeout, self.reader.read_head()).await {
+            Ok(Ok(Some(head))) => head,
+            Ok(Ok(None)) | Err(_) => return false,
+            Ok(Err(e)) => {
+                self.write_response(&error_response(&e), false).await;
+                return false;
+            }
+        };
+
+        if head.expects_continue() {
+            if let Err(e) = write_continue(&mut self.writer).await {
+                eprintln!("Error writing response: {}", e);
+                return false;
+            }
+        }
+
+        let body = match timeout(self.timeout, self.reader.read_body(&head)).await {
+            Ok(Ok(body)) => body,
+            Err(_) => return false,
+            Ok(Err(e)) => {
+                self.write_response(&error_response(&e), false).await;
+                return false;
             }
+        };
+
+        let request = HttpRequest { head, body };
+        let keep_alive = request.head.keep_alive();
+        match self.handle_request(&request, keep_alive).await {
+            Answer::Response(response) => self.write_response(&response, keep_alive).await && keep_alive,
+            // Without the chunked coding (HTTP/1.0) the end of the body is the end of the connection
+            Answer::Streamed { completed } => completed && keep_alive && request.head.version == HttpVersion::Http11,
+        }
+    }
+
+    async fn handle_request(&mut self, request: &HttpRequest, keep_alive: bool) -> Answer {
+        if request.path() != SPARQL_ENDPOINT_PATH {
+            return Answer::Response(HttpResponse::error(404, &format!("No resource at {}", request.path())));
+        }
+
+        let sparql_request = match parse_sparql_request(request) {
+            Ok(sparql_request) => sparql_request,
+            Err(e) => return Answer::Response(error_response(&e)),
+        };
+
+        let query = match parse_query(&sparql_request.query) {
+            Ok(query) => query,
+            Err(e) => return Answer::Response(error_response(&e)),
+        };
+
+        let response_type = match negotiate_response_type(
+            query.form,
+            request.header("Accept"),
+            sparql_request.parameter("format"),
+        ) {
+            Ok(response_type) => response_type,
+            Err(e) => return Answer::Response(error_response(&e)),
+        };
+
+        let prefixes = query.prefixes.clone();
+        let mut running = match self.execute_query(&sparql_request, query).await {
+            Ok(running) => running,
+            Err(e) => return Answer::Response(error_response(e.as_ref())),
+        };
+        let head = match (&mut running.head).await {
+            Ok(Ok(head)) => head,
+            Ok(Err(e)) => return Answer::Response(error_response(e.as_ref())),
+            Err(_) => return Answer::Response(error_response(&LogicException::new("The query ended without results"))),
+        };
+
+        let streamed = match head {
+            ResultsHead::Boolean(value) => {
+                return Answer::Response(boolean_response(response_type, value).unwrap_or_else(|e| error_response(e.as_ref())))
+            }
+            ResultsHead::Solutions(variables) => {
+                let first = match first_result(&mut running.solutions).await {
+                    Ok(first) => first,
+                    Err(e) => return Answer::Response(error_response(e.as_ref())),
+                };
+                let rest = stream::poll_fn(|cx| running.solutions.poll_recv(cx));
+                let solutions = stream::iter(first.map(Ok)).chain(rest);
+                stream_solutions(&mut self.writer, response_type, &variables, solutions, request.head.version, keep_alive).await
+            }
+            ResultsHead::Graph => {
+                let first = match first_result(&mut running.quads).await {
+                    Ok(first) => first,
+                    Err(e) => return Answer::Response(error_response(e.as_ref())),
+                };
+                let rest = stream::poll_fn(|cx| running.quads.poll_recv(cx));
+                let quads = stream::iter(first.map(Ok)).chain(rest);
+                stream_graph(&mut self.writer, response_type, &prefixes, quads, request.head.version, keep_alive).await
+            }
+        };
+        if let Err(e) = &streamed {
+            eprintln!("Error streaming results: {}", e);
+        }
+        Answer::Streamed { completed: streamed.is_ok() }
+    }
+
+    // Starts the query in a blocking thread, as the operators read the database files. The
+    // query is interrupted by the timeout task of the server once the session timeout passes,
+    // and ends early if the session stops reading its results.
+    async fn execute_query(&self, request: &SparqlQueryRequest, mut query: Query) -> Result<RunningQuery, StreamError> {
+        // The dataset of the protocol replaces FROM and FROM NAMED
+        if !request.dataset.is_empty() {
+            query.dataset = Dataset {
+                default_graphs: request.dataset.default_graph_uris.clone(),
+                named_graphs: request.dataset.named_graph_uris.clone(),
+            };
+        }
+
+        let server = self.server.upgrade().ok_or_else(|| LogicException::new("The server is shutting down"))?;
+        let (database, query_ctx) = {
+            let server = server.lock().await;
+            let database = match (&server.buffer_manager, &server.string_manager, &server.iri_prefixes) {
+                (Some(buffer_manager), Some(string_manager), Some(iri_prefixes)) => {
+                    (Arc::clone(buffer_manager), Arc::clone(string_manager), Arc::clone(iri_prefixes))
+                }
+                _ => return Err(Box::new(LogicException::new("The server has no database"))),
+            };
+            (database, server.register_query_context(self.timeout).await)
+        };
+        let (interruption, worker_index) = {
+            let qc = query_ctx.lock().await;
+            (qc.thread_info.interruption(), qc.thread_info.worker_index as usize)
+        };
+        let guard = QueryContextGuard { runtime: Handle::current(), server, query_ctx };
+
+        let (head_sender, head) = oneshot::channel();
+        let (solution_sender, solutions) = mpsc::channel(RESULTS_CHANNEL_CAPACITY);
+        let (quad_sender, quads) = mpsc::channel(RESULTS_CHANNEL_CAPACITY);
+        let senders = ResultSenders { head: head_sender, solutions: solution_sender, quads: quad_sender };
+        tokio::task::spawn_blocking(move || {
+            let _guard = guard;
+            run_query(query, database, interruption, worker_index, senders);
+        });
+        Ok(RunningQuery { head, solutions, quads })
+    }
+
+    async fn write_response(&mut self, response: &HttpResponse, keep_alive: bool) -> bool {
+        match response.write_to(&mut self.writer, keep_alive).await {
+            Ok(()) => true,
             Err(e) => {
-                eprintln!("Error reading from socket: {}", e);
+                eprintln!("Error writing response: {}", e);
+                false
+            }
+        }
+    }
+
+    async fn server_is_shutting_down(&self) -> bool {
+        match self.server.upgrade() {
+            Some(server) => {
+                let shutdown = server.lock().await.shutdown_server.clone();
+                let is_shutdown = *shutdown.lock().await;
+                is_shutdown
             }
+            None => true,
         }
     }
 }
@@ -0,0 +1,149 @@
+use std::error::Error;
+
+use crate::network::exceptions::{HttpException, ResponseTypeException};
+use crate::network::http::{media_type, parse_form_urlencoded, HttpRequest, HttpResponse};
+use crate::query::exceptions::{
+    InterruptedException, NotSupportedException, QueryException, QuerySemanticException,
+};
+
+// Path where the SPARQL 1.1 Protocol query operation is served
+pub const SPARQL_ENDPOINT_PATH: &str = "/sparql";
+
+// RDF dataset given through the protocol. When present it overrides any
+// FROM / FROM NAMED clauses of the query (SPARQL 1.1 Protocol section 2.1.4).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SparqlDataset {
+    pub default_graph_uris: Vec<String>,
+    pub named_graph_uris: Vec<String>,
+}
+
+impl SparqlDataset {
+    pub fn is_empty(&self) -> bool {
+        self.default_graph_uris.is_empty() && self.named_graph_uris.is_empty()
+    }
+}
+
+// A query operation received through any of the three protocol bindings
+#[derive(Debug, Clone)]
+pub struct SparqlQueryRequest {
+    pub query: String,
+    pub dataset: SparqlDataset,
+    // Every other parameter of the request (e.g. `format`), in the order received
+    pub parameters: Vec<(String, String)>,
+}
+
+impl SparqlQueryRequest {
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(parameter_name, _)| parameter_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// Extracts the query operation from an HTTP request, following the
+// query via GET, query via URL-encoded POST and query via POST directly bindings.
+pub fn parse_sparql_request(request: &HttpRequest) -> Result<SparqlQueryRequest, HttpException> {
+    let url_parameters = match request.query_string() {
+        Some(query_string) => parse_form_urlencoded(query_string)?,
+        None => Vec::new(),
+    };
+
+    match request.method() {
+        "GET" => from_parameters(url_parameters, None),
+        "POST" => {
+            let content_type = match request.header("Content-Type") {
+                Some(content_type) => media_type(content_type),
+                None => return Err(HttpException::new(415, "Missing Content-Type")),
+            };
+            match content_type.as_str() {
+                "application/x-www-form-urlencoded" => {
+                    let body = std::str::from_utf8(&request.body)
+                        .map_err(|_| HttpException::new(400, "Request body is not valid UTF-8"))?;
+                    if url_parameters.iter().any(|(name, _)| name == "query") {
+                        return Err(HttpException::new(400, "The query must be sent in the request body"));
+                    }
+                    let mut parameters = parse_form_urlencoded(body)?;
+                    parameters.extend(url_parameters);
+                    from_parameters(parameters, None)
+                }
+                "application/sparql-query" => {
+                    let query = String::from_utf8(request.body.clone())
+                        .map_err(|_| HttpException::new(400, "Request body is not valid UTF-8"))?;
+                    from_parameters(url_parameters, Some(query))
+                }
+                "application/sparql-update" => {
+                    Err(HttpException::new(400, "SPARQL Update is not supported by this endpoint"))
+                }
+                _ => Err(HttpException::new(
+                    415,
+                    &format!("Unsupported Content-Type `{}`", content_type),
+                )),
+            }
+        }
+        method => Err(HttpException::new(405, &format!("Method {} not allowed", method))),
+    }
+}
+
+fn from_parameters(
+    parameters: Vec<(String, String)>,
+    body_query: Option<String>,
+) -> Result<SparqlQueryRequest, HttpException> {
+    let mut query = body_query;
+    let query_in_body = query.is_some();
+    let mut dataset = SparqlDataset::default();
+    let mut others = Vec::new();
+
+    for (name, value) in parameters {
+        match name.as_str() {
+            "query" => {
+                if query_in_body {
+                    return Err(HttpException::new(400, "The query must be sent in the request body only"));
+                }
+                if query.is_some() {
+                    return Err(HttpException::new(400, "Multiple query parameters"));
+                }
+                query = Some(value);
+            }
+            "update" | "using-graph-uri" | "using-named-graph-uri" => {
+                return Err(HttpException::new(400, "SPARQL Update is not supported by this endpoint"));
+            }
+            "default-graph-uri" => dataset.default_graph_uris.push(value),
+            "named-graph-uri" => dataset.named_graph_uris.push(value),
+            _ => others.push((name, value)),
+        }
+    }
+
+    match query {
+        Some(query) if !query.trim().is_empty() => Ok(SparqlQueryRequest {
+            query,
+            dataset,
+            parameters: others,
+        }),
+        _ => Err(HttpException::new(400, "Missing query")),
+    }
+}
+
+// Maps the errors of the protocol, parsing and execution layers to a response
+// with the status code the SPARQL 1.1 Protocol expects.
+pub fn error_response(error: &(dyn Error + 'static)) -> HttpResponse {
+    let status = if let Some(e) = error.downcast_ref::<HttpException>() {
+        e.status()
+    } else if error.is::<QueryException>() || error.is::<QuerySemanticException>() {
+        400
+    } else if error.is::<ResponseTypeException>() {
+        406
+    } else if error.is::<NotSupportedException>() {
+        501
+    } else if error.is::<InterruptedException>() {
+        503
+    } else {
+        500
+    };
+
+    let mut response = HttpResponse::error(status, &error.to_string());
+    if status == 405 {
+        response.set_header("Allow", "GET, POST");
+    }
+    response
+}
@@ -1,4 +1,5 @@
 
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{SystemTime, Duration};
 use tokio::signal::unix::{signal, SignalKind};
@@ -6,19 +7,26 @@ use tokio::time;
 use std::error::Error;
 
 
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::Mutex;
 
 
 use crate::network::listener::Listener;
 use crate::query::query_contexts::QueryContext;
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::iri_prefixes::IriPrefixes;
+use crate::storage::string_manager::StringManager;
 
 pub const DEFAULT_PORT: u16 = 8080;
 
 pub struct Server {
     //thread_info_vec_mutex: Mutex<()>,
-    query_contexts: Vec<Arc<Mutex<QueryContext>>>,
+    // Contexts of the running queries, watched by the timeout task
+    query_contexts: Arc<Mutex<Vec<Arc<Mutex<QueryContext>>>>>,
     pub shutdown_server: Arc<Mutex<bool>>,
-    interrupt: Arc<Mutex<mpsc::Receiver<bool>>>,
+    // Pages of the database files, set by the orchestrator before running the server
+    pub buffer_manager: Option<Arc<BufferManager>>,
+    pub string_manager: Option<Arc<StringManager>>,
+    pub iri_prefixes: Option<Arc<IriPrefixes>>,
 }
 
 impl Server {
@@ -26,12 +34,66 @@ impl Server {
     pub fn new() -> Arc<Mutex<Self>>  {
         Arc::new(Mutex::new(Self {
             shutdown_server: Arc::new(Mutex::new(false)),
-            query_contexts: Vec::new(),
-            interrupt: Arc::new(Mutex::new(mpsc::channel(1).1)),
+            query_contexts: Arc::new(Mutex::new(Vec::new())),
+            buffer_manager: None,
+            string_manager: None,
+            iri_prefixes: None,
             //thread_info_vec_mutex: Mutex::new(()),
         }))
     }
 
+    // Context of a new query, interrupted by the timeout task once `timeout` has passed.
+    // The query sets `finished` when it ends so the context is forgotten.
+    // The query gets the first worker no running query has, so it has the private pages of
+    // the worker for itself. When every worker is busy the workers are shared.
+    pub async fn register_query_context(&self, timeout: Duration) -> Arc<Mutex<QueryContext>> {
+        let mut query_ctx = QueryContext::new();
+        query_ctx.thread_info.time_start = SystemTime::now();
+        query_ctx.thread_info.timeout = query_ctx.thread_info.time_start + timeout;
+
+        let mut query_contexts = self.query_contexts.lock().await;
+        let workers = self.buffer_manager.as_ref().map_or(1, |buffer_manager| buffer_manager.workers().max(1));
+        let busy = self.busy_workers(&query_contexts).await;
+        query_ctx.thread_info.worker_index =
+            (0..workers as u32).find(|index| !busy.contains(index)).unwrap_or((query_contexts.len() % workers) as u32);
+
+        let query_ctx = Arc::new(Mutex::new(query_ctx));
+        query_contexts.push(Arc::clone(&query_ctx));
+        query_ctx
+    }
+
+    // Marks the query as finished and drops the temporary pages of its worker, unless
+    // another running query shares the worker
+    pub async fn finish_query_context(&self, query_ctx: &Arc<Mutex<QueryContext>>) {
+        let worker_index = {
+            let mut qc = query_ctx.lock().await;
+            qc.thread_info.finished = true;
+            qc.thread_info.worker_index
+        };
+        let query_contexts = self.query_contexts.lock().await;
+        if self.busy_workers(&query_contexts).await.contains(&worker_index) {
+            return;
+        }
+        if let Some(buffer_manager) = &self.buffer_manager {
+            if let Ok(mut pool) = buffer_manager.private_pool(worker_index as usize) {
+                if let Err(e) = pool.clear() {
+                    eprintln!("Error dropping the temporary pages of worker {}: {}", worker_index, e);
+                }
+            }
+        }
+    }
+
+    async fn busy_workers(&self, query_contexts: &[Arc<Mutex<QueryContext>>]) -> Vec<u32> {
+        let mut busy = Vec::new();
+        for query_ctx in query_contexts {
+            let qc = query_ctx.lock().await;
+            if !qc.thread_info.finished {
+                busy.push(qc.thread_info.worker_index);
+            }
+        }
+        busy
+    }
+
     pub async fn execute_timeouts(&self) {
         let shutdown_server = self.shutdown_server.clone();
         let query_contexts = self.query_contexts.clone();
@@ -45,13 +107,22 @@ impl Server {
                         break; // Exit the loop if the server is shutting down
                     }
 
-                    // Asynchronously access each QueryContext
-                    for query_ctx in query_contexts.iter() {
-                        let mut qc = query_ctx.lock().await; // Await the lock
+                    // Asynchronously access each QueryContext, the running query sees the
+                    // flag without locking its context
+                    let mut query_contexts = query_contexts.lock().await;
+                    let mut running = Vec::with_capacity(query_contexts.len());
+                    for query_ctx in query_contexts.drain(..) {
+                        let qc = query_ctx.lock().await; // Await the lock
+                        if qc.thread_info.finished {
+                            continue;
+                        }
                         if qc.thread_info.timeout <= now {
-                            qc.thread_info.interruption_requested = true;
+                            qc.thread_info.interruption_requested.store(true, Ordering::Relaxed);
                         }
+                        drop(qc);
+                        running.push(query_ctx);
                     }
+                    *query_contexts = running;
                 }
                 // Asynchronously wait before the next iteration
                 time::sleep(Duration::from_secs(1)).await;
@@ -63,7 +134,10 @@ impl Server {
         server: Arc<Mutex<Self>>,      
         port: u16,
         //worker_threads: usize, 
-        _timeout: Duration) -> Result<(), Box<dyn Error>> {       
+        timeout: Duration) -> Result<(), Box<dyn Error>> {       
+
+            // Interrupt the queries that run for too long
+            server.lock().await.execute_timeouts().await;
 
             let server_clone_for_signals = server.clone();
             let server_clone_for_listener = server.clone();
@@ -75,7 +149,8 @@ impl Server {
         let server_loop = tokio::spawn(async move {
             Server::start_listener(
                 server_clone_for_listener,
-                port).await; 
+                port,
+                timeout).await; 
         });        
 
 
@@ -89,19 +164,11 @@ impl Server {
 
     async fn start_listener(
         server: Arc<Mutex<Self>>,         
-        port: u16 ) -> Option<Result<(), Box<std::io::Error>>> {      
-
-
+        port: u16,
+        // Of the queries and of the idle connections
+        timeout: Duration) -> Option<Result<(), Box<std::io::Error>>> {      
 
-        let io_context_result = 
-            tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .build();
 
-        let io_context = match io_context_result {
-            Ok(runtime) => Arc::new(runtime),
-            Err(e) => return Some(Err(Box::new(e))),
-                    };        
 
         let server_weak = 
             Arc::downgrade(&server);
@@ -120,9 +187,8 @@ impl Server {
         let listener_result = 
             Listener::new(
                 server_weak, 
-                io_context, 
                 endpoint, 
-                Duration::from_secs(10))
+                timeout)
                     .await;
             
         if let Err(err) = listener_result {
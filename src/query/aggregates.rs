@@ -0,0 +1,183 @@
+use crate::query::algebra::AggregateFunction;
+use crate::query::executor::{Accumulator, AccumulatorFactory, ExecutionError};
+use crate::query::expressions::{numeric_operation, order_terms, Arithmetic};
+use crate::query::object_id::ObjectId;
+use crate::query::query_executor::QueryTerms;
+use crate::query::rdf_terms::{RdfTerm, XSD_INTEGER};
+
+// Accumulators of an aggregate function. `star` is set for COUNT(*), which counts the
+// results whatever their values. The other aggregates read the value of one expression:
+// COUNT, MIN, MAX and SAMPLE skip the unbound values and errors, while they make SUM,
+// AVG and GROUP_CONCAT an error, as their operations have no value for them.
+pub fn accumulator_factory<'a>(
+    function: AggregateFunction,
+    separator: Option<String>,
+    star: bool,
+    terms: &'a QueryTerms<'a>,
+) -> AccumulatorFactory<'a> {
+    match function {
+        AggregateFunction::Count => Box::new(move || Box::new(Count { star, count: 0, terms })),
+        AggregateFunction::Sum => Box::new(move || Box::new(Sum { sum: Some(integer(0)), terms })),
+        AggregateFunction::Avg => Box::new(move || Box::new(Avg { sum: Some(integer(0)), count: 0, terms })),
+        AggregateFunction::Min => Box::new(move || Box::new(Extreme { maximum: false, value: ObjectId::NULL, term: None, terms })),
+        AggregateFunction::Max => Box::new(move || Box::new(Extreme { maximum: true, value: ObjectId::NULL, term: None, terms })),
+        AggregateFunction::Sample => Box::new(|| Box::new(Sample { value: ObjectId::NULL })),
+        AggregateFunction::GroupConcat => {
+            let separator = separator.unwrap_or_else(|| " ".to_string());
+            Box::new(move || Box::new(GroupConcat { separator: separator.clone(), value: Some(String::new()), first: true, terms }))
+        }
+    }
+}
+
+fn integer(value: i64) -> RdfTerm {
+    RdfTerm::typed_literal(&value.to_string(), XSD_INTEGER)
+}
+
+struct Count<'a> {
+    star: bool,
+    count: i64,
+    terms: &'a QueryTerms<'a>,
+}
+
+impl Accumulator for Count<'_> {
+    fn add(&mut self, values: &[ObjectId]) -> Result<(), ExecutionError> {
+        if self.star || values.iter().all(|value| !value.is_null()) {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn value(&self) -> Result<ObjectId, ExecutionError> {
+        self.terms.get_or_create(&integer(self.count))
+    }
+}
+
+// Adds the numbers of the group, `None` once a value is not a number
+struct Sum<'a> {
+    sum: Option<RdfTerm>,
+    terms: &'a QueryTerms<'a>,
+}
+
+impl Accumulator for Sum<'_> {
+    fn add(&mut self, values: &[ObjectId]) -> Result<(), ExecutionError> {
+        self.sum = add_number(self.sum.take(), values, self.terms)?;
+        Ok(())
+    }
+
+    fn value(&self) -> Result<ObjectId, ExecutionError> {
+        term_or_null(self.sum.as_ref(), self.terms)
+    }
+}
+
+struct Avg<'a> {
+    sum: Option<RdfTerm>,
+    count: i64,
+    terms: &'a QueryTerms<'a>,
+}
+
+impl Accumulator for Avg<'_> {
+    fn add(&mut self, values: &[ObjectId]) -> Result<(), ExecutionError> {
+        self.sum = add_number(self.sum.take(), values, self.terms)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn value(&self) -> Result<ObjectId, ExecutionError> {
+        // The average of no values is 0
+        if self.count == 0 {
+            return self.terms.get_or_create(&integer(0));
+        }
+        let average = self.sum.as_ref().and_then(|sum| numeric_operation(Arithmetic::Divide, sum, &integer(self.count)));
+        term_or_null(average.as_ref(), self.terms)
+    }
+}
+
+fn add_number(sum: Option<RdfTerm>, values: &[ObjectId], terms: &QueryTerms) -> Result<Option<RdfTerm>, ExecutionError> {
+    let (sum, value) = match (sum, terms.decode(values[0])?) {
+        (Some(sum), Some(value)) => (sum, value),
+        _ => return Ok(None),
+    };
+    Ok(numeric_operation(Arithmetic::Add, &sum, &value))
+}
+
+fn term_or_null(term: Option<&RdfTerm>, terms: &QueryTerms) -> Result<ObjectId, ExecutionError> {
+    match term {
+        Some(term) => terms.get_or_create(term),
+        None => Ok(ObjectId::NULL),
+    }
+}
+
+// MIN and MAX, in the order of ORDER BY. The term of the value is kept to compare it with
+// the next ones.
+struct Extreme<'a> {
+    maximum: bool,
+    value: ObjectId,
+    term: Option<RdfTerm>,
+    terms: &'a QueryTerms<'a>,
+}
+
+impl Accumulator for Extreme<'_> {
+    fn add(&mut self, values: &[ObjectId]) -> Result<(), ExecutionError> {
+        let value = values[0];
+        if value.is_null() || value == self.value {
+            return Ok(());
+        }
+        let term = self.terms.decode(value)?;
+        let order = order_terms(term.as_ref(), self.term.as_ref());
+        if self.value.is_null() || (self.maximum && order.is_gt()) || (!self.maximum && order.is_lt()) {
+            self.value = value;
+            self.term = term;
+        }
+        Ok(())
+    }
+
+    fn value(&self) -> Result<ObjectId, ExecutionError> {
+        Ok(self.value)
+    }
+}
+
+struct Sample {
+    value: ObjectId,
+}
+
+impl Accumulator for Sample {
+    fn add(&mut self, values: &[ObjectId]) -> Result<(), ExecutionError> {
+        if self.value.is_null() {
+            self.value = values[0];
+        }
+        Ok(())
+    }
+
+    fn value(&self) -> Result<ObjectId, ExecutionError> {
+        Ok(self.value)
+    }
+}
+
+// Joins the lexical forms of the literals of the group, `None` once a value is not a literal
+struct GroupConcat<'a> {
+    separator: String,
+    value: Option<String>,
+    first: bool,
+    terms: &'a QueryTerms<'a>,
+}
+
+impl Accumulator for GroupConcat<'_> {
+    fn add(&mut self, values: &[ObjectId]) -> Result<(), ExecutionError> {
+        let term = self.terms.decode(values[0])?;
+        match (self.value.as_mut(), term) {
+            (Some(concatenation), Some(RdfTerm::Literal { value, .. })) => {
+                if !self.first {
+                    concatenation.push_str(&self.separator);
+                }
+                concatenation.push_str(&value);
+                self.first = false;
+            }
+            _ => self.value = None,
+        }
+        Ok(())
+    }
+
+    fn value(&self) -> Result<ObjectId, ExecutionError> {
+        term_or_null(self.value.as_deref().map(RdfTerm::simple_literal).as_ref(), self.terms)
+    }
+}
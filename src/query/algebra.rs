@@ -0,0 +1,328 @@
+use crate::query::query_forms::QueryForm;
+use crate::query::rdf_terms::RdfTerm;
+
+// Prefix of the names of the variables the parser creates (aggregates, the middle of
+// path sequences...). Blank nodes of the patterns are variables named `_:label`. Neither
+// '.' nor ':' can be part of the name of a variable of the query.
+pub const INTERNAL_VAR_PREFIX: &str = ".";
+
+// A term of a pattern. Blank nodes are kept apart from variables: they behave as
+// variables in WHERE but are new blank nodes in a CONSTRUCT template.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TermPattern {
+    Variable(String),
+    BlankNode(String),
+    Term(RdfTerm),
+}
+
+impl TermPattern {
+    // Name of the variable the term is in WHERE, `None` for IRIs and literals
+    pub fn variable(&self) -> Option<String> {
+        match self {
+            TermPattern::Variable(name) => Some(name.clone()),
+            TermPattern::BlankNode(label) => Some(format!("_:{}", label)),
+            TermPattern::Term(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TriplePattern {
+    pub subject: TermPattern,
+    pub predicate: TermPattern,
+    pub object: TermPattern,
+}
+
+impl TriplePattern {
+    pub fn new(subject: TermPattern, predicate: TermPattern, object: TermPattern) -> Self {
+        Self { subject, predicate, object }
+    }
+}
+
+// IRI of a negated property set, `inverse` for the ones written `^iri`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NegatedIri {
+    pub iri: String,
+    pub inverse: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PropertyPath {
+    Iri(String),
+    Inverse(Box<PropertyPath>),
+    Sequence(Vec<PropertyPath>),
+    Alternative(Vec<PropertyPath>),
+    ZeroOrMore(Box<PropertyPath>),
+    OneOrMore(Box<PropertyPath>),
+    ZeroOrOne(Box<PropertyPath>),
+    NegatedSet(Vec<NegatedIri>),
+}
+
+// Semantics of a path pattern written with a path mode, which returns the paths themselves.
+// Without a mode a path pattern has the semantics of SPARQL 1.1: only its ends are returned,
+// once per pair of ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathMode {
+    // One path per pair of ends
+    Any,
+    AnyShortest,
+    // Every path of the smallest length for each pair of ends
+    AllShortest,
+    // Every path without a repeated edge
+    Trail,
+    // Every path without a repeated node, except for paths that end where they start
+    Simple,
+    // Every path without a repeated node
+    Acyclic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Sample,
+    GroupConcat,
+}
+
+// An aggregate of SELECT, HAVING or ORDER BY. In the expressions the parser replaces it by
+// an internal variable, bound by the `Group` of the query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    pub distinct: bool,
+    // `None` for COUNT(*)
+    pub expression: Option<Box<Expression>>,
+    // Separator of GROUP_CONCAT, a single space when the query has none
+    pub separator: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expression {
+    Variable(String),
+    Constant(RdfTerm),
+    Or(Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Equal(Box<Expression>, Box<Expression>),
+    NotEqual(Box<Expression>, Box<Expression>),
+    Less(Box<Expression>, Box<Expression>),
+    LessOrEqual(Box<Expression>, Box<Expression>),
+    Greater(Box<Expression>, Box<Expression>),
+    GreaterOrEqual(Box<Expression>, Box<Expression>),
+    In(Box<Expression>, Vec<Expression>),
+    NotIn(Box<Expression>, Vec<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+    UnaryPlus(Box<Expression>),
+    UnaryMinus(Box<Expression>),
+    Not(Box<Expression>),
+    // A built-in call, the name in upper case (e.g. "STRLEN")
+    Builtin(String, Vec<Expression>),
+    // A call of a function named by an IRI, such as a cast to an XSD datatype
+    Function(String, Vec<Expression>),
+    Exists(Box<GraphPattern>),
+    NotExists(Box<GraphPattern>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OrderCondition {
+    pub expression: Expression,
+    pub ascending: bool,
+}
+
+// The SPARQL algebra of a query (section 18 of the SPARQL 1.1 recommendation)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GraphPattern {
+    Bgp(Vec<TriplePattern>),
+    Path {
+        subject: TermPattern,
+        path: PropertyPath,
+        object: TermPattern,
+        mode: Option<PathMode>,
+        // Variable bound to the path, only with a mode
+        variable: Option<String>,
+    },
+    Join(Box<GraphPattern>, Box<GraphPattern>),
+    LeftJoin {
+        left: Box<GraphPattern>,
+        right: Box<GraphPattern>,
+        condition: Option<Expression>,
+    },
+    Union(Box<GraphPattern>, Box<GraphPattern>),
+    Minus(Box<GraphPattern>, Box<GraphPattern>),
+    Filter {
+        condition: Expression,
+        inner: Box<GraphPattern>,
+    },
+    Extend {
+        inner: Box<GraphPattern>,
+        variable: String,
+        expression: Expression,
+    },
+    // Rows of VALUES, `None` for UNDEF
+    Values {
+        variables: Vec<String>,
+        rows: Vec<Vec<Option<RdfTerm>>>,
+    },
+    Graph {
+        name: TermPattern,
+        inner: Box<GraphPattern>,
+    },
+    // Groups by the expressions (each one bound to a variable) and computes the aggregates
+    Group {
+        inner: Box<GraphPattern>,
+        by: Vec<(Expression, String)>,
+        aggregates: Vec<(String, Aggregate)>,
+    },
+    OrderBy {
+        inner: Box<GraphPattern>,
+        conditions: Vec<OrderCondition>,
+    },
+    Project {
+        inner: Box<GraphPattern>,
+        variables: Vec<String>,
+    },
+    Distinct(Box<GraphPattern>),
+    Reduced(Box<GraphPattern>),
+    Slice {
+        inner: Box<GraphPattern>,
+        offset: u64,
+        limit: Option<u64>,
+    },
+}
+
+impl GraphPattern {
+    // The pattern of `{}`, one solution without variables
+    pub fn empty() -> Self {
+        GraphPattern::Bgp(Vec::new())
+    }
+
+    // Variables that may be bound in the solutions, in the order they appear
+    pub fn in_scope_variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        self.add_in_scope_variables(&mut variables);
+        variables
+    }
+
+    fn add_in_scope_variables(&self, variables: &mut Vec<String>) {
+        match self {
+            GraphPattern::Bgp(triples) => {
+                for triple in triples {
+                    add_variable(variables, triple.subject.variable());
+                    add_variable(variables, triple.predicate.variable());
+                    add_variable(variables, triple.object.variable());
+                }
+            }
+            GraphPattern::Path { subject, object, variable, .. } => {
+                add_variable(variables, subject.variable());
+                add_variable(variables, object.variable());
+                add_variable(variables, variable.clone());
+            }
+            GraphPattern::Join(left, right)
+            | GraphPattern::LeftJoin { left, right, .. }
+            | GraphPattern::Union(left, right) => {
+                left.add_in_scope_variables(variables);
+                right.add_in_scope_variables(variables);
+            }
+            GraphPattern::Minus(left, _) => left.add_in_scope_variables(variables),
+            GraphPattern::Filter { inner, .. }
+            | GraphPattern::OrderBy { inner, .. }
+            | GraphPattern::Slice { inner, .. } => inner.add_in_scope_variables(variables),
+            GraphPattern::Distinct(inner) | GraphPattern::Reduced(inner) => inner.add_in_scope_variables(variables),
+            GraphPattern::Extend { inner, variable, .. } => {
+                inner.add_in_scope_variables(variables);
+                add_variable(variables, Some(variable.clone()));
+            }
+            GraphPattern::Graph { name, inner } => {
+                add_variable(variables, name.variable());
+                inner.add_in_scope_variables(variables);
+            }
+            GraphPattern::Group { by, aggregates, .. } => {
+                by.iter().for_each(|(_, name)| add_variable(variables, Some(name.clone())));
+                aggregates.iter().for_each(|(name, _)| add_variable(variables, Some(name.clone())));
+            }
+            GraphPattern::Values { variables: names, .. } | GraphPattern::Project { variables: names, .. } => {
+                names.iter().for_each(|name| add_variable(variables, Some(name.clone())));
+            }
+        }
+    }
+}
+
+impl Expression {
+    // Variables the expression reads, without the ones of EXISTS patterns
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        self.add_variables(&mut variables);
+        variables
+    }
+
+    fn add_variables(&self, variables: &mut Vec<String>) {
+        match self {
+            Expression::Variable(name) => add_variable(variables, Some(name.clone())),
+            Expression::Constant(_) | Expression::Exists(_) | Expression::NotExists(_) => {}
+            Expression::Or(a, b)
+            | Expression::And(a, b)
+            | Expression::Equal(a, b)
+            | Expression::NotEqual(a, b)
+            | Expression::Less(a, b)
+            | Expression::LessOrEqual(a, b)
+            | Expression::Greater(a, b)
+            | Expression::GreaterOrEqual(a, b)
+            | Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b) => {
+                a.add_variables(variables);
+                b.add_variables(variables);
+            }
+            Expression::In(a, list) | Expression::NotIn(a, list) => {
+                a.add_variables(variables);
+                list.iter().for_each(|e| e.add_variables(variables));
+            }
+            Expression::UnaryPlus(a) | Expression::UnaryMinus(a) | Expression::Not(a) => a.add_variables(variables),
+            Expression::Builtin(_, arguments) | Expression::Function(_, arguments) => {
+                arguments.iter().for_each(|e| e.add_variables(variables));
+            }
+        }
+    }
+}
+
+fn add_variable(variables: &mut Vec<String>, name: Option<String>) {
+    if let Some(name) = name {
+        if !variables.contains(&name) {
+            variables.push(name);
+        }
+    }
+}
+
+// FROM and FROM NAMED of a query
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dataset {
+    pub default_graphs: Vec<String>,
+    pub named_graphs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub form: QueryForm,
+    // The (prefix, namespace) pairs of the prologue, for the graph serializations
+    pub prefixes: Vec<(String, String)>,
+    pub dataset: Dataset,
+    // The algebra of the query, the solution modifiers included
+    pub pattern: GraphPattern,
+    // Projected variables of a SELECT query
+    pub variables: Vec<String>,
+    // Template of a CONSTRUCT query
+    pub template: Vec<TriplePattern>,
+    // Resources of a DESCRIBE query, variables of the pattern or IRIs
+    pub describe: Vec<TermPattern>,
+}
+
+// Whether the variable was created by the parser instead of written in the query
+pub fn is_internal_variable(name: &str) -> bool {
+    name.starts_with(INTERNAL_VAR_PREFIX) || name.starts_with("_:")
+}
@@ -0,0 +1,1871 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+
+use crate::query::algebra::PathMode;
+use crate::query::exceptions::{InterruptedException, LogicException};
+use crate::query::expressions::order_terms;
+use crate::query::leapfrog_join::LeapfrogJoin;
+use crate::query::object_id::ObjectId;
+use crate::query::paths::{named_graphs, PathAutomaton, PathGraph, PathSearch, QueryPaths};
+use crate::query::planner::{JoinPlan, LeapfrogPattern, PatternTerm, TriplePattern};
+use crate::query::query_executor::QueryTerms;
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::file_manager::PAGE_SIZE;
+use crate::storage::quad_indexes::{PatternScan, QuadIndexes};
+
+// Index of a variable of the query, its position in the bindings
+pub type VarId = usize;
+
+pub type ExecutionError = Box<dyn Error + Send + Sync>;
+
+// Values of the variables of a query, NULL for the unbound ones
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Binding {
+    values: Vec<ObjectId>,
+}
+
+impl Binding {
+    pub fn new(variables: usize) -> Self {
+        Self { values: vec![ObjectId::NULL; variables] }
+    }
+
+    pub fn get(&self, var: VarId) -> ObjectId {
+        self.values[var]
+    }
+
+    pub fn set(&mut self, var: VarId, value: ObjectId) {
+        self.values[var] = value;
+    }
+
+    pub fn values(&self) -> &[ObjectId] {
+        &self.values
+    }
+}
+
+// Flag the timeout task of the server sets when a query has to stop. The operators reading
+// the indexes check it before every result, so an interrupted query ends with an
+// `InterruptedException` after at most one more index read.
+#[derive(Debug, Clone, Default)]
+pub struct Interruption(Arc<AtomicBool>);
+
+impl Interruption {
+    pub fn new(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+
+    pub fn request(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+
+    pub fn check(&self) -> Result<(), ExecutionError> {
+        if self.0.load(atomic::Ordering::Relaxed) {
+            return Err(Box::new(InterruptedException));
+        }
+        Ok(())
+    }
+}
+
+// An operator of a query plan. The results are written into the binding given to every
+// call, which also has the values set by the operators above (e.g. the left side of a
+// nested loop join), that the operator reads as constants.
+pub trait BindingIter {
+    // Starts the iteration. The variables the operator binds must be NULL.
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError>;
+
+    // Writes the next result into the binding, false when there are no more
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError>;
+
+    // Starts the iteration again, with the values the operators above have now. The
+    // variables the operator bound before were set to NULL by the operator above, so the
+    // ones bound now are decided again from the binding.
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError>;
+
+    // Sets the variables the operator binds to NULL, for an OPTIONAL without match. The
+    // ones set by the operators above keep their values.
+    fn assign_nulls(&self, binding: &mut Binding);
+
+    // Variables the operator binds
+    fn variables(&self) -> Vec<VarId>;
+}
+
+pub type BoxedIter<'a> = Box<dyn BindingIter + 'a>;
+
+// The variables of `first` followed by the ones of `second` that are not in `first`
+fn merge_variables(mut first: Vec<VarId>, second: Vec<VarId>) -> Vec<VarId> {
+    for var in second {
+        if !first.contains(&var) {
+            first.push(var);
+        }
+    }
+    first
+}
+
+fn assign_nulls_to(variables: &[VarId], binding: &mut Binding) {
+    for &var in variables {
+        binding.set(var, ObjectId::NULL);
+    }
+}
+
+// Scan of the triples matching a pattern. Variables already bound when the scan begins are
+// constants of the range, the others are set by the scan.
+pub struct IndexScan<'a> {
+    indexes: &'a QuadIndexes,
+    pattern: TriplePattern,
+    interruption: Interruption,
+    // Variables set by the scan, decided when it begins or resets
+    free: Vec<VarId>,
+    scan: Option<PatternScan<'a, 3>>,
+}
+
+impl<'a> IndexScan<'a> {
+    pub fn new(indexes: &'a QuadIndexes, pattern: TriplePattern, interruption: Interruption) -> Self {
+        Self { indexes, pattern, interruption, free: Vec::new(), scan: None }
+    }
+
+    fn open_scan(&mut self, binding: &Binding) -> Result<(), ExecutionError> {
+        let mut bounds = [None; 3];
+        for (bound, term) in bounds.iter_mut().zip(&self.pattern.terms) {
+            *bound = match *term {
+                PatternTerm::Constant(value) => Some(value),
+                PatternTerm::Variable(var) if !self.free.contains(&var) => Some(binding.get(var).raw()),
+                PatternTerm::Variable(_) => None,
+            };
+        }
+        self.scan = Some(self.indexes.scan_triples(&bounds)?);
+        Ok(())
+    }
+}
+
+impl BindingIter for IndexScan<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.free = self.pattern.variables().into_iter().filter(|&var| binding.get(var).is_null()).collect();
+        self.open_scan(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        let scan = match self.scan.as_mut() {
+            Some(scan) => scan,
+            None => return Ok(false),
+        };
+        loop {
+            self.interruption.check()?;
+            let triple = match scan.next() {
+                Some(triple) => triple?,
+                None => return Ok(false),
+            };
+            // A variable repeated in the pattern has to have the same value everywhere
+            let mut values: Vec<(VarId, u64)> = Vec::with_capacity(3);
+            let consistent = self.pattern.terms.iter().zip(triple).all(|(term, value)| match *term {
+                PatternTerm::Variable(var) if self.free.contains(&var) => match values.iter().find(|(v, _)| *v == var) {
+                    Some(&(_, previous)) => previous == value,
+                    None => {
+                        values.push((var, value));
+                        true
+                    }
+                },
+                _ => true,
+            });
+            if consistent {
+                for (var, value) in values {
+                    binding.set(var, ObjectId::from_raw(value));
+                }
+                return Ok(true);
+            }
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.begin(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        assign_nulls_to(&self.free, binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.pattern.variables()
+    }
+}
+
+// Named graphs a pattern is matched in, instead of the default graph of the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuadGraph {
+    // The merge of these graphs, the default graph given by FROM
+    Merge(Vec<u64>),
+    // The graph of GRAPH, one of `allowed` (FROM NAMED) or any named graph when `None`
+    Named { graph: PatternTerm, allowed: Option<Vec<u64>> },
+}
+
+// Scan of the quads of the named graphs matching a pattern. A triple in several graphs of a
+// merge is returned once.
+pub struct QuadScan<'a> {
+    indexes: &'a QuadIndexes,
+    pattern: TriplePattern,
+    graph: QuadGraph,
+    interruption: Interruption,
+    // Variables set by the scan, decided when it begins or resets
+    free: Vec<VarId>,
+    bounds: [Option<u64>; 3],
+    // Graphs to scan one after the other, `None` scans every graph at once
+    graphs: Vec<Option<u64>>,
+    position: usize,
+    scan: Option<PatternScan<'a, 4>>,
+}
+
+impl<'a> QuadScan<'a> {
+    pub fn new(indexes: &'a QuadIndexes, pattern: TriplePattern, graph: QuadGraph, interruption: Interruption) -> Self {
+        Self {
+            indexes,
+            pattern,
+            graph,
+            interruption,
+            free: Vec::new(),
+            bounds: [None; 3],
+            graphs: Vec::new(),
+            position: 0,
+            scan: None,
+        }
+    }
+
+    fn graph_term(&self) -> Option<PatternTerm> {
+        match self.graph {
+            QuadGraph::Named { graph, .. } => Some(graph),
+            QuadGraph::Merge(_) => None,
+        }
+    }
+
+    fn bound_value(&self, term: PatternTerm, binding: &Binding) -> Option<u64> {
+        match term {
+            PatternTerm::Constant(value) => Some(value),
+            PatternTerm::Variable(var) if !self.free.contains(&var) => Some(binding.get(var).raw()),
+            PatternTerm::Variable(_) => None,
+        }
+    }
+
+    fn open_scans(&mut self, binding: &Binding) {
+        self.bounds = self.pattern.terms.map(|term| self.bound_value(term, binding));
+        self.graphs = match &self.graph {
+            QuadGraph::Merge(graphs) => graphs.iter().map(|&graph| Some(graph)).collect(),
+            QuadGraph::Named { graph, allowed } => match (self.bound_value(*graph, binding), allowed) {
+                (Some(value), Some(allowed)) if !allowed.contains(&value) => Vec::new(),
+                (Some(value), _) => vec![Some(value)],
+                (None, Some(allowed)) => allowed.iter().map(|&graph| Some(graph)).collect(),
+                (None, None) => vec![None],
+            },
+        };
+        self.position = 0;
+        self.scan = None;
+    }
+
+    // Starts the scan of the next graph, false after the last one
+    fn next_graph(&mut self) -> Result<bool, ExecutionError> {
+        let graph = match self.graphs.get(self.position) {
+            Some(&graph) => graph,
+            None => return Ok(false),
+        };
+        self.position += 1;
+        let [subject, predicate, object] = self.bounds;
+        self.scan = Some(self.indexes.scan_quads(&[subject, predicate, object, graph])?);
+        Ok(true)
+    }
+
+    // Whether a graph of the merge scanned before the current one has the triple
+    fn seen_in_earlier_graph(&self, quad: &[u64; 4]) -> Result<bool, ExecutionError> {
+        if !matches!(self.graph, QuadGraph::Merge(_)) {
+            return Ok(false);
+        }
+        for &graph in &self.graphs[..self.position - 1] {
+            if self.indexes.scan_quads(&[Some(quad[0]), Some(quad[1]), Some(quad[2]), graph])?.next().is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl BindingIter for QuadScan<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.free = self.variables().into_iter().filter(|&var| binding.get(var).is_null()).collect();
+        self.open_scans(binding);
+        Ok(())
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        loop {
+            self.interruption.check()?;
+            let quad = match self.scan.as_mut().and_then(Iterator::next) {
+                Some(quad) => quad?,
+                None if self.next_graph()? => continue,
+                None => return Ok(false),
+            };
+            if self.seen_in_earlier_graph(&quad)? {
+                continue;
+            }
+            // A variable repeated in the pattern or as the graph has to have the same value everywhere
+            let graph_term = self.graph_term();
+            let mut values: Vec<(VarId, u64)> = Vec::with_capacity(4);
+            let consistent = self.pattern.terms.iter().chain(graph_term.as_ref()).zip(quad).all(|(term, value)| match *term {
+                PatternTerm::Variable(var) if self.free.contains(&var) => match values.iter().find(|(v, _)| *v == var) {
+                    Some(&(_, previous)) => previous == value,
+                    None => {
+                        values.push((var, value));
+                        true
+                    }
+                },
+                _ => true,
+            });
+            if consistent {
+                for (var, value) in values {
+                    binding.set(var, ObjectId::from_raw(value));
+                }
+                return Ok(true);
+            }
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.begin(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        assign_nulls_to(&self.free, binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        let mut variables = self.pattern.variables();
+        if let Some(PatternTerm::Variable(var)) = self.graph_term() {
+            if !variables.contains(&var) {
+                variables.push(var);
+            }
+        }
+        variables
+    }
+}
+
+// Leapfrog Triejoin of a cyclic basic graph pattern (see `JoinPlan::Leapfrog`). The
+// variables of the plan bound by the operators above filter the results.
+pub struct LeapfrogIter<'a> {
+    indexes: &'a QuadIndexes,
+    variable_order: Vec<VarId>,
+    patterns: Vec<LeapfrogPattern>,
+    interruption: Interruption,
+    // Values the operators above set for the variables of the plan
+    fixed: Vec<(usize, ObjectId)>,
+    join: Option<LeapfrogJoin<'a>>,
+}
+
+impl<'a> LeapfrogIter<'a> {
+    pub fn new(
+        indexes: &'a QuadIndexes,
+        variable_order: Vec<VarId>,
+        patterns: Vec<LeapfrogPattern>,
+        interruption: Interruption,
+    ) -> Self {
+        Self { indexes, variable_order, patterns, interruption, fixed: Vec::new(), join: None }
+    }
+}
+
+impl BindingIter for LeapfrogIter<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.join = None;
+        self.reset(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        let join = match self.join.as_mut() {
+            Some(join) => join,
+            None => return Ok(false),
+        };
+        loop {
+            self.interruption.check()?;
+            let values = match join.next() {
+                Some(values) => values?,
+                None => return Ok(false),
+            };
+            if self.fixed.iter().all(|&(level, value)| values[level] == value.raw()) {
+                for (&var, value) in self.variable_order.iter().zip(values) {
+                    binding.set(var, ObjectId::from_raw(value));
+                }
+                return Ok(true);
+            }
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.fixed = (0..self.variable_order.len())
+            .map(|level| (level, binding.get(self.variable_order[level])))
+            .filter(|(_, value)| !value.is_null())
+            .collect();
+        self.join = Some(LeapfrogJoin::new(self.indexes, self.variable_order.len(), &self.patterns));
+        Ok(())
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        for (level, &var) in self.variable_order.iter().enumerate() {
+            if !self.fixed.iter().any(|&(fixed, _)| fixed == level) {
+                binding.set(var, ObjectId::NULL);
+            }
+        }
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.variable_order.clone()
+    }
+}
+
+// Operator tree of a basic graph pattern: the Leapfrog Triejoin or index nested loop
+// joins of the scans, in the order of the plan
+pub fn basic_graph_pattern_iter<'a>(indexes: &'a QuadIndexes, plan: JoinPlan, interruption: &Interruption) -> BoxedIter<'a> {
+    match plan {
+        JoinPlan::Leapfrog { variable_order, patterns } => {
+            Box::new(LeapfrogIter::new(indexes, variable_order, patterns, interruption.clone()))
+        }
+        JoinPlan::Binary { patterns } => {
+            let mut scans = patterns
+                .into_iter()
+                .map(|pattern| Box::new(IndexScan::new(indexes, pattern, interruption.clone())) as BoxedIter<'a>);
+            let first = scans.next().unwrap_or_else(|| Box::new(SingleResult::default()));
+            scans.fold(first, |lhs, rhs| Box::new(NestedLoopJoin::new(lhs, rhs)))
+        }
+    }
+}
+
+// Index nested loop joins of the quad scans of a basic graph pattern over named graphs
+pub fn quad_pattern_iter<'a>(
+    indexes: &'a QuadIndexes,
+    patterns: Vec<TriplePattern>,
+    graph: &QuadGraph,
+    interruption: &Interruption,
+) -> BoxedIter<'a> {
+    let mut scans = patterns
+        .into_iter()
+        .map(|pattern| Box::new(QuadScan::new(indexes, pattern, graph.clone(), interruption.clone())) as BoxedIter<'a>);
+    let first = scans.next().unwrap_or_else(|| Box::new(SingleResult::default()));
+    scans.fold(first, |lhs, rhs| Box::new(NestedLoopJoin::new(lhs, rhs)))
+}
+
+// One result binding nothing, the solutions of an empty group pattern
+#[derive(Default)]
+pub struct SingleResult {
+    done: bool,
+}
+
+impl BindingIter for SingleResult {
+    fn begin(&mut self, _binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.done = false;
+        Ok(())
+    }
+
+    fn next(&mut self, _binding: &mut Binding) -> Result<bool, ExecutionError> {
+        Ok(!std::mem::replace(&mut self.done, true))
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.begin(binding)
+    }
+
+    fn assign_nulls(&self, _binding: &mut Binding) {}
+
+    fn variables(&self) -> Vec<VarId> {
+        Vec::new()
+    }
+}
+
+// For every result of the left side, the results of the right side, which sees the values
+// of the left one (index nested loop join when the right side is a scan)
+pub struct NestedLoopJoin<'a> {
+    lhs: BoxedIter<'a>,
+    rhs: BoxedIter<'a>,
+    rhs_begun: bool,
+    // The right side has no more results for the current left one
+    need_left: bool,
+}
+
+impl<'a> NestedLoopJoin<'a> {
+    pub fn new(lhs: BoxedIter<'a>, rhs: BoxedIter<'a>) -> Self {
+        Self { lhs, rhs, rhs_begun: false, need_left: true }
+    }
+
+    // Moves the left side and starts the right one below it, false at the end of the left side
+    fn next_left(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        // The right side must not see the values of the previous left result
+        if self.rhs_begun {
+            self.rhs.assign_nulls(binding);
+        }
+        if !self.lhs.next(binding)? {
+            return Ok(false);
+        }
+        if self.rhs_begun {
+            self.rhs.reset(binding)?;
+        } else {
+            self.rhs.begin(binding)?;
+            self.rhs_begun = true;
+        }
+        self.need_left = false;
+        Ok(true)
+    }
+}
+
+impl BindingIter for NestedLoopJoin<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.need_left = true;
+        self.lhs.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        loop {
+            if self.need_left && !self.next_left(binding)? {
+                return Ok(false);
+            }
+            if self.rhs.next(binding)? {
+                return Ok(true);
+            }
+            self.need_left = true;
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.need_left = true;
+        self.lhs.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.lhs.assign_nulls(binding);
+        self.rhs.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        merge_variables(self.lhs.variables(), self.rhs.variables())
+    }
+}
+
+// OPTIONAL: like the nested loop join, but a left result without any right one is
+// returned once with the variables of the right side NULL
+pub struct LeftOuterJoin<'a> {
+    lhs: BoxedIter<'a>,
+    rhs: BoxedIter<'a>,
+    rhs_begun: bool,
+    need_left: bool,
+    // The current left result has had a right one
+    matched: bool,
+}
+
+impl<'a> LeftOuterJoin<'a> {
+    pub fn new(lhs: BoxedIter<'a>, rhs: BoxedIter<'a>) -> Self {
+        Self { lhs, rhs, rhs_begun: false, need_left: true, matched: false }
+    }
+}
+
+impl BindingIter for LeftOuterJoin<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.need_left = true;
+        self.lhs.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        loop {
+            if self.need_left {
+                // The right side must not see the values of the previous left result
+                self.rhs.assign_nulls(binding);
+                if !self.lhs.next(binding)? {
+                    return Ok(false);
+                }
+                if self.rhs_begun {
+                    self.rhs.reset(binding)?;
+                } else {
+                    self.rhs.begin(binding)?;
+                    self.rhs_begun = true;
+                }
+                self.need_left = false;
+                self.matched = false;
+            }
+            if self.rhs.next(binding)? {
+                self.matched = true;
+                return Ok(true);
+            }
+            self.need_left = true;
+            if !self.matched {
+                self.rhs.assign_nulls(binding);
+                return Ok(true);
+            }
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.need_left = true;
+        self.lhs.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.lhs.assign_nulls(binding);
+        self.rhs.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        merge_variables(self.lhs.variables(), self.rhs.variables())
+    }
+}
+
+// Join on the variables both sides bind. The right side is read once into a hash table by
+// the values of those variables, then the left side probes it. Both sides see only the
+// values set by the operators above, so it suits sides that share few variables. A NULL
+// join value is compatible with any other, so the right results with one are kept apart
+// and checked one by one, as is the whole table for a left result with one.
+pub struct HashJoin<'a> {
+    lhs: BoxedIter<'a>,
+    rhs: BoxedIter<'a>,
+    join_variables: Vec<VarId>,
+    // Variables only the right side binds, the values kept in the table
+    rhs_variables: Vec<VarId>,
+    table: HashMap<Vec<ObjectId>, Vec<Vec<ObjectId>>>,
+    // Right results with a NULL join value: their join values and the ones of `rhs_variables`
+    partial: Vec<(Vec<ObjectId>, Vec<ObjectId>)>,
+    // Join values of the current left result
+    left_key: Vec<ObjectId>,
+    // Right results matching the current left one and the next of them to return
+    matches: Vec<(Vec<ObjectId>, Vec<ObjectId>)>,
+    next_match: usize,
+}
+
+impl<'a> HashJoin<'a> {
+    pub fn new(lhs: BoxedIter<'a>, rhs: BoxedIter<'a>) -> Self {
+        let lhs_variables = lhs.variables();
+        let (join_variables, rhs_variables) = rhs.variables().into_iter().partition(|var| lhs_variables.contains(var));
+        Self {
+            lhs,
+            rhs,
+            join_variables,
+            rhs_variables,
+            table: HashMap::new(),
+            partial: Vec::new(),
+            left_key: Vec::new(),
+            matches: Vec::new(),
+            next_match: 0,
+        }
+    }
+
+    fn build(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.table.clear();
+        self.partial.clear();
+        self.matches.clear();
+        self.next_match = 0;
+        while self.rhs.next(binding)? {
+            let key: Vec<ObjectId> = self.join_variables.iter().map(|&var| binding.get(var)).collect();
+            let values = self.rhs_variables.iter().map(|&var| binding.get(var)).collect();
+            if key.iter().any(|value| value.is_null()) {
+                self.partial.push((key, values));
+            } else {
+                self.table.entry(key).or_default().push(values);
+            }
+        }
+        self.rhs.assign_nulls(binding);
+        Ok(())
+    }
+
+    // Right results compatible with the current left one
+    fn probe(&self) -> Vec<(Vec<ObjectId>, Vec<ObjectId>)> {
+        let compatible = |key: &[ObjectId]| {
+            self.left_key.iter().zip(key).all(|(&left, &right)| left.is_null() || right.is_null() || left == right)
+        };
+        let mut matches = Vec::new();
+        if self.left_key.iter().any(|value| value.is_null()) {
+            for (key, rows) in &self.table {
+                if compatible(key) {
+                    matches.extend(rows.iter().map(|values| (key.clone(), values.clone())));
+                }
+            }
+        } else if let Some(rows) = self.table.get(&self.left_key) {
+            matches.extend(rows.iter().map(|values| (self.left_key.clone(), values.clone())));
+        }
+        matches.extend(self.partial.iter().filter(|(key, _)| compatible(key)).cloned());
+        matches
+    }
+}
+
+impl BindingIter for HashJoin<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.rhs.begin(binding)?;
+        self.build(binding)?;
+        self.lhs.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        loop {
+            if let Some((key, values)) = self.matches.get(self.next_match) {
+                // The right side binds the join variables the left one left NULL
+                for ((&var, &left), &right) in self.join_variables.iter().zip(&self.left_key).zip(key) {
+                    binding.set(var, if left.is_null() { right } else { left });
+                }
+                for (&var, &value) in self.rhs_variables.iter().zip(values) {
+                    binding.set(var, value);
+                }
+                self.next_match += 1;
+                return Ok(true);
+            }
+            if !self.lhs.next(binding)? {
+                return Ok(false);
+            }
+            self.left_key = self.join_variables.iter().map(|&var| binding.get(var)).collect();
+            self.matches = self.probe();
+            self.next_match = 0;
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.rhs.reset(binding)?;
+        self.build(binding)?;
+        self.lhs.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.lhs.assign_nulls(binding);
+        self.rhs.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        let mut variables = self.lhs.variables();
+        variables.extend(&self.rhs_variables);
+        variables
+    }
+}
+
+pub type FilterCondition<'a> = Box<dyn Fn(&Binding) -> Result<bool, ExecutionError> + 'a>;
+
+// Results of the child for which the condition holds
+pub struct Filter<'a> {
+    child: BoxedIter<'a>,
+    condition: FilterCondition<'a>,
+}
+
+impl<'a> Filter<'a> {
+    pub fn new(child: BoxedIter<'a>, condition: FilterCondition<'a>) -> Self {
+        Self { child, condition }
+    }
+}
+
+impl BindingIter for Filter<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.child.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        while self.child.next(binding)? {
+            if (self.condition)(binding)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.child.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.child.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.child.variables()
+    }
+}
+
+// Keeps only the given variables of the results, the others are set to NULL
+pub struct Project<'a> {
+    child: BoxedIter<'a>,
+    variables: Vec<VarId>,
+    // Variables of the child that are not projected
+    hidden: Vec<VarId>,
+}
+
+impl<'a> Project<'a> {
+    pub fn new(child: BoxedIter<'a>, variables: Vec<VarId>) -> Self {
+        let hidden = child.variables().into_iter().filter(|var| !variables.contains(var)).collect();
+        Self { child, variables, hidden }
+    }
+}
+
+impl BindingIter for Project<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.child.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        if !self.child.next(binding)? {
+            return Ok(false);
+        }
+        assign_nulls_to(&self.hidden, binding);
+        Ok(true)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        assign_nulls_to(&self.hidden, binding);
+        self.child.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.child.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.variables.clone()
+    }
+}
+
+// Results of the child with different values for its variables, the first time they appear
+pub struct Distinct<'a> {
+    child: BoxedIter<'a>,
+    variables: Vec<VarId>,
+    seen: HashSet<Vec<ObjectId>>,
+}
+
+impl<'a> Distinct<'a> {
+    pub fn new(child: BoxedIter<'a>) -> Self {
+        let variables = child.variables();
+        Self { child, variables, seen: HashSet::new() }
+    }
+}
+
+impl BindingIter for Distinct<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.seen.clear();
+        self.child.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        while self.child.next(binding)? {
+            if self.seen.insert(self.variables.iter().map(|&var| binding.get(var)).collect()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.seen.clear();
+        self.child.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.child.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.variables.clone()
+    }
+}
+
+// A key of ORDER BY: the variable and whether the order is ascending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderKey {
+    pub var: VarId,
+    pub ascending: bool,
+}
+
+// Order of the values of a variable in ORDER BY without the terms of the query: unbound
+// first, then the inlined numbers by value, then everything else by ObjectId
+pub fn compare_values(a: ObjectId, b: ObjectId) -> Ordering {
+    match (a.is_null(), b.is_null()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        (false, false) => {}
+    }
+    match (a.numeric_value(), b.numeric_value()) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal).then(a.cmp(&b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(&b),
+    }
+}
+
+// Reads every result of the child and returns them sorted by the keys. The sort is stable,
+// results with the same keys keep the order of the child. With the terms of the query the
+// values are ordered by `order_terms`, otherwise by `compare_values`.
+pub struct OrderBy<'a> {
+    child: BoxedIter<'a>,
+    keys: Vec<OrderKey>,
+    variables: Vec<VarId>,
+    terms: Option<&'a QueryTerms<'a>>,
+    results: Vec<Vec<ObjectId>>,
+    position: usize,
+}
+
+impl<'a> OrderBy<'a> {
+    pub fn new(child: BoxedIter<'a>, keys: Vec<OrderKey>) -> Self {
+        let variables = child.variables();
+        Self { child, keys, variables, terms: None, results: Vec::new(), position: 0 }
+    }
+
+    pub fn with_terms(mut self, terms: &'a QueryTerms<'a>) -> Self {
+        self.terms = Some(terms);
+        self
+    }
+
+    fn sort(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.results.clear();
+        self.position = 0;
+        while self.child.next(binding)? {
+            self.results.push(self.variables.iter().map(|&var| binding.get(var)).collect());
+        }
+        let positions: Vec<(usize, bool)> = self
+            .keys
+            .iter()
+            .filter_map(|key| self.variables.iter().position(|&var| var == key.var).map(|i| (i, key.ascending)))
+            .collect();
+        let ordered = |order: Ordering, ascending: bool| if ascending { order } else { order.reverse() };
+        let terms = match self.terms {
+            Some(terms) => terms,
+            None => {
+                self.results.sort_by(|a, b| {
+                    positions.iter().fold(Ordering::Equal, |order, &(i, ascending)| {
+                        order.then_with(|| ordered(compare_values(a[i], b[i]), ascending))
+                    })
+                });
+                return Ok(());
+            }
+        };
+        // The terms of the keys are decoded once for each result
+        let mut rows = Vec::with_capacity(self.results.len());
+        for values in self.results.drain(..) {
+            let mut keys = Vec::with_capacity(positions.len());
+            for &(i, _) in &positions {
+                keys.push(terms.decode(values[i])?);
+            }
+            rows.push((keys, values));
+        }
+        rows.sort_by(|(a, _), (b, _)| {
+            positions.iter().enumerate().fold(Ordering::Equal, |order, (k, &(_, ascending))| {
+                order.then_with(|| ordered(order_terms(a[k].as_ref(), b[k].as_ref()), ascending))
+            })
+        });
+        self.results = rows.into_iter().map(|(_, values)| values).collect();
+        Ok(())
+    }
+}
+
+impl BindingIter for OrderBy<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.child.begin(binding)?;
+        self.sort(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        let values = match self.results.get(self.position) {
+            Some(values) => values,
+            None => return Ok(false),
+        };
+        for (&var, &value) in self.variables.iter().zip(values) {
+            binding.set(var, value);
+        }
+        self.position += 1;
+        Ok(true)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.child.reset(binding)?;
+        self.sort(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.child.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.variables.clone()
+    }
+}
+
+// LIMIT and OFFSET: skips the first `offset` results and stops after `limit` more
+pub struct Slice<'a> {
+    child: BoxedIter<'a>,
+    offset: u64,
+    limit: Option<u64>,
+    returned: u64,
+    skipped: bool,
+}
+
+impl<'a> Slice<'a> {
+    pub fn new(child: BoxedIter<'a>, offset: u64, limit: Option<u64>) -> Self {
+        Self { child, offset, limit, returned: 0, skipped: false }
+    }
+}
+
+impl BindingIter for Slice<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.returned = 0;
+        self.skipped = false;
+        self.child.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        if !self.skipped {
+            self.skipped = true;
+            for _ in 0..self.offset {
+                if !self.child.next(binding)? {
+                    return Ok(false);
+                }
+            }
+        }
+        if self.limit.is_some_and(|limit| self.returned >= limit) || !self.child.next(binding)? {
+            return Ok(false);
+        }
+        self.returned += 1;
+        Ok(true)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.returned = 0;
+        self.skipped = false;
+        self.child.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.child.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.child.variables()
+    }
+}
+
+// UNION: the results of every child, one after the other. The variables of a child that
+// the current one does not bind are NULL.
+pub struct Union<'a> {
+    children: Vec<BoxedIter<'a>>,
+    current: usize,
+    begun: Vec<bool>,
+}
+
+impl<'a> Union<'a> {
+    pub fn new(children: Vec<BoxedIter<'a>>) -> Self {
+        let begun = vec![false; children.len()];
+        Self { children, current: 0, begun }
+    }
+
+    fn start_child(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        if self.begun[self.current] {
+            self.children[self.current].reset(binding)
+        } else {
+            self.begun[self.current] = true;
+            self.children[self.current].begin(binding)
+        }
+    }
+}
+
+impl BindingIter for Union<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.current = 0;
+        if self.children.is_empty() {
+            return Ok(());
+        }
+        self.start_child(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        while self.current < self.children.len() {
+            if self.children[self.current].next(binding)? {
+                return Ok(true);
+            }
+            self.children[self.current].assign_nulls(binding);
+            self.current += 1;
+            if self.current < self.children.len() {
+                self.start_child(binding)?;
+            }
+        }
+        Ok(false)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.begin(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        for child in &self.children {
+            child.assign_nulls(binding);
+        }
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.children.iter().fold(Vec::new(), |variables, child| merge_variables(variables, child.variables()))
+    }
+}
+
+// Value of an expression for the current result, NULL when the expression has an error
+pub type ValueFunction<'a> = Box<dyn Fn(&Binding) -> Result<ObjectId, ExecutionError> + 'a>;
+
+// BIND: the results of the child with the variable set to the value of the expression.
+// A variable bound by the operators above keeps its value, and the results for which the
+// expression has another one are skipped.
+pub struct Extend<'a> {
+    child: BoxedIter<'a>,
+    var: VarId,
+    value: ValueFunction<'a>,
+    // The variable was bound when the iteration began
+    fixed: bool,
+}
+
+impl<'a> Extend<'a> {
+    pub fn new(child: BoxedIter<'a>, var: VarId, value: ValueFunction<'a>) -> Self {
+        Self { child, var, value, fixed: false }
+    }
+}
+
+impl BindingIter for Extend<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.fixed = !binding.get(self.var).is_null();
+        self.child.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        while self.child.next(binding)? {
+            let value = (self.value)(binding)?;
+            if !self.fixed {
+                binding.set(self.var, value);
+                return Ok(true);
+            }
+            if value.is_null() || value == binding.get(self.var) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        if !self.fixed {
+            binding.set(self.var, ObjectId::NULL);
+        }
+        self.child.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.child.assign_nulls(binding);
+        if !self.fixed {
+            binding.set(self.var, ObjectId::NULL);
+        }
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        merge_variables(self.child.variables(), vec![self.var])
+    }
+}
+
+// VALUES: one result per row, NULL for UNDEF. Variables bound by the operators above are
+// not changed, the rows with another value for them are skipped.
+pub struct Values {
+    variables: Vec<VarId>,
+    rows: Vec<Vec<ObjectId>>,
+    // Whether each variable was bound when the iteration began or reset
+    fixed: Vec<bool>,
+    position: usize,
+}
+
+impl Values {
+    pub fn new(variables: Vec<VarId>, rows: Vec<Vec<ObjectId>>) -> Self {
+        let fixed = vec![false; variables.len()];
+        Self { variables, rows, fixed, position: 0 }
+    }
+}
+
+impl BindingIter for Values {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.fixed = self.variables.iter().map(|&var| !binding.get(var).is_null()).collect();
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        while let Some(row) = self.rows.get(self.position) {
+            self.position += 1;
+            let compatible = self.variables.iter().zip(row).zip(&self.fixed).all(|((&var, &value), &fixed)| {
+                !fixed || value.is_null() || value == binding.get(var)
+            });
+            if compatible {
+                for ((&var, &value), &fixed) in self.variables.iter().zip(row).zip(&self.fixed) {
+                    if !fixed {
+                        binding.set(var, value);
+                    }
+                }
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.begin(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        for (&var, &fixed) in self.variables.iter().zip(&self.fixed) {
+            if !fixed {
+                binding.set(var, ObjectId::NULL);
+            }
+        }
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.variables.clone()
+    }
+}
+
+// MINUS: the results of the left side that are not compatible with any result of the
+// right one, not counting the right results without a common bound variable. The right
+// side is read first, seeing only the values set by the operators above.
+pub struct Minus<'a> {
+    lhs: BoxedIter<'a>,
+    rhs: BoxedIter<'a>,
+    // Variables both sides may bind, and their values in every right result
+    shared: Vec<VarId>,
+    rows: Vec<Vec<ObjectId>>,
+}
+
+impl<'a> Minus<'a> {
+    pub fn new(lhs: BoxedIter<'a>, rhs: BoxedIter<'a>) -> Self {
+        let lhs_variables = lhs.variables();
+        let shared = rhs.variables().into_iter().filter(|var| lhs_variables.contains(var)).collect();
+        Self { lhs, rhs, shared, rows: Vec::new() }
+    }
+
+    fn build(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.rows.clear();
+        while self.rhs.next(binding)? {
+            self.rows.push(self.shared.iter().map(|&var| binding.get(var)).collect());
+        }
+        self.rhs.assign_nulls(binding);
+        Ok(())
+    }
+
+    fn is_removed(&self, binding: &Binding) -> bool {
+        self.rows.iter().any(|row| {
+            let mut common = false;
+            for (&var, &value) in self.shared.iter().zip(row) {
+                let left = binding.get(var);
+                if left.is_null() || value.is_null() {
+                    continue;
+                }
+                if left != value {
+                    return false;
+                }
+                common = true;
+            }
+            common
+        })
+    }
+}
+
+impl BindingIter for Minus<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.rhs.begin(binding)?;
+        self.build(binding)?;
+        self.lhs.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        while self.lhs.next(binding)? {
+            if !self.is_removed(binding) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.rhs.reset(binding)?;
+        self.build(binding)?;
+        self.lhs.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        self.lhs.assign_nulls(binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        self.lhs.variables()
+    }
+}
+
+// A property path between two terms, evaluated by `PathSearch`. The search starts from
+// the subject when it is known, from the object (with the inverse automaton) when only the
+// object is, and from every node of the graph otherwise. With a path mode the paths found
+// are kept in `paths` and bound to `path_var`. Inside GRAPH the path is evaluated in each
+// named graph on its own.
+pub struct PathScan<'a> {
+    indexes: &'a QuadIndexes,
+    graph: Option<QuadGraph>,
+    automaton: PathAutomaton,
+    inverse_automaton: PathAutomaton,
+    subject: PatternTerm,
+    object: PatternTerm,
+    interruption: Interruption,
+    mode: Option<PathMode>,
+    path_var: Option<VarId>,
+    paths: Option<&'a QueryPaths>,
+    // Variables set by the scan, decided when it begins or resets
+    free: Vec<VarId>,
+    // The search goes from the object to the subject
+    backward: bool,
+    // The other end when both are known, the results have to reach it
+    target: Option<ObjectId>,
+    // Neither end is known, the search starts from every node of each graph
+    from_every_node: bool,
+    graphs: Vec<PathGraph<'a>>,
+    position: usize,
+    starts: Vec<ObjectId>,
+    next_start: usize,
+    current_start: ObjectId,
+    search: PathSearch,
+    searching: bool,
+}
+
+impl<'a> PathScan<'a> {
+    pub fn new(
+        indexes: &'a QuadIndexes,
+        automaton: PathAutomaton,
+        inverse_automaton: PathAutomaton,
+        subject: PatternTerm,
+        object: PatternTerm,
+        interruption: Interruption,
+    ) -> Self {
+        Self {
+            indexes,
+            graph: None,
+            automaton,
+            inverse_automaton,
+            subject,
+            object,
+            interruption,
+            mode: None,
+            path_var: None,
+            paths: None,
+            free: Vec::new(),
+            backward: false,
+            target: None,
+            from_every_node: false,
+            graphs: Vec::new(),
+            position: 0,
+            starts: Vec::new(),
+            next_start: 0,
+            current_start: ObjectId::NULL,
+            search: PathSearch::new(None),
+            searching: false,
+        }
+    }
+
+    // Matches the paths of the mode instead of the pairs of ends, binding them to `path_var`
+    pub fn with_mode(mut self, mode: PathMode, path_var: Option<VarId>, paths: &'a QueryPaths) -> Self {
+        self.mode = Some(mode);
+        self.path_var = path_var;
+        self.paths = Some(paths);
+        self.search = PathSearch::new(Some(mode));
+        self
+    }
+
+    // Evaluates the path over named graphs instead of the default graph of the database
+    pub fn with_graph(mut self, graph: QuadGraph) -> Self {
+        self.graph = Some(graph);
+        self
+    }
+
+    // The variable of GRAPH when the scan sets it
+    fn graph_var(&self) -> Option<VarId> {
+        match self.graph {
+            Some(QuadGraph::Named { graph: PatternTerm::Variable(var), .. }) if self.free.contains(&var) => Some(var),
+            _ => None,
+        }
+    }
+
+    // Whether a search ends at the first path that reaches a known end
+    fn one_path_per_ends(&self) -> bool {
+        matches!(self.mode, None | Some(PathMode::Any) | Some(PathMode::AnyShortest))
+    }
+
+    // Value of an end of the path, `None` when the scan sets it
+    fn value(&self, term: PatternTerm, binding: &Binding) -> Option<ObjectId> {
+        match term {
+            PatternTerm::Constant(value) => Some(ObjectId::from_raw(value)),
+            PatternTerm::Variable(var) if self.free.contains(&var) => None,
+            PatternTerm::Variable(var) => Some(binding.get(var)),
+        }
+    }
+
+    fn start(&mut self, binding: &Binding) -> Result<(), ExecutionError> {
+        let subject = self.value(self.subject, binding);
+        let object = self.value(self.object, binding);
+        self.backward = subject.is_none() && object.is_some();
+        self.target = subject.and(object);
+        self.from_every_node = subject.is_none() && object.is_none();
+        self.starts = subject.or(object).into_iter().collect();
+        self.graphs = self.graphs(binding)?;
+        self.position = 0;
+        self.searching = false;
+        if !self.next_graph()? {
+            self.starts.clear();
+        }
+        Ok(())
+    }
+
+    // Graphs to evaluate the path in, one after the other
+    fn graphs(&self, binding: &Binding) -> Result<Vec<PathGraph<'a>>, ExecutionError> {
+        let indexes = self.indexes;
+        let named = match &self.graph {
+            None => return Ok(vec![PathGraph { indexes, named: None }]),
+            Some(QuadGraph::Merge(graphs)) => return Ok(vec![PathGraph { indexes, named: Some(graphs.clone()) }]),
+            Some(QuadGraph::Named { graph, allowed }) => match (self.value(*graph, binding), allowed) {
+                (Some(value), Some(allowed)) if !allowed.contains(&value.raw()) => Vec::new(),
+                (Some(value), _) => vec![value.raw()],
+                (None, Some(allowed)) => allowed.clone(),
+                (None, None) => named_graphs(indexes, &self.interruption)?,
+            },
+        };
+        Ok(named.into_iter().map(|graph| PathGraph { indexes, named: Some(vec![graph]) }).collect())
+    }
+
+    // Moves to the next graph, false after the last one
+    fn next_graph(&mut self) -> Result<bool, ExecutionError> {
+        let graph = match self.graphs.get(self.position) {
+            Some(graph) => graph,
+            None => return Ok(false),
+        };
+        self.position += 1;
+        if self.from_every_node {
+            self.starts = graph.nodes(&self.interruption)?;
+        }
+        self.next_start = 0;
+        Ok(true)
+    }
+
+    fn set(&self, term: PatternTerm, value: ObjectId, binding: &mut Binding) {
+        if let PatternTerm::Variable(var) = term {
+            if self.free.contains(&var) {
+                binding.set(var, value);
+            }
+        }
+    }
+}
+
+impl BindingIter for PathScan<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.free = self.variables().into_iter().filter(|&var| binding.get(var).is_null()).collect();
+        self.start(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        loop {
+            if !self.searching {
+                let start = match self.starts.get(self.next_start).copied() {
+                    Some(start) => start,
+                    None if self.next_graph()? => continue,
+                    None => return Ok(false),
+                };
+                self.next_start += 1;
+                self.current_start = start;
+                let automaton = if self.backward { &self.inverse_automaton } else { &self.automaton };
+                self.search.start(automaton, start);
+                self.searching = true;
+            }
+            let automaton = if self.backward { &self.inverse_automaton } else { &self.automaton };
+            let graph = &self.graphs[self.position - 1];
+            let found = match self.search.next(automaton, graph, &self.interruption)? {
+                Some(found) => found,
+                None => {
+                    self.searching = false;
+                    continue;
+                }
+            };
+            if let Some(target) = self.target {
+                if found.end != target {
+                    continue;
+                }
+                // Only one result for two known ends, unless the mode returns every path
+                if self.one_path_per_ends() {
+                    self.searching = false;
+                }
+            }
+            let end = found.end;
+            let (subject, object) = if self.backward { (end, self.current_start) } else { (self.current_start, end) };
+            // `?x path ?x` only matches paths that end where they start
+            if self.subject == self.object && subject != object {
+                continue;
+            }
+            // The graph of the match, when the scan sets the variable of GRAPH
+            let graph_value = self.graph_var().map(|var| (var, ObjectId::from_raw(graph.named.as_ref().unwrap()[0])));
+            if let Some((var, value)) = graph_value {
+                // `GRAPH ?g { ?g path ?x }` only matches paths from the graph's own name
+                let other_end = [(self.subject, subject), (self.object, object)]
+                    .into_iter()
+                    .any(|(term, end)| term == PatternTerm::Variable(var) && end != value);
+                if other_end {
+                    continue;
+                }
+            }
+            if let (Some(var), Some(paths), Some(path)) = (self.path_var, self.paths, found.path) {
+                // Paths found from the object are walked back to go from the subject
+                let path = if self.backward { path.reversed() } else { path };
+                let id = paths.add(path);
+                if self.free.contains(&var) {
+                    binding.set(var, id);
+                } else if binding.get(var) != id {
+                    continue;
+                }
+            }
+            self.set(self.subject, subject, binding);
+            self.set(self.object, object, binding);
+            if let Some((var, value)) = graph_value {
+                binding.set(var, value);
+            }
+            return Ok(true);
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.begin(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        assign_nulls_to(&self.free, binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        let mut variables = Vec::new();
+        for term in [self.subject, self.object] {
+            if let PatternTerm::Variable(var) = term {
+                if !variables.contains(&var) {
+                    variables.push(var);
+                }
+            }
+        }
+        let graph_var = match self.graph {
+            Some(QuadGraph::Named { graph: PatternTerm::Variable(var), .. }) => Some(var),
+            _ => None,
+        };
+        for var in [self.path_var, graph_var].into_iter().flatten() {
+            if !variables.contains(&var) {
+                variables.push(var);
+            }
+        }
+        variables
+    }
+}
+
+// The private buffer pages of the worker running a query, where operators write the
+// intermediate results that do not fit in memory. The pool is locked for each access, and
+// the pages are dropped when the query ends.
+#[derive(Clone, Copy)]
+pub struct TemporaryPages<'a> {
+    buffer: &'a BufferManager,
+    worker_index: usize,
+}
+
+impl<'a> TemporaryPages<'a> {
+    pub fn new(buffer: &'a BufferManager, worker_index: usize) -> Self {
+        Self { buffer, worker_index }
+    }
+
+    // Bytes an operator may keep in memory before writing to pages, the size of the pool
+    pub fn memory_limit(&self) -> Result<usize, ExecutionError> {
+        Ok(self.buffer.private_pool(self.worker_index)?.capacity() * PAGE_SIZE)
+    }
+
+    // Writes the data at the start of a new page and returns the number of the page
+    pub fn write_page(&self, data: &[u8]) -> Result<u64, ExecutionError> {
+        let mut pool = self.buffer.private_pool(self.worker_index)?;
+        let page = pool.new_page()?;
+        page.write()[..data.len()].copy_from_slice(data);
+        Ok(page.page_id().page_number)
+    }
+
+    pub fn read_page(&self, page_number: u64, data: &mut [u8]) -> Result<(), ExecutionError> {
+        let mut pool = self.buffer.private_pool(self.worker_index)?;
+        let page = pool.pin(page_number)?;
+        data.copy_from_slice(&page.read()[..data.len()]);
+        Ok(())
+    }
+}
+
+// Folds the values of an aggregate for the results of a group
+pub trait Accumulator {
+    fn add(&mut self, values: &[ObjectId]) -> Result<(), ExecutionError>;
+
+    // Value of the aggregate for the group, NULL when it is an error
+    fn value(&self) -> Result<ObjectId, ExecutionError>;
+}
+
+pub type AccumulatorFactory<'a> = Box<dyn Fn() -> Box<dyn Accumulator + 'a> + 'a>;
+
+// An aggregate computed by `GroupBy`: the values it reads from each result of the child
+// and the accumulators that fold them. With `distinct` a group folds the same values once.
+pub struct GroupAggregate<'a> {
+    pub var: VarId,
+    pub arguments: Vec<ValueFunction<'a>>,
+    pub distinct: bool,
+    pub new_accumulator: AccumulatorFactory<'a>,
+}
+
+// Partitions a pass of `GroupBy` writes the rows of the groups that do not fit in memory to
+const SPILL_PARTITIONS: usize = 16;
+
+// Estimated bytes of a group in memory besides its key
+const GROUP_OVERHEAD_BYTES: usize = 64;
+const ACCUMULATOR_BYTES: usize = 64;
+
+// Rows of a partition: full pages, then the rows of the page being filled
+#[derive(Default)]
+struct Partition {
+    pages: Vec<u64>,
+    rows: usize,
+    last_page: Vec<u64>,
+}
+
+// A group in memory: the values of its keys, an accumulator per aggregate and the values
+// each DISTINCT aggregate has already folded
+struct Group<'a> {
+    key: Vec<ObjectId>,
+    accumulators: Vec<Box<dyn Accumulator + 'a>>,
+    seen: Vec<HashSet<Vec<ObjectId>>>,
+}
+
+// The groups of a pass, in the order they were found
+#[derive(Default)]
+struct Groups<'a> {
+    index: HashMap<Vec<ObjectId>, usize>,
+    groups: Vec<Group<'a>>,
+}
+
+// GROUP BY: one result per group of results of the child with the same values for the
+// keys, with the keys and the aggregates of the group. Without keys there is a single
+// group, even when the child has no results.
+//
+// Groups are kept in a hash table. Once the table fills the memory the temporary pages
+// allow, the rows (values of the keys and of the arguments of the aggregates) of the
+// groups that are not in the table are written to partitions by the hash of their keys.
+// The groups of each partition are computed after the ones in memory, partitioning again
+// with another hash if they do not fit either.
+pub struct GroupBy<'a> {
+    child: BoxedIter<'a>,
+    keys: Vec<(VarId, ValueFunction<'a>)>,
+    aggregates: Vec<GroupAggregate<'a>>,
+    pages: Option<TemporaryPages<'a>>,
+    interruption: Interruption,
+    started: bool,
+    max_groups: usize,
+    // Values of the keys and of the aggregates of the groups to return
+    results: VecDeque<Vec<ObjectId>>,
+    // Partitions left, with the level of the hash that made them
+    partitions: Vec<(Partition, u64)>,
+}
+
+impl<'a> GroupBy<'a> {
+    pub fn new(
+        child: BoxedIter<'a>,
+        keys: Vec<(VarId, ValueFunction<'a>)>,
+        aggregates: Vec<GroupAggregate<'a>>,
+        pages: Option<TemporaryPages<'a>>,
+        interruption: Interruption,
+    ) -> Self {
+        Self {
+            child,
+            keys,
+            aggregates,
+            pages,
+            interruption,
+            started: false,
+            max_groups: usize::MAX,
+            results: VecDeque::new(),
+            partitions: Vec::new(),
+        }
+    }
+
+    // Number of values of a row: the keys, then the arguments of each aggregate
+    fn row_width(&self) -> usize {
+        self.keys.len() + self.aggregates.iter().map(|aggregate| aggregate.arguments.len()).sum::<usize>()
+    }
+
+    fn new_group(&self, key: Vec<ObjectId>) -> Group<'a> {
+        Group {
+            key,
+            accumulators: self.aggregates.iter().map(|aggregate| (aggregate.new_accumulator)()).collect(),
+            seen: vec![HashSet::new(); self.aggregates.len()],
+        }
+    }
+
+    // Folds a row into its group, or writes it to a partition when the group is not in
+    // memory and there is no room for it
+    fn add_row(&self, row: &[ObjectId], groups: &mut Groups<'a>, spilled: &mut Vec<Partition>, level: u64) -> Result<(), ExecutionError> {
+        let key = &row[..self.keys.len()];
+        let index = match groups.index.get(key) {
+            Some(&index) => index,
+            None if groups.groups.len() < self.max_groups => {
+                groups.groups.push(self.new_group(key.to_vec()));
+                groups.index.insert(key.to_vec(), groups.groups.len() - 1);
+                groups.groups.len() - 1
+            }
+            None => return self.spill(row, spilled, level),
+        };
+        let group = &mut groups.groups[index];
+        let mut start = self.keys.len();
+        for (i, aggregate) in self.aggregates.iter().enumerate() {
+            let values = &row[start..start + aggregate.arguments.len()];
+            start += aggregate.arguments.len();
+            if !aggregate.distinct || group.seen[i].insert(values.to_vec()) {
+                group.accumulators[i].add(values)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn spill(&self, row: &[ObjectId], spilled: &mut Vec<Partition>, level: u64) -> Result<(), ExecutionError> {
+        let pages = match &self.pages {
+            Some(pages) => pages,
+            // Without pages every group is kept in memory
+            None => return Err(Box::new(LogicException::new("GroupBy spilled without temporary pages"))),
+        };
+        if spilled.is_empty() {
+            spilled.resize_with(SPILL_PARTITIONS, Partition::default);
+        }
+        let mut hasher = DefaultHasher::new();
+        level.hash(&mut hasher);
+        row[..self.keys.len()].hash(&mut hasher);
+        let partition = &mut spilled[(hasher.finish() % SPILL_PARTITIONS as u64) as usize];
+        partition.last_page.extend(row.iter().map(|value| value.raw()));
+        partition.rows += 1;
+        if partition.last_page.len() + row.len() > PAGE_SIZE / 8 {
+            partition.pages.push(pages.write_page(&encode_values(&partition.last_page))?);
+            partition.last_page.clear();
+        }
+        Ok(())
+    }
+
+    // Results of the groups of a pass, and the partitions it wrote for the next ones
+    fn finish_pass(&mut self, groups: Groups<'a>, spilled: Vec<Partition>, level: u64) -> Result<(), ExecutionError> {
+        for group in groups.groups {
+            let mut result = group.key;
+            for accumulator in &group.accumulators {
+                result.push(accumulator.value()?);
+            }
+            self.results.push_back(result);
+        }
+        for mut partition in spilled {
+            if partition.rows == 0 {
+                continue;
+            }
+            if let (Some(pages), false) = (&self.pages, partition.last_page.is_empty()) {
+                partition.pages.push(pages.write_page(&encode_values(&partition.last_page))?);
+                partition.last_page.clear();
+            }
+            self.partitions.push((partition, level + 1));
+        }
+        Ok(())
+    }
+
+    // First pass: the results of the child
+    fn group_child(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        if let Some(pages) = &self.pages {
+            let group_bytes = GROUP_OVERHEAD_BYTES + 8 * self.keys.len() + ACCUMULATOR_BYTES * self.aggregates.len();
+            self.max_groups = (pages.memory_limit()? / group_bytes).max(1);
+        }
+        let mut groups = Groups::default();
+        let mut spilled = Vec::new();
+        let mut row = Vec::with_capacity(self.row_width());
+        while self.child.next(binding)? {
+            self.interruption.check()?;
+            row.clear();
+            for (_, value) in &self.keys {
+                row.push(value(binding)?);
+            }
+            for aggregate in &self.aggregates {
+                for argument in &aggregate.arguments {
+                    row.push(argument(binding)?);
+                }
+            }
+            self.add_row(&row, &mut groups, &mut spilled, 0)?;
+        }
+        self.child.assign_nulls(binding);
+        if self.keys.is_empty() && groups.groups.is_empty() {
+            groups.groups.push(self.new_group(Vec::new()));
+        }
+        self.finish_pass(groups, spilled, 0)
+    }
+
+    fn group_partition(&mut self, partition: Partition, level: u64) -> Result<(), ExecutionError> {
+        let pages = match self.pages {
+            Some(pages) => pages,
+            None => return Ok(()),
+        };
+        let width = self.row_width();
+        let rows_per_page = (PAGE_SIZE / 8) / width;
+        let mut groups = Groups::default();
+        let mut spilled = Vec::new();
+        let mut data = vec![0; PAGE_SIZE];
+        let mut remaining = partition.rows;
+        for page_number in partition.pages {
+            self.interruption.check()?;
+            pages.read_page(page_number, &mut data)?;
+            let values = decode_values(&data);
+            for row in values.chunks_exact(width).take(remaining.min(rows_per_page)) {
+                self.add_row(row, &mut groups, &mut spilled, level)?;
+            }
+            remaining -= remaining.min(rows_per_page);
+        }
+        self.finish_pass(groups, spilled, level)
+    }
+}
+
+fn encode_values(values: &[u64]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn decode_values(data: &[u8]) -> Vec<ObjectId> {
+    data.chunks_exact(8).map(|bytes| ObjectId::from_raw(u64::from_le_bytes(bytes.try_into().unwrap()))).collect()
+}
+
+impl BindingIter for GroupBy<'_> {
+    fn begin(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.started = false;
+        self.results.clear();
+        self.partitions.clear();
+        self.child.begin(binding)
+    }
+
+    fn next(&mut self, binding: &mut Binding) -> Result<bool, ExecutionError> {
+        if !self.started {
+            self.started = true;
+            self.group_child(binding)?;
+        }
+        loop {
+            if let Some(result) = self.results.pop_front() {
+                let vars = self.keys.iter().map(|(var, _)| *var).chain(self.aggregates.iter().map(|aggregate| aggregate.var));
+                for (var, value) in vars.zip(result) {
+                    binding.set(var, value);
+                }
+                return Ok(true);
+            }
+            match self.partitions.pop() {
+                Some((partition, level)) => self.group_partition(partition, level)?,
+                None => return Ok(false),
+            }
+        }
+    }
+
+    fn reset(&mut self, binding: &mut Binding) -> Result<(), ExecutionError> {
+        self.assign_nulls(binding);
+        self.started = false;
+        self.results.clear();
+        self.partitions.clear();
+        self.child.reset(binding)
+    }
+
+    fn assign_nulls(&self, binding: &mut Binding) {
+        assign_nulls_to(&self.variables(), binding);
+    }
+
+    fn variables(&self) -> Vec<VarId> {
+        let keys = self.keys.iter().map(|(var, _)| *var).collect();
+        merge_variables(keys, self.aggregates.iter().map(|aggregate| aggregate.var).collect())
+    }
+}
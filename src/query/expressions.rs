@@ -0,0 +1,573 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+
+use crate::query::executor::{Binding, BoxedIter, ExecutionError, VarId};
+use crate::query::functions::{cast, evaluate_builtin};
+use crate::query::object_id::{canonical_integer, double_to_string, float_to_string, parse_double};
+use crate::query::query_executor::QueryTerms;
+use crate::query::rdf_terms::*;
+use crate::query::xsd_datetime::{XsdDateTime, XsdDuration};
+use crate::query::xsd_decimal::XsdDecimal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+// An expression of FILTER, BIND or ORDER BY with the variables replaced by their ids.
+// EXISTS keeps the operators of its pattern, started again for every result.
+pub enum CompiledExpression<'a> {
+    Variable(VarId),
+    Constant(RdfTerm),
+    Or(Box<CompiledExpression<'a>>, Box<CompiledExpression<'a>>),
+    And(Box<CompiledExpression<'a>>, Box<CompiledExpression<'a>>),
+    Not(Box<CompiledExpression<'a>>),
+    Compare(Comparison, Box<CompiledExpression<'a>>, Box<CompiledExpression<'a>>),
+    In {
+        value: Box<CompiledExpression<'a>>,
+        list: Vec<CompiledExpression<'a>>,
+        negated: bool,
+    },
+    Arithmetic(Arithmetic, Box<CompiledExpression<'a>>, Box<CompiledExpression<'a>>),
+    UnaryMinus(Box<CompiledExpression<'a>>),
+    UnaryPlus(Box<CompiledExpression<'a>>),
+    Bound(VarId),
+    // One of `SUPPORTED_BUILTINS`
+    Builtin(String, Vec<CompiledExpression<'a>>),
+    // The first argument without an error
+    Coalesce(Vec<CompiledExpression<'a>>),
+    If(Box<CompiledExpression<'a>>, Box<CompiledExpression<'a>>, Box<CompiledExpression<'a>>),
+    // A constructor function of XSD, to one of `SUPPORTED_CASTS`
+    Cast(String, Box<CompiledExpression<'a>>),
+    Exists {
+        pattern: RefCell<BoxedIter<'a>>,
+        negated: bool,
+    },
+}
+
+impl CompiledExpression<'_> {
+    // Value of the expression for the result in the binding. `None` is an error of SPARQL
+    // (an unbound variable, a type error...), that only makes the expression fail, while
+    // `Err` stops the query.
+    pub fn evaluate(&self, binding: &Binding, terms: &QueryTerms) -> Result<Option<RdfTerm>, ExecutionError> {
+        Ok(match self {
+            CompiledExpression::Variable(var) => terms.decode(binding.get(*var))?,
+            CompiledExpression::Constant(term) => Some(term.clone()),
+            CompiledExpression::Or(a, b) => {
+                let a = effective_boolean_value(a.evaluate(binding, terms)?.as_ref());
+                let b = effective_boolean_value(b.evaluate(binding, terms)?.as_ref());
+                match (a, b) {
+                    (Some(true), _) | (_, Some(true)) => Some(boolean(true)),
+                    (Some(false), Some(false)) => Some(boolean(false)),
+                    _ => None,
+                }
+            }
+            CompiledExpression::And(a, b) => {
+                let a = effective_boolean_value(a.evaluate(binding, terms)?.as_ref());
+                let b = effective_boolean_value(b.evaluate(binding, terms)?.as_ref());
+                match (a, b) {
+                    (Some(false), _) | (_, Some(false)) => Some(boolean(false)),
+                    (Some(true), Some(true)) => Some(boolean(true)),
+                    _ => None,
+                }
+            }
+            CompiledExpression::Not(a) => effective_boolean_value(a.evaluate(binding, terms)?.as_ref()).map(|value| boolean(!value)),
+            CompiledExpression::Compare(comparison, a, b) => {
+                match (a.evaluate(binding, terms)?, b.evaluate(binding, terms)?) {
+                    (Some(a), Some(b)) => compare(*comparison, &a, &b).map(boolean),
+                    _ => None,
+                }
+            }
+            CompiledExpression::In { value, list, negated } => {
+                let value = match value.evaluate(binding, terms)? {
+                    Some(value) => value,
+                    None => return Ok(None),
+                };
+                // Found if some member is equal, an error if none is but some had an error
+                let mut error = false;
+                let mut found = false;
+                for member in list {
+                    match member.evaluate(binding, terms)?.and_then(|member| compare(Comparison::Equal, &value, &member)) {
+                        Some(true) => {
+                            found = true;
+                            break;
+                        }
+                        Some(false) => {}
+                        None => error = true,
+                    }
+                }
+                if !found && error {
+                    None
+                } else {
+                    Some(boolean(found != *negated))
+                }
+            }
+            CompiledExpression::Arithmetic(operator, a, b) => match (a.evaluate(binding, terms)?, b.evaluate(binding, terms)?) {
+                (Some(a), Some(b)) => numeric_operation(*operator, &a, &b),
+                _ => None,
+            },
+            CompiledExpression::UnaryMinus(a) => {
+                a.evaluate(binding, terms)?.as_ref().and_then(Numeric::from_term).map(Numeric::negate).map(Numeric::to_term)
+            }
+            CompiledExpression::UnaryPlus(a) => {
+                a.evaluate(binding, terms)?.as_ref().and_then(Numeric::from_term).map(Numeric::to_term)
+            }
+            CompiledExpression::Bound(var) => Some(boolean(!binding.get(*var).is_null())),
+            CompiledExpression::Builtin(name, arguments) => {
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    match argument.evaluate(binding, terms)? {
+                        Some(value) => values.push(value),
+                        None => return Ok(None),
+                    }
+                }
+                evaluate_builtin(name, &values, binding, &terms.functions)
+            }
+            CompiledExpression::Coalesce(arguments) => {
+                for argument in arguments {
+                    if let Some(value) = argument.evaluate(binding, terms)? {
+                        return Ok(Some(value));
+                    }
+                }
+                None
+            }
+            CompiledExpression::If(condition, then, otherwise) => {
+                match effective_boolean_value(condition.evaluate(binding, terms)?.as_ref()) {
+                    Some(true) => then.evaluate(binding, terms)?,
+                    Some(false) => otherwise.evaluate(binding, terms)?,
+                    None => None,
+                }
+            }
+            CompiledExpression::Cast(datatype, argument) => argument.evaluate(binding, terms)?.and_then(|value| cast(&value, datatype)),
+            CompiledExpression::Exists { pattern, negated } => {
+                // The pattern reads the values of the result as constants
+                let mut binding = binding.clone();
+                let mut pattern = pattern.borrow_mut();
+                pattern.begin(&mut binding)?;
+                let found = pattern.next(&mut binding)?;
+                Some(boolean(found != *negated))
+            }
+        })
+    }
+
+    // Whether the result passes a FILTER with this expression
+    pub fn holds(&self, binding: &Binding, terms: &QueryTerms) -> Result<bool, ExecutionError> {
+        Ok(effective_boolean_value(self.evaluate(binding, terms)?.as_ref()) == Some(true))
+    }
+}
+
+pub fn boolean(value: bool) -> RdfTerm {
+    RdfTerm::typed_literal(if value { "true" } else { "false" }, XSD_BOOLEAN)
+}
+
+// Simple literals and xsd:string, the only strings comparable with < and >
+fn string_value(term: &RdfTerm) -> Option<&str> {
+    match term {
+        RdfTerm::Literal { value, datatype: None, language: None } => Some(value),
+        _ => None,
+    }
+}
+
+fn boolean_value(term: &RdfTerm) -> Option<bool> {
+    match term {
+        RdfTerm::Literal { value, datatype: Some(datatype), .. } if datatype == XSD_BOOLEAN => match value.as_str() {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Effective boolean value (section 17.2.2 of SPARQL 1.1), `None` when it is an error.
+// Booleans and numbers with an invalid lexical form are false.
+pub fn effective_boolean_value(term: Option<&RdfTerm>) -> Option<bool> {
+    let term = term?;
+    if let Some(value) = string_value(term) {
+        return Some(!value.is_empty());
+    }
+    match term {
+        RdfTerm::Literal { datatype: Some(datatype), .. } if datatype == XSD_BOOLEAN => Some(boolean_value(term).unwrap_or(false)),
+        RdfTerm::Literal { datatype: Some(datatype), .. } if Numeric::is_numeric_datatype(datatype) => {
+            Some(Numeric::from_term(term).is_some_and(|value| value.is_true()))
+        }
+        _ => None,
+    }
+}
+
+// Order of two terms for the operators, `None` when they can not be compared
+fn partial_order(a: &RdfTerm, b: &RdfTerm) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (Numeric::from_term(a), Numeric::from_term(b)) {
+        return a.partial_cmp(&b);
+    }
+    if let (Some(a), Some(b)) = (string_value(a), string_value(b)) {
+        return Some(a.cmp(b));
+    }
+    if let (Some(a), Some(b)) = (boolean_value(a), boolean_value(b)) {
+        return Some(a.cmp(&b));
+    }
+    // Dates are only compared with dates, and dateTimes with dateTimes
+    if let (Some(a), Some(b)) = (date_time_value(a), date_time_value(b)) {
+        return (a.date_only == b.date_only).then(|| a.compare(&b));
+    }
+    if let (Some(a), Some(b)) = (duration_value(a), duration_value(b)) {
+        return a.partial_cmp(&b);
+    }
+    None
+}
+
+fn date_time_value(term: &RdfTerm) -> Option<XsdDateTime> {
+    match term {
+        RdfTerm::Literal { value, datatype: Some(datatype), .. } => match datatype.as_str() {
+            XSD_DATE_TIME => XsdDateTime::parse_date_time(value),
+            XSD_DATE => XsdDateTime::parse_date(value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn duration_value(term: &RdfTerm) -> Option<XsdDuration> {
+    match term {
+        RdfTerm::Literal { value, datatype: Some(datatype), .. } => match datatype.as_str() {
+            XSD_DURATION => XsdDuration::parse(value),
+            XSD_YEAR_MONTH_DURATION => XsdDuration::parse_year_month(value),
+            XSD_DAY_TIME_DURATION => XsdDuration::parse_day_time(value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Literals whose values the evaluator understands, so that different terms are different
+// values. Comparing other literals that are not the same term is an error.
+fn has_known_value(term: &RdfTerm) -> bool {
+    match term {
+        RdfTerm::Literal { datatype: None, .. } => true,
+        RdfTerm::Literal { datatype: Some(datatype), .. } => {
+            matches!(
+                datatype.as_str(),
+                XSD_BOOLEAN | XSD_DATE_TIME | XSD_DATE | XSD_DURATION | XSD_YEAR_MONTH_DURATION | XSD_DAY_TIME_DURATION
+            ) || Numeric::is_numeric_datatype(datatype)
+        }
+        _ => true,
+    }
+}
+
+fn compare(comparison: Comparison, a: &RdfTerm, b: &RdfTerm) -> Option<bool> {
+    let order = partial_order(a, b);
+    match comparison {
+        Comparison::Equal | Comparison::NotEqual => {
+            let equal = match order {
+                Some(order) => order == Ordering::Equal,
+                None if a == b => true,
+                None if matches!(a, RdfTerm::Literal { .. }) && matches!(b, RdfTerm::Literal { .. }) => {
+                    if !has_known_value(a) || !has_known_value(b) {
+                        return None;
+                    }
+                    false
+                }
+                None => false,
+            };
+            Some(equal == (comparison == Comparison::Equal))
+        }
+        Comparison::Less => order.map(Ordering::is_lt),
+        Comparison::LessOrEqual => order.map(Ordering::is_le),
+        Comparison::Greater => order.map(Ordering::is_gt),
+        Comparison::GreaterOrEqual => order.map(Ordering::is_ge),
+    }
+}
+
+// Integer types derived from xsd:integer, with their smallest and largest values
+const DERIVED_INTEGERS: &[(&str, Option<&str>, Option<&str>)] = &[
+    ("nonPositiveInteger", None, Some("0")),
+    ("negativeInteger", None, Some("-1")),
+    ("long", Some("-9223372036854775808"), Some("9223372036854775807")),
+    ("int", Some("-2147483648"), Some("2147483647")),
+    ("short", Some("-32768"), Some("32767")),
+    ("byte", Some("-128"), Some("127")),
+    ("nonNegativeInteger", Some("0"), None),
+    ("unsignedLong", Some("0"), Some("18446744073709551615")),
+    ("unsignedInt", Some("0"), Some("4294967295")),
+    ("unsignedShort", Some("0"), Some("65535")),
+    ("unsignedByte", Some("0"), Some("255")),
+    ("positiveInteger", Some("1"), None),
+];
+
+// Order of ORDER BY, MIN and MAX (section 15.1 of SPARQL 1.1): unbound values, blank
+// nodes, IRIs, literals and then paths. Literals of a kind the operators compare are
+// ordered by value, and the rest of the ties by datatype and lexical form, so the order
+// is total even for values the operators can not compare.
+pub fn order_terms(a: Option<&RdfTerm>, b: Option<&RdfTerm>) -> Ordering {
+    let (a, b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return a.is_some().cmp(&b.is_some()),
+    };
+    let rank = |term: &RdfTerm| match term {
+        RdfTerm::BlankNode(_) => 0,
+        RdfTerm::Iri(_) => 1,
+        RdfTerm::Literal { .. } => 2,
+        RdfTerm::Path(_) => 3,
+    };
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (RdfTerm::BlankNode(a), RdfTerm::BlankNode(b)) | (RdfTerm::Iri(a), RdfTerm::Iri(b)) => a.cmp(b),
+        (
+            RdfTerm::Literal { value: x, datatype: x_datatype, language: x_language },
+            RdfTerm::Literal { value: y, datatype: y_datatype, language: y_language },
+        ) => literal_key(a).cmp(&literal_key(b)).then_with(|| (x_datatype, x_language, x).cmp(&(y_datatype, y_language, y))),
+        _ => a.to_string().cmp(&b.to_string()),
+    })
+}
+
+// The kind of a literal in ORDER BY, and the value that orders it within the kind
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum LiteralKey<'a> {
+    Numeric(NumericKey),
+    Boolean(bool),
+    DateTime((i64, u32)),
+    Date((i64, u32)),
+    // Ordered by months first, an order that agrees with the one of the operators
+    Duration(i64, XsdDecimal),
+    String(&'a str),
+    LangString(&'a str),
+    Other,
+}
+
+// Numbers of all the types in one order: NaN, which the operators do not order, is the
+// smallest. Floats and doubles are the shortest decimal that reads back as them.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum NumericKey {
+    NaN,
+    NegativeInfinity,
+    Finite(XsdDecimal),
+    Infinity,
+}
+
+fn literal_key(term: &RdfTerm) -> LiteralKey<'_> {
+    if let Some(number) = Numeric::from_term(term) {
+        let value = number.as_f64();
+        return LiteralKey::Numeric(match number {
+            _ if value.is_nan() => NumericKey::NaN,
+            _ if value == f64::NEG_INFINITY => NumericKey::NegativeInfinity,
+            _ if value == f64::INFINITY => NumericKey::Infinity,
+            Numeric::Integer(value) | Numeric::Decimal(value) => NumericKey::Finite(value),
+            Numeric::Float(value) => NumericKey::Finite(XsdDecimal::from_f32(value).unwrap_or_else(XsdDecimal::zero)),
+            Numeric::Double(value) => NumericKey::Finite(XsdDecimal::from_f64(value).unwrap_or_else(XsdDecimal::zero)),
+        });
+    }
+    if let Some(value) = boolean_value(term) {
+        return LiteralKey::Boolean(value);
+    }
+    if let Some(value) = date_time_value(term) {
+        return if value.date_only { LiteralKey::Date(value.instant()) } else { LiteralKey::DateTime(value.instant()) };
+    }
+    if let Some(value) = duration_value(term) {
+        return LiteralKey::Duration(value.months, value.seconds);
+    }
+    match term {
+        RdfTerm::Literal { value, datatype: None, language: None } => LiteralKey::String(value),
+        RdfTerm::Literal { value, datatype: None, language: Some(_) } => LiteralKey::LangString(value),
+        _ => LiteralKey::Other,
+    }
+}
+
+// A number of the query, promoted integer -> decimal -> float -> double by the operators.
+// Integers and decimals have any number of digits, so their operations do not overflow.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Numeric {
+    Integer(XsdDecimal),
+    Decimal(XsdDecimal),
+    Float(f32),
+    Double(f64),
+}
+
+impl Numeric {
+    fn is_numeric_datatype(datatype: &str) -> bool {
+        matches!(datatype, XSD_INTEGER | XSD_DECIMAL | XSD_FLOAT | XSD_DOUBLE) || derived_integer(datatype).is_some()
+    }
+
+    pub fn from_term(term: &RdfTerm) -> Option<Self> {
+        let (value, datatype) = match term {
+            RdfTerm::Literal { value, datatype: Some(datatype), .. } => (value, datatype.as_str()),
+            _ => return None,
+        };
+        match datatype {
+            XSD_INTEGER => XsdDecimal::parse(&canonical_integer(value)?).map(Numeric::Integer),
+            XSD_DECIMAL => XsdDecimal::parse(value).map(Numeric::Decimal),
+            XSD_FLOAT => {
+                parse_double(value)?;
+                value.parse().ok().map(Numeric::Float)
+            }
+            XSD_DOUBLE => parse_double(value).map(Numeric::Double),
+            _ => {
+                let (minimum, maximum) = derived_integer(datatype)?;
+                let value = XsdDecimal::parse(&canonical_integer(value)?)?;
+                let in_range = minimum.is_none_or(|minimum| XsdDecimal::parse(minimum).is_some_and(|minimum| value >= minimum))
+                    && maximum.is_none_or(|maximum| XsdDecimal::parse(maximum).is_some_and(|maximum| value <= maximum));
+                in_range.then_some(Numeric::Integer(value))
+            }
+        }
+    }
+
+    pub fn to_term(self) -> RdfTerm {
+        match self {
+            Numeric::Integer(value) => RdfTerm::typed_literal(&value.integer_string(), XSD_INTEGER),
+            Numeric::Decimal(value) => RdfTerm::typed_literal(&value.to_string(), XSD_DECIMAL),
+            Numeric::Float(value) => RdfTerm::typed_literal(&float_to_string(value), XSD_FLOAT),
+            Numeric::Double(value) => RdfTerm::typed_literal(&double_to_string(value), XSD_DOUBLE),
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Numeric::Integer(value) | Numeric::Decimal(value) => value.to_f64(),
+            Numeric::Float(value) => *value as f64,
+            Numeric::Double(value) => *value,
+        }
+    }
+
+    fn as_f32(&self) -> f32 {
+        match self {
+            Numeric::Float(value) => *value,
+            _ => self.as_f64() as f32,
+        }
+    }
+
+    pub fn is_true(&self) -> bool {
+        match self {
+            Numeric::Integer(value) | Numeric::Decimal(value) => !value.is_zero(),
+            _ => {
+                let value = self.as_f64();
+                value != 0.0 && !value.is_nan()
+            }
+        }
+    }
+
+    pub fn abs(self) -> Self {
+        match self {
+            Numeric::Integer(value) => Numeric::Integer(value.abs()),
+            Numeric::Decimal(value) => Numeric::Decimal(value.abs()),
+            Numeric::Float(value) => Numeric::Float(value.abs()),
+            Numeric::Double(value) => Numeric::Double(value.abs()),
+        }
+    }
+
+    // fn:round: to the nearest integer, halves rounded up
+    pub fn round(self) -> Self {
+        match self {
+            Numeric::Decimal(value) => Numeric::Decimal(value.round()),
+            _ => self.map_float(|value| (value + 0.5).floor()),
+        }
+    }
+
+    pub fn ceil(self) -> Self {
+        match self {
+            Numeric::Decimal(value) => Numeric::Decimal(value.ceil()),
+            _ => self.map_float(f64::ceil),
+        }
+    }
+
+    pub fn floor(self) -> Self {
+        match self {
+            Numeric::Decimal(value) => Numeric::Decimal(value.floor()),
+            _ => self.map_float(f64::floor),
+        }
+    }
+
+    // The integer part as an integer, `None` when it is not finite
+    pub fn truncate(self) -> Option<Self> {
+        match self {
+            Numeric::Integer(_) => Some(self),
+            Numeric::Decimal(value) => Some(Numeric::Integer(value.truncate())),
+            _ => XsdDecimal::from_f64(self.as_f64().trunc()).map(Numeric::Integer),
+        }
+    }
+
+    // `None` for NaN and the infinities, which are not decimals
+    pub fn to_decimal(self) -> Option<Self> {
+        match self {
+            Numeric::Integer(value) | Numeric::Decimal(value) => Some(Numeric::Decimal(value)),
+            Numeric::Float(value) => XsdDecimal::from_f32(value).map(Numeric::Decimal),
+            Numeric::Double(value) => XsdDecimal::from_f64(value).map(Numeric::Decimal),
+        }
+    }
+
+    // Operations of floats are done on doubles, whose result rounds to the one of floats
+    fn map_float(self, function: impl Fn(f64) -> f64) -> Self {
+        match self {
+            Numeric::Float(value) => Numeric::Float(function(value as f64) as f32),
+            Numeric::Double(value) => Numeric::Double(function(value)),
+            _ => self,
+        }
+    }
+
+    fn negate(self) -> Self {
+        match self {
+            Numeric::Integer(value) => Numeric::Integer(value.negate()),
+            Numeric::Decimal(value) => Numeric::Decimal(value.negate()),
+            Numeric::Float(value) => Numeric::Float(-value),
+            Numeric::Double(value) => Numeric::Double(-value),
+        }
+    }
+
+    // Compared in the type both are promoted to
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Numeric::Integer(a) | Numeric::Decimal(a), Numeric::Integer(b) | Numeric::Decimal(b)) => Some(a.cmp(b)),
+            (Numeric::Double(_), _) | (_, Numeric::Double(_)) => self.as_f64().partial_cmp(&other.as_f64()),
+            _ => self.as_f32().partial_cmp(&other.as_f32()),
+        }
+    }
+}
+
+// The smallest and largest values of an integer type derived from xsd:integer
+fn derived_integer(datatype: &str) -> Option<(Option<&'static str>, Option<&'static str>)> {
+    let name = datatype.strip_prefix(XSD_PREFIX)?;
+    DERIVED_INTEGERS.iter().find(|(derived, ..)| *derived == name).map(|&(_, minimum, maximum)| (minimum, maximum))
+}
+
+// Result of an arithmetic operator, `None` when a term is not a number or the operation fails
+pub fn numeric_operation(operator: Arithmetic, a: &RdfTerm, b: &RdfTerm) -> Option<RdfTerm> {
+    arithmetic(operator, Numeric::from_term(a)?, Numeric::from_term(b)?).map(Numeric::to_term)
+}
+
+// `None` for a division by zero that is not of floats or doubles. The division of two
+// integers is a decimal.
+fn arithmetic(operator: Arithmetic, a: Numeric, b: Numeric) -> Option<Numeric> {
+    match (&a, &b) {
+        (Numeric::Integer(x) | Numeric::Decimal(x), Numeric::Integer(y) | Numeric::Decimal(y)) => {
+            let value = match operator {
+                Arithmetic::Add => x.add(y),
+                Arithmetic::Subtract => x.subtract(y),
+                Arithmetic::Multiply => x.multiply(y),
+                Arithmetic::Divide => x.divide(y)?,
+            };
+            let integers = matches!((&a, &b), (Numeric::Integer(_), Numeric::Integer(_)));
+            Some(if integers && operator != Arithmetic::Divide { Numeric::Integer(value) } else { Numeric::Decimal(value) })
+        }
+        (Numeric::Double(_), _) | (_, Numeric::Double(_)) => Some(Numeric::Double(float_arithmetic(operator, a.as_f64(), b.as_f64()))),
+        _ => Some(Numeric::Float(float_arithmetic(operator, a.as_f32() as f64, b.as_f32() as f64) as f32)),
+    }
+}
+
+fn float_arithmetic(operator: Arithmetic, a: f64, b: f64) -> f64 {
+    match operator {
+        Arithmetic::Add => a + b,
+        Arithmetic::Subtract => a - b,
+        Arithmetic::Multiply => a * b,
+        Arithmetic::Divide => a / b,
+    }
+}
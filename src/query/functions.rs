@@ -0,0 +1,437 @@
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::query::executor::Binding;
+use crate::query::expressions::{boolean, Numeric};
+use crate::query::hashes::{md5, sha1, sha256, sha384, sha512, to_hex};
+use crate::query::object_id::ObjectId;
+use crate::query::rdf_terms::*;
+use crate::query::regex::Regex;
+use crate::query::xsd_datetime::XsdDateTime;
+use crate::query::xsd_decimal::XsdDecimal;
+
+// Built-in calls the evaluator knows, the others are refused when the query is compiled.
+// COALESCE and IF have their own expressions, as they do not evaluate all their arguments.
+pub const SUPPORTED_BUILTINS: &[&str] = &[
+    "STR", "LANG", "LANGMATCHES", "DATATYPE", "IRI", "URI", "BNODE", "RAND", "ABS", "CEIL", "FLOOR", "ROUND", "CONCAT",
+    "STRLEN", "UCASE", "LCASE", "ENCODE_FOR_URI", "CONTAINS", "STRSTARTS", "STRENDS", "STRBEFORE", "STRAFTER", "YEAR",
+    "MONTH", "DAY", "HOURS", "MINUTES", "SECONDS", "TIMEZONE", "TZ", "NOW", "UUID", "STRUUID", "MD5", "SHA1", "SHA256",
+    "SHA384", "SHA512", "STRLANG", "STRDT", "SAMETERM", "ISIRI", "ISURI", "ISBLANK", "ISLITERAL", "ISNUMERIC", "REGEX",
+    "SUBSTR", "REPLACE",
+];
+
+// Datatypes a function call with their IRI can cast to (section 17.5 of SPARQL 1.1)
+pub const SUPPORTED_CASTS: &[&str] = &[XSD_STRING, XSD_BOOLEAN, XSD_INTEGER, XSD_DECIMAL, XSD_FLOAT, XSD_DOUBLE, XSD_DATE_TIME];
+
+// Patterns of REGEX and REPLACE compiled by a query are kept until there are this many
+const MAX_CACHED_REGEXES: usize = 64;
+
+// Compiled patterns by pattern and flags, `None` for the invalid ones
+type RegexCache = HashMap<(String, String), Option<Rc<Regex>>>;
+
+// State of the functions whose value does not only depend on their arguments. A query has
+// one, so NOW is the same in the whole query and the blank nodes of BNODE are not the ones
+// of other queries.
+pub struct FunctionContext {
+    now: XsdDateTime,
+    // Of the blank nodes of the query
+    salt: u64,
+    random: Cell<u64>,
+    blank_nodes: Cell<u64>,
+    // The binding of the last BNODE with a label, and the blank nodes of its labels
+    blank_node_labels: RefCell<(Vec<ObjectId>, HashMap<String, RdfTerm>)>,
+    regexes: RefCell<RegexCache>,
+}
+
+impl Default for FunctionContext {
+    fn default() -> Self {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let seed = RandomState::new().build_hasher().finish();
+        Self {
+            now: XsdDateTime::from_unix_time(since_epoch.as_secs() as i64, since_epoch.subsec_nanos()),
+            salt: seed >> 32,
+            // xorshift needs a state that is not 0
+            random: Cell::new(seed | 1),
+            blank_nodes: Cell::new(0),
+            blank_node_labels: RefCell::new((Vec::new(), HashMap::new())),
+            regexes: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl FunctionContext {
+    // xorshift64*
+    fn next_random(&self) -> u64 {
+        let mut x = self.random.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.random.set(x);
+        x.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    // A random UUID (version 4)
+    fn uuid(&self) -> String {
+        let (high, low) = (self.next_random(), self.next_random());
+        let high = (high & !0xF000) | 0x4000;
+        let low = (low & !(0xC << 60)) | (0x8 << 60);
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xFFFF,
+            high & 0xFFFF,
+            low >> 48,
+            low & 0xFFFF_FFFF_FFFF
+        )
+    }
+
+    // A new blank node, or with a label the same blank node for the same label within a
+    // result. A binding that only adds values to the one of the last call (as the next BIND
+    // does) is taken as the same result.
+    fn blank_node(&self, label: Option<&str>, binding: &Binding) -> RdfTerm {
+        let label = match label {
+            Some(label) => label,
+            None => return self.new_blank_node(),
+        };
+        let mut labels = self.blank_node_labels.borrow_mut();
+        let (values, blank_nodes) = &mut *labels;
+        let same_result = values.len() == binding.values().len()
+            && values.iter().zip(binding.values()).all(|(value, current)| value.is_null() || value == current);
+        if !same_result {
+            blank_nodes.clear();
+        }
+        *values = binding.values().to_vec();
+        blank_nodes.entry(label.to_string()).or_insert_with(|| self.new_blank_node()).clone()
+    }
+
+    fn new_blank_node(&self) -> RdfTerm {
+        self.blank_nodes.set(self.blank_nodes.get() + 1);
+        RdfTerm::BlankNode(format!("q{:x}_{}", self.salt, self.blank_nodes.get()))
+    }
+
+    fn regex(&self, pattern: &str, flags: &str) -> Option<Rc<Regex>> {
+        let mut regexes = self.regexes.borrow_mut();
+        let key = (pattern.to_string(), flags.to_string());
+        if let Some(regex) = regexes.get(&key) {
+            return regex.clone();
+        }
+        if regexes.len() >= MAX_CACHED_REGEXES {
+            regexes.clear();
+        }
+        let regex = Regex::new(pattern, flags).map(Rc::new);
+        regexes.insert(key, regex.clone());
+        regex
+    }
+}
+
+// Value of a built-in call with the values of its arguments, `None` when it is an error
+pub fn evaluate_builtin(name: &str, arguments: &[RdfTerm], binding: &Binding, context: &FunctionContext) -> Option<RdfTerm> {
+    match (name, arguments) {
+        // Terms
+        ("STR", [RdfTerm::Iri(iri)]) => Some(RdfTerm::simple_literal(iri)),
+        ("STR", [RdfTerm::Literal { value, .. }]) => Some(RdfTerm::simple_literal(value)),
+        ("LANG", [RdfTerm::Literal { language, .. }]) => Some(RdfTerm::simple_literal(language.as_deref().unwrap_or(""))),
+        ("DATATYPE", [RdfTerm::Literal { datatype, language, .. }]) => Some(RdfTerm::iri(match (datatype, language) {
+            (Some(datatype), _) => datatype,
+            (None, Some(_)) => RDF_LANG_STRING,
+            (None, None) => XSD_STRING,
+        })),
+        ("ISIRI" | "ISURI", [term]) => Some(boolean(matches!(term, RdfTerm::Iri(_)))),
+        ("ISBLANK", [term]) => Some(boolean(matches!(term, RdfTerm::BlankNode(_)))),
+        ("ISLITERAL", [term]) => Some(boolean(matches!(term, RdfTerm::Literal { .. }))),
+        ("ISNUMERIC", [term]) => Some(boolean(Numeric::from_term(term).is_some())),
+        ("SAMETERM", [a, b]) => Some(boolean(a == b)),
+        // The IRI is not resolved against the base of the query
+        ("IRI" | "URI", [RdfTerm::Iri(iri)]) => Some(RdfTerm::iri(iri)),
+        ("IRI" | "URI", [term]) => Some(RdfTerm::iri(simple_string(term)?)),
+        ("BNODE", []) => Some(context.blank_node(None, binding)),
+        ("BNODE", [label]) => Some(context.blank_node(Some(simple_string(label)?), binding)),
+        ("STRDT", [lexical, RdfTerm::Iri(datatype)]) => Some(RdfTerm::typed_literal(simple_string(lexical)?, datatype)),
+        ("STRLANG", [lexical, language]) => {
+            let (lexical, language) = (simple_string(lexical)?, simple_string(language)?);
+            is_language_tag(language).then(|| RdfTerm::lang_literal(lexical, language))
+        }
+        ("UUID", []) => Some(RdfTerm::iri(&format!("urn:uuid:{}", context.uuid()))),
+        ("STRUUID", []) => Some(RdfTerm::simple_literal(&context.uuid())),
+
+        // Strings
+        ("STRLEN", [string]) => Some(integer(string_literal(string)?.0.chars().count() as i64)),
+        ("SUBSTR", [string, start]) => substring(string, start, None),
+        ("SUBSTR", [string, start, length]) => substring(string, start, Some(length)),
+        ("UCASE", [string]) => {
+            let (value, language) = string_literal(string)?;
+            Some(string_with_language(&value.to_uppercase(), language))
+        }
+        ("LCASE", [string]) => {
+            let (value, language) = string_literal(string)?;
+            Some(string_with_language(&value.to_lowercase(), language))
+        }
+        ("STRSTARTS", [a, b]) => compatible_strings(a, b).map(|(a, _, b)| boolean(a.starts_with(b))),
+        ("STRENDS", [a, b]) => compatible_strings(a, b).map(|(a, _, b)| boolean(a.ends_with(b))),
+        ("CONTAINS", [a, b]) => compatible_strings(a, b).map(|(a, _, b)| boolean(a.contains(b))),
+        // Without a match the result is the empty simple literal
+        ("STRBEFORE", [a, b]) => {
+            let (a, language, b) = compatible_strings(a, b)?;
+            Some(match a.find(b) {
+                Some(position) => string_with_language(&a[..position], language),
+                None => RdfTerm::simple_literal(""),
+            })
+        }
+        ("STRAFTER", [a, b]) => {
+            let (a, language, b) = compatible_strings(a, b)?;
+            Some(match a.find(b) {
+                Some(position) => string_with_language(&a[position + b.len()..], language),
+                None => RdfTerm::simple_literal(""),
+            })
+        }
+        ("ENCODE_FOR_URI", [string]) => Some(RdfTerm::simple_literal(&encode_for_uri(string_literal(string)?.0))),
+        ("CONCAT", strings) => {
+            let mut result = String::new();
+            let mut languages = Vec::with_capacity(strings.len());
+            for string in strings {
+                let (value, language) = string_literal(string)?;
+                result.push_str(value);
+                languages.push(language);
+            }
+            // The language is kept when all the strings have the same
+            let language = match languages.first() {
+                Some(&first) if languages.iter().all(|&language| language == first) => first,
+                _ => None,
+            };
+            Some(string_with_language(&result, language))
+        }
+        ("LANGMATCHES", [tag, range]) => Some(boolean(language_matches(simple_string(tag)?, simple_string(range)?))),
+        ("REGEX", [text, pattern]) => regex_matches(text, pattern, None, context),
+        ("REGEX", [text, pattern, flags]) => regex_matches(text, pattern, Some(flags), context),
+        ("REPLACE", [text, pattern, replacement]) => regex_replace(text, pattern, replacement, None, context),
+        ("REPLACE", [text, pattern, replacement, flags]) => regex_replace(text, pattern, replacement, Some(flags), context),
+
+        // Numbers
+        ("ABS", [number]) => Some(Numeric::from_term(number)?.abs().to_term()),
+        ("ROUND", [number]) => Some(Numeric::from_term(number)?.round().to_term()),
+        ("CEIL", [number]) => Some(Numeric::from_term(number)?.ceil().to_term()),
+        ("FLOOR", [number]) => Some(Numeric::from_term(number)?.floor().to_term()),
+        ("RAND", []) => Some(Numeric::Double((context.next_random() >> 11) as f64 / (1u64 << 53) as f64).to_term()),
+
+        // Dates
+        ("NOW", []) => Some(RdfTerm::typed_literal(&context.now.to_string(), XSD_DATE_TIME)),
+        ("YEAR", [date]) => Some(integer(date_time(date, true)?.year)),
+        ("MONTH", [date]) => Some(integer(date_time(date, true)?.month as i64)),
+        ("DAY", [date]) => Some(integer(date_time(date, true)?.day as i64)),
+        ("HOURS", [date]) => Some(integer(date_time(date, false)?.hour as i64)),
+        ("MINUTES", [date]) => Some(integer(date_time(date, false)?.minute as i64)),
+        ("SECONDS", [date]) => {
+            let date = date_time(date, false)?;
+            let fraction = format!("{:09}", date.nanosecond);
+            let fraction = fraction.trim_end_matches('0');
+            let fraction = if fraction.is_empty() { "0" } else { fraction };
+            Some(RdfTerm::typed_literal(&format!("{}.{}", date.second, fraction), XSD_DECIMAL))
+        }
+        ("TIMEZONE", [date]) => {
+            let offset = date_time(date, true)?.timezone?;
+            Some(RdfTerm::typed_literal(&day_time_duration(offset), XSD_DAY_TIME_DURATION))
+        }
+        ("TZ", [date]) => Some(RdfTerm::simple_literal(&match date_time(date, true)?.timezone {
+            None => String::new(),
+            Some(0) => "Z".to_string(),
+            Some(offset) => format!("{}{:02}:{:02}", if offset < 0 { '-' } else { '+' }, offset.abs() / 60, offset.abs() % 60),
+        })),
+
+        // Hashes of the UTF-8 bytes of a simple literal
+        ("MD5", [string]) => Some(RdfTerm::simple_literal(&to_hex(&md5(simple_string(string)?.as_bytes())))),
+        ("SHA1", [string]) => Some(RdfTerm::simple_literal(&to_hex(&sha1(simple_string(string)?.as_bytes())))),
+        ("SHA256", [string]) => Some(RdfTerm::simple_literal(&to_hex(&sha256(simple_string(string)?.as_bytes())))),
+        ("SHA384", [string]) => Some(RdfTerm::simple_literal(&to_hex(&sha384(simple_string(string)?.as_bytes())))),
+        ("SHA512", [string]) => Some(RdfTerm::simple_literal(&to_hex(&sha512(simple_string(string)?.as_bytes())))),
+        _ => None,
+    }
+}
+
+// A term cast with a constructor function of XSD (section 17.5 of SPARQL 1.1), `None`
+// when the cast is not allowed or the value is not in the value space of the datatype.
+// Strings are cast by their lexical form without the surrounding whitespace.
+pub fn cast(term: &RdfTerm, datatype: &str) -> Option<RdfTerm> {
+    let (value, source) = match term {
+        RdfTerm::Iri(iri) if datatype == XSD_STRING => return Some(RdfTerm::simple_literal(iri)),
+        RdfTerm::Literal { value, datatype, language: None } => (value.as_str(), datatype.as_deref().unwrap_or(XSD_STRING)),
+        _ => return None,
+    };
+    if datatype == XSD_STRING {
+        return Some(RdfTerm::simple_literal(value));
+    }
+    // Booleans are cast as the numbers 1 and 0
+    let number = match source {
+        XSD_STRING | XSD_DATE_TIME => None,
+        XSD_BOOLEAN => match value {
+            "true" | "1" => Some(Numeric::Integer(XsdDecimal::from(1))),
+            "false" | "0" => Some(Numeric::Integer(XsdDecimal::from(0))),
+            _ => return None,
+        },
+        _ => Some(Numeric::from_term(term)?),
+    };
+    match (datatype, number) {
+        (XSD_BOOLEAN, Some(number)) => Some(boolean(number.is_true())),
+        (XSD_INTEGER, Some(number)) => number.truncate().map(Numeric::to_term),
+        (XSD_DECIMAL, Some(number)) => number.to_decimal().map(Numeric::to_term),
+        (XSD_FLOAT, Some(number)) => Some(Numeric::Float(number.as_f64() as f32).to_term()),
+        (XSD_DOUBLE, Some(number)) => Some(Numeric::Double(number.as_f64()).to_term()),
+        (XSD_BOOLEAN, None) if source == XSD_STRING => match value.trim() {
+            "true" | "1" => Some(boolean(true)),
+            "false" | "0" => Some(boolean(false)),
+            _ => None,
+        },
+        (XSD_INTEGER | XSD_DECIMAL | XSD_FLOAT | XSD_DOUBLE, None) if source == XSD_STRING => {
+            Numeric::from_term(&RdfTerm::typed_literal(value.trim(), datatype)).map(Numeric::to_term)
+        }
+        (XSD_DATE_TIME, None) => {
+            let value = XsdDateTime::parse_date_time(value.trim())?;
+            Some(RdfTerm::typed_literal(&value.to_string(), XSD_DATE_TIME))
+        }
+        _ => None,
+    }
+}
+
+fn integer(value: i64) -> RdfTerm {
+    RdfTerm::typed_literal(&value.to_string(), XSD_INTEGER)
+}
+
+// The lexical form and language of a string literal: a simple literal, an xsd:string or
+// a literal with a language
+fn string_literal(term: &RdfTerm) -> Option<(&str, Option<&str>)> {
+    match term {
+        RdfTerm::Literal { value, datatype: None, language } => Some((value, language.as_deref())),
+        _ => None,
+    }
+}
+
+// The lexical form of a simple literal or an xsd:string
+fn simple_string(term: &RdfTerm) -> Option<&str> {
+    match string_literal(term)? {
+        (value, None) => Some(value),
+        _ => None,
+    }
+}
+
+fn string_with_language(value: &str, language: Option<&str>) -> RdfTerm {
+    match language {
+        Some(language) => RdfTerm::lang_literal(value, language),
+        None => RdfTerm::simple_literal(value),
+    }
+}
+
+// Arguments of STRSTARTS, STRENDS, CONTAINS, STRBEFORE and STRAFTER (section 17.4.3.1.1):
+// two string literals where the second is simple or has the language of the first
+fn compatible_strings<'t>(a: &'t RdfTerm, b: &'t RdfTerm) -> Option<(&'t str, Option<&'t str>, &'t str)> {
+    let ((a, a_language), (b, b_language)) = (string_literal(a)?, string_literal(b)?);
+    if b_language.is_some() && b_language != a_language {
+        return None;
+    }
+    Some((a, a_language, b))
+}
+
+// fn:substring: the chars from position `start` (from 1), both rounded
+fn substring(string: &RdfTerm, start: &RdfTerm, length: Option<&RdfTerm>) -> Option<RdfTerm> {
+    let (value, language) = string_literal(string)?;
+    let start = Numeric::from_term(start)?.round().as_f64();
+    let end = match length {
+        Some(length) => start + Numeric::from_term(length)?.round().as_f64(),
+        None => f64::INFINITY,
+    };
+    let result: String = value
+        .chars()
+        .enumerate()
+        .filter(|(i, _)| {
+            let position = (i + 1) as f64;
+            position >= start && position < end
+        })
+        .map(|(_, c)| c)
+        .collect();
+    Some(string_with_language(&result, language))
+}
+
+// Percent-encoding of the UTF-8 bytes of every char that is not unreserved (RFC 3986)
+fn encode_for_uri(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+// Basic filtering of RFC 4647: "*" matches all the tags but the empty one, and a range
+// matches the tags it is equal to or a prefix of followed by '-', ignoring case
+fn language_matches(tag: &str, range: &str) -> bool {
+    if range == "*" {
+        return !tag.is_empty();
+    }
+    let (tag, range) = (tag.to_ascii_lowercase(), range.to_ascii_lowercase());
+    !range.is_empty() && (tag == range || tag.strip_prefix(&range).is_some_and(|rest| rest.starts_with('-')))
+}
+
+// [a-zA-Z]+ ('-' [a-zA-Z0-9]+)*
+fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let first = subtags.next().unwrap_or_default();
+    !first.is_empty()
+        && first.bytes().all(|b| b.is_ascii_alphabetic())
+        && subtags.all(|subtag| !subtag.is_empty() && subtag.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+fn regex_matches(text: &RdfTerm, pattern: &RdfTerm, flags: Option<&RdfTerm>, context: &FunctionContext) -> Option<RdfTerm> {
+    let flags = match flags {
+        Some(flags) => simple_string(flags)?,
+        None => "",
+    };
+    let regex = context.regex(simple_string(pattern)?, flags)?;
+    Some(boolean(regex.is_match(string_literal(text)?.0)))
+}
+
+fn regex_replace(
+    text: &RdfTerm,
+    pattern: &RdfTerm,
+    replacement: &RdfTerm,
+    flags: Option<&RdfTerm>,
+    context: &FunctionContext,
+) -> Option<RdfTerm> {
+    let flags = match flags {
+        Some(flags) => simple_string(flags)?,
+        None => "",
+    };
+    let regex = context.regex(simple_string(pattern)?, flags)?;
+    let (text, language) = string_literal(text)?;
+    Some(string_with_language(&regex.replace(text, simple_string(replacement)?)?, language))
+}
+
+// The value of an xsd:dateTime, or of an xsd:date when `dates` is set
+fn date_time(term: &RdfTerm, dates: bool) -> Option<XsdDateTime> {
+    match term {
+        RdfTerm::Literal { value, datatype: Some(datatype), .. } if datatype == XSD_DATE_TIME => XsdDateTime::parse_date_time(value),
+        RdfTerm::Literal { value, datatype: Some(datatype), .. } if dates && datatype == XSD_DATE => XsdDateTime::parse_date(value),
+        _ => None,
+    }
+}
+
+// A timezone offset in minutes as an xsd:dayTimeDuration, such as -PT5H or PT5H30M
+fn day_time_duration(offset: i16) -> String {
+    if offset == 0 {
+        return "PT0S".to_string();
+    }
+    let sign = if offset < 0 { "-" } else { "" };
+    let (hours, minutes) = (offset.abs() / 60, offset.abs() % 60);
+    let mut duration = format!("{}PT", sign);
+    if hours > 0 {
+        duration.push_str(&format!("{}H", hours));
+    }
+    if minutes > 0 {
+        duration.push_str(&format!("{}M", minutes));
+    }
+    duration
+}
@@ -0,0 +1,282 @@
+use crate::query::planner::LeapfrogPattern;
+use crate::storage::bplus_tree::{BPlusTree, BPlusTreeIter};
+use crate::storage::exceptions::StorageError;
+use crate::storage::quad_indexes::QuadIndexes;
+
+// A permutation read as a trie: below the constants of the pattern, every level is one
+// more position of the tuples. The iterator is at a key of its current level, and moves
+// forward (next, seek) among the keys sharing the keys of the levels above.
+struct TrieIter<'a> {
+    tree: &'a BPlusTree<3>,
+    iter: Option<BPlusTreeIter<'a, 3>>,
+    // First tuple not consumed by the iterator
+    current: Option<[u64; 3]>,
+    // The constants followed by the key of every open level, the current one last
+    prefix: Vec<u64>,
+    constants: usize,
+    // The current level has no keys left
+    exhausted: bool,
+    // The iterator went past the first tuple of the current key in a level below, which
+    // has to be opened again from the start
+    rewind: bool,
+}
+
+impl<'a> TrieIter<'a> {
+    fn new(tree: &'a BPlusTree<3>, constants: &[u64]) -> Self {
+        Self {
+            tree,
+            iter: None,
+            current: None,
+            prefix: constants.to_vec(),
+            constants: constants.len(),
+            exhausted: false,
+            rewind: false,
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.prefix.len() - self.constants
+    }
+
+    // Whether some tuple has the constants, for patterns without variables
+    fn matches_constants(&self) -> Result<bool, StorageError> {
+        Ok(self.tree.scan_prefix(&self.prefix)?.next().transpose()?.is_some())
+    }
+
+    // Goes down to the first key of the level below the current key
+    fn open(&mut self) -> Result<(), StorageError> {
+        if self.depth() == 0 || self.rewind {
+            let mut min = [0; 3];
+            min[..self.prefix.len()].copy_from_slice(&self.prefix);
+            let mut max = [u64::MAX; 3];
+            max[..self.constants].copy_from_slice(&self.prefix[..self.constants]);
+            let mut iter = self.tree.range(&min, &max)?;
+            self.current = iter.next().transpose()?;
+            self.iter = Some(iter);
+        }
+        self.exhausted = false;
+        self.rewind = false;
+        match self.current {
+            Some(tuple) if tuple[..self.prefix.len()] == self.prefix[..] => self.prefix.push(tuple[self.prefix.len()]),
+            _ => {
+                self.prefix.push(0);
+                self.exhausted = true;
+            }
+        }
+        Ok(())
+    }
+
+    // Goes back to the key of the level above
+    fn up(&mut self) {
+        self.prefix.pop();
+        self.exhausted = false;
+        self.rewind = true;
+        if self.depth() == 0 {
+            self.iter = None;
+            self.current = None;
+        }
+    }
+
+    fn key(&self) -> u64 {
+        *self.prefix.last().unwrap()
+    }
+
+    fn at_end(&self) -> bool {
+        self.exhausted
+    }
+
+    fn next(&mut self) -> Result<(), StorageError> {
+        match self.key().checked_add(1) {
+            Some(key) => self.seek(key),
+            None => {
+                self.exhausted = true;
+                Ok(())
+            }
+        }
+    }
+
+    // Moves to the first key not smaller than `key`
+    fn seek(&mut self, key: u64) -> Result<(), StorageError> {
+        if self.exhausted || self.key() >= key {
+            return Ok(());
+        }
+        self.rewind = false;
+        let level = self.prefix.len() - 1;
+        let mut target = [0; 3];
+        target[..level].copy_from_slice(&self.prefix[..level]);
+        target[level] = key;
+
+        if let Some(tuple) = self.current {
+            if tuple < target {
+                let iter = self.iter.as_mut().unwrap();
+                iter.seek(&target)?;
+                self.current = iter.next().transpose()?;
+            }
+        }
+        match self.current {
+            Some(tuple) if tuple[..level] == self.prefix[..level] => self.prefix[level] = tuple[level],
+            _ => self.exhausted = true,
+        }
+        Ok(())
+    }
+}
+
+// Worst-case optimal join of triple patterns (Leapfrog Triejoin). The variables are
+// bound one level at a time: the tries of the patterns having the variable of a level
+// leapfrog over each other with seeks, stopping at the keys all of them have, and the
+// next level is opened below each of those keys.
+//
+// Every item has the values of the variables in the order of the levels, i.e. the
+// `variable_order` of the plan.
+pub struct LeapfrogJoin<'a> {
+    tries: Vec<TrieIter<'a>>,
+    // Tries having the variable of each level, sorted by key when the level is opened
+    participants: Vec<Vec<usize>>,
+    // Position in `participants` of the trie to move next, for each level
+    positions: Vec<usize>,
+    level_ends: Vec<bool>,
+    bindings: Vec<u64>,
+    // Number of open levels
+    depth: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl<'a> LeapfrogJoin<'a> {
+    pub fn new(indexes: &'a QuadIndexes, levels: usize, patterns: &[LeapfrogPattern]) -> Self {
+        let mut participants = vec![Vec::new(); levels];
+        for (trie, pattern) in patterns.iter().enumerate() {
+            for &level in &pattern.levels {
+                participants[level].push(trie);
+            }
+        }
+        Self {
+            tries: patterns
+                .iter()
+                .map(|pattern| TrieIter::new(indexes.triple_index(pattern.permutation), &pattern.constants))
+                .collect(),
+            participants,
+            positions: vec![0; levels],
+            level_ends: vec![false; levels],
+            bindings: vec![0; levels],
+            depth: 0,
+            started: false,
+            finished: false,
+        }
+    }
+
+    // Moves to the next result, false when there are no more
+    fn advance(&mut self) -> Result<bool, StorageError> {
+        if self.finished {
+            return Ok(false);
+        }
+        let levels = self.participants.len();
+        if !self.started {
+            self.started = true;
+            for trie in self.tries.iter().filter(|trie| trie.constants == 3) {
+                if !trie.matches_constants()? {
+                    self.finished = true;
+                    return Ok(false);
+                }
+            }
+            if levels == 0 || self.participants.iter().any(Vec::is_empty) {
+                self.finished = true;
+                return Ok(levels == 0);
+            }
+            self.open_level(0)?;
+        } else {
+            self.leapfrog_next(levels - 1)?;
+        }
+
+        loop {
+            let level = self.depth - 1;
+            if self.level_ends[level] {
+                self.close_level(level);
+                if level == 0 {
+                    self.finished = true;
+                    return Ok(false);
+                }
+                self.leapfrog_next(level - 1)?;
+                continue;
+            }
+            self.bindings[level] = self.tries[self.participants[level][0]].key();
+            if level + 1 == levels {
+                return Ok(true);
+            }
+            self.open_level(level + 1)?;
+        }
+    }
+
+    fn open_level(&mut self, level: usize) -> Result<(), StorageError> {
+        self.depth = level + 1;
+        for &trie in &self.participants[level] {
+            self.tries[trie].open()?;
+        }
+        if self.participants[level].iter().any(|&trie| self.tries[trie].at_end()) {
+            self.level_ends[level] = true;
+            return Ok(());
+        }
+        let tries = &self.tries;
+        self.participants[level].sort_by_key(|&trie| tries[trie].key());
+        self.positions[level] = 0;
+        self.level_ends[level] = false;
+        self.leapfrog_search(level)
+    }
+
+    fn close_level(&mut self, level: usize) {
+        for &trie in &self.participants[level] {
+            self.tries[trie].up();
+        }
+        self.depth = level;
+    }
+
+    fn leapfrog_next(&mut self, level: usize) -> Result<(), StorageError> {
+        let count = self.participants[level].len();
+        let trie = self.participants[level][self.positions[level]];
+        self.tries[trie].next()?;
+        if self.tries[trie].at_end() {
+            self.level_ends[level] = true;
+            return Ok(());
+        }
+        self.positions[level] = (self.positions[level] + 1) % count;
+        self.leapfrog_search(level)
+    }
+
+    // Seeks the tries of the level, in turn, to the largest key of the others until all
+    // of them are at the same key
+    fn leapfrog_search(&mut self, level: usize) -> Result<(), StorageError> {
+        let participants = &self.participants[level];
+        let count = participants.len();
+        let mut position = self.positions[level];
+        let mut max = self.tries[participants[(position + count - 1) % count]].key();
+        loop {
+            let trie = &mut self.tries[participants[position]];
+            if trie.key() == max {
+                self.positions[level] = position;
+                return Ok(());
+            }
+            trie.seek(max)?;
+            if trie.at_end() {
+                self.level_ends[level] = true;
+                return Ok(());
+            }
+            max = trie.key();
+            position = (position + 1) % count;
+        }
+    }
+}
+
+impl Iterator for LeapfrogJoin<'_> {
+    type Item = Result<Vec<u64>, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(true) => Some(Ok(self.bindings.clone())),
+            Ok(false) => None,
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
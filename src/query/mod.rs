@@ -1,3 +1,22 @@
 pub mod query_services;
 pub mod query_contexts;
-pub mod exceptions;
\ No newline at end of file
+pub mod exceptions;
+pub mod query_forms;
+pub mod rdf_terms;
+pub mod object_id;
+pub mod xsd_datetime;
+pub mod xsd_decimal;
+pub mod result_writers;
+pub mod quad_model_graph;
+pub mod planner;
+pub mod leapfrog_join;
+pub mod executor;
+pub mod algebra;
+pub mod sparql_parser;
+pub mod expressions;
+pub mod query_executor;
+pub mod paths;
+pub mod aggregates;
+pub mod functions;
+pub mod regex;
+pub mod hashes;
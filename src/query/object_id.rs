@@ -0,0 +1,515 @@
+use crate::import::exceptions::ImportException;
+use crate::import::ntriples_parser::parse_term;
+use crate::query::rdf_terms::{RdfTerm, XSD_BOOLEAN, XSD_DATE, XSD_DATE_TIME, XSD_DECIMAL, XSD_DOUBLE, XSD_INTEGER};
+use crate::query::xsd_datetime::XsdDateTime;
+use crate::storage::iri_prefixes::IriPrefixes;
+
+// The highest byte of an ObjectId is the type of the value, the other 7 bytes are the
+// value itself (`_INLINED` types and numbers) or its id in the dictionary (`_EXTERN` types)
+const TYPE_SHIFT: u32 = 56;
+pub const TYPE_MASK: u64 = 0xFF << TYPE_SHIFT;
+pub const VALUE_MASK: u64 = (1 << TYPE_SHIFT) - 1;
+
+pub const MASK_NULL: u64 = 0x00 << TYPE_SHIFT;
+pub const MASK_IRI_INLINED: u64 = 0x01 << TYPE_SHIFT;
+pub const MASK_IRI_EXTERN: u64 = 0x02 << TYPE_SHIFT;
+pub const MASK_BLANK_NODE_INLINED: u64 = 0x03 << TYPE_SHIFT;
+pub const MASK_BLANK_NODE_EXTERN: u64 = 0x04 << TYPE_SHIFT;
+pub const MASK_STRING_INLINED: u64 = 0x05 << TYPE_SHIFT;
+pub const MASK_STRING_EXTERN: u64 = 0x06 << TYPE_SHIFT;
+pub const MASK_STRING_LANG_EXTERN: u64 = 0x07 << TYPE_SHIFT;
+pub const MASK_TYPED_LITERAL_EXTERN: u64 = 0x08 << TYPE_SHIFT;
+pub const MASK_INTEGER: u64 = 0x09 << TYPE_SHIFT;
+pub const MASK_INTEGER_EXTERN: u64 = 0x0A << TYPE_SHIFT;
+pub const MASK_DECIMAL_INLINED: u64 = 0x0B << TYPE_SHIFT;
+pub const MASK_DECIMAL_EXTERN: u64 = 0x0C << TYPE_SHIFT;
+pub const MASK_DOUBLE_INLINED: u64 = 0x0D << TYPE_SHIFT;
+pub const MASK_DOUBLE_EXTERN: u64 = 0x0E << TYPE_SHIFT;
+pub const MASK_DATETIME_INLINED: u64 = 0x0F << TYPE_SHIFT;
+pub const MASK_DATETIME_EXTERN: u64 = 0x10 << TYPE_SHIFT;
+pub const MASK_BOOLEAN: u64 = 0x11 << TYPE_SHIFT;
+pub const MASK_PATH: u64 = 0x12 << TYPE_SHIFT;
+
+// Strings of up to 7 bytes fit in the id
+pub const MAX_INLINED_BYTES: usize = 7;
+
+// IRIs keep the id of their prefix in the highest byte of the value, followed by the
+// rest of the IRI (up to 6 bytes) or its id in the dictionary
+const IRI_PREFIX_SHIFT: u32 = 48;
+const IRI_VALUE_MASK: u64 = (1 << IRI_PREFIX_SHIFT) - 1;
+pub const MAX_INLINED_IRI_BYTES: usize = 6;
+
+// Range of the integers stored in 56 bits
+pub const MIN_INLINED_INTEGER: i64 = -(1 << 55);
+pub const MAX_INLINED_INTEGER: i64 = (1 << 55) - 1;
+
+// Decimals are a 48-bit mantissa and the number of digits after the point
+const DECIMAL_MANTISSA_BITS: u32 = 48;
+const DECIMAL_SCALE_BITS: u32 = 8;
+
+// Inlined dateTimes: year (17 bits, offset), month, day, hour, minute, second,
+// whether there is a timezone, the timezone (offset) and whether it is a date
+const YEAR_OFFSET: i64 = 1 << 16;
+const TIMEZONE_OFFSET: i64 = 14 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Null,
+    Iri,
+    BlankNode,
+    String,
+    LangString,
+    TypedLiteral,
+    Integer,
+    Decimal,
+    Double,
+    DateTime,
+    Boolean,
+    Path,
+}
+
+// How a term is stored: inlined in its ObjectId, or in the dictionary under `key` with an
+// id that is combined with `mask`. The key of an IRI is the rest of it after its prefix
+// (the prefix id is part of the mask), other terms use their N-Triples syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TermEncoding {
+    Inlined(ObjectId),
+    External { mask: u64, key: String },
+}
+
+// A value of the database in 8 bytes, so the tuples of the indexes have a fixed width.
+// Small values are inlined, large ones are an id in the dictionary. Numbers, dates and
+// booleans are kept in their canonical form: "01"^^xsd:integer is stored as 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(u64);
+
+impl ObjectId {
+    pub const NULL: ObjectId = ObjectId(MASK_NULL);
+
+    pub fn from_raw(raw: u64) -> Self {
+        ObjectId(raw)
+    }
+
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    pub fn mask(self) -> u64 {
+        self.0 & TYPE_MASK
+    }
+
+    // An id of the dictionary with the type of its value
+    pub fn external(mask: u64, dictionary_id: u64) -> Self {
+        debug_assert!(dictionary_id <= VALUE_MASK);
+        ObjectId(mask | dictionary_id)
+    }
+
+    pub fn is_null(self) -> bool {
+        self.0 == MASK_NULL
+    }
+
+    pub fn kind(self) -> ObjectKind {
+        match self.mask() {
+            MASK_IRI_INLINED | MASK_IRI_EXTERN => ObjectKind::Iri,
+            MASK_BLANK_NODE_INLINED | MASK_BLANK_NODE_EXTERN => ObjectKind::BlankNode,
+            MASK_STRING_INLINED | MASK_STRING_EXTERN => ObjectKind::String,
+            MASK_STRING_LANG_EXTERN => ObjectKind::LangString,
+            MASK_TYPED_LITERAL_EXTERN => ObjectKind::TypedLiteral,
+            MASK_INTEGER | MASK_INTEGER_EXTERN => ObjectKind::Integer,
+            MASK_DECIMAL_INLINED | MASK_DECIMAL_EXTERN => ObjectKind::Decimal,
+            MASK_DOUBLE_INLINED | MASK_DOUBLE_EXTERN => ObjectKind::Double,
+            MASK_DATETIME_INLINED | MASK_DATETIME_EXTERN => ObjectKind::DateTime,
+            MASK_BOOLEAN => ObjectKind::Boolean,
+            MASK_PATH => ObjectKind::Path,
+            _ => ObjectKind::Null,
+        }
+    }
+
+    // Whether the value has to be read from the dictionary
+    pub fn is_external(self) -> bool {
+        matches!(
+            self.mask(),
+            MASK_IRI_EXTERN
+                | MASK_BLANK_NODE_EXTERN
+                | MASK_STRING_EXTERN
+                | MASK_STRING_LANG_EXTERN
+                | MASK_TYPED_LITERAL_EXTERN
+                | MASK_INTEGER_EXTERN
+                | MASK_DECIMAL_EXTERN
+                | MASK_DOUBLE_EXTERN
+                | MASK_DATETIME_EXTERN
+        )
+    }
+
+    pub fn dictionary_id(self) -> Option<u64> {
+        match self.mask() {
+            MASK_IRI_EXTERN => Some(self.0 & IRI_VALUE_MASK),
+            _ => self.is_external().then_some(self.0 & VALUE_MASK),
+        }
+    }
+
+    // Id of the prefix of an IRI in the prefix table of the database
+    pub fn iri_prefix_id(self) -> Option<u8> {
+        matches!(self.mask(), MASK_IRI_INLINED | MASK_IRI_EXTERN).then_some((self.0 >> IRI_PREFIX_SHIFT) as u8)
+    }
+
+    pub fn from_integer(value: i64) -> Option<Self> {
+        (MIN_INLINED_INTEGER..=MAX_INLINED_INTEGER)
+            .contains(&value)
+            .then_some(ObjectId(MASK_INTEGER | (value as u64 & VALUE_MASK)))
+    }
+
+    pub fn integer(self) -> Option<i64> {
+        (self.mask() == MASK_INTEGER).then_some(sign_extend(self.0, TYPE_SHIFT))
+    }
+
+    // `mantissa` * 10^-`scale`
+    pub fn from_decimal(mantissa: i64, scale: u32) -> Option<Self> {
+        let limit = 1 << (DECIMAL_MANTISSA_BITS - 1);
+        if mantissa < -limit || mantissa >= limit || scale >= 1 << DECIMAL_SCALE_BITS {
+            return None;
+        }
+        let mantissa = mantissa as u64 & ((1 << DECIMAL_MANTISSA_BITS) - 1);
+        Some(ObjectId(MASK_DECIMAL_INLINED | (mantissa << DECIMAL_SCALE_BITS) | scale as u64))
+    }
+
+    // Mantissa and scale of an inlined decimal
+    pub fn decimal(self) -> Option<(i64, u32)> {
+        if self.mask() != MASK_DECIMAL_INLINED {
+            return None;
+        }
+        let value = self.0 & VALUE_MASK;
+        let mantissa = sign_extend(value >> DECIMAL_SCALE_BITS, DECIMAL_MANTISSA_BITS);
+        Some((mantissa, (value & ((1 << DECIMAL_SCALE_BITS) - 1)) as u32))
+    }
+
+    // Doubles are inlined when the 8 lowest bits of their mantissa are 0
+    pub fn from_double(value: f64) -> Option<Self> {
+        let bits = value.to_bits();
+        (bits & 0xFF == 0).then_some(ObjectId(MASK_DOUBLE_INLINED | (bits >> 8)))
+    }
+
+    pub fn double(self) -> Option<f64> {
+        (self.mask() == MASK_DOUBLE_INLINED).then(|| f64::from_bits((self.0 & VALUE_MASK) << 8))
+    }
+
+    pub fn from_boolean(value: bool) -> Self {
+        ObjectId(MASK_BOOLEAN | value as u64)
+    }
+
+    pub fn boolean(self) -> Option<bool> {
+        (self.mask() == MASK_BOOLEAN).then_some(self.0 & 1 == 1)
+    }
+
+    // Values with fractions of seconds or years outside of 17 bits are not inlined
+    pub fn from_datetime(value: &XsdDateTime) -> Option<Self> {
+        let year = value.year + YEAR_OFFSET;
+        if value.nanosecond != 0 || !(0..2 * YEAR_OFFSET).contains(&year) {
+            return None;
+        }
+        let mut bits = year as u64;
+        for (field, width) in [
+            (value.month as u64, 4),
+            (value.day as u64, 5),
+            (value.hour as u64, 5),
+            (value.minute as u64, 6),
+            (value.second as u64, 6),
+            (value.timezone.is_some() as u64, 1),
+            ((value.timezone.unwrap_or(0) as i64 + TIMEZONE_OFFSET) as u64, 11),
+            (value.date_only as u64, 1),
+        ] {
+            bits = (bits << width) | field;
+        }
+        Some(ObjectId(MASK_DATETIME_INLINED | bits))
+    }
+
+    pub fn datetime(self) -> Option<XsdDateTime> {
+        if self.mask() != MASK_DATETIME_INLINED {
+            return None;
+        }
+        let mut bits = self.0 & VALUE_MASK;
+        let mut take = |width: u32| {
+            let field = bits & ((1 << width) - 1);
+            bits >>= width;
+            field
+        };
+        let date_only = take(1) == 1;
+        let timezone = take(11) as i64 - TIMEZONE_OFFSET;
+        let has_timezone = take(1) == 1;
+        let second = take(6) as u8;
+        let minute = take(6) as u8;
+        let hour = take(5) as u8;
+        let day = take(5) as u8;
+        let month = take(4) as u8;
+        let year = take(17) as i64 - YEAR_OFFSET;
+        Some(XsdDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            nanosecond: 0,
+            timezone: has_timezone.then_some(timezone as i16),
+            date_only,
+        })
+    }
+
+    // Ids of the paths found by a query, the paths themselves are kept by the query
+    pub fn path(id: u64) -> Self {
+        ObjectId(MASK_PATH | (id & VALUE_MASK))
+    }
+
+    pub fn path_id(self) -> Option<u64> {
+        (self.mask() == MASK_PATH).then_some(self.0 & VALUE_MASK)
+    }
+
+    // Value of an inlined number, so FILTER comparisons do not need the dictionary
+    pub fn numeric_value(self) -> Option<f64> {
+        match self.mask() {
+            MASK_INTEGER => self.integer().map(|value| value as f64),
+            MASK_DECIMAL_INLINED => self.decimal().map(|(mantissa, scale)| mantissa as f64 / 10f64.powi(scale as i32)),
+            MASK_DOUBLE_INLINED => self.double(),
+            _ => None,
+        }
+    }
+
+    pub fn encode(term: &RdfTerm, prefixes: &IriPrefixes) -> TermEncoding {
+        let external = |mask| TermEncoding::External { mask, key: term.to_string() };
+        let inlined_string = |mask: u64, value: &str| {
+            inline_string(value, MAX_INLINED_BYTES).map(|bits| TermEncoding::Inlined(ObjectId(mask | bits)))
+        };
+
+        match term {
+            RdfTerm::Iri(iri) => {
+                let (prefix_id, suffix) = prefixes.split(iri);
+                let prefix = (prefix_id as u64) << IRI_PREFIX_SHIFT;
+                match inline_string(suffix, MAX_INLINED_IRI_BYTES) {
+                    Some(bits) => TermEncoding::Inlined(ObjectId(MASK_IRI_INLINED | prefix | bits)),
+                    None => TermEncoding::External { mask: MASK_IRI_EXTERN | prefix, key: suffix.to_string() },
+                }
+            }
+            RdfTerm::BlankNode(label) => {
+                inlined_string(MASK_BLANK_NODE_INLINED, label).unwrap_or_else(|| external(MASK_BLANK_NODE_EXTERN))
+            }
+            RdfTerm::Literal { language: Some(_), .. } => external(MASK_STRING_LANG_EXTERN),
+            RdfTerm::Literal { value, datatype: None, .. } => {
+                inlined_string(MASK_STRING_INLINED, value).unwrap_or_else(|| external(MASK_STRING_EXTERN))
+            }
+            RdfTerm::Literal { value, datatype: Some(datatype), .. } => {
+                encode_typed_literal(value, datatype).unwrap_or_else(|| external(MASK_TYPED_LITERAL_EXTERN))
+            }
+            // Paths are not stored: the query that finds them keeps them
+            RdfTerm::Path(_) => TermEncoding::Inlined(ObjectId::NULL),
+        }
+    }
+
+    // The term of an inlined id. `None` for external ids, paths and null.
+    pub fn decode_inlined(self, prefixes: &IriPrefixes) -> Option<RdfTerm> {
+        let value = self.0 & VALUE_MASK;
+        match self.mask() {
+            MASK_IRI_INLINED => {
+                let suffix = outline_string(value & IRI_VALUE_MASK, MAX_INLINED_IRI_BYTES);
+                Some(RdfTerm::Iri(format!("{}{}", prefixes.prefix(self.iri_prefix_id()?), suffix)))
+            }
+            MASK_BLANK_NODE_INLINED => Some(RdfTerm::BlankNode(outline_string(value, MAX_INLINED_BYTES))),
+            MASK_STRING_INLINED => Some(RdfTerm::simple_literal(&outline_string(value, MAX_INLINED_BYTES))),
+            MASK_INTEGER => Some(RdfTerm::typed_literal(&self.integer()?.to_string(), XSD_INTEGER)),
+            MASK_DECIMAL_INLINED => {
+                let (mantissa, scale) = self.decimal()?;
+                Some(RdfTerm::typed_literal(&decimal_to_string(mantissa, scale), XSD_DECIMAL))
+            }
+            MASK_DOUBLE_INLINED => Some(RdfTerm::typed_literal(&double_to_string(self.double()?), XSD_DOUBLE)),
+            MASK_DATETIME_INLINED => {
+                let datetime = self.datetime()?;
+                Some(RdfTerm::typed_literal(&datetime.to_string(), datetime.datatype()))
+            }
+            MASK_BOOLEAN => Some(RdfTerm::typed_literal(if value == 1 { "true" } else { "false" }, XSD_BOOLEAN)),
+            _ => None,
+        }
+    }
+
+    // The term of an external id given its dictionary key
+    pub fn decode_external(self, key: &str, prefixes: &IriPrefixes) -> Result<RdfTerm, ImportException> {
+        match self.iri_prefix_id() {
+            Some(prefix_id) => Ok(RdfTerm::Iri(format!("{}{}", prefixes.prefix(prefix_id), key))),
+            None => parse_term(key),
+        }
+    }
+}
+
+// Literals of the datatypes with a value space that is inlined (or at least tagged)
+fn encode_typed_literal(value: &str, datatype: &str) -> Option<TermEncoding> {
+    let external = |mask| TermEncoding::External { mask, key: RdfTerm::typed_literal(value, datatype).to_string() };
+    match datatype {
+        XSD_INTEGER => {
+            let canonical = canonical_integer(value)?;
+            Some(match canonical.parse().ok().and_then(ObjectId::from_integer) {
+                Some(id) => TermEncoding::Inlined(id),
+                None => TermEncoding::External {
+                    mask: MASK_INTEGER_EXTERN,
+                    key: RdfTerm::typed_literal(&canonical, datatype).to_string(),
+                },
+            })
+        }
+        XSD_DECIMAL => {
+            let (mantissa, scale) = parse_decimal(value)?;
+            Some(match mantissa.parse().ok().and_then(|mantissa| ObjectId::from_decimal(mantissa, scale)) {
+                Some(id) => TermEncoding::Inlined(id),
+                None => external(MASK_DECIMAL_EXTERN),
+            })
+        }
+        XSD_DOUBLE => {
+            let double = parse_double(value)?;
+            Some(match ObjectId::from_double(double) {
+                Some(id) => TermEncoding::Inlined(id),
+                None => external(MASK_DOUBLE_EXTERN),
+            })
+        }
+        XSD_BOOLEAN => match value {
+            "true" | "1" => Some(TermEncoding::Inlined(ObjectId::from_boolean(true))),
+            "false" | "0" => Some(TermEncoding::Inlined(ObjectId::from_boolean(false))),
+            _ => None,
+        },
+        XSD_DATE_TIME | XSD_DATE => {
+            let datetime = if datatype == XSD_DATE {
+                XsdDateTime::parse_date(value)?
+            } else {
+                XsdDateTime::parse_date_time(value)?
+            };
+            Some(match ObjectId::from_datetime(&datetime) {
+                Some(id) => TermEncoding::Inlined(id),
+                None => external(MASK_DATETIME_EXTERN),
+            })
+        }
+        _ => None,
+    }
+}
+
+// The bytes of the string in `width` bytes, the first one being the highest. Strings
+// containing a null byte are not inlined, as the unused bytes are 0.
+fn inline_string(value: &str, width: usize) -> Option<u64> {
+    if value.len() > width || value.bytes().any(|b| b == 0) {
+        return None;
+    }
+    let mut bits = 0;
+    for (i, byte) in value.bytes().enumerate() {
+        bits |= (byte as u64) << (8 * (width - 1 - i));
+    }
+    Some(bits)
+}
+
+fn outline_string(bits: u64, width: usize) -> String {
+    let bytes: Vec<u8> = (0..width)
+        .map(|i| (bits >> (8 * (width - 1 - i))) as u8)
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+// [+-]?[0-9]+ without the sign '+' and leading zeros
+pub fn canonical_integer(lexical: &str) -> Option<String> {
+    let (negative, digits) = split_sign(lexical);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits = digits.trim_start_matches('0');
+    Some(match (negative, digits.is_empty()) {
+        (_, true) => "0".to_string(),
+        (true, false) => format!("-{}", digits),
+        (false, false) => digits.to_string(),
+    })
+}
+
+// Digits of the mantissa (with its sign) and scale of an xsd:decimal, without
+// leading or trailing zeros
+pub fn parse_decimal(lexical: &str) -> Option<(String, u32)> {
+    let (negative, unsigned) = split_sign(lexical);
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if integer.is_empty() && fraction.is_empty()
+        || !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let fraction = fraction.trim_end_matches('0');
+    let digits = format!("{}{}", integer, fraction);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some(("0".to_string(), 0));
+    }
+    let sign = if negative { "-" } else { "" };
+    Some((format!("{}{}", sign, digits), fraction.len() as u32))
+}
+
+// Canonical form of a decimal: at least one digit on each side of the point
+pub fn decimal_to_string(mantissa: i64, scale: u32) -> String {
+    let digits = format!("{:0width$}", mantissa.unsigned_abs(), width = scale as usize + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale as usize);
+    let sign = if mantissa < 0 { "-" } else { "" };
+    if fraction.is_empty() {
+        format!("{}{}.0", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
+
+// xsd:double: a decimal with an optional exponent, INF, -INF or NaN
+pub fn parse_double(lexical: &str) -> Option<f64> {
+    match lexical {
+        "INF" | "+INF" => return Some(f64::INFINITY),
+        "-INF" => return Some(f64::NEG_INFINITY),
+        "NaN" => return Some(f64::NAN),
+        _ => {}
+    }
+    let (mantissa, exponent) = match lexical.find(['e', 'E']) {
+        Some(position) => (&lexical[..position], Some(&lexical[position + 1..])),
+        None => (lexical, None),
+    };
+    parse_decimal(mantissa)?;
+    if let Some(exponent) = exponent {
+        canonical_integer(exponent)?;
+    }
+    lexical.parse().ok()
+}
+
+// Canonical form of a double: a mantissa with one digit before the point and an exponent
+pub fn double_to_string(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "INF" } else { "-INF" }.to_string();
+    }
+    scientific_to_string(format!("{:E}", value))
+}
+
+// Canonical form of a float, the one of doubles with the digits of the float
+pub fn float_to_string(value: f32) -> String {
+    if !value.is_finite() {
+        return double_to_string(value as f64);
+    }
+    scientific_to_string(format!("{:E}", value))
+}
+
+// Adds ".0" to a mantissa without a point
+fn scientific_to_string(formatted: String) -> String {
+    let (mantissa, exponent) = formatted.split_once('E').unwrap();
+    if mantissa.contains('.') {
+        formatted
+    } else {
+        format!("{}.0E{}", mantissa, exponent)
+    }
+}
+
+fn split_sign(lexical: &str) -> (bool, &str) {
+    match lexical.as_bytes().first() {
+        Some(b'-') => (true, &lexical[1..]),
+        Some(b'+') => (false, &lexical[1..]),
+        _ => (false, lexical),
+    }
+}
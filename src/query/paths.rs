@@ -0,0 +1,631 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::query::algebra::{PathMode, PropertyPath};
+use crate::query::executor::{ExecutionError, Interruption};
+use crate::query::object_id::ObjectId;
+use crate::storage::quad_indexes::QuadIndexes;
+
+// What a transition of the automaton reads: an edge with the predicate followed in its
+// direction (`Forward`) or against it, or any edge whose predicate is not in a list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathLabel {
+    Forward(ObjectId),
+    Backward(ObjectId),
+    NegatedForward(Vec<ObjectId>),
+    NegatedBackward(Vec<ObjectId>),
+}
+
+// Automaton of a property path without epsilon transitions. A node reached in a final
+// state is an end of the path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathAutomaton {
+    pub start: usize,
+    pub transitions: Vec<Vec<(PathLabel, usize)>>,
+    pub finals: Vec<bool>,
+}
+
+// Automaton being built: Thompson's construction, with epsilon transitions
+#[derive(Default)]
+struct AutomatonBuilder {
+    transitions: Vec<Vec<(PathLabel, usize)>>,
+    epsilons: Vec<Vec<usize>>,
+}
+
+impl AutomatonBuilder {
+    fn new_state(&mut self) -> usize {
+        self.transitions.push(Vec::new());
+        self.epsilons.push(Vec::new());
+        self.transitions.len() - 1
+    }
+
+    // Adds the states and transitions that go from `from` to `to` reading the path, or
+    // the inverse of the path when `inverse` is set
+    fn add_path<F>(&mut self, path: &PropertyPath, inverse: bool, from: usize, to: usize, predicate_id: &mut F) -> Result<(), ExecutionError>
+    where
+        F: FnMut(&str) -> Result<ObjectId, ExecutionError>,
+    {
+        match path {
+            PropertyPath::Iri(iri) => {
+                let predicate = predicate_id(iri)?;
+                let label = if inverse { PathLabel::Backward(predicate) } else { PathLabel::Forward(predicate) };
+                self.transitions[from].push((label, to));
+            }
+            PropertyPath::Inverse(inner) => self.add_path(inner, !inverse, from, to, predicate_id)?,
+            PropertyPath::Sequence(steps) => {
+                // The inverse of a sequence is the inverses of its steps in reverse order
+                let steps: Vec<&PropertyPath> = if inverse { steps.iter().rev().collect() } else { steps.iter().collect() };
+                let mut current = from;
+                for (i, step) in steps.iter().enumerate() {
+                    let next = if i + 1 == steps.len() { to } else { self.new_state() };
+                    self.add_path(step, inverse, current, next, predicate_id)?;
+                    current = next;
+                }
+            }
+            PropertyPath::Alternative(options) => {
+                for option in options {
+                    self.add_path(option, inverse, from, to, predicate_id)?;
+                }
+            }
+            PropertyPath::ZeroOrMore(inner) => {
+                // Any number of loops over a state of its own
+                let middle = self.new_state();
+                self.epsilons[from].push(middle);
+                self.epsilons[middle].push(to);
+                self.add_path(inner, inverse, middle, middle, predicate_id)?;
+            }
+            PropertyPath::OneOrMore(inner) => {
+                let (first, last) = (self.new_state(), self.new_state());
+                self.epsilons[from].push(first);
+                self.add_path(inner, inverse, first, last, predicate_id)?;
+                self.epsilons[last].push(first);
+                self.epsilons[last].push(to);
+            }
+            PropertyPath::ZeroOrOne(inner) => {
+                self.epsilons[from].push(to);
+                self.add_path(inner, inverse, from, to, predicate_id)?;
+            }
+            PropertyPath::NegatedSet(iris) => {
+                // !(a|^b) reads the edges that are not `a` forwards and the ones that are not
+                // `b` backwards. Each part exists only when the set has IRIs of its direction.
+                let mut forward = Vec::new();
+                let mut backward = Vec::new();
+                for negated in iris {
+                    let predicate = predicate_id(&negated.iri)?;
+                    if negated.inverse != inverse {
+                        backward.push(predicate);
+                    } else {
+                        forward.push(predicate);
+                    }
+                }
+                let has_forward = iris.iter().any(|negated| !negated.inverse);
+                let has_backward = iris.iter().any(|negated| negated.inverse);
+                if has_forward {
+                    let label = if inverse { PathLabel::NegatedBackward(forward) } else { PathLabel::NegatedForward(forward) };
+                    self.transitions[from].push((label, to));
+                }
+                if has_backward {
+                    let label = if inverse { PathLabel::NegatedForward(backward) } else { PathLabel::NegatedBackward(backward) };
+                    self.transitions[from].push((label, to));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn epsilon_closure(&self, state: usize) -> Vec<usize> {
+        let mut closure = vec![state];
+        let mut i = 0;
+        while i < closure.len() {
+            for &next in &self.epsilons[closure[i]] {
+                if !closure.contains(&next) {
+                    closure.push(next);
+                }
+            }
+            i += 1;
+        }
+        closure
+    }
+}
+
+impl PathAutomaton {
+    // Automaton of the path, or of its inverse (to search from the object). The IRIs are
+    // converted to ids with `predicate_id`.
+    pub fn new<F>(path: &PropertyPath, inverse: bool, mut predicate_id: F) -> Result<Self, ExecutionError>
+    where
+        F: FnMut(&str) -> Result<ObjectId, ExecutionError>,
+    {
+        let mut builder = AutomatonBuilder::default();
+        let start = builder.new_state();
+        let end = builder.new_state();
+        builder.add_path(path, inverse, start, end, &mut predicate_id)?;
+
+        // Every state gets the transitions of the states it reaches through epsilons
+        let mut transitions = Vec::with_capacity(builder.transitions.len());
+        let mut finals = Vec::with_capacity(builder.transitions.len());
+        for state in 0..builder.transitions.len() {
+            let closure = builder.epsilon_closure(state);
+            finals.push(closure.contains(&end));
+            let mut state_transitions: Vec<(PathLabel, usize)> = Vec::new();
+            for reached in closure {
+                for transition in &builder.transitions[reached] {
+                    if !state_transitions.contains(transition) {
+                        state_transitions.push(transition.clone());
+                    }
+                }
+            }
+            transitions.push(state_transitions);
+        }
+        Ok(Self { start, transitions, finals })
+    }
+
+    // Whether the path matches zero-length paths, from every node to itself
+    pub fn accepts_empty_path(&self) -> bool {
+        self.finals[self.start]
+    }
+}
+
+// The graph a path is evaluated over: the default graph of the database, or the merge of
+// the named graphs in `named`
+pub struct PathGraph<'a> {
+    pub indexes: &'a QuadIndexes,
+    pub named: Option<Vec<u64>>,
+}
+
+impl PathGraph<'_> {
+    // Nodes of the graph: every subject and object
+    pub fn nodes(&self, interruption: &Interruption) -> Result<Vec<ObjectId>, ExecutionError> {
+        let mut nodes = BTreeSet::new();
+        self.for_each_triple(&[None, None, None], |[subject, _, object]| {
+            interruption.check()?;
+            nodes.insert(subject);
+            nodes.insert(object);
+            Ok(())
+        })?;
+        Ok(nodes.into_iter().map(ObjectId::from_raw).collect())
+    }
+
+    // Edges of `node` the label reads: the predicate of each and the node at its other side
+    fn neighbors(&self, node: ObjectId, label: &PathLabel) -> Result<Vec<(ObjectId, ObjectId)>, ExecutionError> {
+        let (pattern, excluded, position) = match label {
+            PathLabel::Forward(predicate) => ([Some(node.raw()), Some(predicate.raw()), None], &[][..], 2),
+            PathLabel::Backward(predicate) => ([None, Some(predicate.raw()), Some(node.raw())], &[][..], 0),
+            PathLabel::NegatedForward(predicates) => ([Some(node.raw()), None, None], &predicates[..], 2),
+            PathLabel::NegatedBackward(predicates) => ([None, None, Some(node.raw())], &predicates[..], 0),
+        };
+        let mut edges = Vec::new();
+        self.for_each_triple(&pattern, |triple| {
+            if !excluded.iter().any(|predicate| predicate.raw() == triple[1]) {
+                edges.push((ObjectId::from_raw(triple[1]), ObjectId::from_raw(triple[position])));
+            }
+            Ok(())
+        })?;
+        // An edge of several graphs of a merge is read once
+        if self.named.as_ref().is_some_and(|named| named.len() > 1) {
+            edges.sort();
+            edges.dedup();
+        }
+        Ok(edges)
+    }
+
+    fn for_each_triple(
+        &self,
+        pattern: &[Option<u64>; 3],
+        mut f: impl FnMut([u64; 3]) -> Result<(), ExecutionError>,
+    ) -> Result<(), ExecutionError> {
+        let named = match &self.named {
+            Some(named) => named,
+            None => {
+                for triple in self.indexes.scan_triples(pattern)? {
+                    f(triple?)?;
+                }
+                return Ok(());
+            }
+        };
+        let [subject, predicate, object] = *pattern;
+        for &graph in named {
+            for quad in self.indexes.scan_quads(&[subject, predicate, object, Some(graph)])? {
+                let [subject, predicate, object, _] = quad?;
+                f([subject, predicate, object])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Every named graph of the database
+pub fn named_graphs(indexes: &QuadIndexes, interruption: &Interruption) -> Result<Vec<u64>, ExecutionError> {
+    let mut graphs = BTreeSet::new();
+    for quad in indexes.scan_quads(&[None; 4])? {
+        interruption.check()?;
+        graphs.insert(quad?[3]);
+    }
+    Ok(graphs.into_iter().collect())
+}
+
+// An edge of a path found by a query and the node it leads to. `inverse` is set when the
+// edge goes from `node` to the previous node of the path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathStep {
+    pub predicate: ObjectId,
+    pub inverse: bool,
+    pub node: ObjectId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathValue {
+    pub start: ObjectId,
+    pub steps: Vec<PathStep>,
+}
+
+impl PathValue {
+    pub fn end(&self) -> ObjectId {
+        self.steps.last().map_or(self.start, |step| step.node)
+    }
+
+    // The same edges walked from the end to the start
+    pub fn reversed(&self) -> Self {
+        let mut steps = Vec::with_capacity(self.steps.len());
+        let mut previous = self.start;
+        for step in &self.steps {
+            steps.push(PathStep { predicate: step.predicate, inverse: !step.inverse, node: previous });
+            previous = step.node;
+        }
+        steps.reverse();
+        Self { start: previous, steps }
+    }
+
+    fn contains_node(&self, node: ObjectId) -> bool {
+        self.start == node || self.steps.iter().any(|step| step.node == node)
+    }
+
+    // Whether the path has the edge (subject, predicate, object), in any direction
+    fn contains_edge(&self, edge: [ObjectId; 3]) -> bool {
+        let mut previous = self.start;
+        self.steps.iter().any(|step| {
+            let found = path_edge(previous, step) == edge;
+            previous = step.node;
+            found
+        })
+    }
+}
+
+// The triple of the edge of a step that starts at `from`
+fn path_edge(from: ObjectId, step: &PathStep) -> [ObjectId; 3] {
+    if step.inverse {
+        [step.node, step.predicate, from]
+    } else {
+        [from, step.predicate, step.node]
+    }
+}
+
+// The paths found by a query. A path is bound to a variable as `ObjectId::path` of its
+// position here, and the same path always gets the same id so that paths can be compared.
+#[derive(Debug, Default)]
+pub struct QueryPaths {
+    paths: RefCell<Vec<PathValue>>,
+    ids: RefCell<HashMap<PathValue, ObjectId>>,
+}
+
+impl QueryPaths {
+    pub fn add(&self, path: PathValue) -> ObjectId {
+        let mut paths = self.paths.borrow_mut();
+        *self.ids.borrow_mut().entry(path).or_insert_with_key(|path| {
+            paths.push(path.clone());
+            ObjectId::path(paths.len() as u64 - 1)
+        })
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<PathValue> {
+        self.paths.borrow().get(id.path_id()? as usize).cloned()
+    }
+}
+
+// A node where a path from the start of the search ends, with the path when the mode of
+// the search returns paths
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathMatch {
+    pub end: ObjectId,
+    pub path: Option<PathValue>,
+}
+
+// Where a step of the search comes from: the previous pair (node, state) and the edge
+#[derive(Debug, Clone, Copy)]
+struct Parent {
+    node: ObjectId,
+    state: usize,
+    predicate: ObjectId,
+    inverse: bool,
+}
+
+// A pair (node, state) of the depth-first enumeration, with the edges that leave it
+struct Frame {
+    state: usize,
+    edges: Vec<(PathStep, usize)>,
+    next_edge: usize,
+}
+
+// Search of the paths from a start node, over the pairs (node, state of the automaton).
+//
+// Without a mode the search is breadth-first and visits a pair once, so cycles of the graph
+// end it, and every end node is returned once, as `*` and `+` have set semantics. ANY and
+// ANY SHORTEST keep the first parent of every pair to rebuild one path to each end node;
+// as the search is breadth-first that path is also a shortest one. ALL SHORTEST keeps every
+// parent at the previous depth and returns every shortest path to each end node.
+//
+// TRAIL, SIMPLE and ACYCLIC return every path without a repeated edge, without a repeated
+// node except for a path ending where it starts, and without any repeated node. They are
+// enumerated depth-first, as the same pair has to be visited by different paths.
+pub struct PathSearch {
+    mode: Option<PathMode>,
+    start_node: ObjectId,
+    queue: VecDeque<(ObjectId, usize)>,
+    visited: HashSet<(ObjectId, usize)>,
+    reached: HashSet<ObjectId>,
+    parents: HashMap<(ObjectId, usize), Vec<Parent>>,
+    // Paths of ALL SHORTEST, computed once the breadth-first search is over
+    shortest: Option<VecDeque<PathValue>>,
+    frames: Vec<Frame>,
+    path: Option<PathValue>,
+    returned: HashSet<PathValue>,
+}
+
+impl PathSearch {
+    pub fn new(mode: Option<PathMode>) -> Self {
+        Self {
+            mode,
+            start_node: ObjectId::NULL,
+            queue: VecDeque::new(),
+            visited: HashSet::new(),
+            reached: HashSet::new(),
+            parents: HashMap::new(),
+            shortest: None,
+            frames: Vec::new(),
+            path: None,
+            returned: HashSet::new(),
+        }
+    }
+
+    pub fn start(&mut self, automaton: &PathAutomaton, node: ObjectId) {
+        self.start_node = node;
+        self.queue.clear();
+        self.visited.clear();
+        self.reached.clear();
+        self.parents.clear();
+        self.shortest = None;
+        self.frames.clear();
+        self.path = None;
+        self.returned.clear();
+        self.visited.insert((node, automaton.start));
+        self.queue.push_back((node, automaton.start));
+    }
+
+    // The next match, `None` when the search is over
+    pub fn next(
+        &mut self,
+        automaton: &PathAutomaton,
+        graph: &PathGraph,
+        interruption: &Interruption,
+    ) -> Result<Option<PathMatch>, ExecutionError> {
+        match self.mode {
+            None | Some(PathMode::Any) | Some(PathMode::AnyShortest) => self.next_reached(automaton, graph, interruption),
+            Some(PathMode::AllShortest) => self.next_shortest(automaton, graph, interruption),
+            Some(PathMode::Trail) | Some(PathMode::Simple) | Some(PathMode::Acyclic) => {
+                self.next_enumerated(automaton, graph, interruption)
+            }
+        }
+    }
+
+    fn next_reached(
+        &mut self,
+        automaton: &PathAutomaton,
+        graph: &PathGraph,
+        interruption: &Interruption,
+    ) -> Result<Option<PathMatch>, ExecutionError> {
+        let keep_parents = self.mode.is_some();
+        while let Some((node, state)) = self.queue.pop_front() {
+            interruption.check()?;
+            for (label, next_state) in &automaton.transitions[state] {
+                for (predicate, next_node) in graph.neighbors(node, label)? {
+                    if self.visited.insert((next_node, *next_state)) {
+                        self.queue.push_back((next_node, *next_state));
+                        if keep_parents {
+                            let parent = Parent { node, state, predicate, inverse: label.is_backward() };
+                            self.parents.insert((next_node, *next_state), vec![parent]);
+                        }
+                    }
+                }
+            }
+            if automaton.finals[state] && self.reached.insert(node) {
+                let path = keep_parents.then(|| self.first_path(node, state));
+                return Ok(Some(PathMatch { end: node, path }));
+            }
+        }
+        Ok(None)
+    }
+
+    // The path that reached the pair first, following the first parents back to the start
+    fn first_path(&self, node: ObjectId, state: usize) -> PathValue {
+        let mut steps = Vec::new();
+        let mut current = (node, state);
+        while let Some(parent) = self.parents.get(&current).and_then(|parents| parents.first()) {
+            steps.push(PathStep { predicate: parent.predicate, inverse: parent.inverse, node: current.0 });
+            current = (parent.node, parent.state);
+        }
+        steps.reverse();
+        PathValue { start: self.start_node, steps }
+    }
+
+    fn next_shortest(
+        &mut self,
+        automaton: &PathAutomaton,
+        graph: &PathGraph,
+        interruption: &Interruption,
+    ) -> Result<Option<PathMatch>, ExecutionError> {
+        if self.shortest.is_none() {
+            let paths = self.all_shortest_paths(automaton, graph, interruption)?;
+            self.shortest = Some(paths);
+        }
+        Ok(self.shortest.as_mut().and_then(|paths| paths.pop_front()).map(|path| PathMatch { end: path.end(), path: Some(path) }))
+    }
+
+    fn all_shortest_paths(
+        &mut self,
+        automaton: &PathAutomaton,
+        graph: &PathGraph,
+        interruption: &Interruption,
+    ) -> Result<VecDeque<PathValue>, ExecutionError> {
+        let mut depths = HashMap::new();
+        depths.insert((self.start_node, automaton.start), 0);
+        // Pairs in a final state, in the order they are reached, with their depth
+        let mut finals = Vec::new();
+        while let Some((node, state)) = self.queue.pop_front() {
+            interruption.check()?;
+            let depth = depths[&(node, state)];
+            if automaton.finals[state] {
+                finals.push((node, state, depth));
+            }
+            for (label, next_state) in &automaton.transitions[state] {
+                for (predicate, next_node) in graph.neighbors(node, label)? {
+                    let pair = (next_node, *next_state);
+                    let parent = Parent { node, state, predicate, inverse: label.is_backward() };
+                    match depths.get(&pair) {
+                        None => {
+                            depths.insert(pair, depth + 1);
+                            self.queue.push_back(pair);
+                            self.parents.insert(pair, vec![parent]);
+                        }
+                        Some(&pair_depth) if pair_depth == depth + 1 => self.parents.entry(pair).or_default().push(parent),
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        // Every end node keeps the final pairs at its smallest depth
+        let mut shortest_depths: HashMap<ObjectId, usize> = HashMap::new();
+        for &(node, _, depth) in &finals {
+            shortest_depths.entry(node).or_insert(depth);
+        }
+        let mut paths = VecDeque::new();
+        for (node, state, depth) in finals {
+            if shortest_depths[&node] != depth {
+                continue;
+            }
+            let mut steps = Vec::new();
+            self.add_paths_to((node, state), &mut steps, &mut paths, interruption)?;
+        }
+        Ok(paths)
+    }
+
+    // Adds every path from the start to the pair, followed by `suffix` (in reverse order)
+    fn add_paths_to(
+        &mut self,
+        pair: (ObjectId, usize),
+        suffix: &mut Vec<PathStep>,
+        paths: &mut VecDeque<PathValue>,
+        interruption: &Interruption,
+    ) -> Result<(), ExecutionError> {
+        interruption.check()?;
+        let parents = match self.parents.get(&pair) {
+            Some(parents) => parents.clone(),
+            None => {
+                let path = PathValue { start: self.start_node, steps: suffix.iter().rev().cloned().collect() };
+                // Different states may follow the same edges
+                if self.returned.insert(path.clone()) {
+                    paths.push_back(path);
+                }
+                return Ok(());
+            }
+        };
+        for parent in parents {
+            suffix.push(PathStep { predicate: parent.predicate, inverse: parent.inverse, node: pair.0 });
+            self.add_paths_to((parent.node, parent.state), suffix, paths, interruption)?;
+            suffix.pop();
+        }
+        Ok(())
+    }
+
+    fn next_enumerated(
+        &mut self,
+        automaton: &PathAutomaton,
+        graph: &PathGraph,
+        interruption: &Interruption,
+    ) -> Result<Option<PathMatch>, ExecutionError> {
+        if self.path.is_none() {
+            // The first call pushes the start pair
+            let start = PathValue { start: self.start_node, steps: Vec::new() };
+            self.path = Some(start);
+            if let Some(found) = self.push_frame(automaton.start, automaton, graph)? {
+                return Ok(Some(found));
+            }
+        }
+        while let Some(frame) = self.frames.last_mut() {
+            interruption.check()?;
+            let (step, state) = match frame.edges.get(frame.next_edge) {
+                Some(edge) => edge.clone(),
+                None => {
+                    self.frames.pop();
+                    if let Some(path) = self.path.as_mut() {
+                        path.steps.pop();
+                    }
+                    continue;
+                }
+            };
+            frame.next_edge += 1;
+            let path = self.path.as_mut().unwrap();
+            if !self.mode.is_some_and(|mode| mode.allows(path, &step)) {
+                continue;
+            }
+            path.steps.push(step);
+            if let Some(found) = self.push_frame(state, automaton, graph)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    // Starts the edges of the pair at the end of the current path, and returns the path if
+    // it is a new match
+    fn push_frame(
+        &mut self,
+        state: usize,
+        automaton: &PathAutomaton,
+        graph: &PathGraph,
+    ) -> Result<Option<PathMatch>, ExecutionError> {
+        let path = self.path.as_ref().unwrap();
+        let node = path.end();
+        let mut edges = Vec::new();
+        // A simple path is over once it is back at its start
+        if self.mode != Some(PathMode::Simple) || path.steps.is_empty() || node != path.start {
+            for (label, next_state) in &automaton.transitions[state] {
+                for (predicate, next_node) in graph.neighbors(node, label)? {
+                    edges.push((PathStep { predicate, inverse: label.is_backward(), node: next_node }, *next_state));
+                }
+            }
+        }
+        self.frames.push(Frame { state, edges, next_edge: 0 });
+        if automaton.finals[self.frames.last().unwrap().state] && self.returned.insert(path.clone()) {
+            return Ok(Some(PathMatch { end: node, path: Some(path.clone()) }));
+        }
+        Ok(None)
+    }
+}
+
+impl PathMode {
+    // Whether the path can be extended with the step
+    fn allows(self, path: &PathValue, step: &PathStep) -> bool {
+        match self {
+            PathMode::Trail => !path.contains_edge(path_edge(path.end(), step)),
+            PathMode::Simple => step.node == path.start || !path.contains_node(step.node),
+            PathMode::Acyclic => !path.contains_node(step.node),
+            _ => true,
+        }
+    }
+}
+
+impl PathLabel {
+    fn is_backward(&self) -> bool {
+        matches!(self, PathLabel::Backward(_) | PathLabel::NegatedBackward(_))
+    }
+}
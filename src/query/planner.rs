@@ -0,0 +1,204 @@
+use std::collections::BTreeSet;
+
+use crate::storage::quad_indexes::TRIPLE_PERMUTATIONS;
+
+// Orders with more variables than this are not enumerated, only the first one is tried
+const MAX_ENUMERATED_VARIABLES: usize = 8;
+
+// A term of a triple pattern: a variable (by its index in the query) or the raw ObjectId
+// of a constant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatternTerm {
+    Variable(usize),
+    Constant(u64),
+}
+
+// A triple pattern of a basic graph pattern, its terms in the order (s, p, o)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TriplePattern {
+    pub terms: [PatternTerm; 3],
+}
+
+impl TriplePattern {
+    pub fn new(subject: PatternTerm, predicate: PatternTerm, object: PatternTerm) -> Self {
+        Self { terms: [subject, predicate, object] }
+    }
+
+    // Distinct variables of the pattern, in the order (s, p, o)
+    pub fn variables(&self) -> Vec<usize> {
+        let mut variables = Vec::new();
+        for term in &self.terms {
+            if let PatternTerm::Variable(variable) = term {
+                if !variables.contains(variable) {
+                    variables.push(*variable);
+                }
+            }
+        }
+        variables
+    }
+
+    fn constant_count(&self) -> usize {
+        self.terms.iter().filter(|term| matches!(term, PatternTerm::Constant(_))).count()
+    }
+
+    // A variable used twice (e.g. `?x :p ?x`) can not be a single level of a trie
+    fn repeats_variable(&self) -> bool {
+        self.variables().len() + self.constant_count() < 3
+    }
+}
+
+// A pattern as read by the Leapfrog Triejoin: a range of a permutation whose leading
+// terms are the constants, followed by the variables in the order they are bound
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeapfrogPattern {
+    // Index of the permutation in `TRIPLE_PERMUTATIONS`
+    pub permutation: usize,
+    // The constants of the pattern, in the order of the permutation
+    pub constants: Vec<u64>,
+    // Levels (positions in the variable order) of the variables after the constants
+    pub levels: Vec<usize>,
+}
+
+// How the triple patterns of a basic graph pattern are joined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JoinPlan {
+    // Worst-case optimal join: the variables are bound one at a time in `variable_order`,
+    // intersecting the values every pattern allows for them
+    Leapfrog {
+        variable_order: Vec<usize>,
+        patterns: Vec<LeapfrogPattern>,
+    },
+    // Binary joins of the patterns in this order, each one sharing variables with the
+    // previous ones when possible
+    Binary { patterns: Vec<TriplePattern> },
+}
+
+// Cyclic patterns (e.g. triangles) are joined with the Leapfrog Triejoin, the pairwise
+// joins of their patterns can be much bigger than the result. Acyclic patterns and the
+// ones no variable order fits the available permutations use binary joins.
+pub fn plan_basic_graph_pattern(patterns: &[TriplePattern]) -> JoinPlan {
+    if is_cyclic(patterns) {
+        if let Some(plan) = leapfrog_plan(patterns) {
+            return plan;
+        }
+    }
+    JoinPlan::Binary { patterns: binary_join_order(patterns) }
+}
+
+// Whether the hypergraph of the patterns (a node per variable, an edge per pattern) is
+// cyclic, using the GYO reduction: variables of a single pattern and patterns contained
+// in another one are removed until nothing changes. Acyclic patterns disappear.
+pub fn is_cyclic(patterns: &[TriplePattern]) -> bool {
+    let mut edges: Vec<BTreeSet<usize>> = patterns.iter().map(|pattern| pattern.variables().into_iter().collect()).collect();
+    loop {
+        let mut changed = false;
+        for variable in edges.iter().flatten().copied().collect::<BTreeSet<usize>>() {
+            if edges.iter().filter(|edge| edge.contains(&variable)).count() == 1 {
+                edges.iter_mut().for_each(|edge| {
+                    edge.remove(&variable);
+                });
+                changed = true;
+            }
+        }
+        let contained = (0..edges.len()).find(|&i| {
+            edges[i].is_empty() || (0..edges.len()).any(|j| j != i && edges[i].is_subset(&edges[j]))
+        });
+        if let Some(i) = contained {
+            edges.remove(i);
+            changed = true;
+        }
+        if !changed {
+            return !edges.is_empty();
+        }
+    }
+}
+
+// The first variable order, most shared variables first, where every pattern has a
+// permutation with its constants and then its variables in that order
+fn leapfrog_plan(patterns: &[TriplePattern]) -> Option<JoinPlan> {
+    if patterns.iter().any(TriplePattern::repeats_variable) {
+        return None;
+    }
+    let mut ranked: Vec<usize> = patterns.iter().flat_map(TriplePattern::variables).collect::<BTreeSet<_>>().into_iter().collect();
+    let occurrences = |variable: &usize| patterns.iter().filter(|pattern| pattern.variables().contains(variable)).count();
+    ranked.sort_by_key(|variable| std::cmp::Reverse(occurrences(variable)));
+
+    let mut order: Vec<usize> = (0..ranked.len()).collect();
+    loop {
+        let variable_order: Vec<usize> = order.iter().map(|&i| ranked[i]).collect();
+        let leapfrog_patterns: Option<Vec<LeapfrogPattern>> =
+            patterns.iter().map(|pattern| leapfrog_pattern(pattern, &variable_order)).collect();
+        if let Some(patterns) = leapfrog_patterns {
+            return Some(JoinPlan::Leapfrog { variable_order, patterns });
+        }
+        if ranked.len() > MAX_ENUMERATED_VARIABLES || !next_permutation(&mut order) {
+            return None;
+        }
+    }
+}
+
+fn leapfrog_pattern(pattern: &TriplePattern, variable_order: &[usize]) -> Option<LeapfrogPattern> {
+    let level = |variable: usize| variable_order.iter().position(|&v| v == variable);
+    let constants = pattern.constant_count();
+    let mut variable_positions: Vec<(usize, usize)> = Vec::new();
+    for (position, term) in pattern.terms.iter().enumerate() {
+        if let PatternTerm::Variable(variable) = term {
+            variable_positions.push((level(*variable)?, position));
+        }
+    }
+    variable_positions.sort();
+
+    TRIPLE_PERMUTATIONS.iter().enumerate().find_map(|(permutation, (_, order))| {
+        let constants_first = order[..constants]
+            .iter()
+            .all(|&position| matches!(pattern.terms[position], PatternTerm::Constant(_)));
+        let variables_in_order =
+            order[constants..].iter().zip(&variable_positions).all(|(&position, &(_, expected))| position == expected);
+        if !constants_first || !variables_in_order {
+            return None;
+        }
+        Some(LeapfrogPattern {
+            permutation,
+            constants: order[..constants]
+                .iter()
+                .filter_map(|&position| match pattern.terms[position] {
+                    PatternTerm::Constant(value) => Some(value),
+                    PatternTerm::Variable(_) => None,
+                })
+                .collect(),
+            levels: variable_positions.iter().map(|&(level, _)| level).collect(),
+        })
+    })
+}
+
+// Greedy order: the pattern with most constants first, then the ones sharing a variable
+// with the patterns already joined (most constants first), to avoid cross products
+pub fn binary_join_order(patterns: &[TriplePattern]) -> Vec<TriplePattern> {
+    let mut remaining: Vec<TriplePattern> = patterns.to_vec();
+    let mut ordered = Vec::with_capacity(patterns.len());
+    let mut bound: BTreeSet<usize> = BTreeSet::new();
+    while !remaining.is_empty() {
+        let connected = |pattern: &TriplePattern| pattern.variables().iter().any(|variable| bound.contains(variable));
+        let best = (0..remaining.len())
+            .max_by_key(|&i| {
+                (ordered.is_empty() || connected(&remaining[i]), remaining[i].constant_count(), std::cmp::Reverse(i))
+            })
+            .unwrap();
+        let pattern = remaining.remove(best);
+        bound.extend(pattern.variables());
+        ordered.push(pattern);
+    }
+    ordered
+}
+
+// Next permutation in lexicographic order, false after the last one
+fn next_permutation(values: &mut [usize]) -> bool {
+    let pivot = match (1..values.len()).rev().find(|&i| values[i - 1] < values[i]) {
+        Some(i) => i - 1,
+        None => return false,
+    };
+    let successor = (pivot + 1..values.len()).rev().find(|&i| values[i] > values[pivot]).unwrap();
+    values.swap(pivot, successor);
+    values[pivot + 1..].reverse();
+    true
+}
@@ -0,0 +1,134 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use crate::import::quad_model_loader::{
+    EDGE_PERMUTATIONS, FROM_TO_TYPE_EDGE_FILE, LABEL_PERMUTATIONS, NODES_FILE, NODE_LABEL_FILE, OBJECT_KEY_VALUE_FILE,
+    PROPERTY_PERMUTATIONS, TO_TYPE_FROM_EDGE_FILE,
+};
+use crate::storage::bplus_tree::BPlusTree;
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::exceptions::StorageError;
+use crate::storage::quad_indexes::permutation_file;
+use crate::storage::string_manager::StringManager;
+
+// Read access to a property graph database created by the QuadModelLoader. Every table
+// is a B+tree read through the buffer manager, and the terms are resolved through the
+// dictionary of the database.
+pub struct QuadModelGraph {
+    strings: StringManager,
+    nodes: BPlusTree<1>,
+    // The trees of the permutations, in the order of their arrays
+    labels: Vec<BPlusTree<2>>,
+    properties: Vec<BPlusTree<3>>,
+    edges: Vec<BPlusTree<4>>,
+}
+
+impl QuadModelGraph {
+    // Opens the graph of the database folder of `buffer`, loading the first
+    // `string_populate_size` bytes of the dictionary in memory
+    pub fn open(buffer: &Arc<BufferManager>, string_populate_size: u64) -> Result<Self, StorageError> {
+        // The tables are opened first, so a folder without a graph is not given a dictionary
+        let nodes = BPlusTree::open(buffer, &permutation_file(NODES_FILE))?;
+        let labels = open_trees(buffer, &LABEL_PERMUTATIONS)?;
+        let properties = open_trees(buffer, &PROPERTY_PERMUTATIONS)?;
+        let edges = open_trees(buffer, &EDGE_PERMUTATIONS)?;
+        Ok(Self {
+            strings: StringManager::open(buffer, string_populate_size)?,
+            nodes,
+            labels,
+            properties,
+            edges,
+        })
+    }
+
+    // Id of a term given its dictionary key
+    pub fn term_id(&self, key: &str) -> Result<Option<u64>, StorageError> {
+        self.strings.get_id(key)
+    }
+
+    // Dictionary key of a term given its id
+    pub fn term(&self, id: u64) -> Result<String, StorageError> {
+        self.strings.get_string(id)
+    }
+
+    pub fn node_index(&self) -> &BPlusTree<1> {
+        &self.nodes
+    }
+
+    // Tree of the label permutation `name` of LABEL_PERMUTATIONS
+    pub fn label_index(&self, name: &str) -> &BPlusTree<2> {
+        &self.labels[position(&LABEL_PERMUTATIONS, name)]
+    }
+
+    // Tree of the property permutation `name` of PROPERTY_PERMUTATIONS
+    pub fn property_index(&self, name: &str) -> &BPlusTree<3> {
+        &self.properties[position(&PROPERTY_PERMUTATIONS, name)]
+    }
+
+    // Tree of the edge permutation `name` of EDGE_PERMUTATIONS
+    pub fn edge_index(&self, name: &str) -> &BPlusTree<4> {
+        &self.edges[position(&EDGE_PERMUTATIONS, name)]
+    }
+
+    // The node with its labels and properties followed by its edges, in the syntax of the
+    // text format. `None` if the node does not exist.
+    pub fn describe_node(&self, id: &str) -> Result<Option<String>, StorageError> {
+        let node = match self.term_id(id)? {
+            Some(node) if self.nodes.contains(&[node])? => node,
+            _ => return Ok(None),
+        };
+
+        let mut description = format!("({})", id);
+        for tuple in self.label_index(NODE_LABEL_FILE).scan_prefix(&[node])? {
+            let [_, label] = tuple?;
+            let _ = write!(description, " :{}", self.term(label)?);
+        }
+        self.write_properties(&mut description, node)?;
+        description.push('\n');
+
+        for tuple in self.edge_index(FROM_TO_TYPE_EDGE_FILE).scan_prefix(&[node])? {
+            let [_, to, edge_type, edge] = tuple?;
+            let _ = write!(description, "({})->({}) :{}", id, self.term(to)?, self.term(edge_type)?);
+            self.write_properties(&mut description, edge)?;
+            description.push('\n');
+        }
+        for tuple in self.edge_index(TO_TYPE_FROM_EDGE_FILE).scan_prefix(&[node])? {
+            let [_, edge_type, from, edge] = tuple?;
+            // Loops were already written as outgoing edges
+            if from == node {
+                continue;
+            }
+            let _ = write!(description, "({})<-({}) :{}", id, self.term(from)?, self.term(edge_type)?);
+            self.write_properties(&mut description, edge)?;
+            description.push('\n');
+        }
+        Ok(Some(description))
+    }
+
+    fn write_properties(&self, out: &mut String, object: u64) -> Result<(), StorageError> {
+        let mut separator = " {";
+        for tuple in self.property_index(OBJECT_KEY_VALUE_FILE).scan_prefix(&[object])? {
+            let [_, key, value] = tuple?;
+            let _ = write!(out, "{}{}: {}", separator, self.term(key)?, self.term(value)?);
+            separator = ", ";
+        }
+        if separator == ", " {
+            out.push('}');
+        }
+        Ok(())
+    }
+}
+
+fn open_trees<const N: usize>(
+    buffer: &Arc<BufferManager>,
+    permutations: &[(&str, [usize; N])],
+) -> Result<Vec<BPlusTree<N>>, StorageError> {
+    permutations.iter().map(|(name, _)| BPlusTree::open(buffer, &permutation_file(name))).collect()
+}
+
+fn position<const N: usize>(permutations: &[(&str, [usize; N])], name: &str) -> usize {
+    permutations
+        .iter()
+        .position(|(permutation, _)| *permutation == name)
+        .expect("the name of a permutation of the graph")
+}
@@ -1,12 +1,24 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{SystemTime}; 
 
+use crate::query::executor::{Interruption, VarId};
+
 pub struct ThreadInfo {
-    pub interruption_requested: bool,
-    finished: bool,
+    // Set by the timeout task of the server, the running query checks it without locking
+    // its context
+    pub interruption_requested: Arc<AtomicBool>,
+    pub finished: bool,
     pub worker_index: u32,
     pub timeout: SystemTime,    
-    time_start: SystemTime,
+    pub time_start: SystemTime,
+}
+
+impl Default for ThreadInfo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ThreadInfo {
@@ -14,20 +26,31 @@ impl ThreadInfo {
         Self {
             // Initialize your struct here
             // For example:
-            interruption_requested: false,
+            interruption_requested: Arc::new(AtomicBool::new(false)),
             finished: false,
             worker_index: 0,
             timeout: SystemTime::now(),
             time_start: SystemTime::now(),
         }
     }
+
+    // What the operators of the query check to stop when it is interrupted
+    pub fn interruption(&self) -> Interruption {
+        Interruption::new(Arc::clone(&self.interruption_requested))
+    }
 }
 
 
 pub struct VarContext {
     internal_var_counter: u64,
     var_names: Vec<String>,
-    var_map: HashMap<String, u64>,
+    var_map: HashMap<String, VarId>,
+}
+
+impl Default for VarContext {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VarContext {
@@ -40,6 +63,41 @@ impl VarContext {
             var_map: HashMap::new(),
         }
     }
+
+    // Id of the variable, assigned the first time the name is seen
+    pub fn get_or_create_var(&mut self, name: &str) -> VarId {
+        if let Some(&var) = self.var_map.get(name) {
+            return var;
+        }
+        let var = self.var_names.len();
+        self.var_names.push(name.to_string());
+        self.var_map.insert(name.to_string(), var);
+        var
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<VarId> {
+        self.var_map.get(name).copied()
+    }
+
+    pub fn var_name(&self, var: VarId) -> &str {
+        &self.var_names[var]
+    }
+
+    // A variable the query cannot name, for the values the executor computes
+    pub fn new_internal_var(&mut self) -> VarId {
+        let name = format!(".var{}", self.internal_var_counter);
+        self.internal_var_counter += 1;
+        self.get_or_create_var(&name)
+    }
+
+    // Number of variables, the size of the bindings
+    pub fn len(&self) -> usize {
+        self.var_names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.var_names.is_empty()
+    }
 }
 
 pub struct QueryContext {
@@ -49,8 +107,16 @@ pub struct QueryContext {
     blank_node_count: u64,
 }
 
+impl Default for QueryContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QueryContext {
     pub fn reset(&mut self) {
+        self.thread_info.interruption_requested.store(false, Ordering::Relaxed);
+        self.thread_info.finished = false;
         self.blank_node_ids.clear();
         self.blank_node_count = 0;
 
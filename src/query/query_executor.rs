@@ -0,0 +1,704 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use crate::query::aggregates::accumulator_factory;
+use crate::query::algebra::{Expression, GraphPattern, Query, TermPattern};
+use crate::query::exceptions::NotSupportedException;
+use crate::query::executor::*;
+use crate::query::expressions::{Arithmetic, CompiledExpression, Comparison};
+use crate::query::functions::{FunctionContext, SUPPORTED_BUILTINS, SUPPORTED_CASTS};
+use crate::query::object_id::{ObjectId, TermEncoding};
+use crate::query::paths::{PathAutomaton, PathStep, PathValue, QueryPaths};
+use crate::query::planner::{binary_join_order, plan_basic_graph_pattern, PatternTerm, TriplePattern};
+use crate::query::query_contexts::VarContext;
+use crate::query::query_forms::QueryForm;
+use crate::query::rdf_terms::{RdfPath, RdfPathStep, RdfQuad, RdfTerm, Solution};
+use crate::storage::exceptions::StorageError;
+use crate::storage::iri_prefixes::IriPrefixes;
+use crate::storage::quad_indexes::QuadIndexes;
+use crate::storage::string_manager::StringManager;
+
+// Flag of the dictionary ids of the terms a query needs that are not in the database
+// (constants of the query, computed values). They only exist while the query runs.
+const TEMPORARY_ID_FLAG: u64 = 1 << 47;
+
+// Conversion between the terms of a query and the ObjectIds of the database
+pub struct QueryTerms<'a> {
+    strings: &'a StringManager,
+    prefixes: &'a IriPrefixes,
+    // Keys of the temporary terms, by their id without the flag
+    temporary_keys: RefCell<Vec<String>>,
+    temporary_ids: RefCell<HashMap<(u64, String), ObjectId>>,
+    pub paths: QueryPaths,
+    pub functions: FunctionContext,
+}
+
+impl<'a> QueryTerms<'a> {
+    pub fn new(strings: &'a StringManager, prefixes: &'a IriPrefixes) -> Self {
+        Self {
+            strings,
+            prefixes,
+            temporary_keys: RefCell::new(Vec::new()),
+            temporary_ids: RefCell::new(HashMap::new()),
+            paths: QueryPaths::default(),
+            functions: FunctionContext::default(),
+        }
+    }
+
+    // The id of the term in the database, or a temporary one that matches nothing in the
+    // indexes. The same term always has the same id.
+    pub fn get_or_create(&self, term: &RdfTerm) -> Result<ObjectId, ExecutionError> {
+        if let RdfTerm::Path(path) = term {
+            let mut steps = Vec::with_capacity(path.steps.len());
+            for step in &path.steps {
+                let (predicate, node) = (self.get_or_create(&step.predicate)?, self.get_or_create(&step.node)?);
+                steps.push(PathStep { predicate, inverse: step.inverse, node });
+            }
+            let start = self.get_or_create(&path.start)?;
+            return Ok(self.paths.add(PathValue { start, steps }));
+        }
+        let (mask, key) = match ObjectId::encode(term, self.prefixes) {
+            TermEncoding::Inlined(id) => return Ok(id),
+            TermEncoding::External { mask, key } => (mask, key),
+        };
+        if let Some(id) = self.strings.get_id(&key)? {
+            return Ok(ObjectId::external(mask, id));
+        }
+        let mut temporary_ids = self.temporary_ids.borrow_mut();
+        let mut temporary_keys = self.temporary_keys.borrow_mut();
+        let id = *temporary_ids.entry((mask, key.clone())).or_insert_with(|| {
+            temporary_keys.push(key);
+            ObjectId::external(mask, TEMPORARY_ID_FLAG | (temporary_keys.len() as u64 - 1))
+        });
+        Ok(id)
+    }
+
+    // The term of an id, `None` for NULL
+    pub fn decode(&self, id: ObjectId) -> Result<Option<RdfTerm>, ExecutionError> {
+        if id.is_null() {
+            return Ok(None);
+        }
+        if let Some(term) = id.decode_inlined(self.prefixes) {
+            return Ok(Some(term));
+        }
+        if let Some(path) = self.paths.get(id) {
+            return self.decode_path(&path);
+        }
+        let dictionary_id = match id.dictionary_id() {
+            Some(dictionary_id) => dictionary_id,
+            None => return Ok(None),
+        };
+        let key = if dictionary_id & TEMPORARY_ID_FLAG != 0 {
+            self.temporary_keys.borrow()[(dictionary_id & !TEMPORARY_ID_FLAG) as usize].clone()
+        } else {
+            self.strings.get_string(dictionary_id)?
+        };
+        Ok(Some(id.decode_external(&key, self.prefixes)?))
+    }
+
+    fn decode_path(&self, path: &PathValue) -> Result<Option<RdfTerm>, ExecutionError> {
+        let start = match self.decode(path.start)? {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let mut steps = Vec::with_capacity(path.steps.len());
+        for step in &path.steps {
+            match (self.decode(step.predicate)?, self.decode(step.node)?) {
+                (Some(predicate), Some(node)) => steps.push(RdfPathStep { predicate, inverse: step.inverse, node }),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(RdfTerm::Path(Box::new(RdfPath { start, steps }))))
+    }
+}
+
+// Results of a query, computed while they are serialized
+pub enum QueryResults<'a> {
+    Solutions { variables: Vec<String>, solutions: SolutionIter<'a> },
+    Boolean(bool),
+    Graph(GraphIter<'a>),
+}
+
+// Builds the operators of the algebra of a query. Every variable gets an id in the
+// bindings, given the first time the variable is seen.
+pub struct QueryExecutor<'a> {
+    indexes: &'a QuadIndexes,
+    terms: &'a QueryTerms<'a>,
+    interruption: Interruption,
+    vars: VarContext,
+    // Where GROUP BY writes the groups that do not fit in memory. Without pages every
+    // group is kept in memory.
+    pages: Option<TemporaryPages<'a>>,
+    // Graphs the patterns are matched in, the default graph of the database when `None`
+    active_graph: Option<QuadGraph>,
+    // Graphs GRAPH can match (FROM NAMED), every named graph when `None`
+    named_graphs: Option<Vec<u64>>,
+}
+
+impl<'a> QueryExecutor<'a> {
+    pub fn new(indexes: &'a QuadIndexes, terms: &'a QueryTerms<'a>, interruption: Interruption) -> Self {
+        Self {
+            indexes,
+            terms,
+            interruption,
+            vars: VarContext::new(),
+            pages: None,
+            active_graph: None,
+            named_graphs: None,
+        }
+    }
+
+    pub fn with_temporary_pages(mut self, pages: TemporaryPages<'a>) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    // The dataset of FROM and FROM NAMED, as ids of the graph IRIs. Without FROM the
+    // default graph is empty.
+    pub fn with_dataset(mut self, default_graphs: Vec<u64>, named_graphs: Vec<u64>) -> Self {
+        self.active_graph = Some(QuadGraph::Merge(default_graphs));
+        self.named_graphs = Some(named_graphs);
+        self
+    }
+
+    pub fn var(&mut self, name: &str) -> VarId {
+        self.vars.get_or_create_var(name)
+    }
+
+    // A binding with room for every variable seen so far
+    pub fn new_binding(&self) -> Binding {
+        Binding::new(self.vars.len())
+    }
+
+    pub fn compile(&mut self, pattern: &GraphPattern) -> Result<BoxedIter<'a>, ExecutionError> {
+        Ok(match pattern {
+            GraphPattern::Bgp(triples) => {
+                if triples.is_empty() {
+                    return Ok(Box::new(SingleResult::default()));
+                }
+                let mut patterns = Vec::with_capacity(triples.len());
+                for triple in triples {
+                    patterns.push(TriplePattern::new(
+                        self.pattern_term(&triple.subject)?,
+                        self.pattern_term(&triple.predicate)?,
+                        self.pattern_term(&triple.object)?,
+                    ));
+                }
+                match &self.active_graph {
+                    Some(graph) => quad_pattern_iter(self.indexes, binary_join_order(&patterns), graph, &self.interruption),
+                    None => basic_graph_pattern_iter(self.indexes, plan_basic_graph_pattern(&patterns), &self.interruption),
+                }
+            }
+            GraphPattern::Join(left, right) => {
+                if **left == GraphPattern::empty() {
+                    return self.compile(right);
+                }
+                let lhs = self.compile(left)?;
+                let rhs = self.compile(right)?;
+                // Scans, paths and VALUES read the values of the left side as constants,
+                // other patterns have to be evaluated on their own
+                match **right {
+                    GraphPattern::Bgp(_) | GraphPattern::Path { .. } | GraphPattern::Values { .. } => {
+                        Box::new(NestedLoopJoin::new(lhs, rhs))
+                    }
+                    _ => Box::new(HashJoin::new(lhs, rhs)),
+                }
+            }
+            GraphPattern::LeftJoin { left, right, condition } => {
+                let lhs = self.compile(left)?;
+                let mut rhs = self.compile(right)?;
+                if let Some(condition) = condition {
+                    rhs = self.filter(rhs, condition)?;
+                }
+                Box::new(LeftOuterJoin::new(lhs, rhs))
+            }
+            GraphPattern::Union(..) => {
+                let mut children = Vec::new();
+                self.compile_union(pattern, &mut children)?;
+                Box::new(Union::new(children))
+            }
+            GraphPattern::Minus(left, right) => Box::new(Minus::new(self.compile(left)?, self.compile(right)?)),
+            GraphPattern::Filter { condition, inner } => {
+                let child = self.compile(inner)?;
+                self.filter(child, condition)?
+            }
+            GraphPattern::Extend { inner, variable, expression } => {
+                let child = self.compile(inner)?;
+                let var = self.var(variable);
+                self.extend(child, var, expression)?
+            }
+            GraphPattern::Values { variables, rows } => {
+                let variables = variables.iter().map(|name| self.var(name)).collect();
+                let mut ids = Vec::with_capacity(rows.len());
+                for row in rows {
+                    let mut values = Vec::with_capacity(row.len());
+                    for term in row {
+                        values.push(match term {
+                            Some(term) => self.terms.get_or_create(term)?,
+                            None => ObjectId::NULL,
+                        });
+                    }
+                    ids.push(values);
+                }
+                Box::new(Values::new(variables, ids))
+            }
+            GraphPattern::OrderBy { inner, conditions } => {
+                let mut child = self.compile(inner)?;
+                let mut keys = Vec::with_capacity(conditions.len());
+                for condition in conditions {
+                    // Expressions are computed into a variable the query does not see
+                    let var = match &condition.expression {
+                        Expression::Variable(name) => self.var(name),
+                        expression => {
+                            let var = self.vars.new_internal_var();
+                            child = self.extend(child, var, expression)?;
+                            var
+                        }
+                    };
+                    keys.push(OrderKey { var, ascending: condition.ascending });
+                }
+                Box::new(OrderBy::new(child, keys).with_terms(self.terms))
+            }
+            GraphPattern::Project { inner, variables } => {
+                let child = self.compile(inner)?;
+                let variables = variables.iter().map(|name| self.var(name)).collect();
+                Box::new(Project::new(child, variables))
+            }
+            GraphPattern::Distinct(inner) => Box::new(Distinct::new(self.compile(inner)?)),
+            // Removing some duplicates is allowed but not required
+            GraphPattern::Reduced(inner) => self.compile(inner)?,
+            GraphPattern::Slice { inner, offset, limit } => Box::new(Slice::new(self.compile(inner)?, *offset, *limit)),
+            GraphPattern::Path { subject, path, object, mode, variable } => {
+                let terms = self.terms;
+                let predicate_id = |iri: &str| terms.get_or_create(&RdfTerm::iri(iri));
+                let automaton = PathAutomaton::new(path, false, predicate_id)?;
+                let inverse_automaton = PathAutomaton::new(path, true, predicate_id)?;
+                let subject = self.pattern_term(subject)?;
+                let object = self.pattern_term(object)?;
+                let mut scan = PathScan::new(self.indexes, automaton, inverse_automaton, subject, object, self.interruption.clone());
+                if let Some(graph) = &self.active_graph {
+                    scan = scan.with_graph(graph.clone());
+                }
+                match mode {
+                    Some(mode) => {
+                        let path_var = variable.as_ref().map(|name| self.var(name));
+                        Box::new(scan.with_mode(*mode, path_var, &self.terms.paths))
+                    }
+                    None => Box::new(scan),
+                }
+            }
+            GraphPattern::Graph { name, inner } => {
+                let graph = QuadGraph::Named { graph: self.pattern_term(name)?, allowed: self.named_graphs.clone() };
+                let outer = self.active_graph.replace(graph);
+                let child = self.compile(inner);
+                self.active_graph = outer;
+                child?
+            }
+            GraphPattern::Group { inner, by, aggregates } => {
+                let child = self.compile(inner)?;
+                let child_variables = child.variables();
+                let mut keys = Vec::with_capacity(by.len());
+                for (expression, name) in by {
+                    let value = self.value_function(expression)?;
+                    keys.push((self.var(name), value));
+                }
+                let mut group_aggregates = Vec::with_capacity(aggregates.len());
+                for (name, aggregate) in aggregates {
+                    let arguments: Vec<ValueFunction<'a>> = match &aggregate.expression {
+                        Some(expression) => vec![self.value_function(expression)?],
+                        // COUNT(DISTINCT *) counts the different results of the child
+                        None if aggregate.distinct => child_variables
+                            .iter()
+                            .map(|&var| Box::new(move |binding: &Binding| Ok(binding.get(var))) as ValueFunction<'a>)
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    let star = aggregate.expression.is_none();
+                    group_aggregates.push(GroupAggregate {
+                        var: self.var(name),
+                        arguments,
+                        distinct: aggregate.distinct,
+                        new_accumulator: accumulator_factory(aggregate.function, aggregate.separator.clone(), star, self.terms),
+                    });
+                }
+                Box::new(GroupBy::new(child, keys, group_aggregates, self.pages, self.interruption.clone()))
+            }
+        })
+    }
+
+    fn compile_union(&mut self, pattern: &GraphPattern, children: &mut Vec<BoxedIter<'a>>) -> Result<(), ExecutionError> {
+        match pattern {
+            GraphPattern::Union(left, right) => {
+                self.compile_union(left, children)?;
+                self.compile_union(right, children)
+            }
+            _ => {
+                children.push(self.compile(pattern)?);
+                Ok(())
+            }
+        }
+    }
+
+    fn pattern_term(&mut self, term: &TermPattern) -> Result<PatternTerm, ExecutionError> {
+        Ok(match term {
+            TermPattern::Term(term) => PatternTerm::Constant(self.terms.get_or_create(term)?.raw()),
+            _ => PatternTerm::Variable(self.var(&term.variable().unwrap_or_default())),
+        })
+    }
+
+    fn filter(&mut self, child: BoxedIter<'a>, condition: &Expression) -> Result<BoxedIter<'a>, ExecutionError> {
+        let condition = self.compile_expression(condition)?;
+        let terms = self.terms;
+        Ok(Box::new(Filter::new(child, Box::new(move |binding| condition.holds(binding, terms)))))
+    }
+
+    fn extend(&mut self, child: BoxedIter<'a>, var: VarId, expression: &Expression) -> Result<BoxedIter<'a>, ExecutionError> {
+        let value = self.value_function(expression)?;
+        Ok(Box::new(Extend::new(child, var, value)))
+    }
+
+    fn value_function(&mut self, expression: &Expression) -> Result<ValueFunction<'a>, ExecutionError> {
+        let expression = self.compile_expression(expression)?;
+        let terms = self.terms;
+        Ok(Box::new(move |binding: &Binding| match expression.evaluate(binding, terms)? {
+            Some(term) => terms.get_or_create(&term),
+            None => Ok(ObjectId::NULL),
+        }))
+    }
+
+    pub fn compile_expression(&mut self, expression: &Expression) -> Result<CompiledExpression<'a>, ExecutionError> {
+        let mut binary = |a: &Expression, b: &Expression| -> Result<_, ExecutionError> {
+            Ok((Box::new(self.compile_expression(a)?), Box::new(self.compile_expression(b)?)))
+        };
+        Ok(match expression {
+            Expression::Variable(name) => CompiledExpression::Variable(self.var(name)),
+            Expression::Constant(term) => CompiledExpression::Constant(term.clone()),
+            Expression::Or(a, b) => {
+                let (a, b) = binary(a, b)?;
+                CompiledExpression::Or(a, b)
+            }
+            Expression::And(a, b) => {
+                let (a, b) = binary(a, b)?;
+                CompiledExpression::And(a, b)
+            }
+            Expression::Equal(a, b) => compare(Comparison::Equal, binary(a, b)?),
+            Expression::NotEqual(a, b) => compare(Comparison::NotEqual, binary(a, b)?),
+            Expression::Less(a, b) => compare(Comparison::Less, binary(a, b)?),
+            Expression::LessOrEqual(a, b) => compare(Comparison::LessOrEqual, binary(a, b)?),
+            Expression::Greater(a, b) => compare(Comparison::Greater, binary(a, b)?),
+            Expression::GreaterOrEqual(a, b) => compare(Comparison::GreaterOrEqual, binary(a, b)?),
+            Expression::Add(a, b) => arithmetic(Arithmetic::Add, binary(a, b)?),
+            Expression::Subtract(a, b) => arithmetic(Arithmetic::Subtract, binary(a, b)?),
+            Expression::Multiply(a, b) => arithmetic(Arithmetic::Multiply, binary(a, b)?),
+            Expression::Divide(a, b) => arithmetic(Arithmetic::Divide, binary(a, b)?),
+            Expression::In(value, list) | Expression::NotIn(value, list) => CompiledExpression::In {
+                value: Box::new(self.compile_expression(value)?),
+                list: list.iter().map(|member| self.compile_expression(member)).collect::<Result<_, _>>()?,
+                negated: matches!(expression, Expression::NotIn(..)),
+            },
+            Expression::UnaryPlus(a) => CompiledExpression::UnaryPlus(Box::new(self.compile_expression(a)?)),
+            Expression::UnaryMinus(a) => CompiledExpression::UnaryMinus(Box::new(self.compile_expression(a)?)),
+            Expression::Not(a) => CompiledExpression::Not(Box::new(self.compile_expression(a)?)),
+            Expression::Builtin(name, arguments) => match (name.as_str(), arguments.as_slice()) {
+                ("BOUND", [Expression::Variable(variable)]) => CompiledExpression::Bound(self.var(variable)),
+                ("COALESCE", arguments) => CompiledExpression::Coalesce(
+                    arguments.iter().map(|argument| self.compile_expression(argument)).collect::<Result<_, _>>()?,
+                ),
+                ("IF", [condition, then, otherwise]) => CompiledExpression::If(
+                    Box::new(self.compile_expression(condition)?),
+                    Box::new(self.compile_expression(then)?),
+                    Box::new(self.compile_expression(otherwise)?),
+                ),
+                (name, arguments) if SUPPORTED_BUILTINS.contains(&name) => CompiledExpression::Builtin(
+                    name.to_string(),
+                    arguments.iter().map(|argument| self.compile_expression(argument)).collect::<Result<_, _>>()?,
+                ),
+                (name, _) => return Err(Box::new(NotSupportedException::new(name))),
+            },
+            Expression::Function(iri, arguments) => match arguments.as_slice() {
+                [argument] if SUPPORTED_CASTS.contains(&iri.as_str()) => {
+                    CompiledExpression::Cast(iri.clone(), Box::new(self.compile_expression(argument)?))
+                }
+                _ => return Err(Box::new(NotSupportedException::new(iri))),
+            },
+            Expression::Exists(pattern) | Expression::NotExists(pattern) => CompiledExpression::Exists {
+                pattern: RefCell::new(self.compile(pattern)?),
+                negated: matches!(expression, Expression::NotExists(_)),
+            },
+        })
+    }
+}
+
+type Operands<'a> = (Box<CompiledExpression<'a>>, Box<CompiledExpression<'a>>);
+
+fn compare(comparison: Comparison, (a, b): Operands) -> CompiledExpression {
+    CompiledExpression::Compare(comparison, a, b)
+}
+
+fn arithmetic(operator: Arithmetic, (a, b): Operands) -> CompiledExpression {
+    CompiledExpression::Arithmetic(operator, a, b)
+}
+
+// Runs a parsed query over the default graph of the database, or the dataset of the
+// query if it has one. `pages` are the private pages of the worker running the query,
+// if it has some. Solutions and statements are computed as the results are read.
+pub fn execute_query<'a>(
+    query: &Query,
+    indexes: &'a QuadIndexes,
+    terms: &'a QueryTerms<'a>,
+    interruption: Interruption,
+    pages: Option<TemporaryPages<'a>>,
+) -> Result<QueryResults<'a>, ExecutionError> {
+    let mut executor = QueryExecutor::new(indexes, terms, interruption);
+    if let Some(pages) = pages {
+        executor = executor.with_temporary_pages(pages);
+    }
+    let graph_ids = |iris: &[String]| -> Result<Vec<u64>, ExecutionError> {
+        iris.iter().map(|iri| Ok(terms.get_or_create(&RdfTerm::iri(iri))?.raw())).collect()
+    };
+    let default_graphs = graph_ids(&query.dataset.default_graphs)?;
+    if !query.dataset.default_graphs.is_empty() || !query.dataset.named_graphs.is_empty() {
+        executor = executor.with_dataset(default_graphs.clone(), graph_ids(&query.dataset.named_graphs)?);
+    }
+    let mut iter = executor.compile(&query.pattern)?;
+    let variables: Vec<VarId> = query.variables.iter().map(|name| executor.var(name)).collect();
+    let template: Vec<[(Option<VarId>, TermPattern); 3]> = query
+        .template
+        .iter()
+        .map(|triple| {
+            [&triple.subject, &triple.predicate, &triple.object].map(|term| (template_var(&mut executor, term), term.clone()))
+        })
+        .collect();
+    let describe: Vec<(Option<VarId>, &TermPattern)> =
+        query.describe.iter().map(|term| (term.variable().map(|name| executor.var(&name)), term)).collect();
+
+    let mut binding = executor.new_binding();
+    iter.begin(&mut binding)?;
+    match query.form {
+        QueryForm::Ask => Ok(QueryResults::Boolean(iter.next(&mut binding)?)),
+        QueryForm::Select => Ok(QueryResults::Solutions {
+            variables: query.variables.clone(),
+            solutions: SolutionIter { iter, binding, variables, terms, done: false },
+        }),
+        QueryForm::Construct => {
+            Ok(QueryResults::Graph(GraphIter::new(GraphSource::Construct { iter, binding, template, blank_nodes: 0 }, terms)))
+        }
+        QueryForm::Describe => {
+            let mut resources = Vec::new();
+            while iter.next(&mut binding)? {
+                for (var, term) in &describe {
+                    let resource = match (var, term) {
+                        (Some(var), _) => binding.get(*var),
+                        (None, TermPattern::Term(term)) => terms.get_or_create(term)?,
+                        _ => ObjectId::NULL,
+                    };
+                    if !resource.is_null() && !resources.contains(&resource) {
+                        resources.push(resource);
+                    }
+                }
+            }
+            // The triples of the default graph that have the resources as subject: the
+            // triples of the database, or the quads of the graphs of FROM
+            let graphs: Vec<Option<u64>> = match executor.active_graph {
+                None => vec![None],
+                Some(_) => default_graphs.into_iter().map(Some).collect(),
+            };
+            let scans = resources.iter().flat_map(|resource| graphs.iter().map(move |&graph| (resource.raw(), graph))).collect();
+            let source = GraphSource::Describe { indexes, scans, graphs, earlier: Vec::new(), scan: None };
+            Ok(QueryResults::Graph(GraphIter::new(source, terms)))
+        }
+    }
+}
+
+fn template_var(executor: &mut QueryExecutor, term: &TermPattern) -> Option<VarId> {
+    match term {
+        TermPattern::Variable(name) => Some(executor.var(name)),
+        _ => None,
+    }
+}
+
+// Solutions of a SELECT query, each one computed when it is read
+pub struct SolutionIter<'a> {
+    iter: BoxedIter<'a>,
+    binding: Binding,
+    variables: Vec<VarId>,
+    terms: &'a QueryTerms<'a>,
+    done: bool,
+}
+
+impl Iterator for SolutionIter<'_> {
+    type Item = Result<Solution, ExecutionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.iter.next(&mut self.binding) {
+            Ok(true) => Some(self.variables.iter().map(|&var| self.terms.decode(self.binding.get(var))).collect()),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+type TripleScan<'a> = Box<dyn Iterator<Item = Result<[u64; 3], StorageError>> + 'a>;
+
+enum GraphSource<'a> {
+    // CONSTRUCT: the template instantiated with every solution
+    Construct {
+        iter: BoxedIter<'a>,
+        binding: Binding,
+        template: Vec<[(Option<VarId>, TermPattern); 3]>,
+        blank_nodes: usize,
+    },
+    // DESCRIBE: the triples of (resource, graph) with the resource as subject, `None` being
+    // the default graph of the database. A triple of a graph of the merge is skipped when
+    // one of the graphs scanned before it for the resource has it.
+    Describe {
+        indexes: &'a QuadIndexes,
+        scans: VecDeque<(u64, Option<u64>)>,
+        graphs: Vec<Option<u64>>,
+        // Graphs of the merge before the one of the current scan
+        earlier: Vec<Option<u64>>,
+        scan: Option<TripleScan<'a>>,
+    },
+}
+
+// Triples of a CONSTRUCT or DESCRIBE result, computed when they are read. The triples RDF
+// does not allow (a literal as subject...) are skipped, and so are the duplicates within a
+// solution. Duplicates across solutions are kept, as remembering every triple sent would
+// take as much memory as the whole result.
+pub struct GraphIter<'a> {
+    source: GraphSource<'a>,
+    terms: &'a QueryTerms<'a>,
+    // Triples of the last solution not read yet
+    pending: VecDeque<RdfQuad>,
+    done: bool,
+}
+
+impl<'a> GraphIter<'a> {
+    fn new(source: GraphSource<'a>, terms: &'a QueryTerms<'a>) -> Self {
+        Self { source, terms, pending: VecDeque::new(), done: false }
+    }
+
+    fn add(&mut self, subject: RdfTerm, predicate: RdfTerm, object: RdfTerm) {
+        if matches!(subject, RdfTerm::Literal { .. } | RdfTerm::Path(_))
+            || !matches!(predicate, RdfTerm::Iri(_))
+            || matches!(object, RdfTerm::Path(_))
+        {
+            return;
+        }
+        let quad = RdfQuad::triple(subject, predicate, object);
+        if !self.pending.contains(&quad) {
+            self.pending.push_back(quad);
+        }
+    }
+
+    // Computes the triples of the next solution or the next triple of the scans, false at the end
+    fn fill(&mut self) -> Result<bool, ExecutionError> {
+        let terms = self.terms;
+        let triples = match &mut self.source {
+            GraphSource::Construct { iter, binding, template, blank_nodes } => {
+                if !iter.next(binding)? {
+                    return Ok(false);
+                }
+                // Blank nodes of the template are new for every solution
+                let mut labels: HashMap<&str, String> = HashMap::new();
+                let mut triples = Vec::with_capacity(template.len());
+                for triple in template.iter() {
+                    let mut instance = Vec::with_capacity(3);
+                    for (var, term) in triple {
+                        instance.push(match (var, term) {
+                            (Some(var), _) => terms.decode(binding.get(*var))?,
+                            (None, TermPattern::BlankNode(label)) => Some(RdfTerm::BlankNode(
+                                labels
+                                    .entry(label)
+                                    .or_insert_with(|| {
+                                        *blank_nodes += 1;
+                                        format!("b{}", blank_nodes)
+                                    })
+                                    .clone(),
+                            )),
+                            (None, TermPattern::Term(term)) => Some(term.clone()),
+                            (None, TermPattern::Variable(_)) => None,
+                        });
+                    }
+                    if let [Some(subject), Some(predicate), Some(object)] = instance.as_slice() {
+                        triples.push([subject.clone(), predicate.clone(), object.clone()]);
+                    }
+                }
+                triples
+            }
+            GraphSource::Describe { indexes, scans, graphs, earlier, scan } => loop {
+                if let Some(triple) = scan.as_mut().and_then(Iterator::next) {
+                    let triple = triple?;
+                    let [s, p, o] = triple.map(Some);
+                    let mut seen = false;
+                    for &graph in earlier.iter() {
+                        if indexes.scan_quads(&[s, p, o, graph])?.next().is_some() {
+                            seen = true;
+                            break;
+                        }
+                    }
+                    if seen {
+                        continue;
+                    }
+                    let [subject, predicate, object] = triple.map(|value| terms.decode(ObjectId::from_raw(value)));
+                    match (subject?, predicate?, object?) {
+                        (Some(subject), Some(predicate), Some(object)) => break vec![[subject, predicate, object]],
+                        _ => continue,
+                    }
+                }
+                let (resource, graph) = match scans.pop_front() {
+                    Some(next) => next,
+                    None => return Ok(false),
+                };
+                *earlier = match graph {
+                    Some(_) => graphs.iter().take_while(|&&other| other != graph).copied().collect(),
+                    None => Vec::new(),
+                };
+                *scan = Some(match graph {
+                    None => Box::new(indexes.scan_triples(&[Some(resource), None, None])?),
+                    Some(graph) => Box::new(
+                        indexes
+                            .scan_quads(&[Some(resource), None, None, Some(graph)])?
+                            .map(|quad| quad.map(|[subject, predicate, object, _]| [subject, predicate, object])),
+                    ),
+                });
+            },
+        };
+        for [subject, predicate, object] in triples {
+            self.add(subject, predicate, object);
+        }
+        Ok(true)
+    }
+}
+
+impl Iterator for GraphIter<'_> {
+    type Item = Result<RdfQuad, ExecutionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(quad) = self.pending.pop_front() {
+                return Some(Ok(quad));
+            }
+            if self.done {
+                return None;
+            }
+            match self.fill() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::query::exceptions::QueryParsingException;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QueryForm {
+    Select,
+    Ask,
+    Construct,
+    Describe,
+}
+
+impl QueryForm {
+    // Whether the results are a graph (CONSTRUCT / DESCRIBE) rather than solutions
+    pub fn returns_graph(&self) -> bool {
+        matches!(self, QueryForm::Construct | QueryForm::Describe)
+    }
+
+    // Finds the query form by skipping the prologue (BASE / PREFIX declarations and comments)
+    // and reading the first keyword, without parsing the whole query.
+    pub fn detect(query: &str) -> Result<QueryForm, QueryParsingException> {
+        let mut rest = query;
+        loop {
+            rest = skip_whitespace_and_comments(rest);
+            let keyword: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect::<String>()
+                .to_ascii_uppercase();
+
+            match keyword.as_str() {
+                "SELECT" => return Ok(QueryForm::Select),
+                "ASK" => return Ok(QueryForm::Ask),
+                "CONSTRUCT" => return Ok(QueryForm::Construct),
+                "DESCRIBE" => return Ok(QueryForm::Describe),
+                "BASE" | "PREFIX" => {
+                    // BASE <iri>  |  PREFIX pname: <iri>
+                    rest = match rest.find('>') {
+                        Some(end) => &rest[end + 1..],
+                        None => return Err(QueryParsingException::new("Unterminated prologue declaration")),
+                    };
+                }
+                "" => return Err(QueryParsingException::new("Expected a query form")),
+                other => {
+                    return Err(QueryParsingException::new(&format!("Unexpected keyword `{}`", other)));
+                }
+            }
+        }
+    }
+}
+
+fn skip_whitespace_and_comments(mut input: &str) -> &str {
+    loop {
+        input = input.trim_start();
+        if input.starts_with('#') {
+            input = match input.find('\n') {
+                Some(end) => &input[end + 1..],
+                None => "",
+            };
+        } else {
+            return input;
+        }
+    }
+}
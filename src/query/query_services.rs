@@ -1,25 +1,37 @@
+use std::sync::Arc;
 
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
+use crate::query::quad_model_graph::QuadModelGraph;
+
 pub async fn process_query(
-    mut socket: TcpStream) {
+    mut socket: TcpStream, graph: Arc<QuadModelGraph>) {
 
     let mut buffer = [0; 1024];
 
     if let Ok(n) = socket.read(&mut buffer).await {
-         
+
         // Convert buffer to a string and trim to remove extra whitespace
         let query = String::from_utf8_lossy(&buffer[..n]).trim().to_string();
 
-        // Check if the query matches expected format (modify this according to your needs)
-        let response = if query == "EXPECTED_QUERY" {
+        // The query is a node, written as in the import files: `(id)` or just `id`
+        let node = query.strip_prefix('(').and_then(|q| q.strip_suffix(')')).unwrap_or(&query).trim().to_string();
 
-            "Query received and processed.".to_string()
+        let response = if node.is_empty() || node.contains(char::is_whitespace) {
+
+            "Syntax Error: Invalid query format.".to_string()
 
         } else {
 
-            "Syntax Error: Invalid query format.".to_string()
+            // Lookups read the database files, keep them away from the async workers
+            let id = node.clone();
+            match tokio::task::spawn_blocking(move || graph.describe_node(&id)).await {
+                Ok(Ok(Some(description))) => description,
+                Ok(Ok(None)) => format!("Node not found: {}", node),
+                Ok(Err(e)) => format!("Error: {}", e),
+                Err(e) => format!("Error: {}", e),
+            }
 
         };
 
@@ -27,7 +39,7 @@ pub async fn process_query(
         if let Err(e) = socket.write_all(response.as_bytes()).await {
 
             eprintln!("Failed to send response: {}", e);
-            
+
         }
     } else {
 
@@ -35,5 +47,3 @@ pub async fn process_query(
 
     }
 }
-
-
@@ -0,0 +1,173 @@
+pub const XSD_PREFIX: &str = "http://www.w3.org/2001/XMLSchema#";
+pub const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+pub const XSD_INTEGER: &str = "http://www.w3.org/2001/XMLSchema#integer";
+pub const XSD_DECIMAL: &str = "http://www.w3.org/2001/XMLSchema#decimal";
+pub const XSD_FLOAT: &str = "http://www.w3.org/2001/XMLSchema#float";
+pub const XSD_DOUBLE: &str = "http://www.w3.org/2001/XMLSchema#double";
+pub const XSD_BOOLEAN: &str = "http://www.w3.org/2001/XMLSchema#boolean";
+pub const XSD_DATE_TIME: &str = "http://www.w3.org/2001/XMLSchema#dateTime";
+pub const XSD_DATE: &str = "http://www.w3.org/2001/XMLSchema#date";
+pub const XSD_DURATION: &str = "http://www.w3.org/2001/XMLSchema#duration";
+pub const XSD_YEAR_MONTH_DURATION: &str = "http://www.w3.org/2001/XMLSchema#yearMonthDuration";
+pub const XSD_DAY_TIME_DURATION: &str = "http://www.w3.org/2001/XMLSchema#dayTimeDuration";
+pub const RDF_LANG_STRING: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString";
+
+// An RDF term as returned in query results
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RdfTerm {
+    Iri(String),
+    BlankNode(String),
+    Literal {
+        value: String,
+        // `None` for simple literals (xsd:string) and language-tagged strings
+        datatype: Option<String>,
+        language: Option<String>,
+    },
+    // A path bound by a path pattern with a path mode. It is not an RDF term: it only
+    // appears in SELECT results.
+    Path(Box<RdfPath>),
+}
+
+// The nodes and edges of a path, from its first node to the last
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RdfPath {
+    pub start: RdfTerm,
+    pub steps: Vec<RdfPathStep>,
+}
+
+// An edge of a path and the node it leads to. `inverse` is set when the edge was followed
+// from its object to its subject.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RdfPathStep {
+    pub predicate: RdfTerm,
+    pub inverse: bool,
+    pub node: RdfTerm,
+}
+
+impl RdfTerm {
+    pub fn iri(iri: &str) -> Self {
+        RdfTerm::Iri(iri.to_string())
+    }
+
+    pub fn blank_node(id: &str) -> Self {
+        RdfTerm::BlankNode(id.to_string())
+    }
+
+    pub fn simple_literal(value: &str) -> Self {
+        RdfTerm::Literal {
+            value: value.to_string(),
+            datatype: None,
+            language: None,
+        }
+    }
+
+    pub fn lang_literal(value: &str, language: &str) -> Self {
+        RdfTerm::Literal {
+            value: value.to_string(),
+            datatype: None,
+            language: Some(language.to_ascii_lowercase()),
+        }
+    }
+
+    // A literal typed as xsd:string is stored as a simple literal, as RDF 1.1 makes them equal
+    pub fn typed_literal(value: &str, datatype: &str) -> Self {
+        RdfTerm::Literal {
+            value: value.to_string(),
+            datatype: if datatype == XSD_STRING { None } else { Some(datatype.to_string()) },
+            language: None,
+        }
+    }
+}
+
+// A row of a SELECT result, in the same order as the projected variables.
+// `None` marks an unbound variable.
+pub type Solution = Vec<Option<RdfTerm>>;
+
+pub const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+pub const RDF_FIRST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+pub const RDF_REST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+pub const RDF_NIL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+
+// A statement of a graph result. `graph` is `None` for the default graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RdfQuad {
+    pub subject: RdfTerm,
+    pub predicate: RdfTerm,
+    pub object: RdfTerm,
+    pub graph: Option<RdfTerm>,
+}
+
+impl RdfQuad {
+    pub fn triple(subject: RdfTerm, predicate: RdfTerm, object: RdfTerm) -> Self {
+        Self {
+            subject,
+            predicate,
+            object,
+            graph: None,
+        }
+    }
+}
+
+// Formats the term in N-Triples syntax: `<iri>`, `_:id`, `"value"@lang` or `"value"^^<datatype>`.
+// A path is written as its nodes between parentheses joined by its edges, such as
+// `(<a>)-[<p>]->(<b>)<-[<q>]-(<c>)`.
+impl std::fmt::Display for RdfTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RdfTerm::Iri(iri) => write_iri(f, iri),
+            RdfTerm::BlankNode(id) => write!(f, "_:{}", id),
+            RdfTerm::Literal { value, datatype, language } => {
+                write_quoted_string(f, value)?;
+                if let Some(language) = language {
+                    write!(f, "@{}", language)
+                } else if let Some(datatype) = datatype {
+                    f.write_str("^^")?;
+                    write_iri(f, datatype)
+                } else {
+                    Ok(())
+                }
+            }
+            RdfTerm::Path(path) => {
+                write!(f, "({})", path.start)?;
+                for step in &path.steps {
+                    if step.inverse {
+                        write!(f, "<-[{}]-({})", step.predicate, step.node)?;
+                    } else {
+                        write!(f, "-[{}]->({})", step.predicate, step.node)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Writes `<iri>` escaping the characters IRIREF does not allow
+pub fn write_iri(f: &mut impl std::fmt::Write, iri: &str) -> std::fmt::Result {
+    f.write_char('<')?;
+    for c in iri.chars() {
+        match c {
+            '\u{0}'..='\u{20}' | '<' | '>' | '"' | '{' | '}' | '|' | '^' | '`' | '\\' => {
+                write!(f, "\\u{:04X}", c as u32)?
+            }
+            _ => f.write_char(c)?,
+        }
+    }
+    f.write_char('>')
+}
+
+// Writes a double-quoted string with the N-Triples escapes (ECHAR)
+pub fn write_quoted_string(f: &mut impl std::fmt::Write, value: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            _ => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
@@ -0,0 +1,695 @@
+// Regular expressions of REGEX and REPLACE, with the syntax of XPath (XML Schema regular
+// expressions with anchors, reluctant quantifiers and non-capturing groups) and its flags
+// s, m, i, x and q. Back-references are not supported.
+//
+// The pattern is compiled to a program of a backtracking machine that remembers the
+// states (instruction, position) it already tried, so that a search takes at most
+// O(program * text) steps whatever the pattern.
+
+// Patterns that compile to more instructions are refused, as counted repetitions copy
+// their atom
+const MAX_PROGRAM_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Instruction>,
+    groups: usize,
+    ignore_case: bool,
+    dot_all: bool,
+    multiline: bool,
+}
+
+// Positions in the text (in chars) of the whole match and of each group, `None` for the
+// groups that did not participate
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Char(char),
+    Any,
+    Class(CharClass),
+    LineStart,
+    LineEnd,
+    // Tries the first target, then the second
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(CharClass),
+    LineStart,
+    LineEnd,
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: Option<u32>, greedy: bool },
+}
+
+#[derive(Debug, Clone)]
+struct CharClass {
+    negated: bool,
+    items: Vec<ClassItem>,
+    // [a-z-[aeiou]]: the chars of the class that are not in this one
+    subtraction: Option<Box<CharClass>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ClassItem {
+    Range(char, char),
+    Escape(ClassEscape),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ClassEscape {
+    Digit(bool),
+    Space(bool),
+    Word(bool),
+    NameStart(bool),
+    Name(bool),
+    Category(Category, bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Letter,
+    Uppercase,
+    Lowercase,
+    Number,
+    DecimalDigit,
+    Punctuation,
+    Separator,
+    Symbol,
+    Other,
+}
+
+impl Regex {
+    // `None` when the pattern or the flags are invalid
+    pub fn new(pattern: &str, flags: &str) -> Option<Self> {
+        let mut ignore_case = false;
+        let mut dot_all = false;
+        let mut multiline = false;
+        let mut extended = false;
+        let mut literal = false;
+        for flag in flags.chars() {
+            match flag {
+                'i' => ignore_case = true,
+                's' => dot_all = true,
+                'm' => multiline = true,
+                'x' => extended = true,
+                'q' => literal = true,
+                _ => return None,
+            }
+        }
+
+        let (node, groups) = if literal {
+            (Node::Concat(pattern.chars().map(Node::Char).collect()), 0)
+        } else {
+            let mut parser = Parser { chars: pattern.chars().collect(), position: 0, extended, groups: 0 };
+            let node = parser.parse_branches()?;
+            if parser.position < parser.chars.len() {
+                return None;
+            }
+            (node, parser.groups)
+        };
+
+        let mut compiler = Compiler { program: vec![Instruction::Save(0)] };
+        compiler.compile(&node)?;
+        compiler.emit(Instruction::Save(1))?;
+        compiler.emit(Instruction::Match)?;
+        Some(Self { program: compiler.program, groups, ignore_case, dot_all, multiline })
+    }
+
+    // Whether the pattern matches somewhere in the text
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        self.find_at(&chars, 0).is_some()
+    }
+
+    // The first match starting at `start` or after. The first capture is the whole match.
+    pub fn find_at(&self, text: &[char], start: usize) -> Option<Captures> {
+        let mut visited = vec![false; self.program.len() * (text.len() + 1)];
+        (start..=text.len()).find_map(|position| self.run(text, position, &mut visited))
+    }
+
+    // fn:replace: every match replaced by the replacement, where `$n` is the text of the
+    // group n and `\$` and `\\` are the characters. `None` when the pattern matches the
+    // empty string or the replacement is invalid.
+    pub fn replace(&self, text: &str, replacement: &str) -> Option<String> {
+        if self.find_at(&[], 0).is_some() {
+            return None;
+        }
+        let replacement = self.parse_replacement(replacement)?;
+        let chars: Vec<char> = text.chars().collect();
+        let mut result = String::new();
+        let mut position = 0;
+        while let Some(captures) = self.find_at(&chars, position) {
+            let (start, end) = captures[0].unwrap();
+            result.extend(&chars[position..start]);
+            for part in &replacement {
+                match part {
+                    ReplacementPart::Text(text) => result.push_str(text),
+                    ReplacementPart::Group(group) => {
+                        if let Some((start, end)) = captures[*group] {
+                            result.extend(&chars[start..end]);
+                        }
+                    }
+                }
+            }
+            position = end;
+        }
+        result.extend(&chars[position..]);
+        Some(result)
+    }
+
+    // `$` followed by the longest number that is a group of the pattern
+    fn parse_replacement(&self, replacement: &str) -> Option<Vec<ReplacementPart>> {
+        let chars: Vec<char> = replacement.chars().collect();
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => {
+                    match chars.get(i + 1) {
+                        Some(c @ ('\\' | '$')) => text.push(*c),
+                        _ => return None,
+                    }
+                    i += 2;
+                }
+                '$' => {
+                    let mut group = chars.get(i + 1)?.to_digit(10)? as usize;
+                    i += 2;
+                    while let Some(digit) = chars.get(i).and_then(|c| c.to_digit(10)) {
+                        if group * 10 + digit as usize > self.groups {
+                            break;
+                        }
+                        group = group * 10 + digit as usize;
+                        i += 1;
+                    }
+                    parts.push(ReplacementPart::Text(std::mem::take(&mut text)));
+                    // $0 is the whole match, and groups the pattern does not have are empty
+                    if group <= self.groups {
+                        parts.push(ReplacementPart::Group(group));
+                    }
+                }
+                c => {
+                    text.push(c);
+                    i += 1;
+                }
+            }
+        }
+        parts.push(ReplacementPart::Text(text));
+        Some(parts)
+    }
+
+    fn run(&self, text: &[char], start: usize, visited: &mut [bool]) -> Option<Captures> {
+        let mut slots: Vec<Option<usize>> = vec![None; 2 * (self.groups + 1)];
+        // The states to try, and the values of the slots to restore when backtracking
+        let mut stack = vec![Backtrack::State(0, start)];
+        while let Some(entry) = stack.pop() {
+            let (mut pc, mut position) = match entry {
+                Backtrack::State(pc, position) => (pc, position),
+                Backtrack::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+            };
+            loop {
+                let state = pc * (text.len() + 1) + position;
+                if visited[state] {
+                    break;
+                }
+                visited[state] = true;
+                match &self.program[pc] {
+                    Instruction::Char(expected) => match text.get(position) {
+                        Some(&c) if c == *expected || (self.ignore_case && same_ignoring_case(c, *expected)) => {
+                            pc += 1;
+                            position += 1;
+                        }
+                        _ => break,
+                    },
+                    Instruction::Any => match text.get(position) {
+                        Some(&c) if self.dot_all || (c != '\n' && c != '\r') => {
+                            pc += 1;
+                            position += 1;
+                        }
+                        _ => break,
+                    },
+                    Instruction::Class(class) => match text.get(position) {
+                        Some(&c) if class.matches(c, self.ignore_case) => {
+                            pc += 1;
+                            position += 1;
+                        }
+                        _ => break,
+                    },
+                    Instruction::LineStart => {
+                        if position == 0 || (self.multiline && text[position - 1] == '\n') {
+                            pc += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Instruction::LineEnd => {
+                        if position == text.len() || (self.multiline && text[position] == '\n') {
+                            pc += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Instruction::Split(first, second) => {
+                        stack.push(Backtrack::State(*second, position));
+                        pc = *first;
+                    }
+                    Instruction::Jump(target) => pc = *target,
+                    Instruction::Save(slot) => {
+                        stack.push(Backtrack::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(position);
+                        pc += 1;
+                    }
+                    Instruction::Match => {
+                        return Some(
+                            slots
+                                .chunks(2)
+                                .map(|slot| match (slot[0], slot[1]) {
+                                    (Some(start), Some(end)) => Some((start, end)),
+                                    _ => None,
+                                })
+                                .collect(),
+                        );
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+enum Backtrack {
+    State(usize, usize),
+    Restore(usize, Option<usize>),
+}
+
+enum ReplacementPart {
+    Text(String),
+    Group(usize),
+}
+
+fn same_ignoring_case(a: char, b: char) -> bool {
+    a.to_lowercase().eq(b.to_lowercase()) || a.to_uppercase().eq(b.to_uppercase())
+}
+
+impl CharClass {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        self.contains(c) || (ignore_case && c.to_lowercase().chain(c.to_uppercase()).any(|variant| self.contains(variant)))
+    }
+
+    fn contains(&self, c: char) -> bool {
+        let included = self.items.iter().any(|item| item.contains(c)) != self.negated;
+        included && !self.subtraction.as_ref().is_some_and(|subtraction| subtraction.contains(c))
+    }
+}
+
+impl ClassItem {
+    fn contains(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(first, last) => first <= c && c <= last,
+            ClassItem::Escape(escape) => escape.contains(c),
+        }
+    }
+}
+
+impl ClassEscape {
+    fn contains(&self, c: char) -> bool {
+        match *self {
+            ClassEscape::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassEscape::Space(negated) => matches!(c, ' ' | '\t' | '\n' | '\r') != negated,
+            // All the chars but punctuation, separators and other chars
+            ClassEscape::Word(negated) => {
+                let word = !Category::Punctuation.contains(c) && !Category::Separator.contains(c) && !Category::Other.contains(c);
+                word != negated
+            }
+            ClassEscape::NameStart(negated) => (c.is_alphabetic() || c == '_' || c == ':') != negated,
+            ClassEscape::Name(negated) => {
+                (c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.' | '\u{B7}')) != negated
+            }
+            ClassEscape::Category(category, negated) => category.contains(c) != negated,
+        }
+    }
+}
+
+impl Category {
+    // The categories of Unicode the standard library can tell. Punctuation and symbols
+    // are only told apart for ASCII.
+    fn contains(self, c: char) -> bool {
+        match self {
+            Category::Letter => c.is_alphabetic(),
+            Category::Uppercase => c.is_uppercase(),
+            Category::Lowercase => c.is_lowercase(),
+            Category::Number => c.is_numeric(),
+            Category::DecimalDigit => c.is_ascii_digit(),
+            Category::Punctuation => c.is_ascii_punctuation() && !Category::Symbol.contains(c),
+            Category::Separator => c.is_whitespace() && !c.is_control(),
+            Category::Symbol => matches!(c, '$' | '+' | '<' | '=' | '>' | '^' | '`' | '|' | '~'),
+            Category::Other => c.is_control(),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "L" => Category::Letter,
+            "Lu" => Category::Uppercase,
+            "Ll" => Category::Lowercase,
+            "N" => Category::Number,
+            "Nd" => Category::DecimalDigit,
+            "P" => Category::Punctuation,
+            "Z" | "Zs" => Category::Separator,
+            "S" => Category::Symbol,
+            "C" | "Cc" => Category::Other,
+            _ => return None,
+        })
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    // Flag x: whitespace outside classes is ignored
+    extended: bool,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<char> {
+        if self.extended {
+            while matches!(self.chars.get(self.position), Some(' ' | '\t' | '\n' | '\r')) {
+                self.position += 1;
+            }
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    // regExp: branch ('|' branch)*
+    fn parse_branches(&mut self) -> Option<Node> {
+        let mut branches = vec![self.parse_branch()?];
+        while self.eat('|') {
+            branches.push(self.parse_branch()?);
+        }
+        Some(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alternation(branches) })
+    }
+
+    // branch: piece*
+    fn parse_branch(&mut self) -> Option<Node> {
+        let mut pieces = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            pieces.push(self.parse_piece()?);
+        }
+        Some(match pieces.len() {
+            0 => Node::Empty,
+            1 => pieces.pop().unwrap(),
+            _ => Node::Concat(pieces),
+        })
+    }
+
+    // piece: atom quantifier? with '?' after the quantifier making it reluctant
+    fn parse_piece(&mut self) -> Option<Node> {
+        let atom = self.parse_atom()?;
+        let (min, max) = match self.peek() {
+            Some('?') => (0, Some(1)),
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('{') => {
+                self.position += 1;
+                let min = self.parse_number()?;
+                let max = if self.eat(',') {
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.parse_number()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if self.peek() != Some('}') || max.is_some_and(|max| max < min) {
+                    return None;
+                }
+                (min, max)
+            }
+            _ => return Some(atom),
+        };
+        self.position += 1;
+        let greedy = !self.eat('?');
+        if matches!(atom, Node::LineStart | Node::LineEnd) {
+            return None;
+        }
+        Some(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect::<String>().parse().ok()
+    }
+
+    // atom: NormalChar | charClass | '(' regExp ')'
+    fn parse_atom(&mut self) -> Option<Node> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(match c {
+            '.' => Node::Any,
+            '^' => Node::LineStart,
+            '$' => Node::LineEnd,
+            '(' => {
+                let group = if self.chars.get(self.position..self.position + 2) == Some(&['?', ':']) {
+                    self.position += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let node = self.parse_branches()?;
+                if !self.eat(')') {
+                    return None;
+                }
+                Node::Group(Box::new(node), group)
+            }
+            '[' => Node::Class(self.parse_class()?),
+            '\\' => match self.parse_escape()? {
+                ClassItem::Range(c, _) => Node::Char(c),
+                ClassItem::Escape(escape) => Node::Class(CharClass {
+                    negated: false,
+                    items: vec![ClassItem::Escape(escape)],
+                    subtraction: None,
+                }),
+            },
+            '?' | '*' | '+' | '{' | '}' | ')' | ']' => return None,
+            c => Node::Char(c),
+        })
+    }
+
+    // After '[': ('^')? (range | escape)+ ('-' '[' class ']')? ']'
+    fn parse_class(&mut self) -> Option<CharClass> {
+        let negated = self.chars.get(self.position) == Some(&'^');
+        if negated {
+            self.position += 1;
+        }
+        let mut items = Vec::new();
+        let mut subtraction = None;
+        loop {
+            let c = self.next_char()?;
+            match c {
+                ']' if !items.is_empty() => break,
+                '-' if self.chars.get(self.position) == Some(&'[') && !items.is_empty() => {
+                    self.position += 1;
+                    subtraction = Some(Box::new(self.parse_class()?));
+                    if self.next_char()? != ']' {
+                        return None;
+                    }
+                    break;
+                }
+                '[' => return None,
+                _ => {
+                    let first = if c == '\\' { self.parse_escape()? } else { ClassItem::Range(c, c) };
+                    let is_range = self.chars.get(self.position) == Some(&'-')
+                        && !matches!(self.chars.get(self.position + 1), Some(']' | '['));
+                    match (first, is_range) {
+                        (ClassItem::Range(first, _), true) => {
+                            self.position += 1;
+                            let last = match self.next_char()? {
+                                '\\' => match self.parse_escape()? {
+                                    ClassItem::Range(last, _) => last,
+                                    ClassItem::Escape(_) => return None,
+                                },
+                                last => last,
+                            };
+                            if last < first {
+                                return None;
+                            }
+                            items.push(ClassItem::Range(first, last));
+                        }
+                        (item, _) => items.push(item),
+                    }
+                }
+            }
+        }
+        Some(CharClass { negated, items, subtraction })
+    }
+
+    // Chars in classes are not affected by the flag x
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.get(self.position).copied()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    // After '\': a single char escape as a range of one char, or a class escape
+    fn parse_escape(&mut self) -> Option<ClassItem> {
+        let c = self.next_char()?;
+        let escape = match c {
+            'n' => return Some(ClassItem::Range('\n', '\n')),
+            'r' => return Some(ClassItem::Range('\r', '\r')),
+            't' => return Some(ClassItem::Range('\t', '\t')),
+            '\\' | '|' | '.' | '-' | '^' | '?' | '*' | '+' | '{' | '}' | '(' | ')' | '[' | ']' | '$' => {
+                return Some(ClassItem::Range(c, c))
+            }
+            'd' | 'D' => ClassEscape::Digit(c == 'D'),
+            's' | 'S' => ClassEscape::Space(c == 'S'),
+            'w' | 'W' => ClassEscape::Word(c == 'W'),
+            'i' | 'I' => ClassEscape::NameStart(c == 'I'),
+            'c' | 'C' => ClassEscape::Name(c == 'C'),
+            'p' | 'P' => {
+                if self.next_char()? != '{' {
+                    return None;
+                }
+                let start = self.position;
+                while self.chars.get(self.position).is_some_and(|c| *c != '}') {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+                self.next_char()?;
+                ClassEscape::Category(Category::from_name(&name)?, c == 'P')
+            }
+            _ => return None,
+        };
+        Some(ClassItem::Escape(escape))
+    }
+}
+
+struct Compiler {
+    program: Vec<Instruction>,
+}
+
+impl Compiler {
+    fn emit(&mut self, instruction: Instruction) -> Option<usize> {
+        if self.program.len() >= MAX_PROGRAM_SIZE {
+            return None;
+        }
+        self.program.push(instruction);
+        Some(self.program.len() - 1)
+    }
+
+    fn compile(&mut self, node: &Node) -> Option<()> {
+        match node {
+            Node::Empty => {}
+            Node::Char(c) => {
+                self.emit(Instruction::Char(*c))?;
+            }
+            Node::Any => {
+                self.emit(Instruction::Any)?;
+            }
+            Node::Class(class) => {
+                self.emit(Instruction::Class(class.clone()))?;
+            }
+            Node::LineStart => {
+                self.emit(Instruction::LineStart)?;
+            }
+            Node::LineEnd => {
+                self.emit(Instruction::LineEnd)?;
+            }
+            Node::Group(node, group) => match group {
+                Some(group) => {
+                    self.emit(Instruction::Save(2 * group))?;
+                    self.compile(node)?;
+                    self.emit(Instruction::Save(2 * group + 1))?;
+                }
+                None => self.compile(node)?,
+            },
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.compile(node)?;
+                }
+            }
+            Node::Alternation(branches) => {
+                // split L1, next; L1: branch; jump end; next: split ...
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 < branches.len() {
+                        let split = self.emit(Instruction::Split(0, 0))?;
+                        self.compile(branch)?;
+                        jumps.push(self.emit(Instruction::Jump(0))?);
+                        self.program[split] = Instruction::Split(split + 1, self.program.len());
+                    } else {
+                        self.compile(branch)?;
+                    }
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.program[jump] = Instruction::Jump(end);
+                }
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    // split body, end; body; jump split
+                    None => {
+                        let split = self.emit(Instruction::Split(0, 0))?;
+                        self.compile(node)?;
+                        self.emit(Instruction::Jump(split))?;
+                        self.program[split] = self.split(split + 1, self.program.len(), *greedy);
+                    }
+                    // Each optional copy skips all the rest: split body, end; body; ...
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Instruction::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.program[split] = self.split(split + 1, end, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn split(&self, body: usize, skip: usize, greedy: bool) -> Instruction {
+        if greedy {
+            Instruction::Split(body, skip)
+        } else {
+            Instruction::Split(skip, body)
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::{self, Write};
+
+use crate::query::rdf_terms::RdfTerm;
+use crate::query::result_writers::SolutionWriter;
+
+// Writer for the SPARQL 1.1 Query Results CSV Format (text/csv).
+// Values are written without type information and quoted following RFC 4180.
+#[derive(Default)]
+pub struct CsvWriter {
+    columns: usize,
+}
+
+impl CsvWriter {
+    pub fn new() -> Self {
+        Self { columns: 0 }
+    }
+}
+
+impl SolutionWriter for CsvWriter {
+    fn write_head(&mut self, out: &mut dyn Write, variables: &[String]) -> io::Result<()> {
+        self.columns = variables.len();
+        for (i, variable) in variables.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            write_csv_field(out, variable)?;
+        }
+        out.write_all(b"\r\n")
+    }
+
+    fn write_solution(&mut self, out: &mut dyn Write, solution: &[Option<RdfTerm>]) -> io::Result<()> {
+        for (i, term) in solution.iter().take(self.columns).enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            match term {
+                Some(RdfTerm::Iri(iri)) => write_csv_field(out, iri)?,
+                Some(RdfTerm::BlankNode(id)) => write_csv_field(out, &format!("_:{}", id))?,
+                Some(RdfTerm::Literal { value, .. }) => write_csv_field(out, value)?,
+                Some(path @ RdfTerm::Path(_)) => write_csv_field(out, &path.to_string())?,
+                None => {}
+            }
+        }
+        out.write_all(b"\r\n")
+    }
+
+    fn finish(&mut self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_boolean(&mut self, out: &mut dyn Write, value: bool) -> io::Result<()> {
+        write!(out, "_askResult\r\n{}\r\n", value)
+    }
+}
+
+fn write_csv_field(out: &mut dyn Write, value: &str) -> io::Result<()> {
+    if value.contains(['"', ',', '\r', '\n']) {
+        out.write_all(b"\"")?;
+        out.write_all(value.replace('"', "\"\"").as_bytes())?;
+        out.write_all(b"\"")
+    } else {
+        out.write_all(value.as_bytes())
+    }
+}
+
+// Writer for the SPARQL 1.1 Query Results TSV Format (text/tab-separated-values).
+// Terms are written in their N-Triples syntax, so no type information is lost.
+#[derive(Default)]
+pub struct TsvWriter {
+    columns: usize,
+}
+
+impl TsvWriter {
+    pub fn new() -> Self {
+        Self { columns: 0 }
+    }
+}
+
+impl SolutionWriter for TsvWriter {
+    fn write_head(&mut self, out: &mut dyn Write, variables: &[String]) -> io::Result<()> {
+        self.columns = variables.len();
+        let header: Vec<String> = variables.iter().map(|variable| format!("?{}", variable)).collect();
+        out.write_all(header.join("\t").as_bytes())?;
+        out.write_all(b"\n")
+    }
+
+    fn write_solution(&mut self, out: &mut dyn Write, solution: &[Option<RdfTerm>]) -> io::Result<()> {
+        for (i, term) in solution.iter().take(self.columns).enumerate() {
+            if i > 0 {
+                out.write_all(b"\t")?;
+            }
+            if let Some(term) = term {
+                write!(out, "{}", term)?;
+            }
+        }
+        out.write_all(b"\n")
+    }
+
+    fn finish(&mut self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_boolean(&mut self, out: &mut dyn Write, value: bool) -> io::Result<()> {
+        write!(out, "?_askResult\n{}\n", value)
+    }
+}
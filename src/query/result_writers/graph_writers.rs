@@ -0,0 +1,206 @@
+use std::io::{self, Write};
+
+use crate::query::rdf_terms::{write_iri, write_quoted_string, RdfQuad, RdfTerm, RDF_TYPE, XSD_PREFIX};
+use crate::query::result_writers::GraphWriter;
+
+// Writer for Turtle. IRIs are compacted with the prefixes of the query and consecutive
+// statements sharing subject (and predicate) are grouped with `;` (and `,`).
+// Only the default graph can be expressed, the graph of each statement is ignored.
+pub struct TurtleWriter {
+    prefixes: Vec<(String, String)>,
+    // Indexes of `prefixes`, longest namespace first so the most specific prefix is used
+    lookup_order: Vec<usize>,
+    last_subject: Option<RdfTerm>,
+    last_predicate: Option<RdfTerm>,
+}
+
+impl TurtleWriter {
+    pub fn new(prefixes: &[(String, String)]) -> Self {
+        let mut lookup_order: Vec<usize> = (0..prefixes.len()).collect();
+        lookup_order.sort_by_key(|&i| std::cmp::Reverse(prefixes[i].1.len()));
+        Self {
+            prefixes: prefixes.to_vec(),
+            lookup_order,
+            last_subject: None,
+            last_predicate: None,
+        }
+    }
+
+    fn format_iri(&self, iri: &str) -> String {
+        for &i in &self.lookup_order {
+            let (prefix, namespace) = &self.prefixes[i];
+            if let Some(local) = iri.strip_prefix(namespace.as_str()) {
+                if is_safe_local_name(local) {
+                    return format!("{}:{}", prefix, local);
+                }
+            }
+        }
+        let mut formatted = String::with_capacity(iri.len() + 2);
+        let _ = write_iri(&mut formatted, iri);
+        formatted
+    }
+
+    fn format_term(&self, term: &RdfTerm) -> String {
+        match term {
+            RdfTerm::Iri(iri) => self.format_iri(iri),
+            RdfTerm::BlankNode(id) => format!("_:{}", id),
+            RdfTerm::Literal { value, datatype, language } => {
+                if let Some(shorthand) = numeric_or_boolean_shorthand(value, datatype.as_deref()) {
+                    return shorthand.to_string();
+                }
+                let mut formatted = String::with_capacity(value.len() + 2);
+                let _ = write_quoted_string(&mut formatted, value);
+                if let Some(language) = language {
+                    formatted.push('@');
+                    formatted.push_str(language);
+                } else if let Some(datatype) = datatype {
+                    formatted.push_str("^^");
+                    formatted.push_str(&self.format_iri(datatype));
+                }
+                formatted
+            }
+            // Graph results have no paths, CONSTRUCT drops the triples that would have one
+            RdfTerm::Path(_) => term.to_string(),
+        }
+    }
+
+    fn format_predicate(&self, predicate: &RdfTerm) -> String {
+        match predicate {
+            RdfTerm::Iri(iri) if iri == RDF_TYPE => "a".to_string(),
+            _ => self.format_term(predicate),
+        }
+    }
+}
+
+impl GraphWriter for TurtleWriter {
+    fn write_head(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        for (prefix, namespace) in &self.prefixes {
+            let mut iri = String::new();
+            let _ = write_iri(&mut iri, namespace);
+            writeln!(out, "@prefix {}: {} .", prefix, iri)?;
+        }
+        if !self.prefixes.is_empty() {
+            out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn write_quad(&mut self, out: &mut dyn Write, quad: &RdfQuad) -> io::Result<()> {
+        let object = self.format_term(&quad.object);
+
+        if self.last_subject.as_ref() == Some(&quad.subject) {
+            if self.last_predicate.as_ref() == Some(&quad.predicate) {
+                return write!(out, " ,\n        {}", object);
+            }
+            self.last_predicate = Some(quad.predicate.clone());
+            return write!(out, " ;\n    {} {}", self.format_predicate(&quad.predicate), object);
+        }
+
+        if self.last_subject.is_some() {
+            out.write_all(b" .\n")?;
+        }
+        write!(
+            out,
+            "{} {} {}",
+            self.format_term(&quad.subject),
+            self.format_predicate(&quad.predicate),
+            object
+        )?;
+        self.last_subject = Some(quad.subject.clone());
+        self.last_predicate = Some(quad.predicate.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.last_subject.take().is_some() {
+            out.write_all(b" .\n")?;
+        }
+        self.last_predicate = None;
+        Ok(())
+    }
+}
+
+// Writer for N-Triples and N-Quads, one statement per line in the N-Triples term syntax.
+// When `with_graphs` is false the graph of each statement is dropped.
+pub struct NTriplesWriter {
+    with_graphs: bool,
+}
+
+impl NTriplesWriter {
+    pub fn ntriples() -> Self {
+        Self { with_graphs: false }
+    }
+
+    pub fn nquads() -> Self {
+        Self { with_graphs: true }
+    }
+}
+
+impl GraphWriter for NTriplesWriter {
+    fn write_head(&mut self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_quad(&mut self, out: &mut dyn Write, quad: &RdfQuad) -> io::Result<()> {
+        write!(out, "{} {} {}", quad.subject, quad.predicate, quad.object)?;
+        if self.with_graphs {
+            if let Some(graph) = &quad.graph {
+                write!(out, " {}", graph)?;
+            }
+        }
+        out.write_all(b" .\n")
+    }
+
+    fn finish(&mut self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Conservative subset of PN_LOCAL that never needs escaping
+fn is_safe_local_name(local: &str) -> bool {
+    let mut chars = local.chars();
+    match chars.next() {
+        None => true,
+        Some(first) if first.is_ascii_alphanumeric() || first == '_' => {
+            local.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+                && !local.ends_with('.')
+        }
+        _ => false,
+    }
+}
+
+// Turtle allows integers, decimals, doubles and booleans to be written without quotes,
+// when the lexical form matches the corresponding grammar rule.
+fn numeric_or_boolean_shorthand<'a>(value: &'a str, datatype: Option<&str>) -> Option<&'a str> {
+    let local = datatype?.strip_prefix(XSD_PREFIX)?;
+    let unsigned = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    let matches = match local {
+        "integer" => all_digits(unsigned),
+        "decimal" => match unsigned.split_once('.') {
+            Some((integer, fraction)) => (integer.is_empty() || all_digits(integer)) && all_digits(fraction),
+            None => false,
+        },
+        "double" => {
+            let (mantissa, exponent) = unsigned.split_once(['e', 'E'])?;
+            let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            let mantissa_ok = match mantissa.split_once('.') {
+                Some((integer, fraction)) => {
+                    (integer.is_empty() || all_digits(integer))
+                        && (fraction.is_empty() || all_digits(fraction))
+                        && !(integer.is_empty() && fraction.is_empty())
+                }
+                None => all_digits(mantissa),
+            };
+            mantissa_ok && all_digits(exponent)
+        }
+        "boolean" => value == "true" || value == "false",
+        _ => false,
+    };
+    if matches {
+        Some(value)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::{self, Write};
+
+use crate::query::rdf_terms::RdfTerm;
+use crate::query::result_writers::SolutionWriter;
+
+// Writer for the SPARQL 1.1 Query Results JSON Format (application/sparql-results+json)
+pub struct JsonWriter {
+    variables: Vec<String>,
+    first_solution: bool,
+}
+
+impl Default for JsonWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonWriter {
+    pub fn new() -> Self {
+        Self {
+            variables: Vec::new(),
+            first_solution: true,
+        }
+    }
+}
+
+impl SolutionWriter for JsonWriter {
+    fn write_head(&mut self, out: &mut dyn Write, variables: &[String]) -> io::Result<()> {
+        self.variables = variables.to_vec();
+        self.first_solution = true;
+
+        out.write_all(b"{\"head\":{\"vars\":[")?;
+        for (i, variable) in variables.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            write_json_string(out, variable)?;
+        }
+        out.write_all(b"]},\"results\":{\"bindings\":[")
+    }
+
+    fn write_solution(&mut self, out: &mut dyn Write, solution: &[Option<RdfTerm>]) -> io::Result<()> {
+        if !self.first_solution {
+            out.write_all(b",")?;
+        }
+        self.first_solution = false;
+
+        out.write_all(b"\n{")?;
+        let mut first_binding = true;
+        for (variable, term) in self.variables.iter().zip(solution) {
+            let term = match term {
+                Some(term) => term,
+                None => continue,
+            };
+            if !first_binding {
+                out.write_all(b",")?;
+            }
+            first_binding = false;
+            write_json_string(out, variable)?;
+            out.write_all(b":")?;
+            write_json_term(out, term)?;
+        }
+        out.write_all(b"}")
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(b"\n]}}\n")
+    }
+
+    fn write_boolean(&mut self, out: &mut dyn Write, value: bool) -> io::Result<()> {
+        writeln!(out, "{{\"head\":{{}},\"boolean\":{}}}", value)
+    }
+}
+
+fn write_json_term(out: &mut dyn Write, term: &RdfTerm) -> io::Result<()> {
+    match term {
+        RdfTerm::Iri(iri) => {
+            out.write_all(b"{\"type\":\"uri\",\"value\":")?;
+            write_json_string(out, iri)?;
+        }
+        RdfTerm::BlankNode(id) => {
+            out.write_all(b"{\"type\":\"bnode\",\"value\":")?;
+            write_json_string(out, id)?;
+        }
+        RdfTerm::Literal { value, datatype, language } => {
+            out.write_all(b"{\"type\":\"literal\",\"value\":")?;
+            write_json_string(out, value)?;
+            if let Some(language) = language {
+                out.write_all(b",\"xml:lang\":")?;
+                write_json_string(out, language)?;
+            } else if let Some(datatype) = datatype {
+                out.write_all(b",\"datatype\":")?;
+                write_json_string(out, datatype)?;
+            }
+        }
+        // Not part of the standard format: {"type":"path","start":term,"steps":[{"predicate":term,
+        // "inverse":false,"node":term},...]}
+        RdfTerm::Path(path) => {
+            out.write_all(b"{\"type\":\"path\",\"start\":")?;
+            write_json_term(out, &path.start)?;
+            out.write_all(b",\"steps\":[")?;
+            for (i, step) in path.steps.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",")?;
+                }
+                out.write_all(b"{\"predicate\":")?;
+                write_json_term(out, &step.predicate)?;
+                write!(out, ",\"inverse\":{},\"node\":", step.inverse)?;
+                write_json_term(out, &step.node)?;
+                out.write_all(b"}")?;
+            }
+            out.write_all(b"]")?;
+        }
+    }
+    out.write_all(b"}")
+}
+
+pub fn write_json_string(out: &mut dyn Write, value: &str) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    let bytes = value.as_bytes();
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        let escape: Option<&[u8]> = match byte {
+            b'"' => Some(b"\\\""),
+            b'\\' => Some(b"\\\\"),
+            b'\n' => Some(b"\\n"),
+            b'\r' => Some(b"\\r"),
+            b'\t' => Some(b"\\t"),
+            0x08 => Some(b"\\b"),
+            0x0C => Some(b"\\f"),
+            0x00..=0x1F => None,
+            _ => continue,
+        };
+        out.write_all(&bytes[start..i])?;
+        match escape {
+            Some(escape) => out.write_all(escape)?,
+            None => write!(out, "\\u{:04x}", byte)?,
+        }
+        start = i + 1;
+    }
+    out.write_all(&bytes[start..])?;
+    out.write_all(b"\"")
+}
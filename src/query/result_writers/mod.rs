@@ -0,0 +1,58 @@
+pub mod csv_tsv_writers;
+pub mod graph_writers;
+pub mod json_writer;
+pub mod xml_writer;
+
+use std::io::{self, Write};
+
+use crate::network::response_type::ResponseType;
+use crate::query::rdf_terms::{RdfQuad, RdfTerm};
+
+// Serializes the results of SELECT and ASK queries. Writers keep no rows in memory:
+// every call writes its part of the document to `out` so results can be streamed.
+pub trait SolutionWriter {
+    // Called once before the first solution of a SELECT query
+    fn write_head(&mut self, out: &mut dyn Write, variables: &[String]) -> io::Result<()>;
+
+    fn write_solution(&mut self, out: &mut dyn Write, solution: &[Option<RdfTerm>]) -> io::Result<()>;
+
+    // Called once after the last solution of a SELECT query
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()>;
+
+    // Writes the complete document of an ASK query
+    fn write_boolean(&mut self, out: &mut dyn Write, value: bool) -> io::Result<()>;
+}
+
+// Returns the writer for a solutions format, `None` if the format is for graphs
+pub fn solution_writer(response_type: ResponseType) -> Option<Box<dyn SolutionWriter + Send>> {
+    match response_type {
+        ResponseType::JSON => Some(Box::new(json_writer::JsonWriter::new())),
+        ResponseType::XML => Some(Box::new(xml_writer::XmlWriter::new())),
+        ResponseType::CSV => Some(Box::new(csv_tsv_writers::CsvWriter::new())),
+        ResponseType::TSV => Some(Box::new(csv_tsv_writers::TsvWriter::new())),
+        _ => None,
+    }
+}
+
+// Serializes the results of CONSTRUCT and DESCRIBE queries, statement by statement
+pub trait GraphWriter {
+    fn write_head(&mut self, out: &mut dyn Write) -> io::Result<()>;
+
+    fn write_quad(&mut self, out: &mut dyn Write, quad: &RdfQuad) -> io::Result<()>;
+
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()>;
+}
+
+// Returns the writer for a graph format, `None` if the format is for solutions.
+// `prefixes` are the (prefix, namespace) pairs declared in the query.
+pub fn graph_writer(
+    response_type: ResponseType,
+    prefixes: &[(String, String)],
+) -> Option<Box<dyn GraphWriter + Send>> {
+    match response_type {
+        ResponseType::TURTLE => Some(Box::new(graph_writers::TurtleWriter::new(prefixes))),
+        ResponseType::NTRIPLES => Some(Box::new(graph_writers::NTriplesWriter::ntriples())),
+        ResponseType::NQUADS => Some(Box::new(graph_writers::NTriplesWriter::nquads())),
+        _ => None,
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::{self, Write};
+
+use crate::query::rdf_terms::RdfTerm;
+use crate::query::result_writers::SolutionWriter;
+
+const SPARQL_RESULTS_NAMESPACE: &str = "http://www.w3.org/2005/sparql-results#";
+
+// Writer for the SPARQL Query Results XML Format (application/sparql-results+xml)
+pub struct XmlWriter {
+    variables: Vec<String>,
+}
+
+impl Default for XmlWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XmlWriter {
+    pub fn new() -> Self {
+        Self { variables: Vec::new() }
+    }
+}
+
+impl SolutionWriter for XmlWriter {
+    fn write_head(&mut self, out: &mut dyn Write, variables: &[String]) -> io::Result<()> {
+        self.variables = variables.to_vec();
+
+        write_document_start(out)?;
+        out.write_all(b"  <head>\n")?;
+        for variable in variables {
+            out.write_all(b"    <variable name=\"")?;
+            write_xml_escaped(out, variable)?;
+            out.write_all(b"\"/>\n")?;
+        }
+        out.write_all(b"  </head>\n  <results>\n")
+    }
+
+    fn write_solution(&mut self, out: &mut dyn Write, solution: &[Option<RdfTerm>]) -> io::Result<()> {
+        out.write_all(b"    <result>\n")?;
+        for (variable, term) in self.variables.iter().zip(solution) {
+            let term = match term {
+                Some(term) => term,
+                None => continue,
+            };
+            out.write_all(b"      <binding name=\"")?;
+            write_xml_escaped(out, variable)?;
+            out.write_all(b"\">")?;
+            write_xml_term(out, term)?;
+            out.write_all(b"</binding>\n")?;
+        }
+        out.write_all(b"    </result>\n")
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(b"  </results>\n</sparql>\n")
+    }
+
+    fn write_boolean(&mut self, out: &mut dyn Write, value: bool) -> io::Result<()> {
+        write_document_start(out)?;
+        writeln!(out, "  <head/>\n  <boolean>{}</boolean>\n</sparql>", value)
+    }
+}
+
+fn write_document_start(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<sparql xmlns=\"{}\">", SPARQL_RESULTS_NAMESPACE)
+}
+
+fn write_xml_term(out: &mut dyn Write, term: &RdfTerm) -> io::Result<()> {
+    match term {
+        RdfTerm::Iri(iri) => {
+            out.write_all(b"<uri>")?;
+            write_xml_escaped(out, iri)?;
+            out.write_all(b"</uri>")
+        }
+        RdfTerm::BlankNode(id) => {
+            out.write_all(b"<bnode>")?;
+            write_xml_escaped(out, id)?;
+            out.write_all(b"</bnode>")
+        }
+        RdfTerm::Literal { value, datatype, language } => {
+            if let Some(language) = language {
+                out.write_all(b"<literal xml:lang=\"")?;
+                write_xml_escaped(out, language)?;
+                out.write_all(b"\">")?;
+            } else if let Some(datatype) = datatype {
+                out.write_all(b"<literal datatype=\"")?;
+                write_xml_escaped(out, datatype)?;
+                out.write_all(b"\">")?;
+            } else {
+                out.write_all(b"<literal>")?;
+            }
+            write_xml_escaped(out, value)?;
+            out.write_all(b"</literal>")
+        }
+        // Not part of the standard format: <path><start>term</start><step inverse="false">
+        // <predicate>term</predicate><node>term</node></step>...</path>
+        RdfTerm::Path(path) => {
+            out.write_all(b"<path><start>")?;
+            write_xml_term(out, &path.start)?;
+            out.write_all(b"</start>")?;
+            for step in &path.steps {
+                write!(out, "<step inverse=\"{}\"><predicate>", step.inverse)?;
+                write_xml_term(out, &step.predicate)?;
+                out.write_all(b"</predicate><node>")?;
+                write_xml_term(out, &step.node)?;
+                out.write_all(b"</node></step>")?;
+            }
+            out.write_all(b"</path>")
+        }
+    }
+}
+
+// Escapes text for element content and double-quoted attributes. Characters that XML 1.0
+// can not represent at all are replaced by U+FFFD, and carriage returns are written as a
+// character reference so parsers do not normalize them away.
+pub fn write_xml_escaped(out: &mut dyn Write, value: &str) -> io::Result<()> {
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        let escape = match c {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '"' => "&quot;",
+            '\r' => "&#xD;",
+            '\t' | '\n' => continue,
+            '\u{0}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => "\u{FFFD}",
+            _ => continue,
+        };
+        out.write_all(&value.as_bytes()[start..i])?;
+        out.write_all(escape.as_bytes())?;
+        start = i + c.len_utf8();
+    }
+    out.write_all(&value.as_bytes()[start..])
+}
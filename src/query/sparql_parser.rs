@@ -0,0 +1,1769 @@
+use std::collections::HashMap;
+
+use crate::import::ntriples_parser::{has_scheme, is_pn_chars, is_pn_chars_base, is_pn_chars_u};
+use crate::import::turtle_parser::resolve_iri;
+use crate::query::algebra::*;
+use crate::query::exceptions::QueryParsingException;
+use crate::query::query_forms::QueryForm;
+use crate::query::rdf_terms::{RdfTerm, RDF_FIRST, RDF_NIL, RDF_REST, RDF_TYPE, XSD_PREFIX};
+
+// Built-in functions with their minimum and maximum number of arguments (`None` when
+// there is no maximum). BOUND, EXISTS and the aggregates have their own syntax.
+const BUILTINS: &[(&str, usize, Option<usize>)] = &[
+    ("STR", 1, Some(1)),
+    ("LANG", 1, Some(1)),
+    ("LANGMATCHES", 2, Some(2)),
+    ("DATATYPE", 1, Some(1)),
+    ("IRI", 1, Some(1)),
+    ("URI", 1, Some(1)),
+    ("BNODE", 0, Some(1)),
+    ("RAND", 0, Some(0)),
+    ("ABS", 1, Some(1)),
+    ("CEIL", 1, Some(1)),
+    ("FLOOR", 1, Some(1)),
+    ("ROUND", 1, Some(1)),
+    ("CONCAT", 0, None),
+    ("STRLEN", 1, Some(1)),
+    ("UCASE", 1, Some(1)),
+    ("LCASE", 1, Some(1)),
+    ("ENCODE_FOR_URI", 1, Some(1)),
+    ("CONTAINS", 2, Some(2)),
+    ("STRSTARTS", 2, Some(2)),
+    ("STRENDS", 2, Some(2)),
+    ("STRBEFORE", 2, Some(2)),
+    ("STRAFTER", 2, Some(2)),
+    ("YEAR", 1, Some(1)),
+    ("MONTH", 1, Some(1)),
+    ("DAY", 1, Some(1)),
+    ("HOURS", 1, Some(1)),
+    ("MINUTES", 1, Some(1)),
+    ("SECONDS", 1, Some(1)),
+    ("TIMEZONE", 1, Some(1)),
+    ("TZ", 1, Some(1)),
+    ("NOW", 0, Some(0)),
+    ("UUID", 0, Some(0)),
+    ("STRUUID", 0, Some(0)),
+    ("MD5", 1, Some(1)),
+    ("SHA1", 1, Some(1)),
+    ("SHA256", 1, Some(1)),
+    ("SHA384", 1, Some(1)),
+    ("SHA512", 1, Some(1)),
+    ("COALESCE", 0, None),
+    ("IF", 3, Some(3)),
+    ("STRLANG", 2, Some(2)),
+    ("STRDT", 2, Some(2)),
+    ("SAMETERM", 2, Some(2)),
+    ("ISIRI", 1, Some(1)),
+    ("ISURI", 1, Some(1)),
+    ("ISBLANK", 1, Some(1)),
+    ("ISLITERAL", 1, Some(1)),
+    ("ISNUMERIC", 1, Some(1)),
+    ("REGEX", 2, Some(3)),
+    ("SUBSTR", 2, Some(3)),
+    ("REPLACE", 3, Some(4)),
+];
+
+const AGGREGATES: &[(&str, AggregateFunction)] = &[
+    ("COUNT", AggregateFunction::Count),
+    ("SUM", AggregateFunction::Sum),
+    ("MIN", AggregateFunction::Min),
+    ("MAX", AggregateFunction::Max),
+    ("AVG", AggregateFunction::Avg),
+    ("SAMPLE", AggregateFunction::Sample),
+    ("GROUP_CONCAT", AggregateFunction::GroupConcat),
+];
+
+// Keywords starting the group graph patterns that are not triples
+const GRAPH_PATTERN_KEYWORDS: [&str; 7] = ["OPTIONAL", "MINUS", "GRAPH", "SERVICE", "FILTER", "BIND", "VALUES"];
+
+// Constructor of the expression of a binary operator
+type BinaryOperator = fn(Box<Expression>, Box<Expression>) -> Expression;
+
+// Parses a SPARQL 1.1 query into its algebra. The error of an invalid query has the line
+// and column where the parser stopped.
+pub fn parse_query(query: &str) -> Result<Query, QueryParsingException> {
+    SparqlParser::new(query).parse_query()
+}
+
+// An item of the SELECT clause
+enum Projection {
+    Variable(String),
+    Expression(Expression, String),
+}
+
+impl Projection {
+    fn name(&self) -> &String {
+        match self {
+            Projection::Variable(name) | Projection::Expression(_, name) => name,
+        }
+    }
+}
+
+// What comes between SELECT and the WHERE clause
+struct SelectClause {
+    distinct: bool,
+    reduced: bool,
+    // `None` for SELECT *
+    projections: Option<Vec<Projection>>,
+    // Aggregates of the projections, replaced by variables
+    aggregates: Vec<(String, Aggregate)>,
+}
+
+// Predicate of a triple, a path unless it is a variable or an IRI
+enum Verb {
+    Term(TermPattern),
+    Path(PropertyPath),
+    // A path with a path mode, and the variable the paths are bound to
+    ModePath { mode: PathMode, variable: Option<String>, path: PropertyPath },
+}
+
+// Triples of a triples block: the simple ones form a basic graph pattern, the paths are
+// evaluated on their own
+#[derive(Default)]
+struct Triples {
+    triples: Vec<TriplePattern>,
+    paths: Vec<GraphPattern>,
+}
+
+// Recursive descent parser working on the characters of the query, as the Turtle parser
+// does: which token comes next depends on where the parser is (e.g. '<' starts an IRI
+// where a term is expected, and is a comparison after an expression).
+struct SparqlParser {
+    chars: Vec<char>,
+    position: usize,
+    // Position of `chars[position]` in the query, starting at 1
+    line: u64,
+    column: u64,
+    base: Option<String>,
+    prefixes: Vec<(String, String)>,
+    prefix_map: HashMap<String, String>,
+    blank_node_counter: u64,
+    internal_var_counter: u64,
+    // Aggregates found in the clauses of the SELECT being parsed where they are allowed
+    // (SELECT, HAVING and ORDER BY), `None` anywhere else
+    aggregates: Option<Vec<(String, Aggregate)>>,
+    // Inside a CONSTRUCT template the predicates are IRIs, 'a' or variables
+    in_template: bool,
+}
+
+impl SparqlParser {
+    fn new(query: &str) -> Self {
+        Self {
+            chars: query.chars().collect(),
+            position: 0,
+            line: 1,
+            column: 1,
+            base: None,
+            prefixes: Vec::new(),
+            prefix_map: HashMap::new(),
+            blank_node_counter: 0,
+            internal_var_counter: 0,
+            aggregates: None,
+            in_template: false,
+        }
+    }
+
+    fn error(&self, message: &str) -> QueryParsingException {
+        self.error_at(self.line, self.column, message)
+    }
+
+    fn error_at(&self, line: u64, column: u64, message: &str) -> QueryParsingException {
+        QueryParsingException::new(&format!("Parse error at line {}, column {}: {}", line, column, message))
+    }
+
+    // Error for what is found where `expected` should be
+    fn unexpected(&mut self, expected: &str) -> QueryParsingException {
+        self.skip_whitespace();
+        if self.peek().is_none() {
+            return self.error(&format!("Expected {}, found the end of the query", expected));
+        }
+        let found: String = self.chars[self.position..].iter().take_while(|c| !c.is_whitespace()).take(20).collect();
+        self.error(&format!("Expected {}, found '{}'", expected, found))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn consume(&mut self, count: usize) {
+        for _ in 0..count {
+            self.bump();
+        }
+    }
+
+    // Skips white space and comments
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.bump(), None | Some('\n')) {}
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Whether the next token starts with `symbol`, without consuming it
+    fn sees(&mut self, symbol: &str) -> bool {
+        self.skip_whitespace();
+        symbol.chars().enumerate().all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if self.sees(symbol) {
+            self.consume(symbol.chars().count());
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), QueryParsingException> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        Err(self.unexpected(&format!("'{}'", symbol)))
+    }
+
+    // Whether the next token is the keyword, in any case. A keyword followed by a name
+    // character or ':' is the start of a longer name.
+    fn sees_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        keyword.chars().enumerate().all(|(i, c)| matches!(self.peek_at(i), Some(p) if p.eq_ignore_ascii_case(&c)))
+            && !matches!(self.peek_at(keyword.len()), Some(c) if is_pn_chars(c) || c == ':')
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.sees_keyword(keyword) {
+            self.consume(keyword.len());
+            return true;
+        }
+        false
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), QueryParsingException> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        Err(self.unexpected(keyword))
+    }
+
+    // 'a' for rdf:type, which unlike the other keywords is case sensitive
+    fn eat_a(&mut self) -> bool {
+        if self.sees("a") && !matches!(self.peek_at(1), Some(c) if is_pn_chars(c) || c == ':' || c == '.') {
+            self.bump();
+            return true;
+        }
+        false
+    }
+
+    // The name made of letters, digits and '_' that comes next (a keyword or a built-in
+    // function) in upper case, without consuming it
+    fn peek_name(&mut self) -> String {
+        self.skip_whitespace();
+        let mut name = String::new();
+        while let Some(c) = self.peek_at(name.len()).filter(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c.to_ascii_uppercase());
+        }
+        name
+    }
+
+    // Whether the next token is a prefixed name (PNAME_NS or PNAME_LN)
+    fn sees_prefixed_name(&mut self) -> bool {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(':') => true,
+            Some(c) if is_pn_chars_base(c) => {
+                let mut offset = 1;
+                while matches!(self.peek_at(offset), Some(c) if is_pn_chars(c) || c == '.') {
+                    offset += 1;
+                }
+                self.peek_at(offset) == Some(':')
+            }
+            _ => false,
+        }
+    }
+
+    fn sees_var(&mut self) -> bool {
+        self.skip_whitespace();
+        matches!(self.peek(), Some('?') | Some('$')) && matches!(self.peek_at(1), Some(c) if is_var_char(c))
+    }
+
+    fn sees_iri(&mut self) -> bool {
+        self.sees("<") || self.sees_prefixed_name()
+    }
+
+    // Whether a built-in call, an aggregate or [NOT] EXISTS comes next
+    fn sees_builtin(&mut self) -> bool {
+        let name = self.peek_name();
+        !matches!(self.peek_at(name.len()), Some(c) if is_pn_chars(c) || c == ':' || c == '.')
+            && (BUILTINS.iter().any(|(builtin, _, _)| *builtin == name)
+                || AGGREGATES.iter().any(|(aggregate, _)| *aggregate == name)
+                || ["BOUND", "EXISTS", "NOT"].contains(&name.as_str()))
+    }
+
+    // Whether the dots at the current position are followed by a name character, i.e.
+    // they are part of the name instead of the end of a triple
+    fn name_continues_after_dots(&self, is_name_char: fn(char) -> bool) -> bool {
+        let mut offset = 1;
+        while self.peek_at(offset) == Some('.') {
+            offset += 1;
+        }
+        matches!(self.peek_at(offset), Some(c) if is_name_char(c))
+    }
+
+    fn new_blank_node(&mut self) -> TermPattern {
+        self.blank_node_counter += 1;
+        // Labels of the query can not start with '.'
+        TermPattern::BlankNode(format!(".{}", self.blank_node_counter))
+    }
+
+    fn new_internal_var(&mut self, kind: &str) -> String {
+        self.internal_var_counter += 1;
+        format!("{}{}{}", INTERNAL_VAR_PREFIX, kind, self.internal_var_counter)
+    }
+
+    // Query: Prologue ( SelectQuery | ConstructQuery | DescribeQuery | AskQuery ) ValuesClause
+    fn parse_query(mut self) -> Result<Query, QueryParsingException> {
+        self.parse_prologue()?;
+        let query = match self.peek_name().as_str() {
+            "SELECT" => self.parse_select_query()?,
+            "CONSTRUCT" => self.parse_construct_query()?,
+            "DESCRIBE" => self.parse_describe_query()?,
+            "ASK" => self.parse_ask_query()?,
+            _ => return Err(self.unexpected("SELECT, CONSTRUCT, DESCRIBE or ASK")),
+        };
+        self.skip_whitespace();
+        if self.peek().is_some() {
+            return Err(self.unexpected("the end of the query"));
+        }
+        Ok(query)
+    }
+
+    // ( 'BASE' IRIREF | 'PREFIX' PNAME_NS IRIREF )*
+    fn parse_prologue(&mut self) -> Result<(), QueryParsingException> {
+        loop {
+            if self.eat_keyword("BASE") {
+                self.skip_whitespace();
+                self.base = Some(self.parse_iri_ref()?);
+            } else if self.eat_keyword("PREFIX") {
+                self.skip_whitespace();
+                let prefix = self.parse_pname_ns()?;
+                self.skip_whitespace();
+                let namespace = self.parse_iri_ref()?;
+                self.prefixes.retain(|(p, _)| *p != prefix);
+                self.prefixes.push((prefix.clone(), namespace.clone()));
+                self.prefix_map.insert(prefix, namespace);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn new_query(&self, form: QueryForm, dataset: Dataset, pattern: GraphPattern) -> Query {
+        Query {
+            form,
+            prefixes: self.prefixes.clone(),
+            dataset,
+            pattern,
+            variables: Vec::new(),
+            template: Vec::new(),
+            describe: Vec::new(),
+        }
+    }
+
+    fn parse_select_query(&mut self) -> Result<Query, QueryParsingException> {
+        let select = self.parse_select_clause()?;
+        let dataset = self.parse_dataset_clauses()?;
+        let where_pattern = self.parse_where_clause()?;
+        let (pattern, variables) = self.parse_solution_modifiers(where_pattern, Some(select))?;
+        let mut query = self.new_query(QueryForm::Select, dataset, pattern);
+        query.variables = variables;
+        Ok(query)
+    }
+
+    // SubSelect: SelectClause WhereClause SolutionModifier ValuesClause
+    fn parse_sub_select(&mut self) -> Result<GraphPattern, QueryParsingException> {
+        let select = self.parse_select_clause()?;
+        let where_pattern = self.parse_where_clause()?;
+        Ok(self.parse_solution_modifiers(where_pattern, Some(select))?.0)
+    }
+
+    // 'CONSTRUCT' ( ConstructTemplate DatasetClause* WhereClause SolutionModifier
+    //             | DatasetClause* 'WHERE' '{' TriplesTemplate? '}' SolutionModifier )
+    fn parse_construct_query(&mut self) -> Result<Query, QueryParsingException> {
+        self.expect_keyword("CONSTRUCT")?;
+        let (template, dataset, where_pattern) = if self.sees("{") {
+            let template = self.parse_construct_template()?;
+            let dataset = self.parse_dataset_clauses()?;
+            (template, dataset, self.parse_where_clause()?)
+        } else {
+            let dataset = self.parse_dataset_clauses()?;
+            self.expect_keyword("WHERE")?;
+            let template = self.parse_construct_template()?;
+            let pattern = GraphPattern::Bgp(template.clone());
+            (template, dataset, pattern)
+        };
+        let (pattern, _) = self.parse_solution_modifiers(where_pattern, None)?;
+        let mut query = self.new_query(QueryForm::Construct, dataset, pattern);
+        query.template = template;
+        Ok(query)
+    }
+
+    // '{' TriplesTemplate? '}', triples without paths
+    fn parse_construct_template(&mut self) -> Result<Vec<TriplePattern>, QueryParsingException> {
+        self.expect("{")?;
+        self.in_template = true;
+        let mut triples = Triples::default();
+        while !self.sees("}") {
+            self.parse_triples_same_subject(&mut triples)?;
+            if !self.eat(".") {
+                break;
+            }
+        }
+        self.in_template = false;
+        self.expect("}")?;
+        Ok(triples.triples)
+    }
+
+    // 'DESCRIBE' ( VarOrIri+ | '*' ) DatasetClause* WhereClause? SolutionModifier
+    fn parse_describe_query(&mut self) -> Result<Query, QueryParsingException> {
+        self.expect_keyword("DESCRIBE")?;
+        let mut describe = Vec::new();
+        let all = self.eat("*");
+        if !all {
+            while self.sees_var() || self.sees_iri() {
+                describe.push(self.parse_var_or_iri()?);
+            }
+            if describe.is_empty() {
+                return Err(self.unexpected("a variable, an IRI or '*'"));
+            }
+        }
+        let dataset = self.parse_dataset_clauses()?;
+        let where_pattern = if self.sees_keyword("WHERE") || self.sees("{") {
+            self.parse_where_clause()?
+        } else {
+            GraphPattern::empty()
+        };
+        let (pattern, _) = self.parse_solution_modifiers(where_pattern, None)?;
+        if all {
+            describe = pattern
+                .in_scope_variables()
+                .into_iter()
+                .filter(|name| !is_internal_variable(name))
+                .map(TermPattern::Variable)
+                .collect();
+        }
+        let mut query = self.new_query(QueryForm::Describe, dataset, pattern);
+        query.describe = describe;
+        Ok(query)
+    }
+
+    // 'ASK' DatasetClause* WhereClause SolutionModifier
+    fn parse_ask_query(&mut self) -> Result<Query, QueryParsingException> {
+        self.expect_keyword("ASK")?;
+        let dataset = self.parse_dataset_clauses()?;
+        let where_pattern = self.parse_where_clause()?;
+        let (pattern, _) = self.parse_solution_modifiers(where_pattern, None)?;
+        Ok(self.new_query(QueryForm::Ask, dataset, pattern))
+    }
+
+    // ( 'FROM' 'NAMED'? iri )*
+    fn parse_dataset_clauses(&mut self) -> Result<Dataset, QueryParsingException> {
+        let mut dataset = Dataset::default();
+        while self.eat_keyword("FROM") {
+            if self.eat_keyword("NAMED") {
+                dataset.named_graphs.push(self.parse_iri()?);
+            } else {
+                dataset.default_graphs.push(self.parse_iri()?);
+            }
+        }
+        Ok(dataset)
+    }
+
+    // 'WHERE'? GroupGraphPattern
+    fn parse_where_clause(&mut self) -> Result<GraphPattern, QueryParsingException> {
+        self.eat_keyword("WHERE");
+        self.parse_group_graph_pattern()
+    }
+
+    // 'SELECT' ( 'DISTINCT' | 'REDUCED' )? ( ( Var | ( '(' Expression 'AS' Var ')' ) )+ | '*' )
+    fn parse_select_clause(&mut self) -> Result<SelectClause, QueryParsingException> {
+        self.expect_keyword("SELECT")?;
+        let distinct = self.eat_keyword("DISTINCT");
+        let reduced = !distinct && self.eat_keyword("REDUCED");
+        if self.eat("*") {
+            return Ok(SelectClause { distinct, reduced, projections: None, aggregates: Vec::new() });
+        }
+
+        let outer_aggregates = self.aggregates.replace(Vec::new());
+        let mut projections: Vec<Projection> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let (line, column) = (self.line, self.column);
+            let projection = if self.sees_var() {
+                Projection::Variable(self.parse_var()?)
+            } else if self.eat("(") {
+                let expression = self.parse_expression()?;
+                self.expect_keyword("AS")?;
+                let variable = self.parse_var()?;
+                self.expect(")")?;
+                Projection::Expression(expression, variable)
+            } else {
+                break;
+            };
+            if projections.iter().any(|p| p.name() == projection.name()) {
+                return Err(self.error_at(line, column, &format!("Variable ?{} is projected twice", projection.name())));
+            }
+            projections.push(projection);
+        }
+        if projections.is_empty() {
+            return Err(self.unexpected("a variable, '(' or '*'"));
+        }
+        let aggregates = std::mem::replace(&mut self.aggregates, outer_aggregates).unwrap_or_default();
+        Ok(SelectClause { distinct, reduced, projections: Some(projections), aggregates })
+    }
+
+    // SolutionModifier (GROUP BY, HAVING, ORDER BY, LIMIT and OFFSET) and ValuesClause,
+    // applied to the pattern of the WHERE clause in the order of the section 18.2.4 of
+    // the recommendation. Returns the pattern and the projected variables of a SELECT.
+    fn parse_solution_modifiers(
+        &mut self,
+        mut pattern: GraphPattern,
+        mut select: Option<SelectClause>,
+    ) -> Result<(GraphPattern, Vec<String>), QueryParsingException> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        let mut group_by = Vec::new();
+        if self.eat_keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            loop {
+                group_by.push(self.parse_group_condition()?);
+                if !(self.sees_var() || self.sees("(") || self.sees_iri() || self.sees_builtin()) {
+                    break;
+                }
+            }
+        }
+
+        // HAVING and ORDER BY may add aggregates to the ones of the SELECT clause
+        let select_aggregates = select.as_mut().map(|select| std::mem::take(&mut select.aggregates)).unwrap_or_default();
+        let outer_aggregates = self.aggregates.replace(select_aggregates);
+        let mut having = Vec::new();
+        if self.eat_keyword("HAVING") {
+            loop {
+                having.push(self.parse_constraint()?);
+                if !(self.sees("(") || self.sees_iri() || self.sees_builtin()) {
+                    break;
+                }
+            }
+        }
+        let mut order_by = Vec::new();
+        if self.eat_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                order_by.push(self.parse_order_condition()?);
+                let more = self.sees_keyword("ASC") || self.sees_keyword("DESC") || self.sees_var() || self.sees("(");
+                if !(more || self.sees_iri() || self.sees_builtin()) {
+                    break;
+                }
+            }
+        }
+        let aggregates = std::mem::replace(&mut self.aggregates, outer_aggregates).unwrap_or_default();
+        let (offset, limit) = self.parse_limit_offset()?;
+        let values = if self.eat_keyword("VALUES") { Some(self.parse_data_block()?) } else { None };
+
+        // Variables the SELECT clause can use
+        let grouped = !group_by.is_empty() || !aggregates.is_empty();
+        let mut available = pattern.in_scope_variables();
+        if grouped {
+            if matches!(&select, Some(SelectClause { projections: None, .. })) {
+                return Err(self.error_at(line, column, "SELECT * is not allowed with GROUP BY or aggregates"));
+            }
+            available = group_by.iter().map(|(_, name)| name.clone()).collect();
+            available.extend(aggregates.iter().map(|(name, _)| name.clone()));
+            pattern = GraphPattern::Group { inner: Box::new(pattern), by: group_by, aggregates };
+        }
+        for condition in having {
+            pattern = GraphPattern::Filter { condition, inner: Box::new(pattern) };
+        }
+        if let Some(values) = values {
+            if let GraphPattern::Values { variables, .. } = &values {
+                available.extend(variables.iter().filter(|name| !available.contains(name)).cloned().collect::<Vec<_>>());
+            }
+            pattern = join(pattern, values);
+        }
+
+        let is_select = select.is_some();
+        let mut projected = Vec::new();
+        let (mut distinct, mut reduced) = (false, false);
+        if let Some(select) = select {
+            (distinct, reduced) = (select.distinct, select.reduced);
+            match select.projections {
+                None => projected = available.into_iter().filter(|name| !is_internal_variable(name)).collect(),
+                Some(projections) => {
+                    for projection in projections {
+                        match projection {
+                            Projection::Variable(name) => {
+                                if grouped && !available.contains(&name) {
+                                    return Err(self.error_at(line, column, &format!("Variable ?{} is not in GROUP BY", name)));
+                                }
+                                projected.push(name);
+                            }
+                            Projection::Expression(expression, name) => {
+                                if available.contains(&name) {
+                                    return Err(self.error_at(line, column, &format!("Variable ?{} is already in scope", name)));
+                                }
+                                if grouped {
+                                    if let Some(variable) = expression.variables().into_iter().find(|v| !available.contains(v)) {
+                                        return Err(self.error_at(line, column, &format!("Variable ?{} is not in GROUP BY", variable)));
+                                    }
+                                }
+                                available.push(name.clone());
+                                pattern = GraphPattern::Extend { inner: Box::new(pattern), variable: name.clone(), expression };
+                                projected.push(name);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !order_by.is_empty() {
+            pattern = GraphPattern::OrderBy { inner: Box::new(pattern), conditions: order_by };
+        }
+        if is_select {
+            pattern = GraphPattern::Project { inner: Box::new(pattern), variables: projected.clone() };
+        }
+        if distinct {
+            pattern = GraphPattern::Distinct(Box::new(pattern));
+        } else if reduced {
+            pattern = GraphPattern::Reduced(Box::new(pattern));
+        }
+        if offset > 0 || limit.is_some() {
+            pattern = GraphPattern::Slice { inner: Box::new(pattern), offset, limit };
+        }
+        Ok((pattern, projected))
+    }
+
+    // GroupCondition: BuiltInCall | FunctionCall | '(' Expression ( 'AS' Var )? ')' | Var
+    fn parse_group_condition(&mut self) -> Result<(Expression, String), QueryParsingException> {
+        if self.sees_var() {
+            let name = self.parse_var()?;
+            return Ok((Expression::Variable(name.clone()), name));
+        }
+        if self.eat("(") {
+            let expression = self.parse_expression()?;
+            let name = if self.eat_keyword("AS") {
+                self.parse_var()?
+            } else if let Expression::Variable(name) = &expression {
+                name.clone()
+            } else {
+                self.new_internal_var("group")
+            };
+            self.expect(")")?;
+            return Ok((expression, name));
+        }
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        let expression = self.parse_primary_expression()?;
+        if !matches!(expression, Expression::Builtin(..) | Expression::Function(..)) {
+            return Err(self.error_at(line, column, "Expected a variable, '(' or a function call"));
+        }
+        Ok((expression, self.new_internal_var("group")))
+    }
+
+    // OrderCondition: ( ( 'ASC' | 'DESC' ) BrackettedExpression ) | ( Constraint | Var )
+    fn parse_order_condition(&mut self) -> Result<OrderCondition, QueryParsingException> {
+        let ascending = !self.sees_keyword("DESC");
+        if self.eat_keyword("ASC") || self.eat_keyword("DESC") {
+            self.expect("(")?;
+            let expression = self.parse_expression()?;
+            self.expect(")")?;
+            return Ok(OrderCondition { expression, ascending });
+        }
+        let expression = if self.sees_var() { Expression::Variable(self.parse_var()?) } else { self.parse_constraint()? };
+        Ok(OrderCondition { expression, ascending: true })
+    }
+
+    // LimitClause OffsetClause? | OffsetClause LimitClause?
+    fn parse_limit_offset(&mut self) -> Result<(u64, Option<u64>), QueryParsingException> {
+        let mut offset = None;
+        let mut limit = None;
+        loop {
+            if limit.is_none() && self.eat_keyword("LIMIT") {
+                limit = Some(self.parse_unsigned_integer()?);
+            } else if offset.is_none() && self.eat_keyword("OFFSET") {
+                offset = Some(self.parse_unsigned_integer()?);
+            } else {
+                return Ok((offset.unwrap_or(0), limit));
+            }
+        }
+    }
+
+    fn parse_unsigned_integer(&mut self) -> Result<u64, QueryParsingException> {
+        self.skip_whitespace();
+        let mut digits = String::new();
+        if self.take_digits(&mut digits) == 0 {
+            return Err(self.unexpected("an integer"));
+        }
+        digits.parse().map_err(|_| self.error("Integer too large"))
+    }
+
+    // '{' ( SubSelect | GroupGraphPatternSub ) '}'
+    fn parse_group_graph_pattern(&mut self) -> Result<GraphPattern, QueryParsingException> {
+        self.expect("{")?;
+        // Aggregates are not allowed in the patterns, not even inside EXISTS in HAVING
+        let outer_aggregates = self.aggregates.take();
+        let pattern = if self.sees_keyword("SELECT") {
+            self.parse_sub_select()?
+        } else {
+            self.parse_group_graph_pattern_sub()?
+        };
+        self.aggregates = outer_aggregates;
+        self.expect("}")?;
+        Ok(pattern)
+    }
+
+    // TriplesBlock? ( GraphPatternNotTriples '.'? TriplesBlock? )*, translated as in the
+    // section 18.2.2.6 of the recommendation: the filters apply to the whole group
+    fn parse_group_graph_pattern_sub(&mut self) -> Result<GraphPattern, QueryParsingException> {
+        let mut group = GraphPattern::empty();
+        let mut filters = Vec::new();
+        // A triples block that did not end with '.' can only be followed by the patterns
+        // that are not triples
+        let mut after_triples = false;
+        loop {
+            if self.sees("}") {
+                break;
+            }
+            let (line, column) = (self.line, self.column);
+            match self.peek_name().as_str() {
+                "OPTIONAL" if self.eat_keyword("OPTIONAL") => {
+                    let right = self.parse_group_graph_pattern()?;
+                    let left = Box::new(group);
+                    group = match right {
+                        GraphPattern::Filter { condition, inner } => GraphPattern::LeftJoin { left, right: inner, condition: Some(condition) },
+                        right => GraphPattern::LeftJoin { left, right: Box::new(right), condition: None },
+                    };
+                }
+                "MINUS" if self.eat_keyword("MINUS") => {
+                    let right = self.parse_group_graph_pattern()?;
+                    group = GraphPattern::Minus(Box::new(group), Box::new(right));
+                }
+                "GRAPH" if self.eat_keyword("GRAPH") => {
+                    let name = self.parse_var_or_iri()?;
+                    let inner = self.parse_group_graph_pattern()?;
+                    group = join(group, GraphPattern::Graph { name, inner: Box::new(inner) });
+                }
+                "SERVICE" if self.sees_keyword("SERVICE") => {
+                    return Err(self.error("SERVICE is not supported"));
+                }
+                "FILTER" if self.eat_keyword("FILTER") => {
+                    filters.push(self.parse_constraint()?);
+                }
+                "BIND" if self.eat_keyword("BIND") => {
+                    self.expect("(")?;
+                    let expression = self.parse_expression()?;
+                    self.expect_keyword("AS")?;
+                    let variable = self.parse_var()?;
+                    self.expect(")")?;
+                    if group.in_scope_variables().contains(&variable) {
+                        return Err(self.error_at(line, column, &format!("Variable ?{} is already in scope", variable)));
+                    }
+                    group = GraphPattern::Extend { inner: Box::new(group), variable, expression };
+                }
+                "VALUES" if self.eat_keyword("VALUES") => {
+                    let values = self.parse_data_block()?;
+                    group = join(group, values);
+                }
+                _ if self.sees("{") => {
+                    let mut pattern = self.parse_group_graph_pattern()?;
+                    while self.eat_keyword("UNION") {
+                        let right = self.parse_group_graph_pattern()?;
+                        pattern = GraphPattern::Union(Box::new(pattern), Box::new(right));
+                    }
+                    group = join(group, pattern);
+                }
+                _ => {
+                    if after_triples {
+                        return Err(self.unexpected("'.' or '}'"));
+                    }
+                    let mut triples = Triples::default();
+                    loop {
+                        self.parse_triples_same_subject(&mut triples)?;
+                        if !self.eat(".") {
+                            after_triples = true;
+                            break;
+                        }
+                        if !self.sees_triples_start() {
+                            break;
+                        }
+                    }
+                    group = join(group, GraphPattern::Bgp(triples.triples));
+                    for path in triples.paths {
+                        group = join(group, path);
+                    }
+                    continue;
+                }
+            }
+            after_triples = false;
+            self.eat(".");
+        }
+        if let Some(condition) = filters.into_iter().reduce(|a, b| Expression::And(Box::new(a), Box::new(b))) {
+            group = GraphPattern::Filter { condition, inner: Box::new(group) };
+        }
+        Ok(group)
+    }
+
+    // Whether a triple can start here, after the '.' of the previous one
+    fn sees_triples_start(&mut self) -> bool {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('}') | Some('{') | Some('.') => false,
+            _ => !GRAPH_PATTERN_KEYWORDS.iter().any(|keyword| self.sees_keyword(keyword)),
+        }
+    }
+
+    // Constraint: BrackettedExpression | BuiltInCall | FunctionCall
+    fn parse_constraint(&mut self) -> Result<Expression, QueryParsingException> {
+        if self.eat("(") {
+            let expression = self.parse_expression()?;
+            self.expect(")")?;
+            return Ok(expression);
+        }
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        let expression = self.parse_primary_expression()?;
+        match expression {
+            Expression::Builtin(..) | Expression::Function(..) | Expression::Exists(_) | Expression::NotExists(_) => Ok(expression),
+            // Aggregates are replaced by variables
+            Expression::Variable(ref name) if name.starts_with(INTERNAL_VAR_PREFIX) => Ok(expression),
+            _ => Err(self.error_at(line, column, "Expected '(' or a function call")),
+        }
+    }
+
+    // DataBlock: InlineDataOneVar | InlineDataFull
+    fn parse_data_block(&mut self) -> Result<GraphPattern, QueryParsingException> {
+        let mut variables = Vec::new();
+        let mut rows = Vec::new();
+        if self.sees_var() {
+            variables.push(self.parse_var()?);
+            self.expect("{")?;
+            while !self.eat("}") {
+                rows.push(vec![self.parse_data_block_value()?]);
+            }
+            return Ok(GraphPattern::Values { variables, rows });
+        }
+        self.expect("(")?;
+        while !self.eat(")") {
+            variables.push(self.parse_var()?);
+        }
+        self.expect("{")?;
+        while !self.eat("}") {
+            let (line, column) = (self.line, self.column);
+            self.expect("(")?;
+            let mut row = Vec::new();
+            while !self.eat(")") {
+                row.push(self.parse_data_block_value()?);
+            }
+            if row.len() != variables.len() {
+                return Err(self.error_at(line, column, &format!("Expected {} values, found {}", variables.len(), row.len())));
+            }
+            rows.push(row);
+        }
+        Ok(GraphPattern::Values { variables, rows })
+    }
+
+    // iri | RDFLiteral | NumericLiteral | BooleanLiteral | 'UNDEF'
+    fn parse_data_block_value(&mut self) -> Result<Option<RdfTerm>, QueryParsingException> {
+        if self.eat_keyword("UNDEF") {
+            return Ok(None);
+        }
+        let (line, column) = (self.line, self.column);
+        match self.parse_term()? {
+            TermPattern::Term(term) => Ok(Some(term)),
+            _ => Err(self.error_at(line, column, "Expected an IRI, a literal or UNDEF")),
+        }
+    }
+
+    // TriplesSameSubjectPath: VarOrTerm PropertyListPathNotEmpty | TriplesNodePath PropertyListPath
+    fn parse_triples_same_subject(&mut self, triples: &mut Triples) -> Result<(), QueryParsingException> {
+        let (subject, is_node) = self.parse_graph_node(triples)?;
+        if is_node && self.sees_end_of_property_list() {
+            return Ok(());
+        }
+        self.parse_property_list(&subject, triples)
+    }
+
+    fn sees_end_of_property_list(&mut self) -> bool {
+        self.skip_whitespace();
+        matches!(self.peek(), None | Some('.') | Some('}') | Some(']'))
+            || GRAPH_PATTERN_KEYWORDS.iter().any(|keyword| self.sees_keyword(keyword))
+    }
+
+    // ( Verb ObjectList ) ( ';' ( Verb ObjectList )? )*
+    fn parse_property_list(&mut self, subject: &TermPattern, triples: &mut Triples) -> Result<(), QueryParsingException> {
+        loop {
+            let verb = self.parse_verb()?;
+            loop {
+                let (object, _) = self.parse_graph_node(triples)?;
+                match &verb {
+                    Verb::Term(predicate) => triples.triples.push(TriplePattern::new(subject.clone(), predicate.clone(), object)),
+                    Verb::Path(path) => self.add_path(subject.clone(), path.clone(), object, triples),
+                    Verb::ModePath { mode, variable, path } => triples.paths.push(GraphPattern::Path {
+                        subject: subject.clone(),
+                        path: path.clone(),
+                        object,
+                        mode: Some(*mode),
+                        variable: variable.clone(),
+                    }),
+                }
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            if !self.eat(";") {
+                return Ok(());
+            }
+            while self.eat(";") {}
+            if self.sees_end_of_property_list() {
+                return Ok(());
+            }
+        }
+    }
+
+    // A variable or a path; in a template a variable, an IRI or 'a'. A path may start with
+    // a path mode and the variable bound to the paths it matches, which are extensions of
+    // SPARQL: `?x ANY SHORTEST ?p :knows+ ?y`.
+    fn parse_verb(&mut self) -> Result<Verb, QueryParsingException> {
+        if !self.in_template {
+            if let Some(mode) = self.parse_path_mode()? {
+                let variable = if self.sees_var() { Some(self.parse_var()?) } else { None };
+                let path = self.parse_path()?;
+                return Ok(Verb::ModePath { mode, variable, path });
+            }
+        }
+        if self.sees_var() {
+            return Ok(Verb::Term(TermPattern::Variable(self.parse_var()?)));
+        }
+        if self.in_template {
+            if self.eat_a() {
+                return Ok(Verb::Term(iri_pattern(RDF_TYPE)));
+            }
+            if self.sees_iri() {
+                return Ok(Verb::Term(TermPattern::Term(RdfTerm::Iri(self.parse_iri()?))));
+            }
+            return Err(self.unexpected("a variable, an IRI or 'a'"));
+        }
+        match self.parse_path()? {
+            PropertyPath::Iri(iri) => Ok(Verb::Term(TermPattern::Term(RdfTerm::Iri(iri)))),
+            path => Ok(Verb::Path(path)),
+        }
+    }
+
+    // Adds the triples of a path as in the section 18.2.2.4 of the recommendation: IRIs,
+    // inverses and sequences become triples (with internal variables between the steps
+    // of a sequence), the other paths are kept as path patterns
+    fn add_path(&mut self, subject: TermPattern, path: PropertyPath, object: TermPattern, triples: &mut Triples) {
+        match path {
+            PropertyPath::Iri(iri) => {
+                triples.triples.push(TriplePattern::new(subject, TermPattern::Term(RdfTerm::Iri(iri)), object));
+            }
+            PropertyPath::Inverse(inner) => self.add_path(object, *inner, subject, triples),
+            PropertyPath::Sequence(steps) => {
+                let count = steps.len();
+                let mut from = subject;
+                for (i, step) in steps.into_iter().enumerate() {
+                    let to = if i + 1 == count { object.clone() } else { TermPattern::Variable(self.new_internal_var("path")) };
+                    self.add_path(from, step, to.clone(), triples);
+                    from = to;
+                }
+            }
+            path => triples.paths.push(GraphPattern::Path { subject, path, object, mode: None, variable: None }),
+        }
+    }
+
+    // PathMode: 'ANY' 'SHORTEST'? | 'ALL' 'SHORTEST' | 'TRAIL' | 'SIMPLE' | 'ACYCLIC'
+    fn parse_path_mode(&mut self) -> Result<Option<PathMode>, QueryParsingException> {
+        if self.eat_keyword("ANY") {
+            let shortest = self.eat_keyword("SHORTEST");
+            return Ok(Some(if shortest { PathMode::AnyShortest } else { PathMode::Any }));
+        }
+        if self.eat_keyword("ALL") {
+            self.expect_keyword("SHORTEST")?;
+            return Ok(Some(PathMode::AllShortest));
+        }
+        Ok(if self.eat_keyword("TRAIL") {
+            Some(PathMode::Trail)
+        } else if self.eat_keyword("SIMPLE") {
+            Some(PathMode::Simple)
+        } else if self.eat_keyword("ACYCLIC") {
+            Some(PathMode::Acyclic)
+        } else {
+            None
+        })
+    }
+
+    // PathAlternative: PathSequence ( '|' PathSequence )*
+    fn parse_path(&mut self) -> Result<PropertyPath, QueryParsingException> {
+        let mut alternatives = vec![self.parse_path_sequence()?];
+        while self.eat("|") {
+            alternatives.push(self.parse_path_sequence()?);
+        }
+        Ok(if alternatives.len() == 1 { alternatives.pop().unwrap() } else { PropertyPath::Alternative(alternatives) })
+    }
+
+    // PathSequence: PathEltOrInverse ( '/' PathEltOrInverse )*
+    fn parse_path_sequence(&mut self) -> Result<PropertyPath, QueryParsingException> {
+        let mut steps = vec![self.parse_path_elt_or_inverse()?];
+        while self.eat("/") {
+            steps.push(self.parse_path_elt_or_inverse()?);
+        }
+        Ok(if steps.len() == 1 { steps.pop().unwrap() } else { PropertyPath::Sequence(steps) })
+    }
+
+    // PathEltOrInverse: PathElt | '^' PathElt, with PathElt: PathPrimary PathMod?
+    fn parse_path_elt_or_inverse(&mut self) -> Result<PropertyPath, QueryParsingException> {
+        let inverse = self.eat("^");
+        let primary = self.parse_path_primary()?;
+        // A '?' followed by a name is the variable of the object, and a '+' followed by a
+        // digit a number
+        self.skip_whitespace();
+        let path = match (self.peek(), self.peek_at(1)) {
+            (Some('*'), _) => {
+                self.bump();
+                PropertyPath::ZeroOrMore(Box::new(primary))
+            }
+            (Some('+'), next) if !matches!(next, Some(c) if c.is_ascii_digit() || c == '.') => {
+                self.bump();
+                PropertyPath::OneOrMore(Box::new(primary))
+            }
+            (Some('?'), next) if !matches!(next, Some(c) if is_var_char(c)) => {
+                self.bump();
+                PropertyPath::ZeroOrOne(Box::new(primary))
+            }
+            _ => primary,
+        };
+        Ok(if inverse { PropertyPath::Inverse(Box::new(path)) } else { path })
+    }
+
+    // PathPrimary: iri | 'a' | '!' PathNegatedPropertySet | '(' Path ')'
+    fn parse_path_primary(&mut self) -> Result<PropertyPath, QueryParsingException> {
+        if self.eat_a() {
+            return Ok(PropertyPath::Iri(RDF_TYPE.to_string()));
+        }
+        if self.eat("!") {
+            let mut negated = Vec::new();
+            if self.eat("(") {
+                if !self.eat(")") {
+                    loop {
+                        negated.push(self.parse_path_one_in_property_set()?);
+                        if !self.eat("|") {
+                            break;
+                        }
+                    }
+                    self.expect(")")?;
+                }
+            } else {
+                negated.push(self.parse_path_one_in_property_set()?);
+            }
+            return Ok(PropertyPath::NegatedSet(negated));
+        }
+        if self.eat("(") {
+            let path = self.parse_path()?;
+            self.expect(")")?;
+            return Ok(path);
+        }
+        if self.sees_iri() {
+            return Ok(PropertyPath::Iri(self.parse_iri()?));
+        }
+        Err(self.unexpected("a variable or a property path"))
+    }
+
+    // PathOneInPropertySet: iri | 'a' | '^' ( iri | 'a' )
+    fn parse_path_one_in_property_set(&mut self) -> Result<NegatedIri, QueryParsingException> {
+        let inverse = self.eat("^");
+        let iri = if self.eat_a() { RDF_TYPE.to_string() } else { self.parse_iri()? };
+        Ok(NegatedIri { iri, inverse })
+    }
+
+    // GraphNodePath: VarOrTerm | TriplesNodePath. Returns the node and whether it is a
+    // collection or a blank node property list, which may be a subject without properties.
+    fn parse_graph_node(&mut self, triples: &mut Triples) -> Result<(TermPattern, bool), QueryParsingException> {
+        if self.sees("[") && !self.sees_empty_brackets('[', ']') {
+            self.bump();
+            let node = self.new_blank_node();
+            self.parse_property_list(&node, triples)?;
+            self.expect("]")?;
+            return Ok((node, true));
+        }
+        if self.sees("(") && !self.sees_empty_brackets('(', ')') {
+            return Ok((self.parse_collection(triples)?, true));
+        }
+        Ok((self.parse_term()?, false))
+    }
+
+    // Whether the next token is '[' ']' or '(' ')', with only white space inside
+    fn sees_empty_brackets(&mut self, open: char, close: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() != Some(open) {
+            return false;
+        }
+        let mut offset = 1;
+        while matches!(self.peek_at(offset), Some(c) if c.is_whitespace()) {
+            offset += 1;
+        }
+        self.peek_at(offset) == Some(close)
+    }
+
+    // '(' GraphNodePath+ ')', encoded with rdf:first/rdf:rest
+    fn parse_collection(&mut self, triples: &mut Triples) -> Result<TermPattern, QueryParsingException> {
+        self.expect("(")?;
+        let mut head = None;
+        let mut last: Option<TermPattern> = None;
+        while !self.eat(")") {
+            let (item, _) = self.parse_graph_node(triples)?;
+            let node = self.new_blank_node();
+            match last {
+                Some(previous) => triples.triples.push(TriplePattern::new(previous, iri_pattern(RDF_REST), node.clone())),
+                None => head = Some(node.clone()),
+            }
+            triples.triples.push(TriplePattern::new(node.clone(), iri_pattern(RDF_FIRST), item));
+            last = Some(node);
+        }
+        if let Some(last) = last {
+            triples.triples.push(TriplePattern::new(last, iri_pattern(RDF_REST), iri_pattern(RDF_NIL)));
+        }
+        Ok(head.unwrap_or_else(|| iri_pattern(RDF_NIL)))
+    }
+
+    // VarOrTerm: a variable, an IRI, a literal, a blank node or NIL
+    fn parse_term(&mut self) -> Result<TermPattern, QueryParsingException> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('?') | Some('$') => Ok(TermPattern::Variable(self.parse_var()?)),
+            Some('<') => Ok(TermPattern::Term(RdfTerm::Iri(self.parse_iri_ref()?))),
+            Some(quote @ ('"' | '\'')) => Ok(TermPattern::Term(self.parse_rdf_literal(quote)?)),
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' || c == '.' => {
+                Ok(TermPattern::Term(self.parse_numeric_literal()?))
+            }
+            Some('_') if self.peek_at(1) == Some(':') => Ok(TermPattern::BlankNode(self.parse_blank_node_label()?)),
+            Some('[') if self.sees_empty_brackets('[', ']') => {
+                self.bump();
+                self.expect("]")?;
+                Ok(self.new_blank_node())
+            }
+            Some('(') if self.sees_empty_brackets('(', ')') => {
+                self.bump();
+                self.expect(")")?;
+                Ok(iri_pattern(RDF_NIL))
+            }
+            _ if self.sees_keyword("true") || self.sees_keyword("false") => {
+                Ok(TermPattern::Term(self.parse_boolean_literal()))
+            }
+            _ if self.sees_prefixed_name() => Ok(TermPattern::Term(RdfTerm::Iri(self.parse_prefixed_name()?))),
+            _ => Err(self.unexpected("a variable or an RDF term")),
+        }
+    }
+
+    fn parse_var_or_iri(&mut self) -> Result<TermPattern, QueryParsingException> {
+        if self.sees_var() {
+            return Ok(TermPattern::Variable(self.parse_var()?));
+        }
+        Ok(TermPattern::Term(RdfTerm::Iri(self.parse_iri()?)))
+    }
+
+    // ('?' | '$') VARNAME, returns the name without the '?'
+    fn parse_var(&mut self) -> Result<String, QueryParsingException> {
+        if !self.sees_var() {
+            return Err(self.unexpected("a variable"));
+        }
+        self.bump();
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(|c| is_var_char(*c)) {
+            name.push(c);
+            self.bump();
+        }
+        Ok(name)
+    }
+
+    // IRIREF or PrefixedName
+    fn parse_iri(&mut self) -> Result<String, QueryParsingException> {
+        if self.sees("<") {
+            return self.parse_iri_ref();
+        }
+        if self.sees_prefixed_name() {
+            return self.parse_prefixed_name();
+        }
+        Err(self.unexpected("an IRI"))
+    }
+
+    // IRIREF, resolved against the base IRI
+    fn parse_iri_ref(&mut self) -> Result<String, QueryParsingException> {
+        let (line, column) = (self.line, self.column);
+        if self.peek() != Some('<') {
+            return Err(self.unexpected("an IRI"));
+        }
+        self.bump();
+        let mut iri = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error_at(line, column, "Unterminated IRI")),
+                Some('>') => break,
+                Some('\\') => iri.push(self.parse_uchar()?),
+                Some(c) if c <= ' ' || "<\"{}|^`".contains(c) => {
+                    return Err(self.error(&format!("Invalid character {:?} in IRI", c)));
+                }
+                Some(c) => iri.push(c),
+            }
+        }
+        if has_scheme(&iri) {
+            return Ok(iri);
+        }
+        match &self.base {
+            Some(base) => Ok(resolve_iri(base, &iri)),
+            None => Err(self.error_at(line, column, &format!("Relative IRI <{}> without a base IRI", iri))),
+        }
+    }
+
+    // PN_PREFIX? ':', returns the prefix without the ':'
+    fn parse_pname_ns(&mut self) -> Result<String, QueryParsingException> {
+        let mut prefix = String::new();
+        if matches!(self.peek(), Some(c) if is_pn_chars_base(c)) {
+            while let Some(c) = self.peek() {
+                if is_pn_chars(c) || (c == '.' && self.name_continues_after_dots(is_pn_chars)) {
+                    prefix.push(c);
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+        }
+        if self.peek() != Some(':') {
+            return Err(self.unexpected("a prefixed name"));
+        }
+        self.bump();
+        Ok(prefix)
+    }
+
+    // PNAME_LN or PNAME_NS, returns the expanded IRI
+    fn parse_prefixed_name(&mut self) -> Result<String, QueryParsingException> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        let prefix = self.parse_pname_ns()?;
+        let namespace = match self.prefix_map.get(&prefix) {
+            Some(namespace) => namespace.clone(),
+            None => return Err(self.error_at(line, column, &format!("Undefined prefix '{}:'", prefix))),
+        };
+        Ok(namespace + &self.parse_local_name()?)
+    }
+
+    // PN_LOCAL, with the escapes (PN_LOCAL_ESC) removed and the percent encodings kept
+    fn parse_local_name(&mut self) -> Result<String, QueryParsingException> {
+        let mut local = String::new();
+        match self.peek() {
+            Some(c) if is_pn_chars_u(c) || c.is_ascii_digit() || c == ':' || c == '%' || c == '\\' => {}
+            _ => return Ok(local),
+        }
+        while let Some(c) = self.peek() {
+            match c {
+                '%' => {
+                    let hex = (self.peek_at(1), self.peek_at(2));
+                    match hex {
+                        (Some(a), Some(b)) if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+                            local.extend(['%', a, b]);
+                            self.consume(3);
+                        }
+                        _ => return Err(self.error("Invalid percent encoding in local name")),
+                    }
+                }
+                '\\' => match self.peek_at(1) {
+                    Some(escaped) if "_~.-!$&'()*+,;=/?#@%".contains(escaped) => {
+                        local.push(escaped);
+                        self.consume(2);
+                    }
+                    _ => return Err(self.error("Invalid escape in local name")),
+                },
+                '.' if self.name_continues_after_dots(is_local_name_char) => {
+                    local.push('.');
+                    self.bump();
+                }
+                c if is_pn_chars(c) || c == ':' => {
+                    local.push(c);
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+        Ok(local)
+    }
+
+    // BLANK_NODE_LABEL: '_:' (PN_CHARS_U | [0-9]) ((PN_CHARS | '.')* PN_CHARS)?
+    fn parse_blank_node_label(&mut self) -> Result<String, QueryParsingException> {
+        self.consume(2);
+        let mut label = String::new();
+        match self.peek() {
+            Some(c) if is_pn_chars_u(c) || c.is_ascii_digit() => {
+                label.push(c);
+                self.bump();
+            }
+            _ => return Err(self.error("Invalid blank node label")),
+        }
+        while let Some(c) = self.peek() {
+            if is_pn_chars(c) || (c == '.' && self.name_continues_after_dots(is_pn_chars)) {
+                label.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        Ok(label)
+    }
+
+    fn parse_boolean_literal(&mut self) -> RdfTerm {
+        let value = if self.eat_keyword("true") {
+            "true"
+        } else {
+            self.eat_keyword("false");
+            "false"
+        };
+        RdfTerm::typed_literal(value, &format!("{}boolean", XSD_PREFIX))
+    }
+
+    // String (short or long, with either quote) followed by an optional LANGTAG or '^^' iri
+    fn parse_rdf_literal(&mut self, quote: char) -> Result<RdfTerm, QueryParsingException> {
+        let value = self.parse_string(quote)?;
+        match self.peek() {
+            Some('@') => {
+                self.bump();
+                let language = self.parse_language_tag()?;
+                Ok(RdfTerm::lang_literal(&value, &language))
+            }
+            Some('^') if self.peek_at(1) == Some('^') => {
+                self.consume(2);
+                let datatype = self.parse_iri()?;
+                Ok(RdfTerm::typed_literal(&value, &datatype))
+            }
+            _ => Ok(RdfTerm::simple_literal(&value)),
+        }
+    }
+
+    fn parse_string(&mut self, quote: char) -> Result<String, QueryParsingException> {
+        let (line, column) = (self.line, self.column);
+        self.bump();
+        let long = self.peek() == Some(quote) && self.peek_at(1) == Some(quote);
+        if long {
+            self.consume(2);
+        }
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error_at(line, column, "Unterminated string literal")),
+                Some(c) if c == quote => {
+                    if !long {
+                        return Ok(value);
+                    }
+                    if self.peek() == Some(quote) && self.peek_at(1) == Some(quote) {
+                        self.consume(2);
+                        return Ok(value);
+                    }
+                    value.push(c);
+                }
+                Some('\\') => value.push(self.parse_escape()?),
+                Some('\n') | Some('\r') if !long => {
+                    return Err(self.error_at(line, column, "Unterminated string literal"));
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    // LANGTAG: '@' [a-zA-Z]+ ('-' [a-zA-Z0-9]+)*   (the '@' was already consumed)
+    fn parse_language_tag(&mut self) -> Result<String, QueryParsingException> {
+        let mut language = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            language.push(c);
+            self.bump();
+        }
+        if language.is_empty() {
+            return Err(self.error("Invalid language tag"));
+        }
+        while self.peek() == Some('-') {
+            language.push('-');
+            self.bump();
+            let subtag_length = language.len();
+            while let Some(c) = self.peek().filter(char::is_ascii_alphanumeric) {
+                language.push(c);
+                self.bump();
+            }
+            if language.len() == subtag_length {
+                return Err(self.error("Invalid language tag"));
+            }
+        }
+        Ok(language)
+    }
+
+    // ECHAR or UCHAR, the backslash was already consumed
+    fn parse_escape(&mut self) -> Result<char, QueryParsingException> {
+        let escaped = match self.peek() {
+            Some('t') => '\t',
+            Some('b') => '\u{8}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('f') => '\u{c}',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('\\') => '\\',
+            Some('u') | Some('U') => return self.parse_uchar(),
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        self.bump();
+        Ok(escaped)
+    }
+
+    // UCHAR: '\u' HEX{4} | '\U' HEX{8}, the backslash was already consumed
+    fn parse_uchar(&mut self) -> Result<char, QueryParsingException> {
+        let digits = match self.bump() {
+            Some('u') => 4,
+            Some('U') => 8,
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        let mut code_point = 0;
+        for _ in 0..digits {
+            let digit = self
+                .bump()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("Invalid unicode escape"))?;
+            code_point = code_point * 16 + digit;
+        }
+        char::from_u32(code_point).ok_or_else(|| self.error("Invalid unicode code point"))
+    }
+
+    // INTEGER, DECIMAL or DOUBLE, with an optional sign
+    fn parse_numeric_literal(&mut self) -> Result<RdfTerm, QueryParsingException> {
+        let (line, column) = (self.line, self.column);
+        let mut lexical = String::new();
+        if let Some(sign @ ('+' | '-')) = self.peek() {
+            lexical.push(sign);
+            self.bump();
+        }
+        let mut digits = self.take_digits(&mut lexical);
+        let mut datatype = "integer";
+
+        // A '.' not followed by a digit (or an exponent) ends the triple
+        let exponent_follows = |c: Option<char>| matches!(c, Some('e') | Some('E'));
+        if self.peek() == Some('.')
+            && (matches!(self.peek_at(1), Some(c) if c.is_ascii_digit())
+                || (digits > 0 && exponent_follows(self.peek_at(1))))
+        {
+            lexical.push('.');
+            self.bump();
+            digits += self.take_digits(&mut lexical);
+            datatype = "decimal";
+        }
+        if digits == 0 {
+            return Err(self.error_at(line, column, "Invalid number"));
+        }
+        if exponent_follows(self.peek()) {
+            lexical.push('e');
+            self.bump();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                lexical.push(sign);
+                self.bump();
+            }
+            if self.take_digits(&mut lexical) == 0 {
+                return Err(self.error_at(line, column, "Invalid exponent"));
+            }
+            datatype = "double";
+        }
+        Ok(RdfTerm::typed_literal(&lexical, &format!("{}{}", XSD_PREFIX, datatype)))
+    }
+
+    fn take_digits(&mut self, lexical: &mut String) -> usize {
+        let mut count = 0;
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            lexical.push(c);
+            self.bump();
+            count += 1;
+        }
+        count
+    }
+
+    // Expression: ConditionalAndExpression ( '||' ConditionalAndExpression )*
+    fn parse_expression(&mut self) -> Result<Expression, QueryParsingException> {
+        let mut expression = self.parse_and_expression()?;
+        while self.eat("||") {
+            let right = self.parse_and_expression()?;
+            expression = Expression::Or(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    // ConditionalAndExpression: RelationalExpression ( '&&' RelationalExpression )*
+    fn parse_and_expression(&mut self) -> Result<Expression, QueryParsingException> {
+        let mut expression = self.parse_relational_expression()?;
+        while self.eat("&&") {
+            let right = self.parse_relational_expression()?;
+            expression = Expression::And(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    // RelationalExpression: NumericExpression ( ( '=' | '!=' | '<' | '>' | '<=' | '>=' )
+    // NumericExpression | 'IN' ExpressionList | 'NOT' 'IN' ExpressionList )?
+    fn parse_relational_expression(&mut self) -> Result<Expression, QueryParsingException> {
+        let left = Box::new(self.parse_additive_expression()?);
+        let operators: [(&str, BinaryOperator); 6] = [
+            ("=", Expression::Equal),
+            ("!=", Expression::NotEqual),
+            ("<=", Expression::LessOrEqual),
+            (">=", Expression::GreaterOrEqual),
+            ("<", Expression::Less),
+            (">", Expression::Greater),
+        ];
+        for (symbol, operator) in operators {
+            if self.eat(symbol) {
+                let right = self.parse_additive_expression()?;
+                return Ok(operator(left, Box::new(right)));
+            }
+        }
+        if self.eat_keyword("IN") {
+            return Ok(Expression::In(left, self.parse_expression_list()?));
+        }
+        if self.eat_keyword("NOT") {
+            self.expect_keyword("IN")?;
+            return Ok(Expression::NotIn(left, self.parse_expression_list()?));
+        }
+        Ok(*left)
+    }
+
+    // ExpressionList: NIL | '(' Expression ( ',' Expression )* ')'
+    fn parse_expression_list(&mut self) -> Result<Vec<Expression>, QueryParsingException> {
+        self.expect("(")?;
+        let mut expressions = Vec::new();
+        if self.eat(")") {
+            return Ok(expressions);
+        }
+        loop {
+            expressions.push(self.parse_expression()?);
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect(")")?;
+        Ok(expressions)
+    }
+
+    // AdditiveExpression: MultiplicativeExpression ( ( '+' | '-' ) MultiplicativeExpression )*
+    fn parse_additive_expression(&mut self) -> Result<Expression, QueryParsingException> {
+        let mut expression = self.parse_multiplicative_expression()?;
+        loop {
+            if self.eat("+") {
+                let right = self.parse_multiplicative_expression()?;
+                expression = Expression::Add(Box::new(expression), Box::new(right));
+            } else if self.eat("-") {
+                let right = self.parse_multiplicative_expression()?;
+                expression = Expression::Subtract(Box::new(expression), Box::new(right));
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    // MultiplicativeExpression: UnaryExpression ( ( '*' | '/' ) UnaryExpression )*
+    fn parse_multiplicative_expression(&mut self) -> Result<Expression, QueryParsingException> {
+        let mut expression = self.parse_unary_expression()?;
+        loop {
+            if self.eat("*") {
+                let right = self.parse_unary_expression()?;
+                expression = Expression::Multiply(Box::new(expression), Box::new(right));
+            } else if self.eat("/") {
+                let right = self.parse_unary_expression()?;
+                expression = Expression::Divide(Box::new(expression), Box::new(right));
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    // UnaryExpression: ( '!' | '+' | '-' )? PrimaryExpression
+    fn parse_unary_expression(&mut self) -> Result<Expression, QueryParsingException> {
+        if self.eat("!") {
+            return Ok(Expression::Not(Box::new(self.parse_unary_expression()?)));
+        }
+        if self.eat("+") {
+            return Ok(Expression::UnaryPlus(Box::new(self.parse_unary_expression()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expression::UnaryMinus(Box::new(self.parse_unary_expression()?)));
+        }
+        self.parse_primary_expression()
+    }
+
+    // PrimaryExpression: BrackettedExpression | BuiltInCall | iriOrFunction | RDFLiteral
+    // | NumericLiteral | BooleanLiteral | Var (the aggregates are built-in calls)
+    fn parse_primary_expression(&mut self) -> Result<Expression, QueryParsingException> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.bump();
+                let expression = self.parse_expression()?;
+                self.expect(")")?;
+                return Ok(expression);
+            }
+            Some('?') | Some('$') => return Ok(Expression::Variable(self.parse_var()?)),
+            Some(quote @ ('"' | '\'')) => return Ok(Expression::Constant(self.parse_rdf_literal(quote)?)),
+            Some(c) if c.is_ascii_digit() || (c == '.' && matches!(self.peek_at(1), Some(d) if d.is_ascii_digit())) => {
+                return Ok(Expression::Constant(self.parse_numeric_literal()?));
+            }
+            Some('<') => return self.parse_iri_or_function(),
+            _ => {}
+        }
+        if self.sees_keyword("true") || self.sees_keyword("false") {
+            return Ok(Expression::Constant(self.parse_boolean_literal()));
+        }
+        if self.sees_prefixed_name() {
+            return self.parse_iri_or_function();
+        }
+        if self.sees_builtin() {
+            return self.parse_builtin_call();
+        }
+        Err(self.unexpected("an expression"))
+    }
+
+    // iri ArgList?
+    fn parse_iri_or_function(&mut self) -> Result<Expression, QueryParsingException> {
+        let iri = self.parse_iri()?;
+        if !self.sees("(") {
+            return Ok(Expression::Constant(RdfTerm::Iri(iri)));
+        }
+        if self.sees_empty_brackets('(', ')') {
+            self.consume(1);
+            self.expect(")")?;
+            return Ok(Expression::Function(iri, Vec::new()));
+        }
+        self.expect("(")?;
+        if self.sees_keyword("DISTINCT") {
+            return Err(self.error("DISTINCT is only allowed in aggregates"));
+        }
+        let mut arguments = Vec::new();
+        loop {
+            arguments.push(self.parse_expression()?);
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect(")")?;
+        Ok(Expression::Function(iri, arguments))
+    }
+
+    // BuiltInCall, with its number of arguments checked
+    fn parse_builtin_call(&mut self) -> Result<Expression, QueryParsingException> {
+        let (line, column) = (self.line, self.column);
+        let name = self.peek_name();
+        if name == "NOT" {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+            return Ok(Expression::NotExists(Box::new(self.parse_group_graph_pattern()?)));
+        }
+        self.consume(name.len());
+        if name == "EXISTS" {
+            return Ok(Expression::Exists(Box::new(self.parse_group_graph_pattern()?)));
+        }
+        if name == "BOUND" {
+            self.expect("(")?;
+            let variable = self.parse_var()?;
+            self.expect(")")?;
+            return Ok(Expression::Builtin(name, vec![Expression::Variable(variable)]));
+        }
+        if let Some((_, function)) = AGGREGATES.iter().find(|(aggregate, _)| *aggregate == name) {
+            return self.parse_aggregate(*function, line, column);
+        }
+
+        let (min, max) = BUILTINS.iter().find(|(builtin, _, _)| *builtin == name).map(|(_, min, max)| (*min, *max)).unwrap();
+        let arguments = self.parse_expression_list()?;
+        if arguments.len() < min || max.is_some_and(|max| arguments.len() > max) {
+            return Err(self.error_at(line, column, &format!("Wrong number of arguments for {}", name)));
+        }
+        Ok(Expression::Builtin(name, arguments))
+    }
+
+    // Aggregate: the call is replaced by an internal variable, which the group of the
+    // SELECT binds to the value of the aggregate
+    fn parse_aggregate(&mut self, function: AggregateFunction, line: u64, column: u64) -> Result<Expression, QueryParsingException> {
+        if self.aggregates.is_none() {
+            return Err(self.error_at(line, column, "Aggregates are only allowed in SELECT, HAVING and ORDER BY"));
+        }
+        self.expect("(")?;
+        let distinct = self.eat_keyword("DISTINCT");
+        // Aggregates can not be nested
+        let outer_aggregates = self.aggregates.take();
+        let expression = if function == AggregateFunction::Count && self.eat("*") {
+            None
+        } else {
+            Some(Box::new(self.parse_expression()?))
+        };
+        self.aggregates = outer_aggregates;
+        let mut separator = None;
+        if function == AggregateFunction::GroupConcat && self.eat(";") {
+            self.expect_keyword("SEPARATOR")?;
+            self.expect("=")?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(quote @ ('"' | '\'')) => separator = Some(self.parse_string(quote)?),
+                _ => return Err(self.unexpected("a string")),
+            }
+        }
+        self.expect(")")?;
+
+        let aggregate = Aggregate { function, distinct, expression, separator };
+        if let Some((name, _)) = self.aggregates.as_ref().unwrap().iter().find(|(_, a)| *a == aggregate) {
+            return Ok(Expression::Variable(name.clone()));
+        }
+        let name = self.new_internal_var("agg");
+        self.aggregates.as_mut().unwrap().push((name.clone(), aggregate));
+        Ok(Expression::Variable(name))
+    }
+}
+
+// Joins two patterns, merging basic graph patterns and skipping empty groups
+fn join(left: GraphPattern, right: GraphPattern) -> GraphPattern {
+    match (left, right) {
+        (GraphPattern::Bgp(mut left), GraphPattern::Bgp(right)) => {
+            left.extend(right);
+            GraphPattern::Bgp(left)
+        }
+        (GraphPattern::Bgp(left), right) if left.is_empty() => right,
+        (left, GraphPattern::Bgp(right)) if right.is_empty() => left,
+        (left, right) => GraphPattern::Join(Box::new(left), Box::new(right)),
+    }
+}
+
+fn iri_pattern(iri: &str) -> TermPattern {
+    TermPattern::Term(RdfTerm::iri(iri))
+}
+
+// VARNAME characters: the ones of PN_CHARS but '-'
+fn is_var_char(c: char) -> bool {
+    is_pn_chars(c) && c != '-'
+}
+
+fn is_local_name_char(c: char) -> bool {
+    is_pn_chars(c) || c == ':' || c == '%' || c == '\\'
+}
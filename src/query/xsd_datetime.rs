@@ -0,0 +1,352 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::query::rdf_terms::{XSD_DATE, XSD_DATE_TIME};
+use crate::query::xsd_decimal::XsdDecimal;
+
+// A value of xsd:dateTime or xsd:date. The time of a date is midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XsdDateTime {
+    pub year: i64,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub nanosecond: u32,
+    // Offset from UTC in minutes, `None` when the value has no timezone
+    pub timezone: Option<i16>,
+    pub date_only: bool,
+}
+
+impl XsdDateTime {
+    // '-'? yyyy '-' mm '-' dd 'T' hh ':' mm ':' ss ('.' s+)? timezone?
+    // 24:00:00 is the first instant of the next day.
+    pub fn parse_date_time(lexical: &str) -> Option<Self> {
+        let (mut value, rest) = parse_date_part(lexical)?;
+        let rest = rest.strip_prefix('T')?;
+        if rest.len() < 8 || rest.as_bytes()[2] != b':' || rest.as_bytes()[5] != b':' {
+            return None;
+        }
+        value.hour = parse_digits(&rest[0..2])? as u8;
+        value.minute = parse_digits(&rest[3..5])? as u8;
+        value.second = parse_digits(&rest[6..8])? as u8;
+        let mut rest = &rest[8..];
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return None;
+            }
+            // Digits past nanoseconds are dropped
+            let nanos = &fraction[..digits.min(9)];
+            value.nanosecond = parse_digits(nanos)? as u32 * 10u32.pow(9 - nanos.len() as u32);
+            rest = &fraction[digits..];
+        }
+        value.timezone = parse_timezone(rest)?;
+        value.date_only = false;
+
+        if value.minute > 59 || value.second > 59 {
+            return None;
+        }
+        match value.hour {
+            0..=23 => {}
+            24 if value.minute == 0 && value.second == 0 && value.nanosecond == 0 => {
+                value.hour = 0;
+                value.add_day();
+            }
+            _ => return None,
+        }
+        Some(value)
+    }
+
+    // '-'? yyyy '-' mm '-' dd timezone?
+    pub fn parse_date(lexical: &str) -> Option<Self> {
+        let (mut value, rest) = parse_date_part(lexical)?;
+        value.timezone = parse_timezone(rest)?;
+        Some(value)
+    }
+
+    // The instant at `seconds` since 1970-01-01T00:00:00Z, in UTC
+    pub fn from_unix_time(seconds: i64, nanosecond: u32) -> Self {
+        let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        XsdDateTime {
+            year,
+            month,
+            day,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+            nanosecond,
+            timezone: Some(0),
+            date_only: false,
+        }
+    }
+
+    // Seconds since 1970-01-01T00:00:00Z and nanoseconds. A value without timezone is
+    // taken as UTC, the implicit timezone of the queries.
+    pub fn instant(&self) -> (i64, u32) {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let seconds = days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        (seconds - self.timezone.unwrap_or(0) as i64 * 60, self.nanosecond)
+    }
+
+    // Order of the instants, the one of op:dateTime-less-than and op:date-less-than
+    pub fn compare(&self, other: &Self) -> Ordering {
+        self.instant().cmp(&other.instant())
+    }
+
+    pub fn datatype(&self) -> &'static str {
+        if self.date_only {
+            XSD_DATE
+        } else {
+            XSD_DATE_TIME
+        }
+    }
+
+    fn add_day(&mut self) {
+        self.day += 1;
+        if self.day > days_in_month(self.year, self.month) {
+            self.day = 1;
+            self.month += 1;
+            if self.month > 12 {
+                self.month = 1;
+                self.year += 1;
+            }
+        }
+    }
+}
+
+pub fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+pub fn days_in_month(year: i64, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Year, month and day of a number of days since 1970-01-01, with the proleptic Gregorian
+// calendar (the algorithm of Howard Hinnant's chrono-compatible date library)
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u8, day as u8)
+}
+
+// Days since 1970-01-01 of a date, the inverse of `civil_from_days`
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// The date at the start of a dateTime or date, and the rest of the input
+fn parse_date_part(lexical: &str) -> Option<(XsdDateTime, &str)> {
+    let (negative, unsigned) = match lexical.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, lexical),
+    };
+    let year_digits = unsigned.bytes().take_while(u8::is_ascii_digit).count();
+    // Years have at least four digits, and no leading zeros beyond that
+    if year_digits < 4 || (year_digits > 4 && unsigned.starts_with('0')) || year_digits > 18 {
+        return None;
+    }
+    let year = parse_digits(&unsigned[..year_digits])? as i64;
+    let rest = &unsigned[year_digits..];
+    if rest.len() < 6 || rest.as_bytes()[0] != b'-' || rest.as_bytes()[3] != b'-' {
+        return None;
+    }
+    let month = parse_digits(&rest[1..3])? as u8;
+    let day = parse_digits(&rest[4..6])? as u8;
+    let year = if negative { -year } else { year };
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let value = XsdDateTime {
+        year,
+        month,
+        day,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        nanosecond: 0,
+        timezone: None,
+        date_only: true,
+    };
+    Some((value, &rest[6..]))
+}
+
+// 'Z' | ('+' | '-') hh ':' mm, or nothing. `None` if the timezone is invalid.
+fn parse_timezone(lexical: &str) -> Option<Option<i16>> {
+    let sign = match lexical.as_bytes().first() {
+        None => return Some(None),
+        Some(b'Z') if lexical.len() == 1 => return Some(Some(0)),
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return None,
+    };
+    if lexical.len() != 6 || lexical.as_bytes()[3] != b':' {
+        return None;
+    }
+    let hours = parse_digits(&lexical[1..3])? as i16;
+    let minutes = parse_digits(&lexical[4..6])? as i16;
+    if minutes > 59 || hours * 60 + minutes > 14 * 60 {
+        return None;
+    }
+    Some(Some(sign * (hours * 60 + minutes)))
+}
+
+fn parse_digits(digits: &str) -> Option<u64> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+// Canonical lexical form: timezone 'Z' for UTC and no trailing zeros in the fraction
+impl fmt::Display for XsdDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.year < 0 {
+            f.write_str("-")?;
+        }
+        write!(f, "{:04}-{:02}-{:02}", self.year.unsigned_abs(), self.month, self.day)?;
+        if !self.date_only {
+            write!(f, "T{:02}:{:02}:{:02}", self.hour, self.minute, self.second)?;
+            if self.nanosecond > 0 {
+                let fraction = format!("{:09}", self.nanosecond);
+                write!(f, ".{}", fraction.trim_end_matches('0'))?;
+            }
+        }
+        match self.timezone {
+            None => Ok(()),
+            Some(0) => f.write_str("Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                write!(f, "{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+            }
+        }
+    }
+}
+
+// A value of xsd:duration, xsd:yearMonthDuration or xsd:dayTimeDuration: a number of
+// months and a number of seconds, both with the sign of the duration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XsdDuration {
+    pub months: i64,
+    pub seconds: XsdDecimal,
+}
+
+impl XsdDuration {
+    // '-'? 'P' (n 'Y')? (n 'M')? (n 'D')? ('T' (n 'H')? (n 'M')? (n ('.' n)? 'S')?)? with at
+    // least one part, and at least one after 'T'
+    pub fn parse(lexical: &str) -> Option<Self> {
+        let (negative, rest) = match lexical.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, lexical),
+        };
+        let rest = rest.strip_prefix('P')?;
+        let (date, time) = match rest.split_once('T') {
+            Some((_, "")) => return None,
+            Some((date, time)) => (date, Some(time)),
+            None => (rest, None),
+        };
+        let mut months = 0i64;
+        let mut seconds = XsdDecimal::zero();
+        let mut parts = 0;
+        for (number, designator) in duration_parts(date, "YMD")? {
+            let number: i64 = parse_digits(number)?.try_into().ok()?;
+            match designator {
+                'Y' => months = months.checked_add(number.checked_mul(12)?)?,
+                'M' => months = months.checked_add(number)?,
+                'D' => seconds = seconds.add(&XsdDecimal::from(number.checked_mul(86400)?)),
+                _ => return None,
+            }
+            parts += 1;
+        }
+        for (number, designator) in duration_parts(time.unwrap_or_default(), "HMS")? {
+            let value = match designator {
+                'S' if !number.starts_with('.') && !number.ends_with('.') => XsdDecimal::parse(number)?,
+                _ => XsdDecimal::from(i64::try_from(parse_digits(number)?).ok()?),
+            };
+            let factor = match designator {
+                'H' => 3600,
+                'M' => 60,
+                'S' => 1,
+                _ => return None,
+            };
+            seconds = seconds.add(&value.multiply(&XsdDecimal::from(factor)));
+            parts += 1;
+        }
+        if parts == 0 {
+            return None;
+        }
+        if negative {
+            months = -months;
+            seconds = seconds.negate();
+        }
+        Some(Self { months, seconds })
+    }
+
+    // xsd:yearMonthDuration, a duration without days nor time
+    pub fn parse_year_month(lexical: &str) -> Option<Self> {
+        Self::parse(lexical).filter(|_| !lexical.contains(['D', 'T']))
+    }
+
+    // xsd:dayTimeDuration, a duration without years nor months
+    pub fn parse_day_time(lexical: &str) -> Option<Self> {
+        let date = lexical.split('T').next().unwrap_or_default();
+        Self::parse(lexical).filter(|_| !date.contains(['Y', 'M']))
+    }
+
+    // Durations are ordered when both the months and the seconds are: P1M and P30D are not
+    pub fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.months.cmp(&other.months), self.seconds.cmp(&other.seconds)) {
+            (months, Ordering::Equal) => Some(months),
+            (Ordering::Equal, seconds) => Some(seconds),
+            (months, seconds) if months == seconds => Some(months),
+            _ => None,
+        }
+    }
+}
+
+// The numbers of a part of a duration with the designator after each one, which must be
+// in the order of `designators` and at most once
+fn duration_parts<'a>(lexical: &'a str, designators: &str) -> Option<Vec<(&'a str, char)>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in lexical.char_indices() {
+        if c.is_ascii_digit() || c == '.' {
+            continue;
+        }
+        if i == start {
+            return None;
+        }
+        parts.push((&lexical[start..i], c));
+        start = i + 1;
+    }
+    if start != lexical.len() {
+        return None;
+    }
+    let order = |c: char| designators.find(c);
+    if parts.iter().any(|&(_, c)| order(c).is_none()) || parts.windows(2).any(|pair| order(pair[0].1) >= order(pair[1].1)) {
+        return None;
+    }
+    Some(parts)
+}
@@ -0,0 +1,314 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// Fractional digits of a division that does not end before
+const DIVISION_SCALE: u32 = 24;
+
+// A value of xsd:decimal with any number of digits: the integer `digits` (decimal digits,
+// the most significant first) divided by 10^scale. Values are kept normalized, without
+// leading zeros nor trailing zeros in the fraction, so equal values have equal fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct XsdDecimal {
+    negative: bool,
+    digits: Vec<u8>,
+    scale: u32,
+}
+
+impl XsdDecimal {
+    pub fn zero() -> Self {
+        Self { negative: false, digits: Vec::new(), scale: 0 }
+    }
+
+    // ('+' | '-')? [0-9]* ('.' [0-9]*)?, with at least one digit
+    pub fn parse(lexical: &str) -> Option<Self> {
+        let (negative, unsigned) = match lexical.as_bytes().first() {
+            Some(b'-') => (true, &lexical[1..]),
+            Some(b'+') => (false, &lexical[1..]),
+            _ => (false, lexical),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let digits = integer.bytes().chain(fraction.bytes()).map(|b| b - b'0').collect();
+        Some(Self::new(negative, digits, fraction.len() as u32))
+    }
+
+    // The shortest decimal that reads back as the double. Different doubles give different
+    // decimals in the same order.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        // Display of f64 writes the shortest digits without an exponent
+        Self::parse(&value.to_string())
+    }
+
+    // The shortest decimal that reads back as the float
+    pub fn from_f32(value: f32) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        Self::parse(&value.to_string())
+    }
+
+    fn new(negative: bool, mut digits: Vec<u8>, mut scale: u32) -> Self {
+        while scale > 0 && digits.last() == Some(&0) {
+            digits.pop();
+            scale -= 1;
+        }
+        let leading_zeros = digits.iter().take_while(|&&digit| digit == 0).count();
+        digits.drain(..leading_zeros);
+        if digits.is_empty() {
+            return Self::zero();
+        }
+        Self { negative, digits, scale }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.scale == 0
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    // `None` when it has a fraction or does not fit
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.scale > 0 {
+            return None;
+        }
+        self.integer_string().parse().ok()
+    }
+
+    // The lexical form of xsd:integer of a value without fraction
+    pub fn integer_string(&self) -> String {
+        let mut lexical = String::with_capacity(self.digits.len() + 1);
+        if self.negative {
+            lexical.push('-');
+        }
+        if self.digits.is_empty() {
+            lexical.push('0');
+        }
+        lexical.extend(self.digits.iter().map(|digit| (b'0' + digit) as char));
+        lexical
+    }
+
+    pub fn negate(&self) -> Self {
+        Self::new(!self.negative && !self.is_zero(), self.digits.clone(), self.scale)
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { negative: false, ..self.clone() }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (self.scaled_digits(scale), other.scaled_digits(scale));
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_magnitudes(&a, &b), scale);
+        }
+        match compare_magnitudes(&a, &b) {
+            Ordering::Less => Self::new(other.negative, subtract_magnitudes(&b, &a), scale),
+            _ => Self::new(self.negative, subtract_magnitudes(&a, &b), scale),
+        }
+    }
+
+    pub fn subtract(&self, other: &Self) -> Self {
+        self.add(&other.negate())
+    }
+
+    pub fn multiply(&self, other: &Self) -> Self {
+        Self::new(self.negative != other.negative, multiply_magnitudes(&self.digits, &other.digits), self.scale + other.scale)
+    }
+
+    // The quotient truncated to DIVISION_SCALE fractional digits (or the scale of the
+    // dividend if it has more), `None` when dividing by zero
+    pub fn divide(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        // a / b = (A / 10^sa) / (B / 10^sb), so the digits of the quotient with scale s are
+        // A * 10^(sb + s - sa) / B
+        let scale = DIVISION_SCALE.max(self.scale);
+        let mut dividend = self.digits.clone();
+        dividend.resize(dividend.len() + (other.scale + scale - self.scale) as usize, 0);
+        Some(Self::new(self.negative != other.negative, divide_magnitudes(&dividend, &other.digits), scale))
+    }
+
+    // Rounded towards zero to an integer
+    pub fn truncate(&self) -> Self {
+        let integer_digits = self.digits.len().saturating_sub(self.scale as usize);
+        Self::new(self.negative, self.digits[..integer_digits].to_vec(), 0)
+    }
+
+    pub fn floor(&self) -> Self {
+        let truncated = self.truncate();
+        if self.negative && truncated != *self {
+            truncated.subtract(&Self::from(1))
+        } else {
+            truncated
+        }
+    }
+
+    pub fn ceil(&self) -> Self {
+        let truncated = self.truncate();
+        if !self.negative && truncated != *self {
+            truncated.add(&Self::from(1))
+        } else {
+            truncated
+        }
+    }
+
+    // fn:round: to the nearest integer, halves rounded up
+    pub fn round(&self) -> Self {
+        self.add(&Self { negative: false, digits: vec![5], scale: 1 }).floor()
+    }
+
+    // The digits of the value times 10^scale, for a scale not below the own one
+    fn scaled_digits(&self, scale: u32) -> Vec<u8> {
+        let mut digits = self.digits.clone();
+        digits.resize(digits.len() + (scale - self.scale) as usize, 0);
+        digits
+    }
+}
+
+impl From<i64> for XsdDecimal {
+    fn from(value: i64) -> Self {
+        Self::parse(&value.to_string()).unwrap()
+    }
+}
+
+impl Ord for XsdDecimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => return Ordering::Greater,
+            (true, false) => return Ordering::Less,
+            _ => {}
+        }
+        let scale = self.scale.max(other.scale);
+        let order = compare_magnitudes(&self.scaled_digits(scale), &other.scaled_digits(scale));
+        if self.negative {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+}
+
+impl PartialOrd for XsdDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Canonical form: at least one digit on each side of the point
+impl fmt::Display for XsdDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("-")?;
+        }
+        let scale = self.scale as usize;
+        let mut digits: Vec<char> = self.digits.iter().map(|digit| (b'0' + digit) as char).collect();
+        if digits.len() <= scale {
+            let mut padded = vec!['0'; scale + 1 - digits.len()];
+            padded.append(&mut digits);
+            digits = padded;
+        }
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        let integer: String = integer.iter().collect();
+        let fraction: String = fraction.iter().collect();
+        write!(f, "{}.{}", integer, if fraction.is_empty() { "0" } else { &fraction })
+    }
+}
+
+// Magnitudes are digits without leading zeros, the most significant first
+
+fn compare_magnitudes(a: &[u8], b: &[u8]) -> Ordering {
+    let (a, b) = (strip_leading_zeros(a), strip_leading_zeros(b));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+fn strip_leading_zeros(digits: &[u8]) -> &[u8] {
+    let leading_zeros = digits.iter().take_while(|&&digit| digit == 0).count();
+    &digits[leading_zeros..]
+}
+
+fn add_magnitudes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    let (mut a, mut b) = (a.iter().rev(), b.iter().rev());
+    loop {
+        let (x, y) = (a.next(), b.next());
+        if x.is_none() && y.is_none() {
+            break;
+        }
+        let digit = x.unwrap_or(&0) + y.unwrap_or(&0) + carry;
+        sum.push(digit % 10);
+        carry = digit / 10;
+    }
+    if carry > 0 {
+        sum.push(carry);
+    }
+    sum.reverse();
+    sum
+}
+
+// a - b, for a not below b
+fn subtract_magnitudes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    let mut b = b.iter().rev();
+    for &x in a.iter().rev() {
+        let y = b.next().unwrap_or(&0) + borrow;
+        if x >= y {
+            difference.push(x - y);
+            borrow = 0;
+        } else {
+            difference.push(x + 10 - y);
+            borrow = 1;
+        }
+    }
+    difference.reverse();
+    difference
+}
+
+fn multiply_magnitudes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate().rev() {
+        for (j, &y) in b.iter().enumerate().rev() {
+            product[i + j + 1] += x as u32 * y as u32;
+        }
+    }
+    for i in (1..product.len()).rev() {
+        product[i - 1] += product[i] / 10;
+        product[i] %= 10;
+    }
+    product.into_iter().map(|digit| digit as u8).collect()
+}
+
+// Long division, without the remainder
+fn divide_magnitudes(dividend: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut quotient = Vec::with_capacity(dividend.len());
+    let mut remainder: Vec<u8> = Vec::new();
+    for &digit in dividend {
+        remainder.push(digit);
+        let mut times = 0;
+        while compare_magnitudes(&remainder, divisor) != Ordering::Less {
+            remainder = strip_leading_zeros(&subtract_magnitudes(&remainder, divisor)).to_vec();
+            times += 1;
+        }
+        quotient.push(times);
+    }
+    quotient
+}
@@ -1,46 +1,43 @@
+use std::sync::Arc;
+
 use tokio::net::TcpListener;
 
 use crate::query::query_services::process_query;
-use crate::import::import_services::load_data_into_database;
+use crate::query::quad_model_graph::QuadModelGraph;
+use crate::import::import_services::{load_quad_model_into_database, ImportOptions};
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::catalog::{Catalog, DataModel};
 
 use std::error::Error;
 
-pub async fn startup_server() -> Result<(), Box<dyn Error>> {
-    // Initialize the graph database (replace with actual database initialization)
-
-    // Start the query service
-    let query_service = tokio::spawn(async {
-
-        // Define the listener on port 1234
-        let listener = match TcpListener::bind("127.0.0.1:1234").await {
-            Ok(listener) => listener,
-            Err(e) => {
-                eprintln!("Failed to bind to port 1234: {}", e);
-                return; // Exit the task if we can't bind to the port
-            }
-        };
-
-        println!("Listening on port 1234");
-
-        loop {
-            // Accept connections and process them
-            let (socket, _) = listener.accept().await.unwrap();
-            process_query(socket).await;
-        }
-        
-    });
-
-    // Start the data loading service
-    let data_loading_service = tokio::spawn(async {
-        // Implement data loading logic here
-        load_data_into_database().await;
-        
-    });
-
-    // You can handle the join results here (omitted for brevity)
-    let _ = tokio::join!(query_service, data_loading_service);
-    Ok(())
+// Serves the property graph in the database folder of `buffer_manager`, after creating
+// it from the QuadModel files of `import_options.files` (if any)
+pub async fn startup_server(
+    import_options: ImportOptions,
+    buffer_manager: Arc<BufferManager>,
+    string_populate_size: u64,
+    port: u16) -> Result<(), Box<dyn Error>> {
+
+    // Initialize the graph database
+    let db_folder = buffer_manager.file_manager().db_folder().to_path_buf();
+    if let Some(statistics) = load_quad_model_into_database(import_options).await.map_err(|e| e as Box<dyn Error>)? {
+        println!("Loaded {}", statistics);
+    }
+    let catalog = Catalog::load(&db_folder).map_err(|e| e as Box<dyn Error>)?;
+    if catalog.data_model != DataModel::QuadModel {
+        return Err(format!("{} contains a {} database, not a property graph", db_folder.display(), catalog.data_model).into());
+    }
+    println!("Opened {}", catalog);
+    let graph = Arc::new(QuadModelGraph::open(&buffer_manager, string_populate_size).map_err(|e| e as Box<dyn Error>)?);
+
+    // Define the listener on the given port
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+
+    println!("Listening on port {}", port);
+
+    loop {
+        // Accept connections and process them
+        let (socket, _) = listener.accept().await?;
+        tokio::spawn(process_query(socket, Arc::clone(&graph)));
+    }
 }
-
-
-
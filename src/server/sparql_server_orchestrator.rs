@@ -1,32 +1,50 @@
 use crate::network::sparql_servers::Server;
-use crate::import::import_services::load_data_into_database;
+use crate::import::import_services::{load_data_into_database, ImportOptions};
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::catalog::{Catalog, DataModel};
+use crate::storage::string_manager::StringManager;
 
 use std::error::Error;
-
-pub async fn startup_server() -> Result<(), Box<dyn Error>> {
+use std::sync::Arc;
+
+pub async fn startup_server(
+    import_options: ImportOptions,
+    buffer_manager: Arc<BufferManager>,
+    string_populate_size: u64,
+    port: u16,
+    timeout: tokio::time::Duration) -> Result<(), Box<dyn Error>> {
     
     // Initialize the SPARQL server
     let server = Server::new();
 
     // Load data into the server
-    load_data_into_database().await;    
-
-    // Start the server on a specific port with a specified number of worker threads and a timeout
-    let runtime = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap();
-
-    runtime.block_on(async {
-         //Initialization and output code remains the same
-         //let result = startup_server().await;
-
-        Server::run(
-            server, 
-            1234,
-            tokio::time::Duration::from_secs(30)).await.unwrap();
-
-        });
+    if let Some(statistics) = load_data_into_database(import_options).await.map_err(|e| e as Box<dyn Error>)? {
+        println!("Import finished: {}", statistics);
+    }
+
+    let db_folder = buffer_manager.file_manager().db_folder().to_path_buf();
+    let catalog = Catalog::load(&db_folder).map_err(|e| e as Box<dyn Error>)?;
+    if catalog.data_model != DataModel::Rdf {
+        return Err(format!("{} contains a {} database, not an RDF one", db_folder.display(), catalog.data_model).into());
+    }
+    println!("Opened {}", catalog);
+
+    // Open the dictionary, loading the start of it in memory
+    let string_manager = StringManager::open(&buffer_manager, string_populate_size).map_err(|e| e as Box<dyn Error>)?;
+    println!("Dictionary opened: {} strings, {} bytes in memory", string_manager.len(), string_manager.populated_size());
+    {
+        let mut server = server.lock().await;
+        server.iri_prefixes = Some(Arc::new(catalog.prefixes));
+        server.buffer_manager = Some(buffer_manager);
+        server.string_manager = Some(Arc::new(string_manager));
+    }
+
+    // Start the server on the given port. This already runs inside the tokio runtime
+    // created by main, so no nested runtime is built here.
+    Server::run(
+        server, 
+        port,
+        timeout).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
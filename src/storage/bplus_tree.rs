@@ -0,0 +1,501 @@
+use std::sync::Arc;
+
+use crate::storage::buffer_manager::{BufferManager, PinnedPage};
+use crate::storage::exceptions::StorageError;
+use crate::storage::file_manager::{FileId, PageData, PageId, PAGE_SIZE};
+
+pub const BPLUS_TREE_MAGIC: &[u8; 8] = b"MDBBPT01";
+
+// Page 0 of the file holds the metadata: magic, tuple width, root page and tuple count
+const META_PAGE: u64 = 0;
+const META_WIDTH: usize = 8;
+const META_ROOT: usize = 16;
+const META_COUNT: usize = 24;
+
+// Every node starts with its kind and number of entries. Leaves also keep the next leaf.
+const NODE_KIND: usize = 0;
+const NODE_COUNT: usize = 4;
+const NODE_NEXT: usize = 8;
+const NODE_HEADER: usize = 16;
+
+const LEAF: u8 = 1;
+const INTERNAL: u8 = 2;
+
+const NO_PAGE: u64 = u64::MAX;
+
+// A set of tuples of `N` integers, sorted lexicographically, in a B+tree whose nodes
+// are pages of a file read through the buffer manager.
+//
+// Leaves hold the tuples and are linked, so a range scan descends once and then follows
+// the leaves. Internal nodes hold `count` separators and `count + 1` children, the
+// separator `i` being the smallest tuple under child `i + 1`. Deletions do not merge
+// nodes, a leaf may become empty.
+pub struct BPlusTree<const N: usize> {
+    buffer: Arc<BufferManager>,
+    file_id: FileId,
+    root: u64,
+    len: u64,
+}
+
+enum Node<const N: usize> {
+    Leaf { tuples: Vec<[u64; N]>, next: u64 },
+    Internal { keys: Vec<[u64; N]>, children: Vec<u64> },
+}
+
+impl<const N: usize> BPlusTree<N> {
+    pub const LEAF_CAPACITY: usize = (PAGE_SIZE - NODE_HEADER) / (8 * N);
+    pub const INTERNAL_CAPACITY: usize = (PAGE_SIZE - NODE_HEADER - 8) / (8 * N + 8);
+
+    // Opens the tree of the file `name` of the database folder
+    pub fn open(buffer: &Arc<BufferManager>, name: &str) -> Result<Self, StorageError> {
+        let file_id = buffer.get_file_id(name)?;
+        if buffer.file_manager().count_pages(file_id)? == 0 {
+            return Err(format!("{} is not a B+tree", name).into());
+        }
+        let meta = buffer.pin(PageId::new(file_id, META_PAGE))?;
+        let data = meta.read();
+        if &data[..8] != BPLUS_TREE_MAGIC || read_u64(&data, META_WIDTH) != N as u64 {
+            return Err(format!("{} is not a B+tree of {} columns", name, N).into());
+        }
+        Ok(Self {
+            buffer: Arc::clone(buffer),
+            file_id,
+            root: read_u64(&data, META_ROOT),
+            len: read_u64(&data, META_COUNT),
+        })
+    }
+
+    // Creates the tree of the file `name` from tuples in strictly increasing order.
+    // The nodes are built bottom-up and completely filled.
+    pub fn bulk_load<E>(
+        buffer: &Arc<BufferManager>,
+        name: &str,
+        tuples: impl IntoIterator<Item = Result<[u64; N], E>>,
+    ) -> Result<Self, StorageError>
+    where
+        E: Into<StorageError>,
+    {
+        let file_id = buffer.get_file_id(name)?;
+        if buffer.file_manager().count_pages(file_id)? != 0 {
+            return Err(format!("{} already exists", name).into());
+        }
+        drop(buffer.append_page(file_id)?);
+        let mut tree = Self { buffer: Arc::clone(buffer), file_id, root: NO_PAGE, len: 0 };
+
+        // One partially filled node per level, the leaves are level 0
+        let mut leaf = tree.new_node(LEAF)?;
+        let mut leaf_first = None;
+        let mut levels: Vec<LevelBuilder<N>> = Vec::new();
+        let mut last: Option<[u64; N]> = None;
+
+        for tuple in tuples {
+            let tuple = tuple.map_err(Into::into)?;
+            if last.is_some_and(|last| last >= tuple) {
+                return Err(format!("The tuples loaded into {} are not sorted", name).into());
+            }
+            last = Some(tuple);
+
+            if node_count(&leaf.read()) == Self::LEAF_CAPACITY {
+                let next = tree.new_node(LEAF)?;
+                write_u64(&mut leaf.write(), NODE_NEXT, next.page_id().page_number);
+                let full = std::mem::replace(&mut leaf, next);
+                tree.add_child(&mut levels, 0, leaf_first.take().unwrap(), full.page_id().page_number)?;
+            }
+            let mut data = leaf.write();
+            let count = node_count(&data);
+            write_tuple(&mut data, NODE_HEADER, count, &tuple);
+            set_node_count(&mut data, count + 1);
+            leaf_first.get_or_insert(tuple);
+            tree.len += 1;
+        }
+
+        // Close the open nodes from the bottom, the last one is the root
+        let mut child = (leaf_first.unwrap_or([0; N]), leaf.page_id().page_number);
+        drop(leaf);
+        // Closing a full node may add a level, so the number of levels is not fixed
+        let mut level = 0;
+        while level < levels.len() {
+            tree.add_child(&mut levels, level, child.0, child.1)?;
+            let node = &levels[level];
+            child = (node.first, node.page.page_id().page_number);
+            level += 1;
+        }
+        tree.root = child.1;
+        drop(levels);
+        tree.write_meta()?;
+        Ok(tree)
+    }
+
+    // Creates an empty tree in the file `name`
+    pub fn create(buffer: &Arc<BufferManager>, name: &str) -> Result<Self, StorageError> {
+        Self::bulk_load(buffer, name, std::iter::empty::<Result<[u64; N], StorageError>>())
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, tuple: &[u64; N]) -> Result<bool, StorageError> {
+        match self.range(tuple, tuple)?.next() {
+            Some(result) => result.map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    // Tuples between `min` and `max` (both included), in order
+    pub fn range(&self, min: &[u64; N], max: &[u64; N]) -> Result<BPlusTreeIter<'_, N>, StorageError> {
+        let mut page = self.root;
+        loop {
+            let node = self.buffer.pin(PageId::new(self.file_id, page))?;
+            let data = node.read();
+            if data[NODE_KIND] == LEAF {
+                let position = lower_bound(&data, node_count(&data), min);
+                drop(data);
+                return Ok(BPlusTreeIter { tree: self, leaf: Some(node), position, max: *max });
+            }
+            page = child_at(&data, N, upper_bound(&data, node_count(&data), min));
+        }
+    }
+
+    // Tuples starting with `prefix`, in order. The prefix may have up to `N` values.
+    pub fn scan_prefix(&self, prefix: &[u64]) -> Result<BPlusTreeIter<'_, N>, StorageError> {
+        let mut min = [0; N];
+        let mut max = [u64::MAX; N];
+        min[..prefix.len()].copy_from_slice(prefix);
+        max[..prefix.len()].copy_from_slice(prefix);
+        self.range(&min, &max)
+    }
+
+    // Adds a tuple, returns false if it was already in the tree
+    pub fn insert(&mut self, tuple: &[u64; N]) -> Result<bool, StorageError> {
+        let (path, leaf) = self.find_leaf(tuple)?;
+        let (mut tuples, next) = match self.read_node(leaf)? {
+            Node::Leaf { tuples, next } => (tuples, next),
+            Node::Internal { .. } => unreachable!(),
+        };
+        let position = match tuples.binary_search(tuple) {
+            Ok(_) => return Ok(false),
+            Err(position) => position,
+        };
+        tuples.insert(position, *tuple);
+        self.len += 1;
+
+        if tuples.len() <= Self::LEAF_CAPACITY {
+            self.write_node(leaf, &Node::Leaf { tuples, next })?;
+        } else {
+            // Split the leaf in halves, the new leaf goes to the right
+            let right_tuples = tuples.split_off(tuples.len() / 2);
+            let separator = right_tuples[0];
+            let right = self.new_node(LEAF)?.page_id().page_number;
+            self.write_node(right, &Node::Leaf { tuples: right_tuples, next })?;
+            self.write_node(leaf, &Node::Leaf { tuples, next: right })?;
+            self.insert_separator(path, separator, right)?;
+        }
+        self.write_meta()?;
+        Ok(true)
+    }
+
+    // Removes a tuple, returns false if it was not in the tree
+    pub fn delete(&mut self, tuple: &[u64; N]) -> Result<bool, StorageError> {
+        let (_, leaf) = self.find_leaf(tuple)?;
+        let page = self.buffer.pin(PageId::new(self.file_id, leaf))?;
+        let (count, position) = {
+            let data = page.read();
+            let count = node_count(&data);
+            (count, lower_bound(&data, count, tuple))
+        };
+        if position == count || tuple_at::<N>(&page.read(), NODE_HEADER, position) != *tuple {
+            return Ok(false);
+        }
+        {
+            let mut data = page.write();
+            let start = NODE_HEADER + position * 8 * N;
+            data.copy_within(start + 8 * N..NODE_HEADER + count * 8 * N, start);
+            set_node_count(&mut data, count - 1);
+        }
+        drop(page);
+        self.len -= 1;
+        self.write_meta()?;
+        Ok(true)
+    }
+
+    // Writes back the modified pages of the tree
+    pub fn flush(&self) -> Result<(), StorageError> {
+        self.buffer.flush_file(self.file_id)
+    }
+
+    // Internal nodes from the root to the leaf where `tuple` belongs, with the child
+    // followed at each one, and the leaf
+    fn find_leaf(&self, tuple: &[u64; N]) -> Result<(Vec<(u64, usize)>, u64), StorageError> {
+        let mut path = Vec::new();
+        let mut page = self.root;
+        loop {
+            let node = self.buffer.pin(PageId::new(self.file_id, page))?;
+            let data = node.read();
+            if data[NODE_KIND] == LEAF {
+                return Ok((path, page));
+            }
+            let index = upper_bound(&data, node_count(&data), tuple);
+            path.push((page, index));
+            page = child_at(&data, N, index);
+        }
+    }
+
+    // Adds `separator` and its right child `right` to the parents of a split node,
+    // splitting them in turn when they overflow
+    fn insert_separator(&mut self, mut path: Vec<(u64, usize)>, mut separator: [u64; N], mut right: u64) -> Result<(), StorageError> {
+        while let Some((page, index)) = path.pop() {
+            let (mut keys, mut children) = match self.read_node(page)? {
+                Node::Internal { keys, children } => (keys, children),
+                Node::Leaf { .. } => unreachable!(),
+            };
+            keys.insert(index, separator);
+            children.insert(index + 1, right);
+            if keys.len() <= Self::INTERNAL_CAPACITY {
+                return self.write_node(page, &Node::Internal { keys, children });
+            }
+
+            // The middle key moves up to the parent
+            let middle = keys.len() / 2;
+            let right_keys = keys.split_off(middle + 1);
+            separator = keys.pop().unwrap();
+            let right_children = children.split_off(middle + 1);
+            right = self.new_node(INTERNAL)?.page_id().page_number;
+            self.write_node(right, &Node::Internal { keys: right_keys, children: right_children })?;
+            self.write_node(page, &Node::Internal { keys, children })?;
+        }
+
+        // The root was split
+        let root = self.new_node(INTERNAL)?.page_id().page_number;
+        self.write_node(root, &Node::Internal { keys: vec![separator], children: vec![self.root, right] })?;
+        self.root = root;
+        Ok(())
+    }
+
+    // Adds a child to the open node of `level` during a bulk load. A full node is
+    // closed and added to the level above.
+    fn add_child(&self, levels: &mut Vec<LevelBuilder<N>>, level: usize, first: [u64; N], page: u64) -> Result<(), StorageError> {
+        if level == levels.len() {
+            levels.push(LevelBuilder { page: self.new_node(INTERNAL)?, first, children: 0 });
+        }
+        if levels[level].children == Self::INTERNAL_CAPACITY + 1 {
+            let next = LevelBuilder { page: self.new_node(INTERNAL)?, first, children: 0 };
+            let full = std::mem::replace(&mut levels[level], next);
+            let full_page = full.page.page_id().page_number;
+            drop(full.page);
+            self.add_child(levels, level + 1, full.first, full_page)?;
+        }
+
+        let node = &mut levels[level];
+        let mut data = node.page.write();
+        if node.children > 0 {
+            write_tuple(&mut data, NODE_HEADER, node.children - 1, &first);
+            set_node_count(&mut data, node.children);
+        }
+        write_u64(&mut data, child_offset(N, node.children), page);
+        node.children += 1;
+        Ok(())
+    }
+
+    fn new_node(&self, kind: u8) -> Result<PinnedPage, StorageError> {
+        let page = self.buffer.append_page(self.file_id)?;
+        {
+            let mut data = page.write();
+            data[NODE_KIND] = kind;
+            write_u64(&mut data, NODE_NEXT, NO_PAGE);
+        }
+        Ok(page)
+    }
+
+    fn read_node(&self, page: u64) -> Result<Node<N>, StorageError> {
+        let node = self.buffer.pin(PageId::new(self.file_id, page))?;
+        let data = node.read();
+        let count = node_count(&data);
+        let tuples = (0..count).map(|i| tuple_at(&data, NODE_HEADER, i)).collect();
+        Ok(if data[NODE_KIND] == LEAF {
+            Node::Leaf { tuples, next: read_u64(&data, NODE_NEXT) }
+        } else {
+            Node::Internal { keys: tuples, children: (0..=count).map(|i| child_at(&data, N, i)).collect() }
+        })
+    }
+
+    fn write_node(&self, page: u64, node: &Node<N>) -> Result<(), StorageError> {
+        let pinned = self.buffer.pin(PageId::new(self.file_id, page))?;
+        let mut data = pinned.write();
+        let tuples = match node {
+            Node::Leaf { tuples, next } => {
+                data[NODE_KIND] = LEAF;
+                write_u64(&mut data, NODE_NEXT, *next);
+                tuples
+            }
+            Node::Internal { keys, children } => {
+                data[NODE_KIND] = INTERNAL;
+                for (i, &child) in children.iter().enumerate() {
+                    write_u64(&mut data, child_offset(N, i), child);
+                }
+                keys
+            }
+        };
+        for (i, tuple) in tuples.iter().enumerate() {
+            write_tuple(&mut data, NODE_HEADER, i, tuple);
+        }
+        set_node_count(&mut data, tuples.len());
+        Ok(())
+    }
+
+    fn write_meta(&self) -> Result<(), StorageError> {
+        let meta = self.buffer.pin(PageId::new(self.file_id, META_PAGE))?;
+        let mut data = meta.write();
+        data[..8].copy_from_slice(BPLUS_TREE_MAGIC);
+        write_u64(&mut data, META_WIDTH, N as u64);
+        write_u64(&mut data, META_ROOT, self.root);
+        write_u64(&mut data, META_COUNT, self.len);
+        Ok(())
+    }
+}
+
+struct LevelBuilder<const N: usize> {
+    page: PinnedPage,
+    // Smallest tuple under the node
+    first: [u64; N],
+    children: usize,
+}
+
+// Iterator over a range of a tree. The current leaf stays pinned until the iterator
+// moves to the next one.
+pub struct BPlusTreeIter<'a, const N: usize> {
+    tree: &'a BPlusTree<N>,
+    leaf: Option<PinnedPage>,
+    position: usize,
+    max: [u64; N],
+}
+
+impl<const N: usize> BPlusTreeIter<'_, N> {
+    // Moves forward so that the next tuple returned is the first one not smaller than
+    // `key`, which must not be smaller than the tuples already returned. The current
+    // leaf is searched first, the tree is only descended again when `key` is past it.
+    pub fn seek(&mut self, key: &[u64; N]) -> Result<(), StorageError> {
+        let leaf = match &self.leaf {
+            Some(leaf) => leaf,
+            None => return Ok(()),
+        };
+        {
+            let data = leaf.read();
+            let count = node_count(&data);
+            if count > 0 && tuple_at::<N>(&data, NODE_HEADER, count - 1) >= *key {
+                self.position = self.position.max(lower_bound(&data, count, key));
+                return Ok(());
+            }
+        }
+        let BPlusTreeIter { leaf, position, .. } = self.tree.range(key, &self.max)?;
+        self.leaf = leaf;
+        self.position = position;
+        Ok(())
+    }
+}
+
+impl<const N: usize> Iterator for BPlusTreeIter<'_, N> {
+    type Item = Result<[u64; N], StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let leaf = self.leaf.as_ref()?;
+            let next = {
+                let data = leaf.read();
+                if self.position < node_count(&data) {
+                    let tuple = tuple_at::<N>(&data, NODE_HEADER, self.position);
+                    if tuple > self.max {
+                        drop(data);
+                        self.leaf = None;
+                        return None;
+                    }
+                    self.position += 1;
+                    return Some(Ok(tuple));
+                }
+                read_u64(&data, NODE_NEXT)
+            };
+
+            self.leaf = None;
+            if next == NO_PAGE {
+                return None;
+            }
+            match self.tree.buffer.pin(PageId::new(self.tree.file_id, next)) {
+                Ok(page) => self.leaf = Some(page),
+                Err(e) => return Some(Err(e)),
+            }
+            self.position = 0;
+        }
+    }
+}
+
+fn read_u64(data: &PageData, offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn write_u64(data: &mut PageData, offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn node_count(data: &PageData) -> usize {
+    u32::from_le_bytes(data[NODE_COUNT..NODE_COUNT + 4].try_into().unwrap()) as usize
+}
+
+fn set_node_count(data: &mut PageData, count: usize) {
+    data[NODE_COUNT..NODE_COUNT + 4].copy_from_slice(&(count as u32).to_le_bytes());
+}
+
+fn tuple_at<const N: usize>(data: &PageData, start: usize, index: usize) -> [u64; N] {
+    let mut tuple = [0; N];
+    for (i, value) in tuple.iter_mut().enumerate() {
+        *value = read_u64(data, start + (index * N + i) * 8);
+    }
+    tuple
+}
+
+fn write_tuple<const N: usize>(data: &mut PageData, start: usize, index: usize, tuple: &[u64; N]) {
+    for (i, &value) in tuple.iter().enumerate() {
+        write_u64(data, start + (index * N + i) * 8, value);
+    }
+}
+
+// The children of an internal node are stored after room for every separator
+fn child_offset(width: usize, index: usize) -> usize {
+    let capacity = (PAGE_SIZE - NODE_HEADER - 8) / (8 * width + 8);
+    NODE_HEADER + capacity * 8 * width + index * 8
+}
+
+fn child_at(data: &PageData, width: usize, index: usize) -> u64 {
+    read_u64(data, child_offset(width, index))
+}
+
+// Position of the first tuple not smaller than `tuple`
+fn lower_bound<const N: usize>(data: &PageData, count: usize, tuple: &[u64; N]) -> usize {
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let middle = (low + high) / 2;
+        if tuple_at::<N>(data, NODE_HEADER, middle) < *tuple {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+// Position of the first tuple greater than `tuple`, the child of an internal node to follow
+fn upper_bound<const N: usize>(data: &PageData, count: usize, tuple: &[u64; N]) -> usize {
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let middle = (low + high) / 2;
+        if tuple_at::<N>(data, NODE_HEADER, middle) <= *tuple {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
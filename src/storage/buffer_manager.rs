@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::storage::exceptions::{BufferException, StorageError};
+use crate::storage::file_manager::{FileId, FileManager, PageData, PageId, PAGE_SIZE};
+use crate::storage::wal::{Wal, WalSyncPolicy};
+
+// Number of pages fitting in a buffer of `megabytes` MiB
+pub fn pages_in_megabytes(megabytes: u64) -> usize {
+    (megabytes as usize * 1024 * 1024 / PAGE_SIZE).max(1)
+}
+
+struct Frame {
+    pins: AtomicUsize,
+    referenced: AtomicBool,
+    dirty: AtomicBool,
+    // Changed since its content was last logged, only used with a write-ahead log
+    unlogged: AtomicBool,
+    // Content of the last commit while the current transaction changes the page, when
+    // its file does not have it yet
+    committed: Mutex<Option<Box<PageData>>>,
+    data: RwLock<Box<PageData>>,
+}
+
+impl Frame {
+    fn new() -> Self {
+        Self {
+            pins: AtomicUsize::new(0),
+            referenced: AtomicBool::new(false),
+            dirty: AtomicBool::new(false),
+            unlogged: AtomicBool::new(false),
+            committed: Mutex::new(None),
+            data: RwLock::new(Box::new([0; PAGE_SIZE])),
+        }
+    }
+}
+
+// A page kept in memory while the handle is alive. Dropping the handle unpins the page,
+// after that the buffer may evict it (writing it back first if it was modified).
+pub struct PinnedPage {
+    page_id: PageId,
+    frame: Arc<Frame>,
+}
+
+impl PinnedPage {
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Box<PageData>> {
+        self.frame.data.read().unwrap()
+    }
+
+    // Marks the page as dirty, so it is written back before being evicted
+    pub fn write(&self) -> RwLockWriteGuard<'_, Box<PageData>> {
+        let data = self.frame.data.write().unwrap();
+        // The first change after a commit keeps the committed content a checkpoint writes back
+        if !self.frame.unlogged.swap(true, Ordering::AcqRel) && self.frame.dirty.load(Ordering::Acquire) {
+            *self.frame.committed.lock().unwrap() = Some(data.clone());
+        }
+        self.frame.dirty.store(true, Ordering::Release);
+        data
+    }
+
+    pub fn unpin(self) {}
+}
+
+impl Drop for PinnedPage {
+    fn drop(&mut self) {
+        self.frame.pins.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// Frames of a pool, replaced with the clock algorithm. Frames are allocated the
+// first time they are needed, so an unused pool takes no memory.
+//
+// With a write-ahead log, a page changed by the current transaction stays in the pool
+// until the transaction commits, and the log is forced to disk before a page is
+// written back.
+struct BufferPool {
+    capacity: usize,
+    wal: Option<Arc<Wal>>,
+    frames: Vec<Arc<Frame>>,
+    owners: Vec<Option<PageId>>,
+    page_table: HashMap<PageId, usize>,
+    clock_hand: usize,
+}
+
+impl BufferPool {
+    fn new(capacity: usize, wal: Option<Arc<Wal>>) -> Self {
+        Self {
+            capacity,
+            wal,
+            frames: Vec::new(),
+            owners: Vec::new(),
+            page_table: HashMap::new(),
+            clock_hand: 0,
+        }
+    }
+
+    // Pins `page_id`. When the page is not in the pool it is read from its file,
+    // unless `read` is false (the page is new and starts zeroed).
+    fn pin(&mut self, file_manager: &FileManager, page_id: PageId, read: bool) -> Result<PinnedPage, StorageError> {
+        if let Some(&index) = self.page_table.get(&page_id) {
+            let frame = &self.frames[index];
+            frame.pins.fetch_add(1, Ordering::AcqRel);
+            frame.referenced.store(true, Ordering::Release);
+            return Ok(PinnedPage { page_id, frame: Arc::clone(frame) });
+        }
+
+        let index = self.get_free_frame(file_manager)?;
+        let frame = &self.frames[index];
+        {
+            let mut data = frame.data.write().unwrap();
+            if read {
+                file_manager.read_page(page_id, &mut data)?;
+            } else {
+                data.fill(0);
+            }
+        }
+        frame.pins.store(1, Ordering::Release);
+        frame.referenced.store(true, Ordering::Release);
+        frame.dirty.store(!read, Ordering::Release);
+        frame.unlogged.store(!read, Ordering::Release);
+        *frame.committed.lock().unwrap() = None;
+        self.owners[index] = Some(page_id);
+        self.page_table.insert(page_id, index);
+        Ok(PinnedPage { page_id, frame: Arc::clone(frame) })
+    }
+
+    // Index of an unpinned frame not holding any page. A victim that was modified is
+    // written back before being reused.
+    fn get_free_frame(&mut self, file_manager: &FileManager) -> Result<usize, StorageError> {
+        if self.frames.len() < self.capacity {
+            self.frames.push(Arc::new(Frame::new()));
+            self.owners.push(None);
+            return Ok(self.frames.len() - 1);
+        }
+
+        // Two turns are enough: the first one clears the reference bits
+        for _ in 0..2 * self.capacity {
+            let index = self.clock_hand;
+            self.clock_hand = (self.clock_hand + 1) % self.capacity;
+            let frame = &self.frames[index];
+            if frame.pins.load(Ordering::Acquire) > 0 {
+                continue;
+            }
+            if frame.referenced.swap(false, Ordering::AcqRel) {
+                continue;
+            }
+            if self.wal.is_some() && frame.unlogged.load(Ordering::Acquire) {
+                continue;
+            }
+            if let Some(victim) = self.owners[index].take() {
+                if frame.dirty.load(Ordering::Acquire) {
+                    if let Some(wal) = &self.wal {
+                        if let Err(e) = wal.sync() {
+                            self.owners[index] = Some(victim);
+                            return Err(e);
+                        }
+                    }
+                }
+                if frame.dirty.swap(false, Ordering::AcqRel) {
+                    if let Err(e) = file_manager.write_page(victim, &frame.data.read().unwrap()) {
+                        frame.dirty.store(true, Ordering::Release);
+                        self.owners[index] = Some(victim);
+                        return Err(e.into());
+                    }
+                }
+                self.page_table.remove(&victim);
+            }
+            return Ok(index);
+        }
+        let message = match self.wal {
+            Some(_) => format!("All the {} pages of the buffer are pinned or changed by the current transaction", self.capacity),
+            None => format!("All the {} pages of the buffer are pinned", self.capacity),
+        };
+        Err(Box::new(BufferException::new(&message)))
+    }
+
+    // Writes back the modified pages of `file_id`, or of every file if it is `None`.
+    // With a write-ahead log, the pages with changes not logged yet are written back as
+    // of the last commit.
+    fn flush(&self, file_manager: &FileManager, file_id: Option<FileId>) -> Result<(), StorageError> {
+        let mut log_synced = false;
+        for (frame, owner) in self.frames.iter().zip(&self.owners) {
+            if let Some(page_id) = owner {
+                if file_id.is_some_and(|file_id| file_id != page_id.file_id) {
+                    continue;
+                }
+                if let Some(wal) = &self.wal {
+                    let unlogged = frame.unlogged.load(Ordering::Acquire);
+                    let mut committed = frame.committed.lock().unwrap();
+                    if (unlogged && committed.is_none()) || !frame.dirty.load(Ordering::Acquire) {
+                        continue;
+                    }
+                    if !log_synced {
+                        wal.sync()?;
+                        log_synced = true;
+                    }
+                    if unlogged {
+                        file_manager.write_page(*page_id, committed.as_ref().unwrap())?;
+                        *committed = None;
+                        continue;
+                    }
+                }
+                if frame.dirty.swap(false, Ordering::AcqRel) {
+                    let data = frame.data.read().unwrap();
+                    if let Err(e) = file_manager.write_page(*page_id, &data) {
+                        frame.dirty.store(true, Ordering::Release);
+                        return Err(e.into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Forgets the pages of `file_id` without writing them back
+    fn discard(&mut self, file_id: FileId) {
+        for (frame, owner) in self.frames.iter().zip(self.owners.iter_mut()) {
+            if owner.is_some_and(|page_id| page_id.file_id == file_id) {
+                self.page_table.remove(&owner.take().unwrap());
+                frame.dirty.store(false, Ordering::Release);
+                *frame.committed.lock().unwrap() = None;
+                frame.referenced.store(false, Ordering::Release);
+            }
+        }
+    }
+}
+
+// Temporary pages of one worker (intermediate results of a query that do not fit in
+// memory). They live in a file of their own that is deleted with the pool.
+pub struct PrivateBufferPool {
+    file_manager: Arc<FileManager>,
+    pool: BufferPool,
+    temp_file: Option<FileId>,
+    page_count: u64,
+}
+
+impl PrivateBufferPool {
+    fn new(file_manager: Arc<FileManager>, capacity: usize) -> Self {
+        Self {
+            file_manager,
+            pool: BufferPool::new(capacity, None),
+            temp_file: None,
+            page_count: 0,
+        }
+    }
+
+    // Number of pages the pool keeps in memory
+    pub fn capacity(&self) -> usize {
+        self.pool.capacity
+    }
+
+    // Number of temporary pages created since the last `clear`
+    pub fn page_count(&self) -> u64 {
+        self.page_count
+    }
+
+    // A new zeroed temporary page, returned pinned. Its number is used to pin it again.
+    pub fn new_page(&mut self) -> Result<PinnedPage, StorageError> {
+        let file_id = match self.temp_file {
+            Some(file_id) => file_id,
+            None => *self.temp_file.insert(self.file_manager.create_temp_file()?),
+        };
+        let page_id = PageId::new(file_id, self.page_count);
+        let page = self.pool.pin(&self.file_manager, page_id, false)?;
+        self.page_count += 1;
+        Ok(page)
+    }
+
+    pub fn pin(&mut self, page_number: u64) -> Result<PinnedPage, StorageError> {
+        match self.temp_file {
+            Some(file_id) if page_number < self.page_count => {
+                self.pool.pin(&self.file_manager, PageId::new(file_id, page_number), true)
+            }
+            _ => Err(Box::new(BufferException::new(&format!(
+                "Temporary page {} does not exist",
+                page_number
+            )))),
+        }
+    }
+
+    // Drops every temporary page, to be called when the query of the worker finishes
+    pub fn clear(&mut self) -> Result<(), StorageError> {
+        if let Some(file_id) = self.temp_file.take() {
+            self.pool.discard(file_id);
+            self.file_manager.remove_file(file_id)?;
+        }
+        self.page_count = 0;
+        Ok(())
+    }
+}
+
+impl Drop for PrivateBufferPool {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+// Caches the pages of the files of the database folder in a shared pool of
+// `shared_pages` pages. Each worker also gets a private pool of `private_pages`
+// pages for its temporary pages.
+//
+// When opened with a write-ahead log, changes become durable with `commit` and the
+// folder is recovered from the log when it is opened. Without one (e.g. while bulk
+// loading a new database) pages are simply written back.
+pub struct BufferManager {
+    file_manager: Arc<FileManager>,
+    wal: Option<Arc<Wal>>,
+    shared_pool: Mutex<BufferPool>,
+    private_pools: Vec<Mutex<PrivateBufferPool>>,
+}
+
+impl BufferManager {
+    pub fn new(db_folder: &Path, shared_pages: usize, private_pages: usize, workers: usize) -> Self {
+        Self::build(db_folder, shared_pages, private_pages, workers, None)
+    }
+
+    // Opens the buffer of a database folder with its write-ahead log, redoing first the
+    // transactions committed before a crash
+    pub fn with_wal(
+        db_folder: &Path,
+        shared_pages: usize,
+        private_pages: usize,
+        workers: usize,
+        sync_policy: WalSyncPolicy,
+        checkpoint_size: u64,
+    ) -> Result<Self, StorageError> {
+        let wal = Arc::new(Wal::open(db_folder, sync_policy, checkpoint_size)?);
+        Ok(Self::build(db_folder, shared_pages, private_pages, workers, Some(wal)))
+    }
+
+    fn build(db_folder: &Path, shared_pages: usize, private_pages: usize, workers: usize, wal: Option<Arc<Wal>>) -> Self {
+        let file_manager = Arc::new(FileManager::new(db_folder));
+        let private_pools = (0..workers)
+            .map(|_| Mutex::new(PrivateBufferPool::new(Arc::clone(&file_manager), private_pages)))
+            .collect();
+        Self {
+            file_manager,
+            shared_pool: Mutex::new(BufferPool::new(shared_pages, wal.clone())),
+            wal,
+            private_pools,
+        }
+    }
+
+    pub fn wal(&self) -> Option<&Arc<Wal>> {
+        self.wal.as_ref()
+    }
+
+    pub fn file_manager(&self) -> &FileManager {
+        &self.file_manager
+    }
+
+    pub fn shared_capacity(&self) -> usize {
+        self.shared_pool.lock().unwrap().capacity
+    }
+
+    // Number of workers, each with its private pool
+    pub fn workers(&self) -> usize {
+        self.private_pools.len()
+    }
+
+    pub fn get_file_id(&self, name: &str) -> Result<FileId, StorageError> {
+        Ok(self.file_manager.get_file_id(name)?)
+    }
+
+    pub fn pin(&self, page_id: PageId) -> Result<PinnedPage, StorageError> {
+        self.shared_pool.lock().unwrap().pin(&self.file_manager, page_id, true)
+    }
+
+    // Adds a zeroed page at the end of the file, returned pinned. Pages written
+    // through the buffer but not yet flushed count as part of the file.
+    pub fn append_page(&self, file_id: FileId) -> Result<PinnedPage, StorageError> {
+        let mut pool = self.shared_pool.lock().unwrap();
+        let in_buffer = pool
+            .owners
+            .iter()
+            .flatten()
+            .filter(|page_id| page_id.file_id == file_id)
+            .map(|page_id| page_id.page_number + 1)
+            .max()
+            .unwrap_or(0);
+        let page_number = self.file_manager.count_pages(file_id)?.max(in_buffer);
+        pool.pin(&self.file_manager, PageId::new(file_id, page_number), false)
+    }
+
+    // Writes back every modified page of the shared pool
+    pub fn flush(&self) -> Result<(), StorageError> {
+        self.shared_pool.lock().unwrap().flush(&self.file_manager, None)
+    }
+
+    // Writes back the modified pages of a file and forces them to disk
+    pub fn flush_file(&self, file_id: FileId) -> Result<(), StorageError> {
+        self.shared_pool.lock().unwrap().flush(&self.file_manager, Some(file_id))?;
+        Ok(self.file_manager.sync(file_id)?)
+    }
+
+    // Logs the pages changed since the last commit and commits them, checkpointing when
+    // the log grows too big. Without a write-ahead log the changes are written back.
+    pub fn commit(&self) -> Result<(), StorageError> {
+        let wal = match &self.wal {
+            Some(wal) => wal,
+            None => return self.flush(),
+        };
+        {
+            let pool = self.shared_pool.lock().unwrap();
+            for (frame, owner) in pool.frames.iter().zip(&pool.owners) {
+                if let Some(page_id) = owner {
+                    if frame.unlogged.load(Ordering::Acquire) {
+                        let name = self.file_manager.file_name(page_id.file_id)?;
+                        wal.log_write(&name, page_id.page_number * PAGE_SIZE as u64, &frame.data.read().unwrap()[..])?;
+                        frame.unlogged.store(false, Ordering::Release);
+                        *frame.committed.lock().unwrap() = None;
+                    }
+                }
+            }
+        }
+        wal.commit()?;
+        if wal.needs_checkpoint() {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    // Writes back every committed page, as of the last commit, and empties the log
+    pub fn checkpoint(&self) -> Result<(), StorageError> {
+        match &self.wal {
+            Some(wal) => {
+                let pool = self.shared_pool.lock().unwrap();
+                pool.flush(&self.file_manager, None)?;
+                wal.finish_checkpoint()
+            }
+            None => self.flush(),
+        }
+    }
+
+    // Private pool of the worker `worker_index` (see `ThreadInfo::worker_index`)
+    pub fn private_pool(&self, worker_index: usize) -> Result<MutexGuard<'_, PrivateBufferPool>, StorageError> {
+        match self.private_pools.get(worker_index) {
+            Some(pool) => Ok(pool.lock().unwrap()),
+            None => Err(Box::new(BufferException::new(&format!(
+                "There is no private buffer for worker {}",
+                worker_index
+            )))),
+        }
+    }
+}
+
+impl Drop for BufferManager {
+    fn drop(&mut self) {
+        if self.wal.as_ref().is_some_and(|wal| wal.crashed()) {
+            return;
+        }
+        if let Err(e) = self.checkpoint() {
+            eprintln!("Failed to write back the buffer: {}", e);
+        }
+    }
+}
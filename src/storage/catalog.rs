@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::storage::exceptions::{CatalogException, StorageError};
+use crate::storage::iri_prefixes::IriPrefixes;
+
+// Description of the database of a folder. It is written when the database is created,
+// once everything else is in place, so a folder without it is not a (complete) database.
+pub const CATALOG_FILE: &str = "catalog.dat";
+pub const CATALOG_FILE_MAGIC: &[u8; 8] = b"MDBCATLG";
+
+// Version of the on-disk format, increased whenever the files of a database change in
+// a way older or newer servers can not read
+pub const FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataModel {
+    // Triples of the default graph and quads of named graphs, served with SPARQL
+    Rdf,
+    // Nodes, labels, properties and edges of a QuadModel property graph
+    QuadModel,
+}
+
+impl DataModel {
+    fn code(self) -> u8 {
+        match self {
+            DataModel::Rdf => 1,
+            DataModel::QuadModel => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(DataModel::Rdf),
+            2 => Some(DataModel::QuadModel),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DataModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataModel::Rdf => write!(f, "RDF quads"),
+            DataModel::QuadModel => write!(f, "property graph"),
+        }
+    }
+}
+
+// The file starts with the magic and the format version. The rest is written in that
+// version: the data model (u8) and then the indexes, counts, prefixes and parameters,
+// each as a number of entries (u32) followed by the entries. Strings are stored as
+// their length (u32) followed by their bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Catalog {
+    pub format_version: u32,
+    pub data_model: DataModel,
+    // Files of the indexes, in the database folder
+    pub indexes: Vec<String>,
+    // Number of elements of each kind when the database was created (e.g. "triples")
+    pub counts: BTreeMap<String, u64>,
+    pub prefixes: IriPrefixes,
+    // Options the database was created with (e.g. "memory_budget")
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    // An empty catalog of the current format version
+    pub fn new(data_model: DataModel) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            data_model,
+            indexes: Vec::new(),
+            counts: BTreeMap::new(),
+            prefixes: IriPrefixes::default(),
+            parameters: BTreeMap::new(),
+        }
+    }
+
+    // Reads the catalog of a database folder, failing when there is none or it was
+    // written with another version of the format
+    pub fn load(db_folder: &Path) -> Result<Self, StorageError> {
+        let path = db_folder.join(CATALOG_FILE);
+        if !path.exists() {
+            return Err(Box::new(CatalogException::new(&format!(
+                "{} does not contain a database: {} is missing",
+                db_folder.display(),
+                CATALOG_FILE
+            ))));
+        }
+        let mut reader = BufReader::new(File::open(&path)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != CATALOG_FILE_MAGIC {
+            return Err(Box::new(CatalogException::new(&format!("{} is not a catalog", path.display()))));
+        }
+        let format_version = read_u32(&mut reader)?;
+        if format_version != FORMAT_VERSION {
+            return Err(Box::new(CatalogException::new(&format!(
+                "The database in {} uses version {} of the format, this server only reads version {}",
+                db_folder.display(),
+                format_version,
+                FORMAT_VERSION
+            ))));
+        }
+
+        let mut code = [0; 1];
+        reader.read_exact(&mut code)?;
+        let data_model = DataModel::from_code(code[0])
+            .ok_or_else(|| CatalogException::new(&format!("Unknown data model {} in {}", code[0], path.display())))?;
+        let mut catalog = Self::new(data_model);
+        for _ in 0..read_u32(&mut reader)? {
+            catalog.indexes.push(read_string(&mut reader)?);
+        }
+        for _ in 0..read_u32(&mut reader)? {
+            let name = read_string(&mut reader)?;
+            let mut count = [0; 8];
+            reader.read_exact(&mut count)?;
+            catalog.counts.insert(name, u64::from_le_bytes(count));
+        }
+        let mut prefixes = Vec::new();
+        for _ in 0..read_u32(&mut reader)? {
+            prefixes.push(read_string(&mut reader)?);
+        }
+        catalog.prefixes = IriPrefixes::new(prefixes);
+        for _ in 0..read_u32(&mut reader)? {
+            let name = read_string(&mut reader)?;
+            catalog.parameters.insert(name, read_string(&mut reader)?);
+        }
+        Ok(catalog)
+    }
+
+    // Writes the catalog to a temporary file first, so a crash never leaves half of it
+    pub fn save(&self, db_folder: &Path) -> io::Result<()> {
+        let temp_path = db_folder.join(format!("{}.tmp", CATALOG_FILE));
+        {
+            let file = File::create(&temp_path)?;
+            let mut out = BufWriter::new(&file);
+            out.write_all(CATALOG_FILE_MAGIC)?;
+            out.write_all(&self.format_version.to_le_bytes())?;
+            out.write_all(&[self.data_model.code()])?;
+            out.write_all(&(self.indexes.len() as u32).to_le_bytes())?;
+            for index in &self.indexes {
+                write_string(&mut out, index)?;
+            }
+            out.write_all(&(self.counts.len() as u32).to_le_bytes())?;
+            for (name, count) in &self.counts {
+                write_string(&mut out, name)?;
+                out.write_all(&count.to_le_bytes())?;
+            }
+            out.write_all(&(self.prefixes.len() as u32).to_le_bytes())?;
+            for prefix in self.prefixes.prefixes() {
+                write_string(&mut out, prefix)?;
+            }
+            out.write_all(&(self.parameters.len() as u32).to_le_bytes())?;
+            for (name, value) in &self.parameters {
+                write_string(&mut out, name)?;
+                write_string(&mut out, value)?;
+            }
+            out.flush()?;
+            drop(out);
+            file.sync_all()?;
+        }
+        fs::rename(temp_path, db_folder.join(CATALOG_FILE))
+    }
+
+    // Count of `name`, 0 if the catalog does not have it
+    pub fn count(&self, name: &str) -> u64 {
+        self.counts.get(name).copied().unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Catalog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} database (format version {})", self.data_model, self.format_version)?;
+        for (name, count) in &self.counts {
+            write!(f, ", {} {}", count, name.replace('_', " "))?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(reader)? as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_string(out: &mut impl Write, string: &str) -> io::Result<()> {
+    out.write_all(&(string.len() as u32).to_le_bytes())?;
+    out.write_all(string.as_bytes())
+}
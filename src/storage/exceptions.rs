@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::fmt;
+
+// Errors of the storage layer: I/O errors of the database files or buffer exceptions
+pub type StorageError = Box<dyn Error + Send + Sync>;
+
+// Used when a page can not be brought into a buffer pool, because every frame
+// of the pool is pinned.
+#[derive(Debug)]
+pub struct BufferException {
+    message: String,
+}
+
+impl BufferException {
+    // Constructor for BufferException
+    pub fn new(message: &str) -> Self {
+        BufferException {
+            message: message.to_string(),
+        }
+    }
+}
+
+// Implement Display trait to format the error message
+impl fmt::Display for BufferException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Implement Error trait for BufferException
+impl Error for BufferException {}
+
+// Used when the write-ahead log can not be read or written: a file that is not a log,
+// or the simulated crash of the crash test mode.
+#[derive(Debug)]
+pub struct WalException {
+    message: String,
+}
+
+impl WalException {
+    // Constructor for WalException
+    pub fn new(message: &str) -> Self {
+        WalException {
+            message: message.to_string(),
+        }
+    }
+}
+
+// Implement Display trait to format the error message
+impl fmt::Display for WalException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Implement Error trait for WalException
+impl Error for WalException {}
+
+// Used when the database of a folder can not be opened: its catalog is missing, or it
+// was written with an incompatible version of the format.
+#[derive(Debug)]
+pub struct CatalogException {
+    message: String,
+}
+
+impl CatalogException {
+    // Constructor for CatalogException
+    pub fn new(message: &str) -> Self {
+        CatalogException {
+            message: message.to_string(),
+        }
+    }
+}
+
+// Implement Display trait to format the error message
+impl fmt::Display for CatalogException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Implement Error trait for CatalogException
+impl Error for CatalogException {}
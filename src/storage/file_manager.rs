@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Size in bytes of every page of the database files
+pub const PAGE_SIZE: usize = 4096;
+
+// Folder (inside the database folder) of the files holding temporary pages
+pub const TEMP_FOLDER: &str = "tmp";
+
+pub type PageData = [u8; PAGE_SIZE];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PageId {
+    pub file_id: FileId,
+    pub page_number: u64,
+}
+
+impl PageId {
+    pub fn new(file_id: FileId, page_number: u64) -> Self {
+        Self { file_id, page_number }
+    }
+}
+
+struct OpenFile {
+    path: PathBuf,
+    file: Arc<File>,
+}
+
+#[derive(Default)]
+struct FileTable {
+    files: Vec<Option<OpenFile>>,
+    ids: HashMap<PathBuf, FileId>,
+    temp_files: u64,
+}
+
+// Owns the files of the database folder and reads and writes them a page at a time.
+// Files are identified by a `FileId` given when they are first opened.
+pub struct FileManager {
+    db_folder: PathBuf,
+    table: Mutex<FileTable>,
+}
+
+impl FileManager {
+    pub fn new(db_folder: &Path) -> Self {
+        Self {
+            db_folder: db_folder.to_path_buf(),
+            table: Mutex::new(FileTable::default()),
+        }
+    }
+
+    pub fn db_folder(&self) -> &Path {
+        &self.db_folder
+    }
+
+    // Id of the file `name` of the database folder, creating the file if it does not exist
+    pub fn get_file_id(&self, name: &str) -> io::Result<FileId> {
+        let path = self.db_folder.join(name);
+        let mut table = self.table.lock().unwrap();
+        if let Some(&file_id) = table.ids.get(&path) {
+            return Ok(file_id);
+        }
+        let file_id = Self::open(&mut table, path.clone())?;
+        table.ids.insert(path, file_id);
+        Ok(file_id)
+    }
+
+    // Creates an empty file for temporary pages, removed with `remove_file`
+    pub fn create_temp_file(&self) -> io::Result<FileId> {
+        let folder = self.db_folder.join(TEMP_FOLDER);
+        fs::create_dir_all(&folder)?;
+        let mut table = self.table.lock().unwrap();
+        table.temp_files += 1;
+        let path = folder.join(format!("private_{}_{}.tmp", std::process::id(), table.temp_files));
+        File::create(&path)?;
+        Self::open(&mut table, path)
+    }
+
+    // Closes and deletes a file. Its id must not be used afterwards.
+    pub fn remove_file(&self, file_id: FileId) -> io::Result<()> {
+        let mut table = self.table.lock().unwrap();
+        match table.files.get_mut(file_id.0 as usize).and_then(Option::take) {
+            Some(open_file) => {
+                table.ids.remove(&open_file.path);
+                fs::remove_file(&open_file.path)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Number of pages of the file, counting a trailing partial page
+    pub fn count_pages(&self, file_id: FileId) -> io::Result<u64> {
+        let length = self.file(file_id)?.metadata()?.len();
+        Ok(length.div_ceil(PAGE_SIZE as u64))
+    }
+
+    // Path of the file relative to the database folder
+    pub fn file_name(&self, file_id: FileId) -> io::Result<String> {
+        let table = self.table.lock().unwrap();
+        match table.files.get(file_id.0 as usize) {
+            Some(Some(open_file)) => {
+                let path = open_file.path.strip_prefix(&self.db_folder).unwrap_or(&open_file.path);
+                Ok(path.to_string_lossy().into_owned())
+            }
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, format!("file {} is not open", file_id.0))),
+        }
+    }
+
+    // Reads a page. The bytes past the end of the file are read as zeros.
+    pub fn read_page(&self, page_id: PageId, buffer: &mut PageData) -> io::Result<()> {
+        let file = self.file(page_id.file_id)?;
+        let mut offset = 0;
+        while offset < PAGE_SIZE {
+            let position = page_id.page_number * PAGE_SIZE as u64 + offset as u64;
+            match file.read_at(&mut buffer[offset..], position) {
+                Ok(0) => break,
+                Ok(n) => offset += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        buffer[offset..].fill(0);
+        Ok(())
+    }
+
+    pub fn write_page(&self, page_id: PageId, buffer: &PageData) -> io::Result<()> {
+        self.file(page_id.file_id)?
+            .write_all_at(buffer, page_id.page_number * PAGE_SIZE as u64)
+    }
+
+    // Forces the written pages of the file to disk
+    pub fn sync(&self, file_id: FileId) -> io::Result<()> {
+        self.file(file_id)?.sync_data()
+    }
+
+    fn open(table: &mut FileTable, path: PathBuf) -> io::Result<FileId> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let file_id = FileId(table.files.len() as u32);
+        table.files.push(Some(OpenFile { path, file: Arc::new(file) }));
+        Ok(file_id)
+    }
+
+    // The I/O itself is done without holding the lock of the table
+    fn file(&self, file_id: FileId) -> io::Result<Arc<File>> {
+        let table = self.table.lock().unwrap();
+        match table.files.get(file_id.0 as usize) {
+            Some(Some(open_file)) => Ok(Arc::clone(&open_file.file)),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, format!("file {} is not open", file_id.0))),
+        }
+    }
+}
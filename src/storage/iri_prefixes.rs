@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Prefix ids use one byte, 0 meaning no prefix
+pub const MAX_IRI_PREFIXES: usize = 255;
+
+// Discovered prefixes have to appear at least this many times and be longer than a scheme
+const MIN_PREFIX_OCCURRENCES: u64 = 2;
+const MIN_PREFIX_LENGTH: usize = 10;
+
+// The prefixes IRIs are compressed with: an IRI is stored as the id of its longest
+// prefix in the table and the rest of the IRI. The id of a prefix is its position plus
+// one, the table of a database is kept in its catalog.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IriPrefixes {
+    prefixes: Vec<String>,
+    // Ids of the prefixes, longest first
+    by_length: Vec<u8>,
+}
+
+impl IriPrefixes {
+    // Keeps the first `MAX_IRI_PREFIXES` distinct non-empty prefixes
+    pub fn new(prefixes: impl IntoIterator<Item = String>) -> Self {
+        let mut table: Vec<String> = Vec::new();
+        for prefix in prefixes {
+            if table.len() < MAX_IRI_PREFIXES && !prefix.is_empty() && !table.contains(&prefix) {
+                table.push(prefix);
+            }
+        }
+        let mut by_length: Vec<u8> = (1..=table.len() as u8).collect();
+        by_length.sort_by_key(|&id| std::cmp::Reverse(table[id as usize - 1].len()));
+        Self { prefixes: table, by_length }
+    }
+
+    // Reads a text file with one prefix per line, ignoring blank lines
+    pub fn read_prefix_list(path: &Path) -> io::Result<Vec<String>> {
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    pub fn prefixes(&self) -> &[String] {
+        &self.prefixes
+    }
+
+    // The prefix of an id, empty for 0 (and unknown ids)
+    pub fn prefix(&self, id: u8) -> &str {
+        match id {
+            0 => "",
+            _ => self.prefixes.get(id as usize - 1).map_or("", String::as_str),
+        }
+    }
+
+    // Id of the longest prefix of the IRI and the rest of it
+    pub fn split<'a>(&self, iri: &'a str) -> (u8, &'a str) {
+        for &id in &self.by_length {
+            if let Some(suffix) = iri.strip_prefix(self.prefix(id)) {
+                return (id, suffix);
+            }
+        }
+        (0, iri)
+    }
+
+    // Ids of the prefixes starting with `start`, whose IRIs all start with it
+    // (e.g. to answer STRSTARTS on IRIs without reading their suffixes)
+    pub fn ids_starting_with(&self, start: &str) -> Vec<u8> {
+        (1..=self.prefixes.len() as u8).filter(|&id| self.prefix(id).starts_with(start)).collect()
+    }
+}
+
+// Counts the candidate prefixes of the IRIs of an import: everything up to the last
+// '/' or '#' of each IRI
+#[derive(Debug, Default)]
+pub struct PrefixDiscovery {
+    counts: HashMap<String, u64>,
+}
+
+impl PrefixDiscovery {
+    pub fn add(&mut self, iri: &str) {
+        if let Some(end) = iri.rfind(['/', '#']) {
+            let candidate = &iri[..=end];
+            if candidate.len() >= MIN_PREFIX_LENGTH {
+                match self.counts.get_mut(candidate) {
+                    Some(count) => *count += 1,
+                    None => {
+                        self.counts.insert(candidate.to_string(), 1);
+                    }
+                }
+            }
+        }
+    }
+
+    // The configured prefixes followed by the discovered ones that save the most bytes
+    pub fn finish(self, configured: &[String]) -> IriPrefixes {
+        let mut candidates: Vec<(String, u64)> = self
+            .counts
+            .into_iter()
+            .filter(|(prefix, count)| *count >= MIN_PREFIX_OCCURRENCES && !configured.contains(prefix))
+            .collect();
+        candidates.sort_by(|(a, a_count), (b, b_count)| {
+            (b.len() as u64 * b_count).cmp(&(a.len() as u64 * a_count)).then_with(|| a.cmp(b))
+        });
+        IriPrefixes::new(configured.iter().cloned().chain(candidates.into_iter().map(|(prefix, _)| prefix)))
+    }
+}
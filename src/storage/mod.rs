@@ -0,0 +1,9 @@
+pub mod exceptions;
+pub mod file_manager;
+pub mod wal;
+pub mod buffer_manager;
+pub mod bplus_tree;
+pub mod quad_indexes;
+pub mod string_manager;
+pub mod iri_prefixes;
+pub mod catalog;
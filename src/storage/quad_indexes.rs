@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use crate::storage::bplus_tree::{BPlusTree, BPlusTreeIter};
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::exceptions::StorageError;
+
+// Positions of the terms of a statement: (s, p, o) for triples and (s, p, o, g) for quads
+pub const SUBJECT: usize = 0;
+pub const PREDICATE: usize = 1;
+pub const OBJECT: usize = 2;
+pub const GRAPH: usize = 3;
+
+// Permutations of the default graph triples, as positions of (s, p, o). With a bound
+// predicate both orders of subject and object are available, as worst-case optimal
+// joins need them for patterns like triangles.
+pub const TRIPLE_PERMUTATIONS: [(&str, [usize; 3]); 4] = [
+    ("spo", [SUBJECT, PREDICATE, OBJECT]),
+    ("pos", [PREDICATE, OBJECT, SUBJECT]),
+    ("osp", [OBJECT, SUBJECT, PREDICATE]),
+    ("pso", [PREDICATE, SUBJECT, OBJECT]),
+];
+
+// Permutations of the named graph quads, as positions of (s, p, o, g)
+pub const QUAD_PERMUTATIONS: [(&str, [usize; 4]); 6] = [
+    ("gspo", [GRAPH, SUBJECT, PREDICATE, OBJECT]),
+    ("gpos", [GRAPH, PREDICATE, OBJECT, SUBJECT]),
+    ("gosp", [GRAPH, OBJECT, SUBJECT, PREDICATE]),
+    ("spog", [SUBJECT, PREDICATE, OBJECT, GRAPH]),
+    ("posg", [PREDICATE, OBJECT, SUBJECT, GRAPH]),
+    ("ospg", [OBJECT, SUBJECT, PREDICATE, GRAPH]),
+];
+
+// File of the B+tree of a permutation
+pub fn permutation_file(name: &str) -> String {
+    format!("{}.bpt", name)
+}
+
+// The triples of the default graph and the quads of the named graphs, each in every
+// permutation. Whatever terms of a pattern are bound, some permutation has them as a
+// prefix, so every pattern is answered with a single range scan.
+pub struct QuadIndexes {
+    triples: Vec<BPlusTree<3>>,
+    quads: Vec<BPlusTree<4>>,
+}
+
+impl QuadIndexes {
+    pub fn open(buffer: &Arc<BufferManager>) -> Result<Self, StorageError> {
+        Ok(Self {
+            triples: TRIPLE_PERMUTATIONS
+                .iter()
+                .map(|(name, _)| BPlusTree::open(buffer, &permutation_file(name)))
+                .collect::<Result<_, _>>()?,
+            quads: QUAD_PERMUTATIONS
+                .iter()
+                .map(|(name, _)| BPlusTree::open(buffer, &permutation_file(name)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    // Creates the indexes of an empty database
+    pub fn create(buffer: &Arc<BufferManager>) -> Result<Self, StorageError> {
+        Ok(Self {
+            triples: TRIPLE_PERMUTATIONS
+                .iter()
+                .map(|(name, _)| BPlusTree::create(buffer, &permutation_file(name)))
+                .collect::<Result<_, _>>()?,
+            quads: QUAD_PERMUTATIONS
+                .iter()
+                .map(|(name, _)| BPlusTree::create(buffer, &permutation_file(name)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn triple_count(&self) -> u64 {
+        self.triples[0].len()
+    }
+
+    pub fn quad_count(&self) -> u64 {
+        self.quads[0].len()
+    }
+
+    // Tree of the triple permutation `TRIPLE_PERMUTATIONS[permutation]`
+    pub fn triple_index(&self, permutation: usize) -> &BPlusTree<3> {
+        &self.triples[permutation]
+    }
+
+    // Adds a triple `[s, p, o]` to every permutation, returns false if it already existed
+    pub fn insert_triple(&mut self, triple: &[u64; 3]) -> Result<bool, StorageError> {
+        update(&mut self.triples, &TRIPLE_PERMUTATIONS, triple, BPlusTree::insert)
+    }
+
+    pub fn delete_triple(&mut self, triple: &[u64; 3]) -> Result<bool, StorageError> {
+        update(&mut self.triples, &TRIPLE_PERMUTATIONS, triple, BPlusTree::delete)
+    }
+
+    // Adds a quad `[s, p, o, g]` to every permutation, returns false if it already existed
+    pub fn insert_quad(&mut self, quad: &[u64; 4]) -> Result<bool, StorageError> {
+        update(&mut self.quads, &QUAD_PERMUTATIONS, quad, BPlusTree::insert)
+    }
+
+    pub fn delete_quad(&mut self, quad: &[u64; 4]) -> Result<bool, StorageError> {
+        update(&mut self.quads, &QUAD_PERMUTATIONS, quad, BPlusTree::delete)
+    }
+
+    // Triples matching a pattern of `[s, p, o]` where `None` is a variable. The
+    // triples are returned as `[s, p, o]`, in the order of the chosen permutation.
+    pub fn scan_triples(&self, pattern: &[Option<u64>; 3]) -> Result<PatternScan<'_, 3>, StorageError> {
+        PatternScan::new(&self.triples, &TRIPLE_PERMUTATIONS, pattern)
+    }
+
+    // Quads matching a pattern of `[s, p, o, g]`, returned as `[s, p, o, g]`
+    pub fn scan_quads(&self, pattern: &[Option<u64>; 4]) -> Result<PatternScan<'_, 4>, StorageError> {
+        PatternScan::new(&self.quads, &QUAD_PERMUTATIONS, pattern)
+    }
+
+    // Writes back the modified pages of every index
+    pub fn flush(&self) -> Result<(), StorageError> {
+        self.triples.iter().try_for_each(BPlusTree::flush)?;
+        self.quads.iter().try_for_each(BPlusTree::flush)
+    }
+}
+
+// Index of the first permutation having the bound positions of `pattern` as a prefix
+pub fn choose_permutation<const N: usize>(permutations: &[(&str, [usize; N])], pattern: &[Option<u64>; N]) -> usize {
+    let bound = pattern.iter().filter(|term| term.is_some()).count();
+    permutations
+        .iter()
+        .position(|(_, order)| order[..bound].iter().all(|&position| pattern[position].is_some()))
+        .expect("every pattern has a permutation")
+}
+
+fn permute<const N: usize>(order: &[usize; N], statement: &[u64; N]) -> [u64; N] {
+    let mut tuple = [0; N];
+    for (value, &position) in tuple.iter_mut().zip(order) {
+        *value = statement[position];
+    }
+    tuple
+}
+
+fn update<const N: usize>(
+    trees: &mut [BPlusTree<N>],
+    permutations: &[(&str, [usize; N])],
+    statement: &[u64; N],
+    operation: fn(&mut BPlusTree<N>, &[u64; N]) -> Result<bool, StorageError>,
+) -> Result<bool, StorageError> {
+    let mut changed = false;
+    for (tree, (_, order)) in trees.iter_mut().zip(permutations) {
+        changed = operation(tree, &permute(order, statement))?;
+    }
+    Ok(changed)
+}
+
+// Range scan of a permutation, giving back the statements in their original order
+pub struct PatternScan<'a, const N: usize> {
+    iter: BPlusTreeIter<'a, N>,
+    order: [usize; N],
+}
+
+impl<'a, const N: usize> PatternScan<'a, N> {
+    fn new(
+        trees: &'a [BPlusTree<N>],
+        permutations: &[(&str, [usize; N])],
+        pattern: &[Option<u64>; N],
+    ) -> Result<Self, StorageError> {
+        let index = choose_permutation(permutations, pattern);
+        let order = permutations[index].1;
+        let prefix: Vec<u64> = order.iter().map_while(|&position| pattern[position]).collect();
+        Ok(Self {
+            iter: trees[index].scan_prefix(&prefix)?,
+            order,
+        })
+    }
+}
+
+impl<const N: usize> Iterator for PatternScan<'_, N> {
+    type Item = Result<[u64; N], StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tuple = match self.iter.next()? {
+            Ok(tuple) => tuple,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut statement = [0; N];
+        for (value, &position) in tuple.iter().zip(&self.order) {
+            statement[position] = *value;
+        }
+        Some(Ok(statement))
+    }
+}
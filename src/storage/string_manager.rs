@@ -0,0 +1,291 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::storage::buffer_manager::BufferManager;
+use crate::storage::exceptions::StorageError;
+use crate::storage::file_manager::{FileId, PageData, PageId, PAGE_SIZE};
+
+// Name of the file with the dictionary of strings, the id of a string is its offset in the file
+pub const STRINGS_FILE: &str = "strings.dat";
+pub const STRINGS_FILE_MAGIC: &[u8; 8] = b"MDBSTR01";
+
+// Hash index of the dictionary, from the hash of a string to its id
+pub const STRINGS_HASH_FILE: &str = "strings.hash";
+pub const STRINGS_HASH_MAGIC: &[u8; 8] = b"MDBHSH01";
+
+// Page 0 of the index holds the magic, the number of buckets and the number of strings.
+// Bucket `b` is page `b + 1`, its overflow pages are chained through `BUCKET_NEXT`
+// (0 when there are none, as page 0 is never a bucket).
+const HEADER_BUCKETS: usize = 8;
+const HEADER_STRINGS: usize = 16;
+const BUCKET_COUNT: usize = 0;
+const BUCKET_NEXT: usize = 8;
+const BUCKET_HEADER: usize = 16;
+
+// Entries of a bucket page: the hash of the string and its id
+const ENTRY_SIZE: usize = 16;
+pub const ENTRIES_PER_PAGE: usize = (PAGE_SIZE - BUCKET_HEADER) / ENTRY_SIZE;
+
+pub const GIGABYTE: u64 = 1024 * 1024 * 1024;
+
+// Keeps the strings of the database (IRIs, literals, ...) that are not inlined in their
+// ObjectId. Strings are appended to `strings.dat` and never removed, each one stored as
+// its length (u32) followed by its bytes. The start of the file is kept in memory, up to
+// the size given when opening it, so the first queries do not have to read it from disk.
+pub struct StringManager {
+    buffer: Arc<BufferManager>,
+    index_file: FileId,
+    bucket_count: u64,
+    strings: File,
+    populated: Vec<u8>,
+    writer: Mutex<StringWriter>,
+}
+
+struct StringWriter {
+    end: u64,
+    count: u64,
+}
+
+impl StringManager {
+    // Opens the dictionary of the database folder of `buffer`, creating it if there is
+    // none. The hash index is built from the strings file when it does not exist.
+    pub fn open(buffer: &Arc<BufferManager>, populate_size: u64) -> Result<Self, StorageError> {
+        let db_folder = buffer.file_manager().db_folder().to_path_buf();
+        let strings_path = db_folder.join(STRINGS_FILE);
+        if !strings_path.exists() {
+            if let Some(wal) = buffer.wal() {
+                wal.log_write(STRINGS_FILE, 0, STRINGS_FILE_MAGIC)?;
+            }
+            File::create(&strings_path)?.write_all(STRINGS_FILE_MAGIC)?;
+        }
+        let strings = OpenOptions::new().read(true).write(true).open(&strings_path)?;
+        let mut magic = [0; 8];
+        strings.read_exact_at(&mut magic, 0)?;
+        if &magic != STRINGS_FILE_MAGIC {
+            return Err(format!("{} is not a strings file", strings_path.display()).into());
+        }
+        let end = strings.metadata()?.len();
+
+        let mut populated = vec![0; end.min(populate_size) as usize];
+        strings.read_exact_at(&mut populated, 0)?;
+
+        let index_exists = db_folder.join(STRINGS_HASH_FILE).exists();
+        let index_file = buffer.get_file_id(STRINGS_HASH_FILE)?;
+        let mut manager = Self {
+            buffer: Arc::clone(buffer),
+            index_file,
+            bucket_count: 0,
+            strings,
+            populated,
+            writer: Mutex::new(StringWriter { end, count: 0 }),
+        };
+        if index_exists {
+            manager.read_header()?;
+        } else {
+            manager.build_index(&strings_path)?;
+        }
+        Ok(manager)
+    }
+
+    // Number of strings in the dictionary
+    pub fn len(&self) -> u64 {
+        self.writer.lock().unwrap().count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Bytes of the strings file kept in memory
+    pub fn populated_size(&self) -> u64 {
+        self.populated.len() as u64
+    }
+
+    pub fn get_id(&self, string: &str) -> Result<Option<u64>, StorageError> {
+        let hash = hash_string(string.as_bytes());
+        let mut page_number = self.bucket_page(hash);
+        loop {
+            let page = self.buffer.pin(PageId::new(self.index_file, page_number))?;
+            let (candidates, next) = {
+                let data = page.read();
+                let count = read_u32(&data, BUCKET_COUNT) as usize;
+                let candidates: Vec<u64> = (0..count)
+                    .filter(|&i| read_u64(&data, entry_offset(i)) == hash)
+                    .map(|i| read_u64(&data, entry_offset(i) + 8))
+                    .collect();
+                (candidates, read_u64(&data, BUCKET_NEXT))
+            };
+            for id in candidates {
+                if self.get_bytes(id)? == string.as_bytes() {
+                    return Ok(Some(id));
+                }
+            }
+            if next == 0 {
+                return Ok(None);
+            }
+            page_number = next;
+        }
+    }
+
+    // Id of the string, appending it to the dictionary if it is not there
+    pub fn get_or_insert(&self, string: &str) -> Result<u64, StorageError> {
+        if let Some(id) = self.get_id(string)? {
+            return Ok(id);
+        }
+        let mut writer = self.writer.lock().unwrap();
+        // Another thread may have added it in the meantime
+        if let Some(id) = self.get_id(string)? {
+            return Ok(id);
+        }
+        let id = writer.end;
+        let mut record = Vec::with_capacity(4 + string.len());
+        record.extend_from_slice(&(string.len() as u32).to_le_bytes());
+        record.extend_from_slice(string.as_bytes());
+        // Appended strings are only reachable through the index, so an uncommitted
+        // one left at the end of the file by a crash is harmless
+        if let Some(wal) = self.buffer.wal() {
+            wal.log_write(STRINGS_FILE, id, &record)?;
+        }
+        self.strings.write_all_at(&record, id)?;
+        writer.end += record.len() as u64;
+
+        self.insert_entry(hash_string(string.as_bytes()), id)?;
+        writer.count += 1;
+        self.write_header(writer.count)?;
+        Ok(id)
+    }
+
+    pub fn get_string(&self, id: u64) -> Result<String, StorageError> {
+        let bytes = self.get_bytes(id)?;
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+
+    // Writes back the pages of the index and forces the strings to disk
+    pub fn flush(&self) -> Result<(), StorageError> {
+        self.strings.sync_data()?;
+        self.buffer.flush_file(self.index_file)
+    }
+
+    fn get_bytes(&self, id: u64) -> Result<Vec<u8>, StorageError> {
+        let start = id as usize;
+        if start + 4 <= self.populated.len() {
+            let length = u32::from_le_bytes(self.populated[start..start + 4].try_into().unwrap()) as usize;
+            if start + 4 + length <= self.populated.len() {
+                return Ok(self.populated[start + 4..start + 4 + length].to_vec());
+            }
+        }
+        let mut length = [0; 4];
+        self.strings.read_exact_at(&mut length, id)?;
+        let mut bytes = vec![0; u32::from_le_bytes(length) as usize];
+        self.strings.read_exact_at(&mut bytes, id + 4)?;
+        Ok(bytes)
+    }
+
+    fn bucket_page(&self, hash: u64) -> u64 {
+        1 + hash % self.bucket_count
+    }
+
+    // Adds an entry to the last page of the chain of its bucket
+    fn insert_entry(&self, hash: u64, id: u64) -> Result<(), StorageError> {
+        let mut page = self.buffer.pin(PageId::new(self.index_file, self.bucket_page(hash)))?;
+        loop {
+            let (count, next) = {
+                let data = page.read();
+                (read_u32(&data, BUCKET_COUNT) as usize, read_u64(&data, BUCKET_NEXT))
+            };
+            if count < ENTRIES_PER_PAGE {
+                let mut data = page.write();
+                write_u64(&mut data, entry_offset(count), hash);
+                write_u64(&mut data, entry_offset(count) + 8, id);
+                data[BUCKET_COUNT..BUCKET_COUNT + 4].copy_from_slice(&(count as u32 + 1).to_le_bytes());
+                return Ok(());
+            }
+            page = if next == 0 {
+                let overflow = self.buffer.append_page(self.index_file)?;
+                write_u64(&mut page.write(), BUCKET_NEXT, overflow.page_id().page_number);
+                overflow
+            } else {
+                self.buffer.pin(PageId::new(self.index_file, next))?
+            };
+        }
+    }
+
+    // Creates the index with buckets half full for the strings already in the file
+    fn build_index(&mut self, strings_path: &Path) -> Result<(), StorageError> {
+        let mut reader = BufReader::new(File::open(strings_path)?);
+        reader.read_exact(&mut [0; 8])?;
+        let mut entries = Vec::new();
+        let mut offset = STRINGS_FILE_MAGIC.len() as u64;
+        let mut length = [0; 4];
+        let mut string = Vec::new();
+        let end = self.writer.lock().unwrap().end;
+        while offset < end {
+            reader.read_exact(&mut length)?;
+            string.resize(u32::from_le_bytes(length) as usize, 0);
+            reader.read_exact(&mut string)?;
+            entries.push((hash_string(&string), offset));
+            offset += 4 + string.len() as u64;
+        }
+
+        self.bucket_count = (entries.len() as u64 * 2 / ENTRIES_PER_PAGE as u64).max(1);
+        drop(self.buffer.append_page(self.index_file)?);
+        for _ in 0..self.bucket_count {
+            drop(self.buffer.append_page(self.index_file)?);
+        }
+        for &(hash, id) in &entries {
+            self.insert_entry(hash, id)?;
+        }
+        self.writer.lock().unwrap().count = entries.len() as u64;
+        self.write_header(entries.len() as u64)?;
+        self.buffer.flush_file(self.index_file)
+    }
+
+    fn read_header(&mut self) -> Result<(), StorageError> {
+        let page = self.buffer.pin(PageId::new(self.index_file, 0))?;
+        let data = page.read();
+        if &data[..8] != STRINGS_HASH_MAGIC {
+            return Err(format!("{} is not a strings index", STRINGS_HASH_FILE).into());
+        }
+        self.bucket_count = read_u64(&data, HEADER_BUCKETS);
+        self.writer.lock().unwrap().count = read_u64(&data, HEADER_STRINGS);
+        Ok(())
+    }
+
+    fn write_header(&self, count: u64) -> Result<(), StorageError> {
+        let page = self.buffer.pin(PageId::new(self.index_file, 0))?;
+        let mut data = page.write();
+        data[..8].copy_from_slice(STRINGS_HASH_MAGIC);
+        write_u64(&mut data, HEADER_BUCKETS, self.bucket_count);
+        write_u64(&mut data, HEADER_STRINGS, count);
+        Ok(())
+    }
+}
+
+// FNV-1a, stable across runs as the hashes are stored in the index
+pub fn hash_string(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn entry_offset(index: usize) -> usize {
+    BUCKET_HEADER + index * ENTRY_SIZE
+}
+
+fn read_u32(data: &PageData, offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &PageData, offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn write_u64(data: &mut PageData, offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,338 @@
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::import::external_sort::read_exact_or_eof;
+use crate::storage::exceptions::{StorageError, WalException};
+
+// Write-ahead log of the database folder. It starts with the magic, followed by the
+// records written since the last checkpoint.
+pub const WAL_FILE: &str = "wal.log";
+pub const WAL_FILE_MAGIC: &[u8; 8] = b"MDBWAL01";
+
+// The log is checkpointed when a commit leaves it bigger than this
+pub const DEFAULT_CHECKPOINT_SIZE: u64 = 64 * 1024 * 1024;
+
+// Every record is its length (u32) and the CRC-32 of its body (u32), followed by the
+// body. The first byte of the body is the kind of the record.
+const RECORD_HEADER: usize = 8;
+const WRITE_RECORD: u8 = 1;
+const COMMIT_RECORD: u8 = 2;
+
+// When the log is forced to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalSyncPolicy {
+    // At every commit, committed transactions survive a power failure
+    Commit,
+    // At the first commit after the interval has passed since the last sync
+    Interval(Duration),
+    // Only at checkpoints, committed transactions survive a crash of the process
+    Never,
+}
+
+impl FromStr for WalSyncPolicy {
+    type Err = String;
+
+    // "commit", "never" or an interval such as "100ms"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commit" => Ok(WalSyncPolicy::Commit),
+            "never" => Ok(WalSyncPolicy::Never),
+            _ => humantime::parse_duration(s)
+                .map(WalSyncPolicy::Interval)
+                .map_err(|_| format!("Invalid sync policy {}, expected commit, never or an interval", s)),
+        }
+    }
+}
+
+// A change of a transaction: `bytes` written at `offset` of the file `file` of the
+// database folder
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WriteRecord {
+    file: String,
+    offset: u64,
+    bytes: Vec<u8>,
+}
+
+struct WalState {
+    file: File,
+    size: u64,
+    records: u64,
+    transaction: u64,
+    // Records appended but not forced to disk yet
+    unsynced: bool,
+    last_sync: Instant,
+    // Files changed since the last checkpoint, forced to disk by the next one
+    written_files: BTreeSet<String>,
+    crash_after: Option<u64>,
+}
+
+// Redo log of the changes of the storage layer. A transaction appends the final content
+// of what it changed and then a commit record. A page changed by a transaction that is
+// not committed is never written to its file, so redoing the committed transactions
+// in order is enough to recover from a crash.
+pub struct Wal {
+    path: PathBuf,
+    db_folder: PathBuf,
+    sync_policy: WalSyncPolicy,
+    checkpoint_size: u64,
+    recovered_transactions: u64,
+    state: Mutex<WalState>,
+}
+
+impl Wal {
+    // Redoes the committed transactions of the log of `db_folder`, if there is one, and
+    // opens it empty for the following ones
+    pub fn open(db_folder: &Path, sync_policy: WalSyncPolicy, checkpoint_size: u64) -> Result<Self, StorageError> {
+        let path = db_folder.join(WAL_FILE);
+        let recovered_transactions = if path.exists() { redo(db_folder, &path)? } else { 0 };
+
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let wal = Self {
+            path,
+            db_folder: db_folder.to_path_buf(),
+            sync_policy,
+            checkpoint_size,
+            recovered_transactions,
+            state: Mutex::new(WalState {
+                file,
+                size: 0,
+                records: 0,
+                transaction: 0,
+                unsynced: false,
+                last_sync: Instant::now(),
+                written_files: BTreeSet::new(),
+                crash_after: None,
+            }),
+        };
+        wal.truncate(&mut wal.state.lock().unwrap())?;
+        Ok(wal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn sync_policy(&self) -> WalSyncPolicy {
+        self.sync_policy
+    }
+
+    // Transactions redone when the log was opened
+    pub fn recovered_transactions(&self) -> u64 {
+        self.recovered_transactions
+    }
+
+    // Records appended since the log was opened, checkpoints included
+    pub fn record_count(&self) -> u64 {
+        self.state.lock().unwrap().records
+    }
+
+    // Bytes of the log, the magic included
+    pub fn size(&self) -> u64 {
+        self.state.lock().unwrap().size
+    }
+
+    pub fn needs_checkpoint(&self) -> bool {
+        self.size() > self.checkpoint_size
+    }
+
+    // Test mode: the process is considered dead once the log has `records` records.
+    // Nothing is written to the log or to the database files afterwards, so dropping
+    // everything and opening the folder again behaves as a restart after a crash at
+    // that record boundary.
+    pub fn simulate_crash_after(&self, records: u64) {
+        self.state.lock().unwrap().crash_after = Some(records);
+    }
+
+    pub fn crashed(&self) -> bool {
+        self.check_crash(&self.state.lock().unwrap()).is_err()
+    }
+
+    // Logs `bytes` written at `offset` of the file `file` of the database folder, as
+    // part of the current transaction
+    pub fn log_write(&self, file: &str, offset: u64, bytes: &[u8]) -> Result<(), StorageError> {
+        let mut body = Vec::with_capacity(15 + file.len() + bytes.len());
+        body.push(WRITE_RECORD);
+        body.extend_from_slice(&(file.len() as u16).to_le_bytes());
+        body.extend_from_slice(file.as_bytes());
+        body.extend_from_slice(&offset.to_le_bytes());
+        body.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        body.extend_from_slice(bytes);
+
+        let mut state = self.state.lock().unwrap();
+        self.append(&mut state, &body)?;
+        if !state.written_files.contains(file) {
+            state.written_files.insert(file.to_string());
+        }
+        Ok(())
+    }
+
+    // Ends the current transaction, forcing the log to disk as the sync policy says.
+    // Returns the number of the committed transaction.
+    pub fn commit(&self) -> Result<u64, StorageError> {
+        let mut state = self.state.lock().unwrap();
+        let transaction = state.transaction + 1;
+        let mut body = vec![COMMIT_RECORD];
+        body.extend_from_slice(&transaction.to_le_bytes());
+        self.append(&mut state, &body)?;
+        state.transaction = transaction;
+
+        let sync = match self.sync_policy {
+            WalSyncPolicy::Commit => true,
+            WalSyncPolicy::Interval(interval) => state.last_sync.elapsed() >= interval,
+            WalSyncPolicy::Never => false,
+        };
+        // After a simulated crash the record is in the log, but nothing is forced to disk
+        if sync && self.check_crash(&state).is_ok() {
+            self.sync_state(&mut state)?;
+        }
+        Ok(transaction)
+    }
+
+    // Forces the log to disk. Must be called before writing a logged page to its file.
+    pub fn sync(&self) -> Result<(), StorageError> {
+        let mut state = self.state.lock().unwrap();
+        self.check_crash(&state)?;
+        self.sync_state(&mut state)
+    }
+
+    // Second half of a checkpoint, once every logged page has been written to its file:
+    // forces the changed files to disk and empties the log
+    pub fn finish_checkpoint(&self) -> Result<(), StorageError> {
+        let mut state = self.state.lock().unwrap();
+        self.check_crash(&state)?;
+        for name in &state.written_files {
+            let path = self.db_folder.join(name);
+            if path.exists() {
+                File::open(path)?.sync_all()?;
+            }
+        }
+        state.written_files.clear();
+        self.truncate(&mut state)
+    }
+
+    fn append(&self, state: &mut WalState, body: &[u8]) -> Result<(), StorageError> {
+        self.check_crash(state)?;
+        let mut record = Vec::with_capacity(RECORD_HEADER + body.len());
+        record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32(body).to_le_bytes());
+        record.extend_from_slice(body);
+        state.file.write_all_at(&record, state.size)?;
+        state.size += record.len() as u64;
+        state.records += 1;
+        state.unsynced = true;
+        Ok(())
+    }
+
+    fn sync_state(&self, state: &mut WalState) -> Result<(), StorageError> {
+        if state.unsynced {
+            state.file.sync_data()?;
+            state.unsynced = false;
+        }
+        state.last_sync = Instant::now();
+        Ok(())
+    }
+
+    fn truncate(&self, state: &mut WalState) -> Result<(), StorageError> {
+        state.file.set_len(0)?;
+        state.file.write_all_at(WAL_FILE_MAGIC, 0)?;
+        state.file.sync_all()?;
+        state.size = WAL_FILE_MAGIC.len() as u64;
+        state.unsynced = false;
+        state.last_sync = Instant::now();
+        Ok(())
+    }
+
+    // Once the simulated crash happened, nothing else reaches the disk
+    fn check_crash(&self, state: &WalState) -> Result<(), StorageError> {
+        match state.crash_after {
+            Some(records) if state.records >= records => Err(Box::new(WalException::new("Simulated crash"))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Applies the writes of the committed transactions of the log, in order, and forces
+// the changed files to disk. The log ends at the first incomplete or corrupt record
+// (the one being written when the crash happened); the writes after the last commit
+// record belong to a transaction that did not commit and are ignored.
+fn redo(db_folder: &Path, path: &Path) -> Result<u64, StorageError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; 8];
+    if !read_exact_or_eof(&mut reader, &mut magic)? {
+        return Ok(0);
+    }
+    if &magic != WAL_FILE_MAGIC {
+        return Err(Box::new(WalException::new(&format!("{} is not a write-ahead log", path.display()))));
+    }
+
+    let mut transactions = 0;
+    let mut pending = Vec::new();
+    let mut written_files = BTreeSet::new();
+    while let Some(body) = read_record(&mut reader)? {
+        match body[0] {
+            WRITE_RECORD => match parse_write(&body[1..]) {
+                Some(write) => pending.push(write),
+                None => break,
+            },
+            COMMIT_RECORD if body.len() == 9 => {
+                for write in pending.drain(..) {
+                    let file = OpenOptions::new().write(true).create(true).truncate(false).open(db_folder.join(&write.file))?;
+                    file.write_all_at(&write.bytes, write.offset)?;
+                    written_files.insert(write.file);
+                }
+                transactions += 1;
+            }
+            _ => break,
+        }
+    }
+    for name in written_files {
+        File::open(db_folder.join(name))?.sync_all()?;
+    }
+    Ok(transactions)
+}
+
+// Body of the next record, `None` at the end of the log or at a damaged record
+fn read_record(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0; RECORD_HEADER];
+    if !read_exact_or_eof(reader, &mut header)? {
+        return Ok(None);
+    }
+    let length = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if length == 0 {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    if reader.take(length as u64).read_to_end(&mut body)? < length || crc32(&body) != checksum {
+        return Ok(None);
+    }
+    Ok(Some(body))
+}
+
+fn parse_write(body: &[u8]) -> Option<WriteRecord> {
+    let name_length = u16::from_le_bytes(body.get(..2)?.try_into().ok()?) as usize;
+    let file = String::from_utf8(body.get(2..2 + name_length)?.to_vec()).ok()?;
+    let rest = &body[2 + name_length..];
+    let offset = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?);
+    let length = u32::from_le_bytes(rest.get(8..12)?.try_into().ok()?) as usize;
+    let bytes = rest.get(12..12 + length)?.to_vec();
+    Some(WriteRecord { file, offset, bytes })
+}
+
+// CRC-32 (IEEE), checksum of the records
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,84 @@
+// Each test crate uses only some of these helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use milleniumdb_rs::import::bulk_loader::BulkLoader;
+use milleniumdb_rs::query::executor::{Interruption, TemporaryPages};
+use milleniumdb_rs::query::query_executor::{execute_query, QueryResults, QueryTerms};
+use milleniumdb_rs::query::rdf_terms::{RdfQuad, RdfTerm, Solution};
+use milleniumdb_rs::query::sparql_parser::parse_query;
+use milleniumdb_rs::storage::buffer_manager::BufferManager;
+use milleniumdb_rs::storage::catalog::Catalog;
+use milleniumdb_rs::storage::quad_indexes::QuadIndexes;
+use milleniumdb_rs::storage::string_manager::StringManager;
+
+pub const PROLOGUE: &str = "PREFIX : <http://ex.org/>\n";
+
+// Creates an empty folder for a test database, removing leftovers of earlier runs
+pub fn temp_db_folder(name: &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("milleniumdb_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+// Results of a query, read to the end
+#[derive(Debug, PartialEq)]
+pub enum Results {
+    Solutions(Vec<Solution>),
+    Boolean(bool),
+    Graph(Vec<RdfQuad>),
+}
+
+pub fn read_results(results: QueryResults) -> Results {
+    match results {
+        QueryResults::Solutions { solutions, .. } => Results::Solutions(solutions.map(Result::unwrap).collect()),
+        QueryResults::Boolean(value) => Results::Boolean(value),
+        QueryResults::Graph(quads) => Results::Graph(quads.map(Result::unwrap).collect()),
+    }
+}
+
+// Imports the Turtle `data` and runs the queries through `test`, after PROLOGUE
+pub fn with_data(name: &str, data: &str, test: impl FnOnce(&dyn Fn(&str) -> Results)) {
+    with_file(name, "data.ttl", data, test)
+}
+
+// Imports `data` from a file named `file_name`, its extension gives the format. The
+// private pool of the worker has a single page, so grouping more than a few dozen groups
+// has to spill to temporary pages.
+pub fn with_file(name: &str, file_name: &str, data: &str, test: impl FnOnce(&dyn Fn(&str) -> Results)) {
+    let db_folder = temp_db_folder(name);
+    let data_file = db_folder.join(file_name);
+    fs::write(&data_file, data).unwrap();
+    let mut loader = BulkLoader::new(&db_folder.join("db"), 64).unwrap();
+    loader.load_file(&data_file).unwrap();
+    loader.finish().unwrap();
+    {
+        let buffer = Arc::new(BufferManager::new(&db_folder.join("db"), 64, 1, 1));
+        let indexes = QuadIndexes::open(&buffer).unwrap();
+        let strings = StringManager::open(&buffer, 0).unwrap();
+        let catalog = Catalog::load(&db_folder.join("db")).unwrap();
+        let run = |query: &str| {
+            let query = parse_query(&format!("{}{}", PROLOGUE, query)).unwrap();
+            let terms = QueryTerms::new(&strings, &catalog.prefixes);
+            let pages = TemporaryPages::new(&buffer, 0);
+            execute_query(&query, &indexes, &terms, Interruption::default(), Some(pages)).map(read_results).unwrap()
+        };
+        test(&run);
+    }
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+pub fn ex(local: &str) -> Option<RdfTerm> {
+    Some(RdfTerm::iri(&format!("http://ex.org/{}", local)))
+}
+
+pub fn solutions(results: Results) -> Vec<Solution> {
+    match results {
+        Results::Solutions(solutions) => solutions,
+        other => panic!("unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,63 @@
+use milleniumdb_rs::network::content_negotiation::negotiate_response_type;
+use milleniumdb_rs::network::response_type::ResponseType;
+use milleniumdb_rs::query::query_forms::QueryForm;
+
+#[test]
+fn test_defaults_per_query_form() {
+    assert_eq!(negotiate_response_type(QueryForm::Select, None, None).unwrap(), ResponseType::JSON);
+    assert_eq!(negotiate_response_type(QueryForm::Ask, Some("*/*"), None).unwrap(), ResponseType::JSON);
+    assert_eq!(negotiate_response_type(QueryForm::Construct, None, None).unwrap(), ResponseType::TURTLE);
+    assert_eq!(negotiate_response_type(QueryForm::Describe, Some(""), None).unwrap(), ResponseType::TURTLE);
+}
+
+#[test]
+fn test_accept_quality_values_and_wildcards() {
+    let accept = "application/sparql-results+json;q=0.5, application/sparql-results+xml, */*;q=0.1";
+    assert_eq!(negotiate_response_type(QueryForm::Select, Some(accept), None).unwrap(), ResponseType::XML);
+
+    let accept = "text/*;q=0.9, application/*;q=0.2";
+    assert_eq!(negotiate_response_type(QueryForm::Select, Some(accept), None).unwrap(), ResponseType::XML);
+
+    let accept = "text/csv, text/*;q=0.3";
+    assert_eq!(negotiate_response_type(QueryForm::Select, Some(accept), None).unwrap(), ResponseType::CSV);
+
+    // The most specific range wins, so q=0 excludes JSON even though */* accepts everything
+    let accept = "application/sparql-results+json;q=0, application/json;q=0, */*";
+    assert_eq!(negotiate_response_type(QueryForm::Ask, Some(accept), None).unwrap(), ResponseType::XML);
+}
+
+#[test]
+fn test_format_parameter_overrides_accept() {
+    let accept = Some("application/sparql-results+json");
+    assert_eq!(negotiate_response_type(QueryForm::Select, accept, Some("tsv")).unwrap(), ResponseType::TSV);
+    assert_eq!(negotiate_response_type(QueryForm::Select, accept, Some("text/csv")).unwrap(), ResponseType::CSV);
+}
+
+#[test]
+fn test_not_acceptable() {
+    let error = negotiate_response_type(QueryForm::Select, Some("text/turtle"), None).unwrap_err();
+    assert_eq!(error.status(), 406);
+    let error = negotiate_response_type(QueryForm::Construct, None, Some("json")).unwrap_err();
+    assert_eq!(error.status(), 406);
+    let error = negotiate_response_type(QueryForm::Select, None, Some("bogus")).unwrap_err();
+    assert_eq!(error.status(), 406);
+}
+
+#[test]
+fn test_response_type_from_str() {
+    assert_eq!("JSON".parse::<ResponseType>().unwrap(), ResponseType::JSON);
+    assert_eq!("nt".parse::<ResponseType>().unwrap(), ResponseType::NTRIPLES);
+    assert_eq!("Application/N-Quads; charset=utf-8".parse::<ResponseType>().unwrap(), ResponseType::NQUADS);
+    for response_type in ResponseType::ALL {
+        assert_eq!(response_type.to_string().parse::<ResponseType>().unwrap(), response_type);
+    }
+    assert!("bogus".parse::<ResponseType>().is_err());
+}
+
+#[test]
+fn test_query_form_detection() {
+    let query = "# comment\nBASE <http://example.org/>\nPREFIX ex: <http://example.org/ns#>\n  construct { ?s ?p ?o } WHERE {}";
+    assert_eq!(QueryForm::detect(query).unwrap(), QueryForm::Construct);
+    assert_eq!(QueryForm::detect("ASK {}").unwrap(), QueryForm::Ask);
+    assert!(QueryForm::detect("INSERT DATA {}").is_err());
+}
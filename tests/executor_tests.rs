@@ -0,0 +1,271 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use milleniumdb_rs::network::sparql_servers::Server;
+use milleniumdb_rs::query::exceptions::InterruptedException;
+use milleniumdb_rs::query::executor::*;
+use milleniumdb_rs::query::object_id::{ObjectId, MASK_IRI_EXTERN};
+use milleniumdb_rs::query::planner::{plan_basic_graph_pattern, JoinPlan, PatternTerm, TriplePattern};
+use milleniumdb_rs::storage::buffer_manager::BufferManager;
+use milleniumdb_rs::storage::quad_indexes::QuadIndexes;
+
+use PatternTerm::{Constant, Variable};
+
+mod common;
+use common::temp_db_folder;
+
+fn iri(id: u64) -> ObjectId {
+    ObjectId::external(MASK_IRI_EXTERN, id)
+}
+
+fn integer(value: i64) -> ObjectId {
+    ObjectId::from_integer(value).unwrap()
+}
+
+const KNOWS: u64 = 1;
+const AGE: u64 = 2;
+
+// People 10..=15: each one knows the next two, everybody but 15 has an age
+fn create_graph(indexes: &mut QuadIndexes) {
+    for person in 10..=15 {
+        for other in person + 1..=(person + 2).min(15) {
+            indexes.insert_triple(&[iri(person).raw(), iri(KNOWS).raw(), iri(other).raw()]).unwrap();
+        }
+        if person != 15 {
+            indexes.insert_triple(&[iri(person).raw(), iri(AGE).raw(), integer(50 - person as i64).raw()]).unwrap();
+        }
+    }
+}
+
+fn with_graph(name: &str, test: impl FnOnce(&QuadIndexes)) {
+    let db_folder = temp_db_folder(name);
+    let buffer = Arc::new(BufferManager::new(&db_folder, 64, 1, 1));
+    {
+        let mut indexes = QuadIndexes::create(&buffer).unwrap();
+        create_graph(&mut indexes);
+        test(&indexes);
+    }
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+fn pattern(s: PatternTerm, p: u64, o: PatternTerm) -> TriplePattern {
+    TriplePattern::new(s, Constant(iri(p).raw()), o)
+}
+
+fn node(id: u64) -> PatternTerm {
+    Constant(iri(id).raw())
+}
+
+fn collect(iter: &mut dyn BindingIter, variables: usize) -> Vec<Vec<ObjectId>> {
+    let mut binding = Binding::new(variables);
+    let mut results = Vec::new();
+    iter.begin(&mut binding).unwrap();
+    while iter.next(&mut binding).unwrap() {
+        results.push(binding.values().to_vec());
+    }
+    results
+}
+
+#[test]
+fn test_joins() {
+    with_graph("executor_joins", |indexes| {
+        let interruption = Interruption::default();
+
+        // ?x knows ?y . ?y age ?a
+        let patterns = [pattern(Variable(0), KNOWS, Variable(1)), pattern(Variable(1), AGE, Variable(2))];
+        let mut expected = BTreeSet::new();
+        for x in 10..=15 {
+            for y in x + 1..=(x + 2).min(14) {
+                expected.insert(vec![iri(x), iri(y), integer(50 - y as i64)]);
+            }
+        }
+
+        let plan = plan_basic_graph_pattern(&patterns);
+        assert!(matches!(plan, JoinPlan::Binary { .. }));
+        let mut nested_loop = basic_graph_pattern_iter(indexes, plan, &interruption);
+        let results = collect(nested_loop.as_mut(), 3);
+        assert_eq!(results.len(), expected.len());
+        assert_eq!(results.into_iter().collect::<BTreeSet<_>>(), expected);
+
+        let mut hash_join = HashJoin::new(
+            Box::new(IndexScan::new(indexes, patterns[0], interruption.clone())),
+            Box::new(IndexScan::new(indexes, patterns[1], interruption.clone())),
+        );
+        assert_eq!(hash_join.variables(), vec![0, 1, 2]);
+        let results = collect(&mut hash_join, 3);
+        assert_eq!(results.len(), expected.len());
+        assert_eq!(results.into_iter().collect::<BTreeSet<_>>(), expected);
+
+        // A cyclic pattern with the Leapfrog Triejoin: ?x knows ?y . ?y knows ?z . ?x knows ?z
+        let triangle = [
+            pattern(Variable(0), KNOWS, Variable(1)),
+            pattern(Variable(1), KNOWS, Variable(2)),
+            pattern(Variable(0), KNOWS, Variable(2)),
+        ];
+        let plan = plan_basic_graph_pattern(&triangle);
+        assert!(matches!(plan, JoinPlan::Leapfrog { .. }));
+        let mut leapfrog = basic_graph_pattern_iter(indexes, plan, &interruption);
+        let results = collect(leapfrog.as_mut(), 3);
+        let expected: Vec<Vec<ObjectId>> = (10..=13).map(|x| vec![iri(x), iri(x + 1), iri(x + 2)]).collect();
+        assert_eq!(results, expected);
+
+        // Below a nested loop the Leapfrog Triejoin sees ?x, bound by the left side
+        let mut nested = NestedLoopJoin::new(
+            Box::new(IndexScan::new(indexes, pattern(Variable(0), AGE, Constant(integer(38).raw())), interruption.clone())),
+            basic_graph_pattern_iter(indexes, plan_basic_graph_pattern(&triangle), &interruption),
+        );
+        assert_eq!(collect(&mut nested, 3), vec![vec![iri(12), iri(13), iri(14)]]);
+
+        // A variable repeated in a pattern, ?x knows ?x, has no results
+        let mut scan = IndexScan::new(indexes, pattern(Variable(0), KNOWS, Variable(0)), interruption.clone());
+        assert!(collect(&mut scan, 1).is_empty());
+    });
+}
+
+#[test]
+fn test_optional_and_union() {
+    with_graph("executor_optional", |indexes| {
+        let interruption = Interruption::default();
+        let scan = |pattern| Box::new(IndexScan::new(indexes, pattern, interruption.clone())) as BoxedIter;
+
+        // ?x age ?a OPTIONAL { ?y knows ?x }: 10 is not known by anybody
+        let mut optional = LeftOuterJoin::new(
+            scan(pattern(Variable(0), AGE, Variable(1))),
+            scan(pattern(Variable(2), KNOWS, Variable(0))),
+        );
+        let results = collect(&mut optional, 3);
+        assert_eq!(results.len(), 1 + 1 + 2 + 2 + 2);
+        assert!(results.contains(&vec![iri(10), integer(40), ObjectId::NULL]));
+        assert!(results.contains(&vec![iri(11), integer(39), iri(10)]));
+        assert_eq!(results.iter().filter(|values| values[2].is_null()).count(), 1);
+
+        // { 12 knows ?x } UNION { ?x knows 12 } UNION { 15 age ?a }
+        let mut union = Union::new(vec![
+            scan(pattern(node(12), KNOWS, Variable(0))),
+            scan(pattern(Variable(0), KNOWS, node(12))),
+            scan(pattern(node(15), AGE, Variable(1))),
+        ]);
+        assert_eq!(union.variables(), vec![0, 1]);
+        let results = collect(&mut union, 2);
+        let expected: Vec<Vec<ObjectId>> = [13, 14, 10, 11].iter().map(|&x| vec![iri(x), ObjectId::NULL]).collect();
+        assert_eq!(results, expected);
+
+        // A union below a join starts every branch with the values of the left side
+        let mut join = NestedLoopJoin::new(
+            scan(pattern(node(10), KNOWS, Variable(0))),
+            Box::new(Union::new(vec![
+                scan(pattern(Variable(0), KNOWS, Variable(1))),
+                scan(pattern(Variable(0), AGE, Variable(2))),
+            ])),
+        );
+        let results = collect(&mut join, 3);
+        assert_eq!(
+            results,
+            vec![
+                vec![iri(11), iri(12), ObjectId::NULL],
+                vec![iri(11), iri(13), ObjectId::NULL],
+                vec![iri(11), ObjectId::NULL, integer(39)],
+                vec![iri(12), iri(13), ObjectId::NULL],
+                vec![iri(12), iri(14), ObjectId::NULL],
+                vec![iri(12), ObjectId::NULL, integer(38)],
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_solution_modifiers() {
+    with_graph("executor_modifiers", |indexes| {
+        let interruption = Interruption::default();
+        let scan = |pattern| Box::new(IndexScan::new(indexes, pattern, interruption.clone())) as BoxedIter;
+
+        // SELECT DISTINCT ?y WHERE { ?x knows ?y } ORDER BY DESC(?y) LIMIT 3 OFFSET 1
+        let project = Box::new(Project::new(scan(pattern(Variable(0), KNOWS, Variable(1))), vec![1]));
+        let distinct = Box::new(Distinct::new(project));
+        let order = Box::new(OrderBy::new(distinct, vec![OrderKey { var: 1, ascending: false }]));
+        let mut slice = Slice::new(order, 1, Some(3));
+        let results = collect(&mut slice, 2);
+        let expected: Vec<Vec<ObjectId>> = [14, 13, 12].iter().map(|&y| vec![ObjectId::NULL, iri(y)]).collect();
+        assert_eq!(results, expected);
+        // Starting again gives the same results
+        assert_eq!(collect(&mut slice, 2), expected);
+
+        // Ages ordered by value, filtered by a condition on the binding
+        let filter = Box::new(Filter::new(
+            scan(pattern(Variable(0), AGE, Variable(1))),
+            Box::new(|binding: &Binding| Ok(binding.get(1).integer().unwrap() >= 37)),
+        ));
+        let mut order = OrderBy::new(filter, vec![OrderKey { var: 1, ascending: true }]);
+        let results: Vec<ObjectId> = collect(&mut order, 2).into_iter().map(|values| values[0]).collect();
+        assert_eq!(results, vec![iri(13), iri(12), iri(11), iri(10)]);
+
+        let mut empty = Slice::new(scan(pattern(Variable(0), KNOWS, Variable(1))), 100, None);
+        assert!(collect(&mut empty, 2).is_empty());
+        let mut none = Slice::new(scan(pattern(Variable(0), KNOWS, Variable(1))), 0, Some(0));
+        assert!(collect(&mut none, 2).is_empty());
+        let mut single = SingleResult::default();
+        assert_eq!(collect(&mut single, 0), vec![Vec::<ObjectId>::new()]);
+    });
+}
+
+#[test]
+fn test_interruption() {
+    with_graph("executor_interruption", |indexes| {
+        let interruption = Interruption::default();
+
+        let plans = [
+            plan_basic_graph_pattern(&[pattern(Variable(0), KNOWS, Variable(1)), pattern(Variable(1), KNOWS, Variable(2))]),
+            plan_basic_graph_pattern(&[
+                pattern(Variable(0), KNOWS, Variable(1)),
+                pattern(Variable(1), KNOWS, Variable(2)),
+                pattern(Variable(0), KNOWS, Variable(2)),
+            ]),
+        ];
+        for plan in plans {
+            let interruption = Interruption::default();
+            let mut iter = basic_graph_pattern_iter(indexes, plan, &interruption);
+            let mut binding = Binding::new(3);
+            iter.begin(&mut binding).unwrap();
+            assert!(iter.next(&mut binding).unwrap());
+            interruption.request();
+            let error = iter.next(&mut binding).unwrap_err();
+            assert!(error.downcast_ref::<InterruptedException>().is_some());
+        }
+
+        // Operators reading all their input stop too
+        let interrupted = Interruption::default();
+        interrupted.request();
+        let scan = Box::new(IndexScan::new(indexes, pattern(Variable(0), KNOWS, Variable(1)), interrupted));
+        let mut order = OrderBy::new(scan, vec![OrderKey { var: 0, ascending: true }]);
+        assert!(order.begin(&mut Binding::new(2)).is_err());
+        drop(interruption);
+    });
+}
+
+#[tokio::test]
+async fn test_timeouts_interrupt_queries() {
+    let server = Server::new();
+    let (expired, running) = {
+        let server = server.lock().await;
+        let expired = server.register_query_context(Duration::ZERO).await;
+        let running = server.register_query_context(Duration::from_secs(3600)).await;
+        server.execute_timeouts().await;
+        (expired, running)
+    };
+    let interruption = expired.lock().await.thread_info.interruption();
+    for _ in 0..50 {
+        if interruption.check().is_err() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(interruption.check().is_err());
+    assert!(!running.lock().await.thread_info.interruption_requested.load(Ordering::Relaxed));
+
+    running.lock().await.reset();
+    *server.lock().await.shutdown_server.lock().await = true;
+}
@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+
+use milleniumdb_rs::query::functions::cast;
+use milleniumdb_rs::query::hashes::{md5, sha1, sha256, sha384, sha512, to_hex};
+use milleniumdb_rs::query::rdf_terms::{RdfTerm, XSD_BOOLEAN, XSD_DATE_TIME, XSD_DECIMAL, XSD_DOUBLE, XSD_INTEGER, XSD_STRING};
+use milleniumdb_rs::query::regex::Regex;
+use milleniumdb_rs::query::xsd_datetime::{XsdDateTime, XsdDuration};
+use milleniumdb_rs::query::xsd_decimal::XsdDecimal;
+
+fn matches(pattern: &str, flags: &str, text: &str) -> bool {
+    Regex::new(pattern, flags).unwrap().is_match(text)
+}
+
+fn replace(text: &str, pattern: &str, replacement: &str) -> Option<String> {
+    Regex::new(pattern, "").unwrap().replace(text, replacement)
+}
+
+#[test]
+fn test_regex() {
+    assert!(matches("b+", "", "abbc"));
+    assert!(!matches("^b+", "", "abbc"));
+    assert!(matches("^a(b|c)*d?$", "", "abcbc"));
+    assert!(matches("^[a-c]{2,3}$", "", "cab"));
+    assert!(!matches("^[a-c]{2,3}$", "", "cabb"));
+    assert!(matches("^[^0-9]+$", "", "abc"));
+    assert!(matches("^\\d{4}-\\d{2}$", "", "2024-05"));
+    assert!(matches("^[a-z-[aeiou]]+$", "", "xyz"));
+    assert!(!matches("^[a-z-[aeiou]]+$", "", "xaz"));
+    assert!(matches("^\\p{Lu}\\w*$", "", "Émile"));
+    assert!(matches("\\.", "", "a.b"));
+    assert!(!matches("\\.", "", "ab"));
+
+    // Flags
+    assert!(matches("^ALICE$", "i", "alice"));
+    assert!(!matches("^a.b$", "", "a\nb"));
+    assert!(matches("^a.b$", "s", "a\nb"));
+    assert!(matches("^b$", "m", "a\nb"));
+    assert!(matches("a b c", "x", "abc"));
+    assert!(matches("a+", "q", "xa+y"));
+    assert!(!matches("a+", "q", "aa"));
+
+    // Invalid patterns and flags
+    assert!(Regex::new("a(b", "").is_none());
+    assert!(Regex::new("*a", "").is_none());
+    assert!(Regex::new("[z-a]", "").is_none());
+    assert!(Regex::new("a{3,1}", "").is_none());
+    assert!(Regex::new("a", "g").is_none());
+
+    // Nested quantifiers of empty matches end
+    assert!(!matches("^(a*)*b$", "", &"a".repeat(5000)));
+}
+
+#[test]
+fn test_regex_replace() {
+    assert_eq!(replace("abcd", "b", "Z").unwrap(), "aZcd");
+    assert_eq!(replace("banana", "a(n)?", "[$1]").unwrap(), "b[n][n][]");
+    assert_eq!(replace("abab", "(a)(b)", "$2$1").unwrap(), "baba");
+    assert_eq!(replace("a.b", "\\.", "\\$").unwrap(), "a$b");
+    // Reluctant quantifiers
+    assert_eq!(replace("<a><b>", "<.+?>", "x").unwrap(), "xx");
+    assert_eq!(replace("<a><b>", "<.+>", "x").unwrap(), "x");
+    // Patterns matching the empty string and invalid replacements are errors
+    assert!(replace("abc", "x*", "y").is_none());
+    assert!(replace("abc", "b", "\\n").is_none());
+}
+
+#[test]
+fn test_hashes() {
+    assert_eq!(to_hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(to_hex(&md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(to_hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(to_hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(
+        to_hex(&sha384(b"abc")),
+        "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"
+    );
+    assert_eq!(
+        to_hex(&sha512(b"abc")),
+        "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+    );
+    // Messages of more than one block
+    let message = "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    assert_eq!(to_hex(&sha1(message.as_bytes())), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    assert_eq!(to_hex(&sha256(message.as_bytes())), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    assert_eq!(to_hex(&md5(&[b'a'; 100])), "36a92cc94a9e0fa21f625f8bfb007adf");
+}
+
+#[test]
+fn test_casts() {
+    let literal = |value: &str, datatype: &str| RdfTerm::typed_literal(value, datatype);
+    assert_eq!(cast(&literal(" 42 ", XSD_STRING), XSD_INTEGER), Some(literal("42", XSD_INTEGER)));
+    assert_eq!(cast(&literal("4.7", XSD_DECIMAL), XSD_INTEGER), Some(literal("4", XSD_INTEGER)));
+    assert_eq!(cast(&literal("true", XSD_BOOLEAN), XSD_DOUBLE), Some(literal("1.0E0", XSD_DOUBLE)));
+    assert_eq!(cast(&literal("0", XSD_INTEGER), XSD_BOOLEAN), Some(literal("false", XSD_BOOLEAN)));
+    assert_eq!(cast(&literal("7", XSD_INTEGER), XSD_STRING), Some(RdfTerm::simple_literal("7")));
+    assert_eq!(cast(&RdfTerm::iri("http://ex.org/a"), XSD_STRING), Some(RdfTerm::simple_literal("http://ex.org/a")));
+    assert_eq!(
+        cast(&RdfTerm::simple_literal("2024-01-02T03:04:05Z"), XSD_DATE_TIME),
+        Some(literal("2024-01-02T03:04:05Z", XSD_DATE_TIME))
+    );
+    assert_eq!(cast(&RdfTerm::simple_literal("abc"), XSD_INTEGER), None);
+    assert_eq!(cast(&literal("INF", XSD_DOUBLE), XSD_INTEGER), None);
+    assert_eq!(cast(&RdfTerm::lang_literal("1", "en"), XSD_INTEGER), None);
+    assert_eq!(cast(&RdfTerm::iri("http://ex.org/a"), XSD_INTEGER), None);
+}
+
+#[test]
+fn test_unix_time() {
+    assert_eq!(XsdDateTime::from_unix_time(0, 0).to_string(), "1970-01-01T00:00:00Z");
+    assert_eq!(XsdDateTime::from_unix_time(951_825_600, 500_000_000).to_string(), "2000-02-29T12:00:00.5Z");
+    assert_eq!(XsdDateTime::from_unix_time(-1, 0).to_string(), "1969-12-31T23:59:59Z");
+}
+
+fn decimal(lexical: &str) -> XsdDecimal {
+    XsdDecimal::parse(lexical).unwrap()
+}
+
+#[test]
+fn test_decimals() {
+    assert_eq!(decimal("+001.500").to_string(), "1.5");
+    assert_eq!(decimal("-0.0").to_string(), "0.0");
+    assert_eq!(decimal("12").to_string(), "12.0");
+    assert_eq!(decimal(".05").to_string(), "0.05");
+    assert!(XsdDecimal::parse("1e3").is_none());
+    assert!(XsdDecimal::parse(".").is_none());
+
+    // Arbitrary precision
+    let big = decimal("123456789012345678901234567890.000000000000000000001");
+    assert_eq!(big.add(&decimal("0.999999999999999999999")).to_string(), "123456789012345678901234567891.0");
+    assert_eq!(decimal("0.1").add(&decimal("0.2")), decimal("0.3"));
+    assert_eq!(decimal("1.5").subtract(&decimal("2.25")).to_string(), "-0.75");
+    assert_eq!(decimal("99999999999999999999").multiply(&decimal("-99999999999999999999")).to_string(), "-9999999999999999999800000000000000000001.0");
+    assert_eq!(decimal("1").divide(&decimal("3")).unwrap().to_string(), "0.333333333333333333333333");
+    assert_eq!(decimal("-7.5").divide(&decimal("2.5")).unwrap().to_string(), "-3.0");
+    assert!(decimal("1").divide(&XsdDecimal::zero()).is_none());
+
+    // Rounding
+    assert_eq!(decimal("-2.5").round(), decimal("-2"));
+    assert_eq!(decimal("2.5").round(), decimal("3"));
+    assert_eq!(decimal("-1.2").floor(), decimal("-2"));
+    assert_eq!(decimal("-1.8").ceil(), decimal("-1"));
+    assert_eq!(decimal("-1.8").truncate(), decimal("-1"));
+    assert_eq!(decimal("0.001").truncate(), XsdDecimal::zero());
+
+    // Order
+    assert!(decimal("-10") < decimal("-9.99"));
+    assert!(decimal("0.1") < decimal("0.10000000000000000001"));
+    assert!(decimal("100") > decimal("99.999"));
+    assert_eq!(XsdDecimal::from_f64(0.1).unwrap(), decimal("0.1"));
+    assert_eq!(XsdDecimal::from_f64(1e21).unwrap(), decimal("1000000000000000000000"));
+}
+
+#[test]
+fn test_durations() {
+    let duration = |lexical: &str| XsdDuration::parse(lexical).unwrap();
+    assert_eq!(duration("P1Y2M"), XsdDuration { months: 14, seconds: XsdDecimal::zero() });
+    assert_eq!(duration("-P1DT1H30M0.5S").seconds, decimal("-91800.5"));
+    assert_eq!(duration("PT36H"), duration("P1DT12H"));
+    for invalid in ["P", "PT", "P1DT", "P1H", "PT1D", "P1M1Y", "P1.5Y", "PT1H1H", "1Y", "P-1Y"] {
+        assert!(XsdDuration::parse(invalid).is_none(), "{}", invalid);
+    }
+    assert!(XsdDuration::parse_year_month("P1Y").is_some());
+    assert!(XsdDuration::parse_year_month("P1D").is_none());
+    assert!(XsdDuration::parse_day_time("PT1M").is_some());
+    assert!(XsdDuration::parse_day_time("P1M").is_none());
+
+    assert_eq!(duration("P1Y").partial_cmp(&duration("P13M")), Some(Ordering::Less));
+    assert_eq!(duration("P1M").partial_cmp(&duration("P1MT1S")), Some(Ordering::Less));
+    assert_eq!(duration("P1M").partial_cmp(&duration("P30D")), None);
+}
+
+#[test]
+fn test_instants() {
+    let date_time = |lexical: &str| XsdDateTime::parse_date_time(lexical).unwrap();
+    assert_eq!(date_time("1970-01-01T00:00:00Z").instant(), (0, 0));
+    assert_eq!(date_time("2000-02-29T12:00:00.5Z").instant(), (951_825_600, 500_000_000));
+    assert_eq!(date_time("1969-12-31T23:59:59Z").instant(), (-1, 0));
+    // The same instant in other timezones, without a timezone taken as UTC
+    assert_eq!(date_time("2024-01-01T05:30:00+05:30").compare(&date_time("2024-01-01T00:00:00Z")), Ordering::Equal);
+    assert_eq!(date_time("2023-12-31T23:00:00-02:00").compare(&date_time("2024-01-01T00:00:00")), Ordering::Greater);
+}
@@ -1,38 +1,73 @@
 use tokio::net::TcpStream;
 use tokio::io::{AsyncWriteExt, AsyncReadExt};
+use std::fs;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 
 
+use milleniumdb_rs::import::import_services::ImportOptions;
 use milleniumdb_rs::server::graph_server_orchestrator::startup_server;
+use milleniumdb_rs::storage::buffer_manager::BufferManager;
+
+const PORT: u16 = 1234;
+
+async fn send_query(query: &str) -> String {
+    // Connect to the server
+    let mut stream = TcpStream::connect(("127.0.0.1", PORT)).await.unwrap();
+
+    // Send the query
+    stream.write_all(query.as_bytes()).await.unwrap();
+
+    // Read the response
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    // Convert buffer to a string and trim to remove extra whitespace
+    String::from_utf8_lossy(&response).trim().to_string()
+}
 
 #[tokio::test]
 async fn test_graph_server() {
+    let db_folder = std::env::temp_dir().join(format!("milleniumdb_graph_server_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&db_folder);
+    fs::create_dir_all(&db_folder).unwrap();
+    let graph_file = db_folder.join("graph.qm");
+    fs::write(
+        &graph_file,
+        "(alice) :Person {name: \"Alice\", age: 42}\n\
+         (bob) :Person :Employee\n\
+         (alice)->(bob) :knows {since: 2010}\n\
+         (carol)->(alice) :follows\n",
+    )
+    .unwrap();
+
     // Start the server in a separate task
+    let import_options = ImportOptions {
+        db_folder: db_folder.clone(),
+        files: vec![graph_file],
+        ..ImportOptions::default()
+    };
+    let buffer_manager = Arc::new(BufferManager::new(&db_folder, 64, 1, 1));
     let server_handle = tokio::spawn(async {
-        // Replace this with the actual function to start your server
-        startup_server().await;
+        let _ = startup_server(import_options, buffer_manager, 0, PORT).await;
     });
 
     // Give the server some time to start
     sleep(Duration::from_secs(1)).await;
 
-    // Connect to the server
-    let mut stream = TcpStream::connect("127.0.0.1:1234").await.unwrap();
-
-    // Send a test query
-    stream.write_all(b"EXPECTED_QUERY").await.unwrap();
-
-    // Read the response
-    let mut buffer = [0; 1024];
-    let n = stream.read(&mut buffer).await.unwrap();
-
-    // Convert buffer to a string and trim to remove extra whitespace
-    let response = String::from_utf8_lossy(&buffer[..n]).trim().to_string();
-
-    // Check the response
-    assert_eq!(response, "Query received and processed.");
+    // Check the responses
+    assert_eq!(
+        send_query("(alice)").await,
+        "(alice) :Person {age: 42, name: \"Alice\"}\n\
+         (alice)->(bob) :knows {since: 2010}\n\
+         (alice)<-(carol) :follows"
+    );
+    assert_eq!(send_query("carol").await, "(carol)\n(carol)->(alice) :follows");
+    assert_eq!(send_query("(dave)").await, "Node not found: dave");
+    assert_eq!(send_query("EXPECTED_QUERY now").await, "Syntax Error: Invalid query format.");
 
     // Stop the server
     server_handle.abort();
-}
\ No newline at end of file
+    fs::remove_dir_all(&db_folder).unwrap();
+}
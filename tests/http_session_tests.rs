@@ -0,0 +1,119 @@
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use milleniumdb_rs::network::http::{HttpVersion, RequestReader};
+use milleniumdb_rs::network::session::Session;
+use milleniumdb_rs::network::sparql_servers::Server;
+
+#[tokio::test]
+async fn test_content_length_and_pipelined_requests() {
+    let input: &[u8] = b"POST /sparql HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhelloGET /a?b=c HTTP/1.0\r\n\r\n";
+    let mut reader = RequestReader::new(input);
+
+    let first = reader.read_request().await.unwrap().unwrap();
+    assert_eq!(first.method(), "POST");
+    assert_eq!(first.path(), "/sparql");
+    assert_eq!(first.header("content-length"), Some("5"));
+    assert_eq!(first.body, b"hello");
+    assert!(first.head.keep_alive());
+
+    let second = reader.read_request().await.unwrap().unwrap();
+    assert_eq!(second.head.version, HttpVersion::Http10);
+    assert_eq!(second.path(), "/a");
+    assert_eq!(second.query_string(), Some("b=c"));
+    assert!(!second.head.keep_alive());
+
+    assert!(reader.read_request().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_chunked_body_with_trailers() {
+    let input: &[u8] = b"POST / HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n\
+        4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Trailer: yes\r\n\r\n";
+    let mut reader = RequestReader::new(input);
+    let request = reader.read_request().await.unwrap().unwrap();
+    assert_eq!(request.body, b"Wikipedia");
+}
+
+#[tokio::test]
+async fn test_body_larger_than_one_read() {
+    let body = vec![b'x'; 100_000];
+    let mut input = format!("PUT / HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+    input.extend_from_slice(&body);
+    let mut reader = RequestReader::new(input.as_slice());
+    let request = reader.read_request().await.unwrap().unwrap();
+    assert_eq!(request.body.len(), body.len());
+}
+
+#[tokio::test]
+async fn test_malformed_and_oversized_requests() {
+    async fn status_of(input: &[u8], max_header: usize, max_body: usize) -> u16 {
+        let mut reader = RequestReader::with_limits(input, max_header, max_body);
+        reader.read_request().await.unwrap_err().status()
+    }
+
+    assert_eq!(status_of(b"GET /\r\n\r\n", 1024, 1024).await, 400);
+    assert_eq!(status_of(b"GET / HTTP/1.1\r\n\r\n", 1024, 1024).await, 400);
+    assert_eq!(status_of(b"GET / HTTP/1.1\r\nHost x\r\n\r\n", 1024, 1024).await, 400);
+    assert_eq!(status_of(b"GET / HTTP/2.0\r\nHost: x\r\n\r\n", 1024, 1024).await, 505);
+    assert_eq!(
+        status_of(b"POST / HTTP/1.1\r\nHost: x\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab", 1024, 1024).await,
+        400
+    );
+    assert_eq!(
+        status_of(b"POST / HTTP/1.1\r\nHost: x\r\nContent-Length: 4096\r\n\r\n", 1024, 1024).await,
+        413
+    );
+    assert_eq!(
+        status_of(b"POST / HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n800\r\n", 1024, 1024).await,
+        413
+    );
+    let long_header = format!("GET / HTTP/1.1\r\nHost: x\r\nX-Long: {}\r\n\r\n", "a".repeat(2048));
+    assert_eq!(status_of(long_header.as_bytes(), 1024, 1024).await, 431);
+}
+
+#[tokio::test]
+async fn test_session_keep_alive() {
+    let server = Server::new();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let server_weak = std::sync::Arc::downgrade(&server);
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        Session::new(server_weak, socket, Duration::from_secs(5)).run().await;
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream
+        .write_all(b"GET /missing HTTP/1.1\r\nHost: x\r\n\r\nGET /other HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert_eq!(response.matches("HTTP/1.1 404 Not Found\r\n").count(), 2);
+    assert!(response.contains("Connection: close\r\n"));
+}
+
+#[tokio::test]
+async fn test_server_timeout_option() {
+    let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let _ = Server::run(Server::new(), port, Duration::from_secs(1)).await;
+    });
+
+    let mut stream = loop {
+        match TcpStream::connect(("127.0.0.1", port)).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    // The idle connection is closed once the configured timeout passes
+    let mut response = String::new();
+    let closed = tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response)).await;
+    assert!(closed.is_ok());
+    assert!(response.is_empty());
+}
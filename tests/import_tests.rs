@@ -0,0 +1,441 @@
+use std::fs;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use milleniumdb_rs::import::bulk_loader::BulkLoader;
+use milleniumdb_rs::import::statement_encoder::{STRINGS_FILE, STRINGS_FILE_MAGIC};
+use milleniumdb_rs::import::external_sort::ExternalSorter;
+use milleniumdb_rs::import::ntriples_parser::NTriplesParser;
+use milleniumdb_rs::import::quad_model_loader::{QuadModelLoader, FROM_TO_TYPE_EDGE_FILE, LABEL_NODE_FILE};
+use milleniumdb_rs::import::quad_model_parser::{
+    EdgeDeclaration, NodeDeclaration, QuadModelElement, QuadModelParser, QuadModelValue,
+};
+use milleniumdb_rs::import::turtle_parser::{resolve_iri, TurtleParser};
+use milleniumdb_rs::query::quad_model_graph::QuadModelGraph;
+use milleniumdb_rs::query::rdf_terms::{RdfQuad, RdfTerm, RDF_FIRST, RDF_NIL, RDF_REST, RDF_TYPE, XSD_PREFIX};
+use milleniumdb_rs::storage::buffer_manager::BufferManager;
+use milleniumdb_rs::storage::catalog::{Catalog, DataModel};
+use milleniumdb_rs::storage::quad_indexes::QuadIndexes;
+use milleniumdb_rs::storage::string_manager::StringManager;
+
+mod common;
+use common::temp_db_folder;
+
+#[test]
+fn test_parse_ntriples() {
+    let input = "# comment\n\
+        <http://ex.org/s> <http://ex.org/p> \"caf\\u00E9\\n\"@EN-us .\n\
+        \n\
+        _:b1 <http://ex.org/p> \"42\"^^<http://www.w3.org/2001/XMLSchema#integer> . # trailing\n\
+        <http://ex.org/s> <http://ex.org/p> <http://ex.org/o> .\n";
+    let quads: Vec<RdfQuad> = NTriplesParser::ntriples(Cursor::new(input), "test.nt")
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(quads.len(), 3);
+    assert_eq!(quads[0].object, RdfTerm::lang_literal("café\n", "en-US"));
+    assert_eq!(quads[1].subject, RdfTerm::blank_node("b1"));
+    assert_eq!(
+        quads[1].object,
+        RdfTerm::typed_literal("42", "http://www.w3.org/2001/XMLSchema#integer")
+    );
+    assert_eq!(quads[2].object, RdfTerm::iri("http://ex.org/o"));
+    assert!(quads.iter().all(|quad| quad.graph.is_none()));
+}
+
+#[test]
+fn test_parse_nquads() {
+    let input = "<http://ex.org/s> <http://ex.org/p> \"o\" <http://ex.org/g> .\n\
+        <http://ex.org/s> <http://ex.org/p> \"o\" .\n";
+    let quads: Vec<RdfQuad> = NTriplesParser::nquads(Cursor::new(input), "test.nq")
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(quads[0].graph, Some(RdfTerm::iri("http://ex.org/g")));
+    assert_eq!(quads[1].graph, None);
+}
+
+#[test]
+fn test_parse_error_position() {
+    let input = "<http://ex.org/s> <http://ex.org/p> <http://ex.org/o> .\n\
+        <http://ex.org/s> <http://ex.org/p> \"unterminated .\n";
+    let error = NTriplesParser::ntriples(Cursor::new(input), "bad.nt")
+        .find_map(|quad| quad.err())
+        .unwrap();
+    assert_eq!(error.file(), "bad.nt");
+    assert_eq!(error.line(), 2);
+    assert!(error.column() > 1);
+
+    // Graphs are not allowed in N-Triples
+    let input = "<http://ex.org/s> <http://ex.org/p> <http://ex.org/o> <http://ex.org/g> .\n";
+    let error = NTriplesParser::ntriples(Cursor::new(input), "bad.nt")
+        .find_map(|quad| quad.err())
+        .unwrap();
+    assert_eq!(error.line(), 1);
+    assert_eq!(error.column(), 55);
+}
+
+#[test]
+fn test_external_sort_spills_and_merges() {
+    let folder = temp_db_folder("external_sort");
+    // A tiny budget makes every few records spill to a run file
+    let mut sorter: ExternalSorter<[u64; 2]> = ExternalSorter::new(&folder, "test", 64);
+    for i in 0..1000u64 {
+        sorter.push([(i * 7919) % 500, 1]).unwrap();
+    }
+    let sorted: Vec<[u64; 2]> = sorter.finish().unwrap().collect::<Result<_, _>>().unwrap();
+
+    let expected: Vec<[u64; 2]> = (0..500).map(|i| [i, 1]).collect();
+    assert_eq!(sorted, expected);
+    assert_eq!(fs::read_dir(&folder).unwrap().count(), 0);
+    fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn test_bulk_load() {
+    let folder = temp_db_folder("bulk_load");
+    let triples = folder.join("data.nt");
+    fs::write(
+        &triples,
+        "<http://ex.org/a> <http://ex.org/acquaintance> <http://ex.org/b> .\n\
+         <http://ex.org/b> <http://ex.org/acquaintance> _:x .\n\
+         <http://ex.org/a> <http://ex.org/acquaintance> <http://ex.org/b> .\n",
+    )
+    .unwrap();
+    let quads = folder.join("data.nq");
+    fs::write(
+        &quads,
+        "<http://ex.org/a> <http://ex.org/fullName> \"A\" <http://ex.org/g> .\n\
+         _:x <http://ex.org/fullName> \"X\" .\n",
+    )
+    .unwrap();
+
+    let mut loader = BulkLoader::new(&folder, 256).unwrap();
+    assert_eq!(loader.load_file(&triples).unwrap(), 3);
+    assert_eq!(loader.load_file(&quads).unwrap(), 2);
+    let statistics = loader.finish().unwrap();
+
+    assert_eq!(statistics.statements_read, 5);
+    // acquaintance and fullName; the IRIs share the discovered prefix http://ex.org/ and
+    // short suffixes, blank nodes and short literals are inlined
+    assert_eq!(statistics.iri_prefixes, 1);
+    assert_eq!(statistics.dictionary_terms, 2);
+    assert_eq!(statistics.default_graph_triples, 3);
+    assert_eq!(statistics.named_graph_quads, 1);
+
+    let strings = fs::read(folder.join(STRINGS_FILE)).unwrap();
+    assert_eq!(&strings[..8], STRINGS_FILE_MAGIC);
+    assert!(!folder.join("tmp_import").exists());
+    {
+        let buffer = Arc::new(BufferManager::new(&folder, 64, 1, 1));
+        let indexes = QuadIndexes::open(&buffer).unwrap();
+        let strings = StringManager::open(&buffer, 0).unwrap();
+        assert_eq!(strings.len(), 2);
+        let acquaintance = strings.get_id("acquaintance").unwrap().unwrap();
+        assert_eq!(strings.get_string(acquaintance).unwrap(), "acquaintance");
+        let catalog = Catalog::load(&folder).unwrap();
+        assert_eq!(catalog.data_model, DataModel::Rdf);
+        assert_eq!(catalog.prefixes.prefixes(), ["http://ex.org/"]);
+        assert_eq!(catalog.count("default_graph_triples"), 3);
+        assert_eq!(catalog.count("named_graph_quads"), 1);
+        assert_eq!(catalog.indexes.len(), 11);
+        assert!(catalog.indexes.iter().all(|index| folder.join(index).exists()));
+        assert_eq!(catalog.parameters["memory_budget"], "256");
+        assert_eq!(indexes.triple_count(), 3);
+        assert_eq!(indexes.quad_count(), 1);
+        let all: Vec<[u64; 3]> = indexes.scan_triples(&[None, None, None]).unwrap().map(Result::unwrap).collect();
+        assert_eq!(all.len(), 3);
+        // Every triple is found again from its object
+        for triple in &all {
+            let by_object: Vec<_> =
+                indexes.scan_triples(&[None, None, Some(triple[2])]).unwrap().map(Result::unwrap).collect();
+            assert!(by_object.contains(triple));
+        }
+    }
+
+    // A second load into the same folder is refused
+    assert!(BulkLoader::new(&folder, 256).is_err());
+    fs::remove_dir_all(&folder).unwrap();
+}
+
+fn parse_turtle(input: &str) -> Vec<RdfQuad> {
+    TurtleParser::turtle(Cursor::new(input), "test.ttl", Some("http://ex.org/base/doc"))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn xsd(local: &str) -> String {
+    format!("{}{}", XSD_PREFIX, local)
+}
+
+#[test]
+fn test_parse_turtle() {
+    let quads = parse_turtle(
+        "@prefix ex: <http://ex.org/> .\n\
+         PREFIX : <http://ex.org/default#>\n\
+         ex:alice a ex:Person ;\n\
+         \x20   ex:name \"Alice\"@en, 'Alicia' ;\n\
+         \x20   ex:age 42 ; ex:height 1.65 ; ex:weight 6.5e1 ; ex:active true ;\n\
+         \x20   ex:bio \"\"\"Line one\n\
+         line \"two\\\"\"\"\" ;\n\
+         \x20   :knows <bob>, ex:carol.\n\
+         ex:ns\\.local ex:p ex:o .\n",
+    );
+    let alice = RdfTerm::iri("http://ex.org/alice");
+    let objects: Vec<&RdfTerm> = quads.iter().map(|quad| &quad.object).collect();
+
+    assert_eq!(quads.len(), 11);
+    assert!(quads[..10].iter().all(|quad| quad.subject == alice));
+    assert_eq!(quads[0].predicate, RdfTerm::iri(RDF_TYPE));
+    assert_eq!(objects[0], &RdfTerm::iri("http://ex.org/Person"));
+    assert_eq!(objects[1], &RdfTerm::lang_literal("Alice", "en"));
+    assert_eq!(objects[2], &RdfTerm::simple_literal("Alicia"));
+    assert_eq!(objects[3], &RdfTerm::typed_literal("42", &xsd("integer")));
+    assert_eq!(objects[4], &RdfTerm::typed_literal("1.65", &xsd("decimal")));
+    assert_eq!(objects[5], &RdfTerm::typed_literal("6.5e1", &xsd("double")));
+    assert_eq!(objects[6], &RdfTerm::typed_literal("true", &xsd("boolean")));
+    assert_eq!(objects[7], &RdfTerm::simple_literal("Line one\nline \"two\""));
+    assert_eq!(quads[8].predicate, RdfTerm::iri("http://ex.org/default#knows"));
+    assert_eq!(objects[8], &RdfTerm::iri("http://ex.org/base/bob"));
+    assert_eq!(objects[9], &RdfTerm::iri("http://ex.org/carol"));
+    assert_eq!(quads[10].subject, RdfTerm::iri("http://ex.org/ns.local"));
+}
+
+#[test]
+fn test_parse_turtle_blank_nodes_and_collections() {
+    let quads = parse_turtle(
+        "@prefix ex: <http://ex.org/> .\n\
+         [ ex:name \"anonymous\" ] ex:likes ( 1 ex:two ) .\n\
+         _:genid1 ex:list () .\n\
+         [ ex:alone true ] .\n",
+    );
+    let statements: Vec<(RdfTerm, RdfTerm, RdfTerm)> = quads
+        .into_iter()
+        .map(|quad| (quad.subject, quad.predicate, quad.object))
+        .collect();
+
+    let anonymous = RdfTerm::blank_node("genid1");
+    let first = RdfTerm::blank_node("genid2");
+    let second = RdfTerm::blank_node("genid3");
+    assert_eq!(
+        statements,
+        vec![
+            (anonymous.clone(), RdfTerm::iri("http://ex.org/name"), RdfTerm::simple_literal("anonymous")),
+            (first.clone(), RdfTerm::iri(RDF_FIRST), RdfTerm::typed_literal("1", &xsd("integer"))),
+            (first.clone(), RdfTerm::iri(RDF_REST), second.clone()),
+            (second.clone(), RdfTerm::iri(RDF_FIRST), RdfTerm::iri("http://ex.org/two")),
+            (second, RdfTerm::iri(RDF_REST), RdfTerm::iri(RDF_NIL)),
+            (anonymous, RdfTerm::iri("http://ex.org/likes"), first),
+            // Labels of the document never clash with the generated ones
+            (RdfTerm::blank_node("genidgenid1"), RdfTerm::iri("http://ex.org/list"), RdfTerm::iri(RDF_NIL)),
+            (
+                RdfTerm::blank_node("genid4"),
+                RdfTerm::iri("http://ex.org/alone"),
+                RdfTerm::typed_literal("true", &xsd("boolean"))
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_trig() {
+    let input = "@prefix ex: <http://ex.org/> .\n\
+        ex:s ex:p ex:o .\n\
+        ex:g1 { ex:s ex:p 1 . ex:s ex:p 2 }\n\
+        GRAPH ex:g2 { ex:s ex:p 3 . }\n\
+        { ex:s ex:p 4 }\n";
+    let quads: Vec<RdfQuad> = TurtleParser::trig(Cursor::new(input), "test.trig", None)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let graphs: Vec<Option<RdfTerm>> = quads.into_iter().map(|quad| quad.graph).collect();
+    let g1 = Some(RdfTerm::iri("http://ex.org/g1"));
+    let g2 = Some(RdfTerm::iri("http://ex.org/g2"));
+    assert_eq!(graphs, vec![None, g1.clone(), g1, g2, None]);
+
+    // The statements of a block are returned as they are parsed, before the rest of the block
+    let block = "<http://ex.org/g> { <http://ex.org/s> <http://ex.org/p> 1 .\n <http://ex.org/s> ; }";
+    let mut parser = TurtleParser::trig(Cursor::new(block), "test.trig", None);
+    assert_eq!(parser.next().unwrap().unwrap().graph, Some(RdfTerm::iri("http://ex.org/g")));
+    assert_eq!(parser.next().unwrap().unwrap_err().line(), 2);
+    assert!(parser.next().is_none());
+
+    // Graph blocks are not Turtle
+    let error = TurtleParser::turtle(Cursor::new(input), "test.ttl", None)
+        .find_map(|quad| quad.err())
+        .unwrap();
+    assert_eq!(error.line(), 3);
+}
+
+#[test]
+fn test_parse_turtle_errors() {
+    let error_of = |input: &str| {
+        TurtleParser::turtle(Cursor::new(input), "bad.ttl", None)
+            .find_map(|quad| quad.err())
+            .unwrap()
+    };
+
+    let error = error_of("@prefix ex: <http://ex.org/> .\nex:s ex:p\n  undefined:o .\n");
+    assert_eq!((error.line(), error.column()), (3, 3));
+
+    let error = error_of("<http://ex.org/s> <http://ex.org/p> <relative> .\n");
+    assert_eq!((error.line(), error.column()), (1, 37));
+
+    let error = error_of("<http://ex.org/s> <http://ex.org/p> \"\"\"never\nclosed .\n");
+    assert_eq!((error.line(), error.column()), (1, 37));
+
+    let error = error_of("<http://ex.org/s> <http://ex.org/p> <http://ex.org/o>\n");
+    assert_eq!(error.line(), 2);
+}
+
+#[test]
+fn test_resolve_iri() {
+    let base = "http://a/b/c/d;p?q";
+    assert_eq!(resolve_iri(base, "g"), "http://a/b/c/g");
+    assert_eq!(resolve_iri(base, "./g/"), "http://a/b/c/g/");
+    assert_eq!(resolve_iri(base, "/g"), "http://a/g");
+    assert_eq!(resolve_iri(base, "//g"), "http://g");
+    assert_eq!(resolve_iri(base, "?y"), "http://a/b/c/d;p?y");
+    assert_eq!(resolve_iri(base, "#s"), "http://a/b/c/d;p?q#s");
+    assert_eq!(resolve_iri(base, ""), "http://a/b/c/d;p?q");
+    assert_eq!(resolve_iri(base, "../../g"), "http://a/g");
+    assert_eq!(resolve_iri(base, "../../../g"), "http://a/g");
+    assert_eq!(resolve_iri(base, "g;x=1/../y"), "http://a/b/c/y");
+}
+
+#[test]
+fn test_bulk_load_turtle() {
+    let folder = temp_db_folder("bulk_load_turtle");
+    let turtle = folder.join("ontology.ttl");
+    fs::write(&turtle, "@prefix ex: <http://ex.org/> .\nex:a ex:p ex:b, ex:c ; ex:q [ ex:r 1 ] .\n").unwrap();
+    let trig = folder.join("graphs.trig");
+    fs::write(&trig, "<g> { <a> <p> <b> }\n").unwrap();
+
+    let mut loader = BulkLoader::new(&folder, 1024).unwrap();
+    assert_eq!(loader.load_file(&turtle).unwrap(), 4);
+    assert_eq!(loader.load_file(&trig).unwrap(), 1);
+    let statistics = loader.finish().unwrap();
+    assert_eq!(statistics.default_graph_triples, 4);
+    assert_eq!(statistics.named_graph_quads, 1);
+
+    // Relative IRIs are resolved against the location of the file, which becomes a prefix
+    let prefixes = Catalog::load(&folder).unwrap().prefixes;
+    let graph = format!("file://{}/g", fs::canonicalize(&folder).unwrap().display());
+    let (prefix_id, suffix) = prefixes.split(&graph);
+    assert!(prefix_id > 0);
+    assert_eq!(suffix, "g");
+    fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn test_parse_quad_model() {
+    let input = "# people\n\
+        (alice) :Person :Employee {name: \"Alice \\\"A\\\"\", age: -42, height: 1.65, active: true}\n\
+        \n\
+        (alice)->(bob) :knows {since: 2010}\n\
+        ( carol )<-( bob ) :follows {}\n";
+    let elements: Vec<QuadModelElement> = QuadModelParser::new(Cursor::new(input), "graph.qm")
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(
+        elements,
+        vec![
+            QuadModelElement::Node(NodeDeclaration {
+                id: "alice".to_string(),
+                labels: vec!["Person".to_string(), "Employee".to_string()],
+                properties: vec![
+                    ("name".to_string(), QuadModelValue::String("Alice \"A\"".to_string())),
+                    ("age".to_string(), QuadModelValue::Integer(-42)),
+                    ("height".to_string(), QuadModelValue::Float(1.65)),
+                    ("active".to_string(), QuadModelValue::Boolean(true)),
+                ],
+            }),
+            QuadModelElement::Edge(EdgeDeclaration {
+                from: "alice".to_string(),
+                to: "bob".to_string(),
+                edge_type: "knows".to_string(),
+                properties: vec![("since".to_string(), QuadModelValue::Integer(2010))],
+            }),
+            QuadModelElement::Edge(EdgeDeclaration {
+                from: "bob".to_string(),
+                to: "carol".to_string(),
+                edge_type: "follows".to_string(),
+                properties: vec![],
+            }),
+        ]
+    );
+    assert_eq!(QuadModelValue::Float(1e20).to_string(), "1e20");
+    assert_eq!(QuadModelValue::String("a\"b".to_string()).to_string(), "\"a\\\"b\"");
+}
+
+#[test]
+fn test_parse_quad_model_errors() {
+    let error_of = |input: &str| {
+        QuadModelParser::new(Cursor::new(input), "bad.qm")
+            .find_map(|element| element.err())
+            .unwrap()
+    };
+
+    let error = error_of("(a) :Person\n(a)->(b) :knows :likes\n");
+    assert_eq!((error.line(), error.column()), (2, 17));
+    let error = error_of("(a)->(b)\n");
+    assert_eq!((error.line(), error.column()), (1, 9));
+    let error = error_of("(a) {age: 12x}\n");
+    assert_eq!((error.line(), error.column()), (1, 13));
+    let error = error_of("(a) {age: 99999999999999999999}\n");
+    assert_eq!((error.line(), error.column()), (1, 11));
+    let error = error_of("(_a)\n");
+    assert_eq!((error.line(), error.column()), (1, 2));
+}
+
+#[test]
+fn test_quad_model_load() {
+    let folder = temp_db_folder("quad_model_load");
+    let graph_file = folder.join("graph.qm");
+    fs::write(
+        &graph_file,
+        "(alice) :Person {name: \"Alice\"}\n\
+         (bob) :Person\n\
+         (bob) :Person\n\
+         (alice)->(bob) :knows\n\
+         (alice)->(bob) :knows {since: 2010}\n\
+         (bob)->(bob) :likes\n",
+    )
+    .unwrap();
+
+    let mut loader = QuadModelLoader::new(&folder, 128).unwrap();
+    assert_eq!(loader.load_file(&graph_file).unwrap(), 6);
+    let statistics = loader.finish().unwrap();
+    assert_eq!(statistics.nodes, 2);
+    assert_eq!(statistics.labels, 2);
+    assert_eq!(statistics.properties, 2);
+    // Parallel edges are different edges
+    assert_eq!(statistics.edges, 3);
+    let catalog = Catalog::load(&folder).unwrap();
+    assert_eq!(catalog.data_model, DataModel::QuadModel);
+    assert_eq!(catalog.count("edges"), 3);
+    assert!(catalog.prefixes.is_empty());
+    assert!(catalog.indexes.iter().all(|index| folder.join(index).exists()));
+
+    let buffer = Arc::new(BufferManager::new(&folder, 64, 1, 1));
+    let graph = QuadModelGraph::open(&buffer, 0).unwrap();
+    let person = graph.term_id("Person").unwrap().unwrap();
+    assert_eq!(graph.term(person).unwrap(), "Person");
+    assert_eq!(graph.term_id("Nobody").unwrap(), None);
+    assert_eq!(graph.node_index().len(), 2);
+    assert_eq!(graph.label_index(LABEL_NODE_FILE).scan_prefix(&[person]).unwrap().count(), 2);
+    let alice = graph.term_id("alice").unwrap().unwrap();
+    assert_eq!(graph.edge_index(FROM_TO_TYPE_EDGE_FILE).scan_prefix(&[alice]).unwrap().count(), 2);
+
+    assert_eq!(
+        graph.describe_node("bob").unwrap().unwrap(),
+        "(bob) :Person\n(bob)->(bob) :likes\n(bob)<-(alice) :knows\n(bob)<-(alice) :knows {since: 2010}\n"
+    );
+    assert_eq!(graph.describe_node("Person").unwrap(), None);
+
+    // A second load into the same folder is refused
+    assert!(QuadModelLoader::new(&folder, 128).is_err());
+    drop(graph);
+    drop(buffer);
+    fs::remove_dir_all(&folder).unwrap();
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::Arc;
+
+use milleniumdb_rs::query::leapfrog_join::LeapfrogJoin;
+use milleniumdb_rs::query::planner::{is_cyclic, plan_basic_graph_pattern, JoinPlan, PatternTerm, TriplePattern};
+use milleniumdb_rs::storage::buffer_manager::BufferManager;
+use milleniumdb_rs::storage::quad_indexes::QuadIndexes;
+
+use PatternTerm::{Constant, Variable};
+
+mod common;
+use common::temp_db_folder;
+
+// Deterministic pseudo-random numbers for the tests
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % bound
+    }
+}
+
+// Results of the join as sets of (variable, value), whatever the variable order
+fn leapfrog_results(indexes: &QuadIndexes, plan: &JoinPlan) -> BTreeSet<Vec<(usize, u64)>> {
+    let (variable_order, patterns) = match plan {
+        JoinPlan::Leapfrog { variable_order, patterns } => (variable_order, patterns),
+        JoinPlan::Binary { .. } => panic!("expected a Leapfrog plan, got {:?}", plan),
+    };
+    let mut results = BTreeSet::new();
+    for bindings in LeapfrogJoin::new(indexes, variable_order.len(), patterns) {
+        let mut result: Vec<(usize, u64)> = variable_order.iter().copied().zip(bindings.unwrap()).collect();
+        result.sort();
+        assert!(results.insert(result), "duplicated result");
+    }
+    results
+}
+
+#[test]
+fn test_cyclic_patterns() {
+    let chain = [
+        TriplePattern::new(Variable(0), Constant(1), Variable(1)),
+        TriplePattern::new(Variable(1), Constant(1), Variable(2)),
+    ];
+    assert!(!is_cyclic(&chain));
+    let star = [
+        TriplePattern::new(Variable(0), Variable(1), Variable(2)),
+        TriplePattern::new(Variable(0), Constant(1), Variable(3)),
+        TriplePattern::new(Variable(0), Constant(2), Constant(3)),
+    ];
+    assert!(!is_cyclic(&star));
+    let triangle = [
+        TriplePattern::new(Variable(0), Constant(1), Variable(1)),
+        TriplePattern::new(Variable(1), Constant(1), Variable(2)),
+        TriplePattern::new(Variable(2), Constant(1), Variable(0)),
+    ];
+    assert!(is_cyclic(&triangle));
+    let square = [
+        TriplePattern::new(Variable(0), Constant(1), Variable(1)),
+        TriplePattern::new(Variable(1), Constant(1), Variable(2)),
+        TriplePattern::new(Variable(2), Constant(1), Variable(3)),
+        TriplePattern::new(Variable(3), Constant(1), Variable(0)),
+    ];
+    assert!(is_cyclic(&square));
+
+    // Acyclic patterns are joined pairwise, without cross products when possible
+    let plan = plan_basic_graph_pattern(&[chain[0], star[2], chain[1]]);
+    assert_eq!(plan, JoinPlan::Binary { patterns: vec![star[2], chain[0], chain[1]] });
+    assert!(matches!(plan_basic_graph_pattern(&triangle), JoinPlan::Leapfrog { .. }));
+
+    // A variable repeated in a pattern is not a level of the tries
+    let repeated = [
+        TriplePattern::new(Variable(0), Variable(0), Variable(1)),
+        TriplePattern::new(Variable(1), Constant(1), Variable(2)),
+        TriplePattern::new(Variable(2), Constant(1), Variable(0)),
+    ];
+    assert!(is_cyclic(&repeated));
+    assert!(matches!(plan_basic_graph_pattern(&repeated), JoinPlan::Binary { .. }));
+}
+
+#[test]
+fn test_leapfrog_triangles() {
+    let db_folder = temp_db_folder("leapfrog_triangles");
+    let buffer = Arc::new(BufferManager::new(&db_folder, 256, 1, 1));
+    let mut indexes = QuadIndexes::create(&buffer).unwrap();
+
+    // A random graph with two predicates, node ids from 100
+    let knows = 1;
+    let likes = 2;
+    let mut random = Lcg(7);
+    let mut triples = BTreeSet::new();
+    for _ in 0..3000 {
+        let triple = [100 + random.next(60), 1 + random.next(2), 100 + random.next(60)];
+        indexes.insert_triple(&triple).unwrap();
+        triples.insert(triple);
+    }
+    let edges = |predicate: u64| triples.iter().filter(move |t| t[1] == predicate).map(|t| (t[0], t[2]));
+
+    // ?x knows ?y . ?y knows ?z . ?z likes ?x
+    let triangle = [
+        TriplePattern::new(Variable(0), Constant(knows), Variable(1)),
+        TriplePattern::new(Variable(1), Constant(knows), Variable(2)),
+        TriplePattern::new(Variable(2), Constant(likes), Variable(0)),
+    ];
+    let mut expected = BTreeSet::new();
+    for (x, y) in edges(knows) {
+        for (y2, z) in edges(knows) {
+            if y == y2 && triples.contains(&[z, likes, x]) {
+                expected.insert(vec![(0, x), (1, y), (2, z)]);
+            }
+        }
+    }
+    assert!(!expected.is_empty());
+    assert_eq!(leapfrog_results(&indexes, &plan_basic_graph_pattern(&triangle)), expected);
+
+    // With the predicate as a variable too, and a pattern that is only constants
+    let (a, b) = edges(knows).next().unwrap();
+    let triangle = [
+        TriplePattern::new(Variable(0), Variable(3), Variable(1)),
+        TriplePattern::new(Variable(1), Constant(knows), Variable(2)),
+        TriplePattern::new(Variable(2), Constant(likes), Variable(0)),
+        TriplePattern::new(Constant(a), Constant(knows), Constant(b)),
+    ];
+    let mut expected = BTreeSet::new();
+    for &[x, p, y] in &triples {
+        for (y2, z) in edges(knows) {
+            if y == y2 && triples.contains(&[z, likes, x]) {
+                expected.insert(vec![(0, x), (1, y), (2, z), (3, p)]);
+            }
+        }
+    }
+    assert_eq!(leapfrog_results(&indexes, &plan_basic_graph_pattern(&triangle)), expected);
+    let mut missing = triangle;
+    missing[3] = TriplePattern::new(Constant(a), Constant(3), Constant(b));
+    assert!(leapfrog_results(&indexes, &plan_basic_graph_pattern(&missing)).is_empty());
+
+    drop(indexes);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
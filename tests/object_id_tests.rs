@@ -0,0 +1,188 @@
+use milleniumdb_rs::query::object_id::{
+    decimal_to_string, double_to_string, ObjectId, ObjectKind, TermEncoding, MASK_IRI_EXTERN, MASK_STRING_LANG_EXTERN,
+    MASK_TYPED_LITERAL_EXTERN, MAX_INLINED_INTEGER,
+};
+use milleniumdb_rs::query::rdf_terms::{RdfTerm, XSD_BOOLEAN, XSD_DATE, XSD_DATE_TIME, XSD_DECIMAL, XSD_DOUBLE, XSD_INTEGER};
+use milleniumdb_rs::query::xsd_datetime::XsdDateTime;
+use milleniumdb_rs::storage::iri_prefixes::{IriPrefixes, PrefixDiscovery};
+
+fn no_prefixes() -> IriPrefixes {
+    IriPrefixes::default()
+}
+
+fn inlined(term: &RdfTerm) -> ObjectId {
+    match ObjectId::encode(term, &no_prefixes()) {
+        TermEncoding::Inlined(id) => id,
+        TermEncoding::External { key, .. } => panic!("{} is not inlined", key),
+    }
+}
+
+// Encodes and decodes an inlined term, returning the decoded lexical form
+fn round_trip(value: &str, datatype: &str) -> String {
+    match inlined(&RdfTerm::typed_literal(value, datatype)).decode_inlined(&no_prefixes()).unwrap() {
+        RdfTerm::Literal { value, datatype: Some(decoded), .. } => {
+            assert_eq!(decoded, datatype);
+            value
+        }
+        other => panic!("unexpected term {:?}", other),
+    }
+}
+
+#[test]
+fn test_inlined_strings() {
+    for term in [RdfTerm::iri("ex:a"), RdfTerm::blank_node("b1"), RdfTerm::simple_literal("seven!!"), RdfTerm::simple_literal("")] {
+        let id = inlined(&term);
+        assert!(!id.is_external());
+        assert_eq!(id.decode_inlined(&no_prefixes()), Some(term));
+    }
+    assert_eq!(inlined(&RdfTerm::iri("ex:a")).kind(), ObjectKind::Iri);
+    assert_eq!(inlined(&RdfTerm::blank_node("b1")).kind(), ObjectKind::BlankNode);
+    assert_eq!(inlined(&RdfTerm::simple_literal("x")).kind(), ObjectKind::String);
+
+    // Long IRIs go to the dictionary without their prefix, other long values and
+    // language strings with their N-Triples key
+    assert_eq!(
+        ObjectId::encode(&RdfTerm::iri("http://example.org/a"), &no_prefixes()),
+        TermEncoding::External { mask: MASK_IRI_EXTERN, key: "http://example.org/a".to_string() }
+    );
+    assert_eq!(
+        ObjectId::encode(&RdfTerm::lang_literal("a", "en"), &no_prefixes()),
+        TermEncoding::External { mask: MASK_STRING_LANG_EXTERN, key: "\"a\"@en".to_string() }
+    );
+    let id = ObjectId::external(MASK_STRING_LANG_EXTERN, 1234);
+    assert_eq!(id.kind(), ObjectKind::LangString);
+    assert_eq!(id.dictionary_id(), Some(1234));
+    assert_eq!(id.decode_inlined(&no_prefixes()), None);
+    assert_eq!(id.decode_external("\"a\"@en", &no_prefixes()).unwrap(), RdfTerm::lang_literal("a", "en"));
+    assert!(id.decode_external("\"a\" x", &no_prefixes()).is_err());
+}
+
+#[test]
+fn test_inlined_numbers() {
+    assert_eq!(round_trip("42", XSD_INTEGER), "42");
+    assert_eq!(round_trip("-0042", XSD_INTEGER), "-42");
+    assert_eq!(round_trip("+0", XSD_INTEGER), "0");
+    assert_eq!(inlined(&RdfTerm::typed_literal("-7", XSD_INTEGER)).integer(), Some(-7));
+    assert!(ObjectId::from_integer(MAX_INLINED_INTEGER).is_some());
+    assert!(ObjectId::from_integer(MAX_INLINED_INTEGER + 1).is_none());
+    match ObjectId::encode(&RdfTerm::typed_literal("123456789012345678901", XSD_INTEGER), &no_prefixes()) {
+        TermEncoding::External { mask, .. } => assert_eq!(ObjectId::external(mask, 0).kind(), ObjectKind::Integer),
+        TermEncoding::Inlined(_) => panic!("big integers are not inlined"),
+    }
+
+    assert_eq!(round_trip("1.50", XSD_DECIMAL), "1.5");
+    assert_eq!(round_trip("-.25", XSD_DECIMAL), "-0.25");
+    assert_eq!(round_trip("007", XSD_DECIMAL), "7.0");
+    assert_eq!(decimal_to_string(-5, 3), "-0.005");
+
+    assert_eq!(round_trip("1.5e3", XSD_DOUBLE), "1.5E3");
+    assert_eq!(round_trip("-INF", XSD_DOUBLE), "-INF");
+    assert_eq!(round_trip("2", XSD_DOUBLE), "2.0E0");
+    assert_eq!(double_to_string(0.1), "1.0E-1");
+    // 0.1 needs every bit of the mantissa
+    assert!(ObjectId::from_double(0.1).is_none());
+
+    assert_eq!(round_trip("1", XSD_BOOLEAN), "true");
+    assert_eq!(inlined(&RdfTerm::typed_literal("false", XSD_BOOLEAN)).boolean(), Some(false));
+
+    // Comparisons of inlined numbers do not need the dictionary
+    let values: Vec<f64> = [("3", XSD_INTEGER), ("2.5", XSD_DECIMAL), ("1.25E0", XSD_DOUBLE)]
+        .iter()
+        .map(|(value, datatype)| inlined(&RdfTerm::typed_literal(value, datatype)).numeric_value().unwrap())
+        .collect();
+    assert_eq!(values, vec![3.0, 2.5, 1.25]);
+
+    // Invalid lexical forms are kept as typed literals
+    for (value, datatype) in [("abc", XSD_INTEGER), ("1.2.3", XSD_DECIMAL), ("inf", XSD_DOUBLE), ("yes", XSD_BOOLEAN)] {
+        match ObjectId::encode(&RdfTerm::typed_literal(value, datatype), &no_prefixes()) {
+            TermEncoding::External { mask, .. } => assert_eq!(mask, MASK_TYPED_LITERAL_EXTERN),
+            TermEncoding::Inlined(id) => panic!("{} was inlined as {:?}", value, id),
+        }
+    }
+}
+
+#[test]
+fn test_inlined_dates() {
+    assert_eq!(round_trip("2024-02-29T13:45:30Z", XSD_DATE_TIME), "2024-02-29T13:45:30Z");
+    assert_eq!(round_trip("2024-01-01T00:00:00+00:00", XSD_DATE_TIME), "2024-01-01T00:00:00Z");
+    assert_eq!(round_trip("1999-12-31T24:00:00-03:30", XSD_DATE_TIME), "2000-01-01T00:00:00-03:30");
+    assert_eq!(round_trip("-0044-03-15T12:00:00", XSD_DATE_TIME), "-0044-03-15T12:00:00");
+    assert_eq!(round_trip("2010-06-01", XSD_DATE), "2010-06-01");
+    assert_eq!(round_trip("2010-06-01+14:00", XSD_DATE), "2010-06-01+14:00");
+
+    let id = inlined(&RdfTerm::typed_literal("2024-05-06T07:08:09Z", XSD_DATE_TIME));
+    assert_eq!(id.kind(), ObjectKind::DateTime);
+    let datetime = id.datetime().unwrap();
+    assert_eq!((datetime.year, datetime.month, datetime.day), (2024, 5, 6));
+    assert_eq!((datetime.hour, datetime.minute, datetime.second), (7, 8, 9));
+    assert_eq!(datetime.timezone, Some(0));
+
+    // Fractions of seconds are valid but not inlined
+    let fraction = XsdDateTime::parse_date_time("2024-05-06T07:08:09.250Z").unwrap();
+    assert_eq!(fraction.nanosecond, 250_000_000);
+    assert_eq!(fraction.to_string(), "2024-05-06T07:08:09.25Z");
+    assert!(ObjectId::from_datetime(&fraction).is_none());
+
+    for invalid in ["2023-02-29T00:00:00", "2024-13-01T00:00:00", "24-01-01T00:00:00", "2024-01-01T10:00:00+15:00"] {
+        assert!(XsdDateTime::parse_date_time(invalid).is_none(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_paths_and_null() {
+    let path = ObjectId::path(17);
+    assert_eq!(path.kind(), ObjectKind::Path);
+    assert_eq!(path.path_id(), Some(17));
+    assert_eq!(path.decode_inlined(&no_prefixes()), None);
+    assert!(ObjectId::NULL.is_null());
+    assert_eq!(ObjectId::NULL.kind(), ObjectKind::Null);
+    assert_eq!(ObjectId::from_raw(path.raw()), path);
+}
+
+#[test]
+fn test_iri_prefixes() {
+    let prefixes = IriPrefixes::new(
+        ["http://www.wikidata.org/", "http://www.wikidata.org/entity/", "http://www.wikidata.org/"].map(String::from),
+    );
+    assert_eq!(prefixes.len(), 2);
+    assert_eq!(prefixes.split("http://www.wikidata.org/entity/Q42"), (2, "Q42"));
+    assert_eq!(prefixes.split("http://www.wikidata.org/prop/P31"), (1, "prop/P31"));
+    assert_eq!(prefixes.split("http://example.org/a"), (0, "http://example.org/a"));
+    assert_eq!(prefixes.ids_starting_with("http://www.wikidata.org/e"), vec![2]);
+    assert_eq!(prefixes.ids_starting_with("http://"), vec![1, 2]);
+
+    // Short suffixes are inlined with the id of their prefix
+    let q42 = RdfTerm::iri("http://www.wikidata.org/entity/Q42");
+    let id = match ObjectId::encode(&q42, &prefixes) {
+        TermEncoding::Inlined(id) => id,
+        TermEncoding::External { key, .. } => panic!("{} is not inlined", key),
+    };
+    assert_eq!(id.kind(), ObjectKind::Iri);
+    assert_eq!(id.iri_prefix_id(), Some(2));
+    assert_eq!(id.decode_inlined(&prefixes), Some(q42));
+
+    // Long suffixes are stored in the dictionary without the prefix
+    let long = RdfTerm::iri("http://www.wikidata.org/entity/Q123456789");
+    match ObjectId::encode(&long, &prefixes) {
+        TermEncoding::External { mask, key } => {
+            assert_eq!(key, "Q123456789");
+            let id = ObjectId::external(mask, 99);
+            assert_eq!(id.kind(), ObjectKind::Iri);
+            assert_eq!(id.iri_prefix_id(), Some(2));
+            assert_eq!(id.dictionary_id(), Some(99));
+            assert_eq!(id.decode_external(&key, &prefixes).unwrap(), long);
+        }
+        TermEncoding::Inlined(id) => panic!("{:?} was inlined", id),
+    }
+}
+
+#[test]
+fn test_prefix_discovery() {
+    let mut discovery = PrefixDiscovery::default();
+    for iri in ["http://ex.org/people/a", "http://ex.org/people/b", "http://ex.org/people/c", "http://ex.org/v#p", "http://ex.org/v#q", "http://once.org/x", "a:b/c"] {
+        discovery.add(iri);
+    }
+    let prefixes = discovery.finish(&["http://configured.org/".to_string()]);
+    // Configured first, then the discovered ones seen at least twice by the bytes they save
+    assert_eq!(prefixes.prefixes(), ["http://configured.org/", "http://ex.org/people/", "http://ex.org/v#"]);
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeSet;
+
+use milleniumdb_rs::query::algebra::PropertyPath;
+use milleniumdb_rs::query::object_id::{ObjectId, MASK_IRI_EXTERN};
+use milleniumdb_rs::query::paths::PathAutomaton;
+use milleniumdb_rs::query::rdf_terms::RdfTerm;
+
+mod common;
+use common::{ex, solutions, with_data, with_file, Results};
+
+// A class hierarchy with a cycle A -> B -> C -> A, and D above C. The links go from D to G
+// through E or F, and back from G to D.
+const DATA: &str = "@prefix : <http://ex.org/> .\n\
+    :A :sub :B ; :label \"First class\" .\n\
+    :B :sub :C .\n\
+    :C :sub :A, :D .\n\
+    :x :type :A .\n\
+    :y :type :D .\n\
+    :D :link :E, :F .\n\
+    :E :link :G .\n\
+    :F :link :G .\n\
+    :G :link :D .\n";
+
+// The values of the only variable of the solutions, which must not repeat
+fn values(results: Results) -> BTreeSet<String> {
+    let solutions = solutions(results);
+    let values: BTreeSet<String> = solutions.iter().map(|solution| solution[0].as_ref().unwrap().to_string()).collect();
+    assert_eq!(values.len(), solutions.len(), "duplicated solutions {:?}", solutions);
+    values
+}
+
+fn nodes(locals: &[&str]) -> BTreeSet<String> {
+    locals.iter().map(|local| ex(local).unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_automaton() {
+    let iri = |name: &str| PropertyPath::Iri(name.to_string());
+    let predicate_id = |name: &str| Ok(ObjectId::external(MASK_IRI_EXTERN, name.len() as u64));
+
+    let star = PathAutomaton::new(&PropertyPath::ZeroOrMore(Box::new(iri("p"))), false, predicate_id).unwrap();
+    assert!(star.accepts_empty_path());
+    let plus = PathAutomaton::new(&PropertyPath::OneOrMore(Box::new(iri("p"))), false, predicate_id).unwrap();
+    assert!(!plus.accepts_empty_path());
+    let optional = PathAutomaton::new(&PropertyPath::ZeroOrOne(Box::new(iri("p"))), false, predicate_id).unwrap();
+    assert!(optional.accepts_empty_path());
+    let sequence = PathAutomaton::new(&PropertyPath::Sequence(vec![iri("p"), iri("q")]), false, predicate_id).unwrap();
+    assert!(!sequence.accepts_empty_path());
+    assert_eq!(sequence.transitions[sequence.start].len(), 1);
+}
+
+#[test]
+fn test_transitive_closure() {
+    with_data("paths_closure", DATA, |run| {
+        // The cycle is traversed once and every class is returned once
+        assert_eq!(values(run("SELECT ?c { :A :sub* ?c }")), nodes(&["A", "B", "C", "D"]));
+        assert_eq!(values(run("SELECT ?c { :A :sub+ ?c }")), nodes(&["A", "B", "C", "D"]));
+        assert_eq!(values(run("SELECT ?c { :D :sub* ?c }")), nodes(&["D"]));
+        assert!(values(run("SELECT ?c { :D :sub+ ?c }")).is_empty());
+        assert_eq!(values(run("SELECT ?c { :A :sub? ?c }")), nodes(&["A", "B"]));
+
+        // From the object, with the inverse automaton
+        assert_eq!(values(run("SELECT ?c { ?c :sub* :D }")), nodes(&["A", "B", "C", "D"]));
+        assert_eq!(values(run("SELECT ?c { ?c :sub+ :B }")), nodes(&["A", "B", "C"]));
+        assert_eq!(values(run("SELECT ?c { ?c ^:sub :B }")), nodes(&["C"]));
+
+        // Zero-length paths match a term that is not in the graph
+        assert_eq!(values(run("SELECT ?c { :unknown :sub* ?c }")), nodes(&["unknown"]));
+
+        assert_eq!(run("ASK { :A :sub* :D }"), Results::Boolean(true));
+        assert_eq!(run("ASK { :D :sub* :A }"), Results::Boolean(false));
+        assert_eq!(solutions(run("SELECT * { :A :sub+ :A }")).len(), 1);
+    });
+}
+
+#[test]
+fn test_composed_paths() {
+    with_data("paths_composed", DATA, |run| {
+        // rdf:type/rdfs:subClassOf* style traversals
+        assert_eq!(values(run("SELECT ?c { :y :type/:sub* ?c }")), nodes(&["D"]));
+        assert_eq!(values(run("SELECT ?c { :x :type/:sub* ?c }")), nodes(&["A", "B", "C", "D"]));
+        assert_eq!(values(run("SELECT ?i { ?i :type/:sub* :D }")), nodes(&["x", "y"]));
+
+        assert_eq!(values(run("SELECT ?c { :A (:sub|^:sub) ?c }")), nodes(&["B", "C"]));
+        assert_eq!(values(run("SELECT ?c { :A (:sub/:sub)+ ?c }")), nodes(&["A", "B", "C", "D"]));
+        assert_eq!(values(run("SELECT ?c { :B (^:sub)* ?c }")), nodes(&["A", "B", "C"]));
+
+        let labels = values(run("SELECT ?o { :A !:sub ?o }"));
+        assert_eq!(labels, BTreeSet::from([RdfTerm::simple_literal("First class").to_string()]));
+        assert_eq!(values(run("SELECT ?o { :A !(^:sub) ?o }")), nodes(&["x"]));
+        assert_eq!(values(run("SELECT ?o { :A !(:label|^:type) ?o }")), nodes(&["B", "C"]));
+    });
+}
+
+#[test]
+fn test_unbound_ends() {
+    with_data("paths_unbound", DATA, |run| {
+        let pairs = solutions(run("SELECT ?a ?b { ?a :sub+ ?b }"));
+        let distinct: BTreeSet<String> = pairs.iter().map(|pair| format!("{:?}", pair)).collect();
+        assert_eq!(distinct.len(), pairs.len());
+        // A, B and C reach the four classes
+        assert_eq!(pairs.len(), 12);
+        assert!(pairs.contains(&vec![ex("B"), ex("D")]));
+
+        // Zero-length paths go from every node of the graph to itself
+        let pairs = solutions(run("SELECT ?a ?b { ?a :sub* ?b }"));
+        assert!(pairs.contains(&vec![ex("x"), ex("x")]));
+        assert!(pairs.contains(&vec![ex("D"), ex("D")]));
+
+        assert_eq!(values(run("SELECT ?a { ?a :sub+ ?a }")), nodes(&["A", "B", "C"]));
+    });
+}
+
+// The chain a -> b -> c -> d split across two graphs, and an edge of the default graph
+const GRAPHS: &str = "@prefix : <http://ex.org/> .\n\
+    :a :p :x .\n\
+    :g1 { :a :p :b . :b :p :c . }\n\
+    :g2 { :b :p :c . :c :p :d . }\n";
+
+#[test]
+fn test_paths_in_named_graphs() {
+    with_file("paths_named_graphs", "data.trig", GRAPHS, |run| {
+        let pairs = solutions(run("SELECT ?g ?o { GRAPH ?g { :a :p+ ?o } }"));
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs.contains(&vec![ex("g1"), ex("b")]));
+        assert!(pairs.contains(&vec![ex("g1"), ex("c")]));
+
+        let triples = solutions(run("SELECT ?g ?s ?o { GRAPH ?g { ?s :p+ ?o } }"));
+        let distinct: BTreeSet<String> = triples.iter().map(|triple| format!("{:?}", triple)).collect();
+        assert_eq!(distinct.len(), triples.len());
+        // a, b and c in g1, and b and c in g2
+        assert_eq!(triples.len(), 6);
+        assert!(triples.contains(&vec![ex("g2"), ex("b"), ex("d")]));
+        assert!(!triples.contains(&vec![ex("g1"), ex("a"), ex("d")]));
+
+        assert_eq!(values(run("SELECT ?s { GRAPH :g2 { ?s :p+ :d } }")), nodes(&["b", "c"]));
+        assert_eq!(values(run("SELECT ?g FROM NAMED :g2 { GRAPH ?g { :b :p+ :d } }")), nodes(&["g2"]));
+        assert_eq!(values(run("SELECT ?g FROM NAMED :g1 { GRAPH ?g { :b :p+ :d } }")), BTreeSet::new());
+        assert_eq!(values(run("SELECT ?o { :a :p+ ?o }")), nodes(&["x"]));
+    });
+}
+
+#[test]
+fn test_paths_over_a_dataset() {
+    with_file("paths_dataset", "data.trig", GRAPHS, |run| {
+        // The path goes through edges of both graphs of the merge
+        assert_eq!(values(run("SELECT ?o FROM :g1 FROM :g2 { :a :p+ ?o }")), nodes(&["b", "c", "d"]));
+        assert_eq!(values(run("SELECT ?o FROM :g1 { :a :p+ ?o }")), nodes(&["b", "c"]));
+        // The edge b -> c of both graphs gives one path
+        let paths = solutions(run("SELECT ?path FROM :g1 FROM :g2 { :a ALL SHORTEST ?path :p+ :d }"));
+        assert_eq!(paths.len(), 1);
+        assert_eq!(values(run("SELECT ?s FROM :g1 FROM :g2 { ?s :p* :a }")), nodes(&["a"]));
+    });
+}
+
+// A path that follows :link from node to node
+fn link_path(locals: &[&str]) -> String {
+    let mut path = format!("({})", ex(locals[0]).unwrap());
+    for local in &locals[1..] {
+        path.push_str(&format!("-[{}]->({})", ex("link").unwrap(), ex(local).unwrap()));
+    }
+    path
+}
+
+fn link_paths(paths: &[&[&str]]) -> BTreeSet<String> {
+    paths.iter().map(|locals| link_path(locals)).collect()
+}
+
+#[test]
+fn test_shortest_paths() {
+    with_data("paths_shortest", DATA, |run| {
+        let paths = values(run("SELECT ?p { :D ANY SHORTEST ?p :link+ :G }"));
+        assert_eq!(paths.len(), 1);
+        assert!(link_paths(&[&["D", "E", "G"], &["D", "F", "G"]]).contains(paths.iter().next().unwrap()));
+        assert_eq!(values(run("SELECT ?p { :D ANY ?p :link* :D }")), link_paths(&[&["D"]]));
+
+        let expected = link_paths(&[&["D", "E", "G"], &["D", "F", "G"]]);
+        assert_eq!(values(run("SELECT ?p { :D ALL SHORTEST ?p :link+ :G }")), expected);
+        // Searched from the object, the paths still go from the subject
+        assert_eq!(values(run("SELECT ?p { ?s ALL SHORTEST ?p :link+ :G FILTER(?s = :D) }")), expected);
+
+        // One shortest path for each end
+        let ends = solutions(run("SELECT ?e ?p { :D ANY SHORTEST ?p :link+ ?e }"));
+        assert_eq!(ends.len(), 4);
+        let to_g = ends.iter().find(|solution| solution[0] == ex("G")).unwrap();
+        assert_eq!(to_g[1].as_ref().unwrap().to_string().matches("->").count(), 2);
+
+        // Edges followed backwards
+        let inverse = format!("({})<-[{}]-({})", ex("G").unwrap(), ex("link").unwrap(), ex("E").unwrap());
+        assert_eq!(values(run("SELECT ?p { :G ANY ?p ^:link :E }")), BTreeSet::from([inverse]));
+        let mixed = values(run("SELECT ?p { :C ANY SHORTEST ?p (:sub/:link) ?e }"));
+        assert_eq!(mixed.len(), 2);
+    });
+}
+
+#[test]
+fn test_enumerated_paths() {
+    with_data("paths_enumerated", DATA, |run| {
+        let trails = values(run("SELECT ?p { :D TRAIL ?p :link+ :G }"));
+        assert_eq!(
+            trails,
+            link_paths(&[&["D", "E", "G"], &["D", "F", "G"], &["D", "E", "G", "D", "F", "G"], &["D", "F", "G", "D", "E", "G"]])
+        );
+        assert_eq!(solutions(run("SELECT ?p { :D TRAIL ?p :link+ ?e }")).len(), 10);
+
+        assert_eq!(values(run("SELECT ?p { :D SIMPLE ?p :link+ :D }")), link_paths(&[&["D", "E", "G", "D"], &["D", "F", "G", "D"]]));
+        assert!(solutions(run("SELECT ?p { :D ACYCLIC ?p :link+ :D }")).is_empty());
+        assert_eq!(
+            values(run("SELECT ?p { :D ACYCLIC ?p :link+ ?e }")),
+            link_paths(&[&["D", "E"], &["D", "F"], &["D", "E", "G"], &["D", "F", "G"]])
+        );
+
+        // The two cycles of the links from each of their nodes: D and G are on both
+        assert_eq!(solutions(run("SELECT ?x ?p { ?x SIMPLE ?p :link+ ?x }")).len(), 6);
+        assert_eq!(solutions(run("SELECT ?x { ?x SIMPLE :sub+ ?x }")).len(), 3);
+
+        // The same path is the same value
+        let results = run("SELECT DISTINCT ?p { { :D TRAIL ?p :link+ :G } UNION { :D ALL SHORTEST ?p :link+ :G } }");
+        assert_eq!(solutions(results).len(), 4);
+        assert_eq!(run("ASK { :D TRAIL ?p :link+ :G . :D ALL SHORTEST ?p :link+ :G }"), Results::Boolean(true));
+    });
+}
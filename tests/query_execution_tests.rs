@@ -0,0 +1,467 @@
+use std::fs;
+use std::sync::Arc;
+
+use milleniumdb_rs::query::exceptions::NotSupportedException;
+use milleniumdb_rs::query::executor::Interruption;
+use milleniumdb_rs::query::query_executor::{execute_query, QueryTerms};
+use milleniumdb_rs::query::rdf_terms::{RdfQuad, RdfTerm, Solution, XSD_BOOLEAN, XSD_DATE_TIME, XSD_DAY_TIME_DURATION, XSD_DECIMAL, XSD_DOUBLE, XSD_FLOAT, XSD_INTEGER};
+use milleniumdb_rs::query::sparql_parser::parse_query;
+use milleniumdb_rs::storage::buffer_manager::BufferManager;
+use milleniumdb_rs::storage::quad_indexes::QuadIndexes;
+use milleniumdb_rs::storage::string_manager::StringManager;
+
+mod common;
+use common::{ex, read_results, solutions, temp_db_folder, with_data, with_file, Results};
+
+const DATA: &str = "@prefix : <http://ex.org/> .\n\
+    :alice :knows :bob, :carol ; :age 30 ; :name \"Alice\" .\n\
+    :bob :knows :carol ; :age 25 ; :name \"Bob\"@en .\n\
+    :carol :knows :alice ; :age 41 .\n\
+    :dave :name \"A name long enough for the dictionary\" .\n";
+
+fn integer(value: i64) -> Option<RdfTerm> {
+    Some(RdfTerm::typed_literal(&value.to_string(), XSD_INTEGER))
+}
+
+fn sorted(mut solutions: Vec<Solution>) -> Vec<Solution> {
+    solutions.sort_by_key(|solution| format!("{:?}", solution));
+    solutions
+}
+
+#[test]
+fn test_select() {
+    with_data("query_select", DATA, |run| {
+        let results = run("SELECT ?x ?y { ?x :knows ?y . ?y :knows ?x }");
+        assert_eq!(
+            sorted(solutions(results)),
+            sorted(vec![vec![ex("alice"), ex("carol")], vec![ex("carol"), ex("alice")]])
+        );
+
+        // Literals of the query are found in the dictionary or inlined
+        let results = run("SELECT ?x { ?x :name \"A name long enough for the dictionary\" }");
+        assert_eq!(solutions(results), vec![vec![ex("dave")]]);
+        let results = run("SELECT ?x { ?x :name \"Bob\"@en }");
+        assert_eq!(solutions(results), vec![vec![ex("bob")]]);
+        // Terms missing from the database match nothing
+        assert!(solutions(run("SELECT ?x { ?x :name \"Nobody by this name\" }")).is_empty());
+
+        let results = run("SELECT ?x ?age { ?x :age ?age } ORDER BY DESC(?age) LIMIT 2");
+        assert_eq!(solutions(results), vec![vec![ex("carol"), integer(41)], vec![ex("alice"), integer(30)]]);
+
+        let results = run("SELECT DISTINCT ?x { ?x :knows ?y } ORDER BY ?x OFFSET 1");
+        assert_eq!(solutions(results).len(), 2);
+    });
+}
+
+#[test]
+fn test_optional_union_minus() {
+    with_data("query_patterns", DATA, |run| {
+        let results = run("SELECT ?x ?n { ?x :age ?a OPTIONAL { ?x :name ?n } } ORDER BY ?a");
+        assert_eq!(
+            solutions(results),
+            vec![
+                vec![ex("bob"), Some(RdfTerm::lang_literal("Bob", "en"))],
+                vec![ex("alice"), Some(RdfTerm::simple_literal("Alice"))],
+                vec![ex("carol"), None],
+            ]
+        );
+
+        let results = run("SELECT ?x { { ?x :age 25 } UNION { ?x :age 41 } }");
+        assert_eq!(sorted(solutions(results)), vec![vec![ex("bob")], vec![ex("carol")]]);
+
+        let results = run("SELECT ?x { ?x :age ?a MINUS { ?x :knows :carol } }");
+        assert_eq!(solutions(results), vec![vec![ex("carol")]]);
+
+        // MINUS without shared variables removes nothing
+        let results = run("SELECT ?x { ?x :age ?a MINUS { ?y :knows :carol } }");
+        assert_eq!(solutions(results).len(), 3);
+    });
+}
+
+#[test]
+fn test_joins_with_optional_variables() {
+    with_data("query_optional_joins", DATA, |run| {
+        let alice = Some(RdfTerm::simple_literal("Alice"));
+        let bob = Some(RdfTerm::lang_literal("Bob", "en"));
+        let dave = Some(RdfTerm::simple_literal("A name long enough for the dictionary"));
+
+        // ?n is bound by the OPTIONAL for some left results only, the scan after it must
+        // not keep treating it as it was for the first one
+        let results = run("SELECT ?x ?n ?z { ?x :knows ?y OPTIONAL { ?x :name ?n } ?z :name ?n }");
+        let by_name = vec![
+            vec![ex("alice"), alice.clone(), ex("alice")],
+            vec![ex("alice"), alice.clone(), ex("alice")],
+            vec![ex("bob"), bob.clone(), ex("bob")],
+            vec![ex("carol"), alice.clone(), ex("alice")],
+            vec![ex("carol"), bob.clone(), ex("bob")],
+            vec![ex("carol"), dave.clone(), ex("dave")],
+        ];
+        assert_eq!(sorted(solutions(results)), sorted(by_name.clone()));
+
+        // The same through a hash join, where NULL join values on either side are compatible
+        let results = run("SELECT ?x ?n ?z { { ?x :knows ?y OPTIONAL { ?x :name ?n } } { { ?z :name ?n } UNION { ?z :age 41 } } }");
+        let mut expected = by_name;
+        expected.extend([
+            vec![ex("alice"), alice.clone(), ex("carol")],
+            vec![ex("alice"), alice, ex("carol")],
+            vec![ex("bob"), bob, ex("carol")],
+            vec![ex("carol"), None, ex("carol")],
+        ]);
+        assert_eq!(sorted(solutions(results)), sorted(expected));
+    });
+}
+
+#[test]
+fn test_filter_bind_values() {
+    with_data("query_expressions", DATA, |run| {
+        let results = run("SELECT ?x { ?x :age ?a FILTER(?a >= 30 && ?a < 41) }");
+        assert_eq!(solutions(results), vec![vec![ex("alice")]]);
+
+        let results = run("SELECT ?x ?next { ?x :age ?a BIND(?a + 1 AS ?next) FILTER(?x = :bob) }");
+        assert_eq!(solutions(results), vec![vec![ex("bob"), integer(26)]]);
+
+        let results = run("SELECT ?x { ?x :name ?n FILTER(LANG(?n) = \"en\" || !isLiteral(?n)) }");
+        assert_eq!(solutions(results), vec![vec![ex("bob")]]);
+
+        let results = run("SELECT ?x { ?x :age ?a FILTER(?a IN (25, 41)) } ORDER BY ?a");
+        assert_eq!(solutions(results), vec![vec![ex("bob")], vec![ex("carol")]]);
+
+        let results = run("SELECT ?x ?a { VALUES ?x { :alice :dave } OPTIONAL { ?x :age ?a } }");
+        assert_eq!(sorted(solutions(results)), sorted(vec![vec![ex("alice"), integer(30)], vec![ex("dave"), None]]));
+
+        let results = run("SELECT ?x { ?x :age ?a FILTER NOT EXISTS { ?x :name ?n } }");
+        assert_eq!(solutions(results), vec![vec![ex("carol")]]);
+
+        // Errors only make the FILTER fail
+        let results = run("SELECT ?x { ?x :name ?n FILTER(?n + 1 > 0 || ?x = :dave) }");
+        assert_eq!(solutions(results), vec![vec![ex("dave")]]);
+    });
+}
+
+// Value of an expression for :alice, `None` when it is unbound (an error)
+fn value_of(run: &dyn Fn(&str) -> Results, expression: &str) -> Option<RdfTerm> {
+    let query = format!("SELECT ?v {{ :alice :name ?name ; :age ?age BIND({} AS ?v) }}", expression);
+    solutions(run(&query)).pop().unwrap().pop().unwrap()
+}
+
+fn literal(value: &str) -> Option<RdfTerm> {
+    Some(RdfTerm::simple_literal(value))
+}
+
+#[test]
+fn test_functions() {
+    with_data("query_functions", DATA, |run| {
+        let boolean = |value: bool| Some(RdfTerm::typed_literal(&value.to_string(), XSD_BOOLEAN));
+
+        // Strings
+        assert_eq!(value_of(run, "STRLEN(?name)"), integer(5));
+        assert_eq!(value_of(run, "SUBSTR(\"foobar\"@en, 2, 3)"), Some(RdfTerm::lang_literal("oob", "en")));
+        assert_eq!(value_of(run, "SUBSTR(?name, 3)"), literal("ice"));
+        assert_eq!(value_of(run, "UCASE(?name)"), literal("ALICE"));
+        assert_eq!(value_of(run, "LCASE(\"ABC\"@fr)"), Some(RdfTerm::lang_literal("abc", "fr")));
+        assert_eq!(value_of(run, "STRBEFORE(\"abc\"@en, \"c\")"), Some(RdfTerm::lang_literal("ab", "en")));
+        assert_eq!(value_of(run, "STRAFTER(?name, \"l\")"), literal("ice"));
+        assert_eq!(value_of(run, "STRAFTER(?name, \"z\")"), literal(""));
+        assert_eq!(value_of(run, "CONTAINS(?name, \"lic\")"), boolean(true));
+        assert_eq!(value_of(run, "STRSTARTS(?name, \"Al\") && STRENDS(?name, \"ce\")"), boolean(true));
+        assert_eq!(value_of(run, "CONCAT(\"a\"@en, \"b\"@en)"), Some(RdfTerm::lang_literal("ab", "en")));
+        assert_eq!(value_of(run, "CONCAT(?name, \"-\", STR(?age))"), literal("Alice-30"));
+        assert_eq!(value_of(run, "ENCODE_FOR_URI(\"Los Angeles/é\")"), literal("Los%20Angeles%2F%C3%A9"));
+        assert_eq!(value_of(run, "LANGMATCHES(\"en-US\", \"en\")"), boolean(true));
+        assert_eq!(value_of(run, "LANGMATCHES(\"\", \"*\")"), boolean(false));
+        assert_eq!(value_of(run, "REGEX(?name, \"^ali\", \"i\")"), boolean(true));
+        assert_eq!(value_of(run, "REPLACE(?name, \"(l)(i)\", \"$2$1\")"), literal("Ailce"));
+
+        // Numbers
+        assert_eq!(value_of(run, "ABS(-?age)"), integer(30));
+        assert_eq!(value_of(run, "ROUND(2.5)"), Some(RdfTerm::typed_literal("3.0", XSD_DECIMAL)));
+        assert_eq!(value_of(run, "ROUND(-2.5)"), Some(RdfTerm::typed_literal("-2.0", XSD_DECIMAL)));
+        assert_eq!(value_of(run, "CEIL(1.2) + FLOOR(1.8)"), Some(RdfTerm::typed_literal("3.0", XSD_DECIMAL)));
+        assert_eq!(value_of(run, "RAND() >= 0 && RAND() < 1"), boolean(true));
+        assert_eq!(value_of(run, "ISNUMERIC(?age) && !ISNUMERIC(?name)"), boolean(true));
+
+        // Dates
+        let date = "\"2011-01-10T14:45:13.815-05:00\"^^xsd:dateTime";
+        let xsd = |query: &str| format!("PREFIX xsd: <http://www.w3.org/2001/XMLSchema#> {}", query);
+        let date_part = |function: &str| {
+            let query = xsd(&format!("SELECT ({}({}) AS ?v) {{}}", function, date));
+            solutions(run(&query)).pop().unwrap().pop().unwrap()
+        };
+        assert_eq!(date_part("YEAR"), integer(2011));
+        assert_eq!(date_part("MONTH"), integer(1));
+        assert_eq!(date_part("DAY"), integer(10));
+        assert_eq!(date_part("HOURS"), integer(14));
+        assert_eq!(date_part("MINUTES"), integer(45));
+        assert_eq!(date_part("SECONDS"), Some(RdfTerm::typed_literal("13.815", XSD_DECIMAL)));
+        assert_eq!(date_part("TIMEZONE"), Some(RdfTerm::typed_literal("-PT5H", XSD_DAY_TIME_DURATION)));
+        assert_eq!(date_part("TZ"), literal("-05:00"));
+        assert_eq!(value_of(run, "DATATYPE(NOW())"), Some(RdfTerm::iri(XSD_DATE_TIME)));
+        let results = solutions(run("SELECT ?a ?b { BIND(NOW() AS ?a) BIND(NOW() AS ?b) }"));
+        assert_eq!(results[0][0], results[0][1]);
+
+        // Hashes
+        assert_eq!(value_of(run, "MD5(\"abc\")"), literal("900150983cd24fb0d6963f7d28e17f72"));
+        assert_eq!(value_of(run, "SHA1(\"abc\")"), literal("a9993e364706816aba3e25717850c26c9cd0d89d"));
+        assert_eq!(value_of(run, "MD5(\"abc\"@en)"), None);
+
+        // Terms
+        assert_eq!(value_of(run, "IRI(CONCAT(\"http://ex.org/\", ?name))"), ex("Alice"));
+        assert_eq!(value_of(run, "STRDT(\"5\", <http://www.w3.org/2001/XMLSchema#integer>)"), integer(5));
+        assert_eq!(value_of(run, "STRLANG(?name, \"en\")"), Some(RdfTerm::lang_literal("Alice", "en")));
+        assert_eq!(value_of(run, "STRLANG(?name, \"\")"), None);
+        assert_eq!(value_of(run, "SAMETERM(?age, 30) && !SAMETERM(?age, 30.0)"), boolean(true));
+        assert_eq!(value_of(run, "ISBLANK(BNODE()) && ISBLANK(BNODE(\"x\"))"), boolean(true));
+        let results = solutions(run("SELECT ?a ?b ?c { VALUES ?x { 1 2 } BIND(BNODE(\"l\") AS ?a) BIND(BNODE(\"l\") AS ?b) BIND(BNODE() AS ?c) }"));
+        assert_eq!(results[0][0], results[0][1]);
+        assert_ne!(results[0][0], results[1][0]);
+        assert_ne!(results[0][2], results[1][2]);
+        let uuid = match value_of(run, "STRUUID()") {
+            Some(RdfTerm::Literal { value, .. }) => value,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!((uuid.len(), uuid.as_bytes()[14]), (36, b'4'));
+        assert!(matches!(value_of(run, "UUID()"), Some(RdfTerm::Iri(iri)) if iri.starts_with("urn:uuid:")));
+
+        // COALESCE and IF only evaluate the arguments they need
+        assert_eq!(value_of(run, "COALESCE(?unbound, 1/0, ?name)"), literal("Alice"));
+        assert_eq!(value_of(run, "COALESCE(?unbound)"), None);
+        assert_eq!(value_of(run, "IF(?age > 18, \"adult\", 1/0)"), literal("adult"));
+        assert_eq!(value_of(run, "IF(?name + 1, 1, 2)"), None);
+
+        // Casts
+        assert_eq!(solutions(run(&xsd("SELECT (xsd:integer(\"12\") + 1 AS ?v) {}")))[0][0], integer(13));
+        assert_eq!(solutions(run(&xsd("SELECT (xsd:boolean(?x) AS ?v) { BIND(\"maybe\" AS ?x) }")))[0][0], None);
+
+        // Errors leave the variable unbound and make the filters false, they do not stop the query
+        assert_eq!(value_of(run, "STRLEN(:alice)"), None);
+        assert_eq!(value_of(run, "REGEX(?name, \"(\")"), None);
+        assert_eq!(value_of(run, "SUBSTR(?name, \"one\")"), None);
+        let results = run("SELECT ?x { ?x :name ?n FILTER(STRLEN(?n) > 3 || UCASE(?x) = \"\") }");
+        assert_eq!(sorted(solutions(results)), vec![vec![ex("alice")], vec![ex("dave")]]);
+    });
+}
+
+#[test]
+fn test_value_comparisons() {
+    with_data("query_value_comparisons", DATA, |run| {
+        let boolean = |value: bool| Some(RdfTerm::typed_literal(&value.to_string(), XSD_BOOLEAN));
+        let xsd = |expression: &str| expression.replace("xsd:", "http://www.w3.org/2001/XMLSchema#");
+
+        // Numbers are promoted integer -> decimal -> float -> double
+        assert_eq!(value_of(run, &xsd("\"1\"^^<xsd:integer> = \"1.0\"^^<xsd:decimal>")), boolean(true));
+        assert_eq!(value_of(run, &xsd("\"7\"^^<xsd:byte> = 7.0 && \"7\"^^<xsd:unsignedLong> < 7.5")), boolean(true));
+        assert_eq!(value_of(run, &xsd("ISNUMERIC(\"300\"^^<xsd:byte>) || ISNUMERIC(\"-1\"^^<xsd:positiveInteger>)")), boolean(false));
+        assert_eq!(value_of(run, &xsd("\"0.1\"^^<xsd:float> = 0.1")), boolean(true));
+        assert_eq!(value_of(run, &xsd("\"0.5\"^^<xsd:float> + 1")), Some(RdfTerm::typed_literal("1.5E0", XSD_FLOAT)));
+        assert_eq!(value_of(run, &xsd("\"0.5\"^^<xsd:float> + 1e0")), Some(RdfTerm::typed_literal("1.5E0", XSD_DOUBLE)));
+        assert_eq!(value_of(run, "0.1 + 0.2 = 0.3 && 0.1e0 + 0.2e0 != 0.3e0"), boolean(true));
+
+        // Integers and decimals of any size
+        assert_eq!(value_of(run, "9223372036854775807 + 1"), Some(RdfTerm::typed_literal("9223372036854775808", XSD_INTEGER)));
+        assert_eq!(value_of(run, "1 / 3"), Some(RdfTerm::typed_literal("0.333333333333333333333333", XSD_DECIMAL)));
+        assert_eq!(value_of(run, "100000000000000000000.5 > 100000000000000000000"), boolean(true));
+        assert_eq!(value_of(run, "1 / 0"), None);
+
+        // dateTimes are compared as instants, dates only with dates
+        let date_time = |lexical: &str| format!("\"{}\"^^<http://www.w3.org/2001/XMLSchema#dateTime>", lexical);
+        let compare = |a: &str, operator: &str, b: &str| value_of(run, &format!("{} {} {}", date_time(a), operator, date_time(b)));
+        assert_eq!(compare("2024-01-01T05:30:00+05:30", "=", "2024-01-01T00:00:00Z"), boolean(true));
+        assert_eq!(compare("2023-12-31T23:00:00-02:00", ">", "2024-01-01T00:00:00Z"), boolean(true));
+        assert_eq!(compare("2024-01-01T00:00:00.5Z", "<", "2024-01-01T00:00:00.25+00:00"), boolean(false));
+        let date = "\"2024-01-01\"^^<http://www.w3.org/2001/XMLSchema#date>";
+        assert_eq!(value_of(run, &format!("{} < \"2024-01-02Z\"^^<http://www.w3.org/2001/XMLSchema#date>", date)), boolean(true));
+        assert_eq!(value_of(run, &format!("{} = {}", date, date_time("2024-01-01T00:00:00Z"))), boolean(false));
+        assert_eq!(value_of(run, &format!("{} < {}", date, date_time("2024-01-01T00:00:00Z"))), None);
+
+        // Durations
+        let duration = |lexical: &str, datatype: &str| format!("\"{}\"^^<http://www.w3.org/2001/XMLSchema#{}>", lexical, datatype);
+        let durations = |a: &str, operator: &str, b: &str, datatype: &str| {
+            value_of(run, &format!("{} {} {}", duration(a, datatype), operator, duration(b, datatype)))
+        };
+        assert_eq!(durations("P1Y", "<", "P13M", "yearMonthDuration"), boolean(true));
+        assert_eq!(durations("PT36H", "=", "P1DT12H", "dayTimeDuration"), boolean(true));
+        assert_eq!(durations("P1M", "=", "P30D", "duration"), boolean(false));
+        assert_eq!(durations("P1M", "<", "P30D", "duration"), None);
+        assert_eq!(durations("P1Y", "<", "P1D", "yearMonthDuration"), None);
+        assert_eq!(value_of(run, &format!("TIMEZONE({}) = {}", date_time("2024-01-01T00:00:00-05:00"), duration("-PT300M", "dayTimeDuration"))), boolean(true));
+    });
+}
+
+#[test]
+fn test_order_by_mixed_types() {
+    let data = "@prefix : <http://ex.org/> .\n\
+        @prefix xsd: <http://www.w3.org/2001/XMLSchema#> .\n\
+        :a :v 10 .\n\
+        :b :v 2.5 .\n\
+        :c :v \"1.0E1\"^^xsd:double .\n\
+        :d :v \"x\" .\n\
+        :e :v :iri .\n\
+        :f :v _:node .\n\
+        :g :v \"2024-01-01T00:00:00Z\"^^xsd:dateTime .\n\
+        :h :v \"2023-12-31T23:00:00-02:00\"^^xsd:dateTime .\n\
+        :i :v \"-INF\"^^xsd:double .\n\
+        :j :v \"3\"^^xsd:float .\n\
+        :k :v true .\n\
+        :m :v 100000000000000000000 .\n";
+    with_data("query_order_mixed", data, |run| {
+        let subjects = |query: &str| solutions(run(query)).into_iter().map(|mut solution| solution.remove(0)).collect::<Vec<_>>();
+        let expected: Vec<_> = ["f", "e", "i", "b", "j", "c", "a", "m", "k", "g", "h", "d"].iter().map(|local| ex(local)).collect();
+        // 10 and 1.0E1 are equal values, ordered by datatype
+        assert_eq!(subjects("SELECT ?x { ?x :v ?v } ORDER BY ?v"), expected);
+        let descending: Vec<_> = expected.into_iter().rev().collect();
+        assert_eq!(subjects("SELECT ?x { ?x :v ?v } ORDER BY DESC(?v)"), descending);
+
+        assert_eq!(subjects("SELECT ?x { ?x :v ?v FILTER(?v > 2.6) } ORDER BY ?v"), vec![ex("j"), ex("c"), ex("a"), ex("m")]);
+
+        let results = run("SELECT (MIN(?v) AS ?min) (MAX(?v) AS ?max) { ?x :v ?v FILTER(ISNUMERIC(?v)) }");
+        assert_eq!(
+            solutions(results),
+            vec![vec![
+                Some(RdfTerm::typed_literal("-INF", XSD_DOUBLE)),
+                Some(RdfTerm::typed_literal("100000000000000000000", XSD_INTEGER))
+            ]]
+        );
+        let results = run("SELECT (MAX(?v) AS ?max) { ?x :v ?v }");
+        assert_eq!(solutions(results), vec![vec![literal("x")]]);
+    });
+}
+
+#[test]
+fn test_other_forms() {
+    with_data("query_forms", DATA, |run| {
+        assert_eq!(run("ASK { :alice :knows :bob }"), Results::Boolean(true));
+        assert_eq!(run("ASK { :bob :knows :alice }"), Results::Boolean(false));
+
+        let graph = match run("CONSTRUCT { ?y :knownBy ?x . ?x :friend [ :of ?y ] } WHERE { ?x :knows ?y FILTER(?x = :bob) }") {
+            Results::Graph(graph) => graph,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph[0], RdfQuad::triple(ex("carol").unwrap(), ex("knownBy").unwrap(), ex("bob").unwrap()));
+        let friend = graph.iter().find(|quad| Some(quad.predicate.clone()) == ex("friend")).unwrap();
+        let of = graph.iter().find(|quad| Some(quad.predicate.clone()) == ex("of")).unwrap();
+        assert!(matches!(friend.object, RdfTerm::BlankNode(_)));
+        assert_eq!(friend.object, of.subject);
+
+        // Duplicates within a solution are skipped, the ones of different solutions are kept
+        let graph = match run("CONSTRUCT { ?x a :Person . ?x a :Person } WHERE { ?x :knows ?y FILTER(?x = :alice) }") {
+            Results::Graph(graph) => graph,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph[0], graph[1]);
+
+        let graph = match run("DESCRIBE :carol") {
+            Results::Graph(graph) => graph,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(graph.len(), 2);
+        assert!(graph.iter().all(|quad| Some(quad.subject.clone()) == ex("carol")));
+    });
+}
+
+#[test]
+fn test_aggregates() {
+    with_data("query_aggregates", DATA, |run| {
+        let results = run("SELECT (COUNT(*) AS ?c) (COUNT(DISTINCT ?x) AS ?d) (COUNT(?n) AS ?n) { ?x ?p ?o OPTIONAL { ?x :name ?n FILTER(?p = :age) } }");
+        assert_eq!(solutions(results), vec![vec![integer(10), integer(4), integer(2)]]);
+
+        let results = run("SELECT (SUM(?a) AS ?s) (AVG(?a) AS ?avg) (MIN(?a) AS ?min) (MAX(?a) AS ?max) { ?x :age ?a }");
+        assert_eq!(
+            solutions(results),
+            vec![vec![integer(96), Some(RdfTerm::typed_literal("32.0", XSD_DECIMAL)), integer(25), integer(41)]]
+        );
+
+        let results = run("SELECT ?x (COUNT(?y) AS ?c) (GROUP_CONCAT(STR(?y); SEPARATOR=\",\") AS ?all) { ?x :knows ?y } GROUP BY ?x HAVING(COUNT(?y) < 2) ORDER BY ?x");
+        assert_eq!(
+            solutions(results),
+            vec![
+                vec![ex("bob"), integer(1), Some(RdfTerm::simple_literal("http://ex.org/carol"))],
+                vec![ex("carol"), integer(1), Some(RdfTerm::simple_literal("http://ex.org/alice"))],
+            ]
+        );
+
+        let results = run("SELECT ?old (SAMPLE(?x) AS ?s) (COUNT(*) AS ?c) { ?x :age ?a } GROUP BY (?a > 28 AS ?old) ORDER BY ?old");
+        let results = solutions(results);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0][0], Some(RdfTerm::typed_literal("false", XSD_BOOLEAN)));
+        assert_eq!(results[0][1], ex("bob"));
+        assert_eq!(results[1][2], integer(2));
+
+        // Without GROUP BY an empty input is one group
+        let results = run("SELECT (COUNT(*) AS ?c) (MAX(?a) AS ?max) { ?x :height ?a }");
+        assert_eq!(solutions(results), vec![vec![integer(0), None]]);
+        assert!(solutions(run("SELECT ?x (COUNT(*) AS ?c) { ?x :height ?a } GROUP BY ?x")).is_empty());
+    });
+}
+
+#[test]
+fn test_group_by_spilling() {
+    let mut data = String::from("@prefix : <http://ex.org/> .\n");
+    for i in 0..500 {
+        data.push_str(&format!(":r{} :s :s{} ; :v {} ; :w {} .\n", i, i % 200, i, i % 7));
+    }
+    with_data("query_group_spill", &data, |run| {
+        let results = solutions(run("SELECT ?s (COUNT(*) AS ?c) (SUM(?v) AS ?sum) (COUNT(DISTINCT ?w) AS ?d) { ?r :s ?s ; :v ?v ; :w ?w } GROUP BY ?s"));
+        assert_eq!(results.len(), 200);
+        for i in 0..200 {
+            let count = if i < 100 { 3 } else { 2 };
+            let sum = (0..count).map(|k| i + 200 * k).sum::<i64>();
+            assert!(results.contains(&vec![ex(&format!("s{}", i)), integer(count), integer(sum), integer(count)]));
+        }
+    });
+}
+
+#[test]
+fn test_dataset() {
+    let data = "@prefix : <http://ex.org/> .\n\
+        :alice :knows :bob .\n\
+        :g1 { :alice :knows :carol . :bob :knows :carol . }\n\
+        :g2 { :alice :knows :carol . :carol :knows :dave . }\n";
+    with_file("query_dataset", "data.trig", data, |run| {
+        assert_eq!(solutions(run("SELECT ?y { :alice :knows ?y }")), vec![vec![ex("bob")]]);
+        // The merge has the triple of both graphs once
+        assert_eq!(
+            sorted(solutions(run("SELECT ?x ?y FROM :g1 FROM :g2 { ?x :knows ?y }"))),
+            vec![vec![ex("alice"), ex("carol")], vec![ex("bob"), ex("carol")], vec![ex("carol"), ex("dave")]]
+        );
+        assert_eq!(solutions(run("SELECT ?x FROM :g2 { ?x :knows :carol }")), vec![vec![ex("alice")]]);
+        assert_eq!(solutions(run("SELECT ?x FROM :unknown { ?x :knows ?y }")), Vec::<Solution>::new());
+        // Without FROM the default graph is empty
+        assert_eq!(solutions(run("SELECT ?x FROM NAMED :g1 { ?x :knows ?y }")), Vec::<Solution>::new());
+
+        assert_eq!(
+            sorted(solutions(run("SELECT ?g ?y { GRAPH ?g { :alice :knows ?y } }"))),
+            vec![vec![ex("g1"), ex("carol")], vec![ex("g2"), ex("carol")]]
+        );
+        assert_eq!(solutions(run("SELECT ?g { GRAPH ?g { ?x :knows :dave } }")), vec![vec![ex("g2")]]);
+        assert_eq!(solutions(run("SELECT ?g FROM NAMED :g1 { GRAPH ?g { ?x :knows :dave } }")), Vec::<Solution>::new());
+        assert_eq!(solutions(run("SELECT ?x { GRAPH :g2 { ?x :knows :dave } }")), vec![vec![ex("carol")]]);
+        // Without FROM NAMED a query with FROM has no named graphs
+        assert_eq!(solutions(run("SELECT ?x FROM :g1 { GRAPH ?g { ?x ?p ?o } }")), Vec::<Solution>::new());
+        assert_eq!(
+            sorted(solutions(run("SELECT ?y ?g ?x FROM :g2 FROM NAMED :g1 { :carol :knows ?y GRAPH ?g { ?x :knows :carol } }"))),
+            vec![vec![ex("dave"), ex("g1"), ex("alice")], vec![ex("dave"), ex("g1"), ex("bob")]]
+        );
+
+        let described = run("DESCRIBE :alice FROM :g1 FROM :g2");
+        let expected = RdfQuad::triple(RdfTerm::iri("http://ex.org/alice"), RdfTerm::iri("http://ex.org/knows"), RdfTerm::iri("http://ex.org/carol"));
+        assert_eq!(described, Results::Graph(vec![expected]));
+    });
+}
+
+#[test]
+fn test_not_supported() {
+    let query = parse_query("SELECT ?x { ?x ?p ?o FILTER(<http://ex.org/f>(?o)) }").unwrap();
+    let db_folder = temp_db_folder("query_not_supported");
+    let buffer = Arc::new(BufferManager::new(&db_folder, 64, 1, 1));
+    let indexes = QuadIndexes::create(&buffer).unwrap();
+    let strings = StringManager::open(&buffer, 0).unwrap();
+    let prefixes = Default::default();
+    let terms = QueryTerms::new(&strings, &prefixes);
+    let error = execute_query(&query, &indexes, &terms, Interruption::default(), None).map(read_results).unwrap_err();
+    assert!(error.is::<NotSupportedException>());
+    drop(indexes);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
@@ -0,0 +1,255 @@
+use tokio::io::AsyncReadExt;
+
+use milleniumdb_rs::network::http::HttpVersion;
+use milleniumdb_rs::network::response_type::ResponseType;
+use milleniumdb_rs::network::result_streaming::{stream_solutions, StreamError};
+use milleniumdb_rs::query::rdf_terms::{RdfPath, RdfPathStep, RdfQuad, RdfTerm, Solution};
+use milleniumdb_rs::query::result_writers::{graph_writer, solution_writer};
+
+fn sample_solutions() -> Vec<Solution> {
+    vec![
+        vec![
+            Some(RdfTerm::iri("http://example.org/a")),
+            Some(RdfTerm::lang_literal("chat \"noir\"", "FR")),
+            None,
+        ],
+        vec![
+            Some(RdfTerm::blank_node("b0")),
+            Some(RdfTerm::typed_literal("42", "http://www.w3.org/2001/XMLSchema#integer")),
+            Some(RdfTerm::simple_literal("line\nbreak<&>")),
+        ],
+    ]
+}
+
+fn serialize(response_type: ResponseType, variables: &[&str], solutions: &[Solution]) -> String {
+    let variables: Vec<String> = variables.iter().map(|v| v.to_string()).collect();
+    let mut writer = solution_writer(response_type).unwrap();
+    let mut out = Vec::new();
+    writer.write_head(&mut out, &variables).unwrap();
+    for solution in solutions {
+        writer.write_solution(&mut out, solution).unwrap();
+    }
+    writer.finish(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn serialize_boolean(response_type: ResponseType, value: bool) -> String {
+    let mut out = Vec::new();
+    solution_writer(response_type).unwrap().write_boolean(&mut out, value).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_json_select_results() {
+    let json = serialize(ResponseType::JSON, &["s", "o", "x"], &sample_solutions());
+    let expected = concat!(
+        "{\"head\":{\"vars\":[\"s\",\"o\",\"x\"]},\"results\":{\"bindings\":[\n",
+        "{\"s\":{\"type\":\"uri\",\"value\":\"http://example.org/a\"},",
+        "\"o\":{\"type\":\"literal\",\"value\":\"chat \\\"noir\\\"\",\"xml:lang\":\"fr\"}},\n",
+        "{\"s\":{\"type\":\"bnode\",\"value\":\"b0\"},",
+        "\"o\":{\"type\":\"literal\",\"value\":\"42\",\"datatype\":\"http://www.w3.org/2001/XMLSchema#integer\"},",
+        "\"x\":{\"type\":\"literal\",\"value\":\"line\\nbreak<&>\"}}\n",
+        "]}}\n"
+    );
+    assert_eq!(json, expected);
+}
+
+#[test]
+fn test_json_ask_and_empty_results() {
+    assert_eq!(serialize_boolean(ResponseType::JSON, true), "{\"head\":{},\"boolean\":true}\n");
+    assert_eq!(
+        serialize(ResponseType::JSON, &[], &[]),
+        "{\"head\":{\"vars\":[]},\"results\":{\"bindings\":[\n]}}\n"
+    );
+}
+
+#[test]
+fn test_xml_select_results() {
+    let xml = serialize(ResponseType::XML, &["s", "o", "x"], &sample_solutions());
+    let expected = concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<sparql xmlns=\"http://www.w3.org/2005/sparql-results#\">\n",
+        "  <head>\n",
+        "    <variable name=\"s\"/>\n",
+        "    <variable name=\"o\"/>\n",
+        "    <variable name=\"x\"/>\n",
+        "  </head>\n",
+        "  <results>\n",
+        "    <result>\n",
+        "      <binding name=\"s\"><uri>http://example.org/a</uri></binding>\n",
+        "      <binding name=\"o\"><literal xml:lang=\"fr\">chat &quot;noir&quot;</literal></binding>\n",
+        "    </result>\n",
+        "    <result>\n",
+        "      <binding name=\"s\"><bnode>b0</bnode></binding>\n",
+        "      <binding name=\"o\"><literal datatype=\"http://www.w3.org/2001/XMLSchema#integer\">42</literal></binding>\n",
+        "      <binding name=\"x\"><literal>line\nbreak&lt;&amp;&gt;</literal></binding>\n",
+        "    </result>\n",
+        "  </results>\n",
+        "</sparql>\n"
+    );
+    assert_eq!(xml, expected);
+}
+
+#[test]
+fn test_xml_ask_result() {
+    let xml = serialize_boolean(ResponseType::XML, false);
+    assert!(xml.contains("<head/>\n  <boolean>false</boolean>\n</sparql>"));
+}
+
+#[test]
+fn test_csv_select_results() {
+    let csv = serialize(ResponseType::CSV, &["s", "o", "x"], &sample_solutions());
+    assert_eq!(
+        csv,
+        "s,o,x\r\nhttp://example.org/a,\"chat \"\"noir\"\"\",\r\n_:b0,42,\"line\nbreak<&>\"\r\n"
+    );
+}
+
+#[test]
+fn test_tsv_select_results() {
+    let tsv = serialize(ResponseType::TSV, &["s", "o", "x"], &sample_solutions());
+    assert_eq!(
+        tsv,
+        "?s\t?o\t?x\n\
+         <http://example.org/a>\t\"chat \\\"noir\\\"\"@fr\t\n\
+         _:b0\t\"42\"^^<http://www.w3.org/2001/XMLSchema#integer>\t\"line\\nbreak<&>\"\n"
+    );
+}
+
+#[test]
+fn test_path_results() {
+    let ex = |local: &str| RdfTerm::iri(&format!("http://example.org/{}", local));
+    let path = RdfTerm::Path(Box::new(RdfPath {
+        start: ex("a"),
+        steps: vec![
+            RdfPathStep { predicate: ex("p"), inverse: false, node: ex("b") },
+            RdfPathStep { predicate: ex("q"), inverse: true, node: RdfTerm::simple_literal("c") },
+        ],
+    }));
+    let solutions = vec![vec![Some(path)]];
+    let text = "(<http://example.org/a>)-[<http://example.org/p>]->(<http://example.org/b>)<-[<http://example.org/q>]-(\"c\")";
+
+    let json = serialize(ResponseType::JSON, &["path"], &solutions);
+    assert!(json.contains(concat!(
+        "{\"path\":{\"type\":\"path\",\"start\":{\"type\":\"uri\",\"value\":\"http://example.org/a\"},\"steps\":[",
+        "{\"predicate\":{\"type\":\"uri\",\"value\":\"http://example.org/p\"},\"inverse\":false,",
+        "\"node\":{\"type\":\"uri\",\"value\":\"http://example.org/b\"}},",
+        "{\"predicate\":{\"type\":\"uri\",\"value\":\"http://example.org/q\"},\"inverse\":true,",
+        "\"node\":{\"type\":\"literal\",\"value\":\"c\"}}]}}"
+    )), "{}", json);
+
+    let xml = serialize(ResponseType::XML, &["path"], &solutions);
+    assert!(xml.contains(concat!(
+        "<binding name=\"path\"><path><start><uri>http://example.org/a</uri></start>",
+        "<step inverse=\"false\"><predicate><uri>http://example.org/p</uri></predicate><node><uri>http://example.org/b</uri></node></step>",
+        "<step inverse=\"true\"><predicate><uri>http://example.org/q</uri></predicate><node><literal>c</literal></node></step>",
+        "</path></binding>"
+    )), "{}", xml);
+
+    assert_eq!(serialize(ResponseType::TSV, &["path"], &solutions), format!("?path\n{}\n", text));
+    let csv = serialize(ResponseType::CSV, &["path"], &solutions);
+    assert_eq!(csv, format!("path\r\n\"{}\"\r\n", text.replace('"', "\"\"")));
+}
+
+fn sample_graph() -> Vec<RdfQuad> {
+    let ex = |local: &str| RdfTerm::iri(&format!("http://example.org/{}", local));
+    let name = RdfTerm::iri("http://xmlns.com/foaf/0.1/name");
+    vec![
+        RdfQuad::triple(ex("alice"), RdfTerm::iri("http://www.w3.org/1999/02/22-rdf-syntax-ns#type"), ex("Person")),
+        RdfQuad::triple(ex("alice"), name.clone(), RdfTerm::lang_literal("Alice", "en")),
+        RdfQuad::triple(ex("alice"), name, RdfTerm::simple_literal("Alicia")),
+        RdfQuad {
+            subject: RdfTerm::blank_node("b1"),
+            predicate: ex("age"),
+            object: RdfTerm::typed_literal("30", "http://www.w3.org/2001/XMLSchema#integer"),
+            graph: Some(ex("g")),
+        },
+        RdfQuad::triple(ex("path/with space"), ex("date"), RdfTerm::typed_literal("2024-01-01", "http://www.w3.org/2001/XMLSchema#date")),
+    ]
+}
+
+fn serialize_graph(response_type: ResponseType, prefixes: &[(&str, &str)]) -> String {
+    let prefixes: Vec<(String, String)> = prefixes.iter().map(|(p, n)| (p.to_string(), n.to_string())).collect();
+    let mut writer = graph_writer(response_type, &prefixes).unwrap();
+    let mut out = Vec::new();
+    writer.write_head(&mut out).unwrap();
+    for quad in sample_graph() {
+        writer.write_quad(&mut out, &quad).unwrap();
+    }
+    writer.finish(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_turtle_graph_with_prefix_compaction() {
+    let turtle = serialize_graph(
+        ResponseType::TURTLE,
+        &[("ex", "http://example.org/"), ("foaf", "http://xmlns.com/foaf/0.1/"), ("xsd", "http://www.w3.org/2001/XMLSchema#")],
+    );
+    let expected = concat!(
+        "@prefix ex: <http://example.org/> .\n",
+        "@prefix foaf: <http://xmlns.com/foaf/0.1/> .\n",
+        "@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .\n",
+        "\n",
+        "ex:alice a ex:Person ;\n",
+        "    foaf:name \"Alice\"@en ,\n",
+        "        \"Alicia\" .\n",
+        "_:b1 ex:age 30 .\n",
+        "<http://example.org/path/with\\u0020space> ex:date \"2024-01-01\"^^xsd:date .\n"
+    );
+    assert_eq!(turtle, expected);
+}
+
+#[test]
+fn test_ntriples_and_nquads_graph() {
+    let ntriples = serialize_graph(ResponseType::NTRIPLES, &[]);
+    assert_eq!(ntriples.lines().count(), 5);
+    assert!(ntriples.contains("_:b1 <http://example.org/age> \"30\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n"));
+
+    let nquads = serialize_graph(ResponseType::NQUADS, &[]);
+    assert!(nquads.contains(
+        "_:b1 <http://example.org/age> \"30\"^^<http://www.w3.org/2001/XMLSchema#integer> <http://example.org/g> .\n"
+    ));
+    assert!(nquads.contains("<http://example.org/alice> <http://xmlns.com/foaf/0.1/name> \"Alice\"@en .\n"));
+}
+
+#[tokio::test]
+async fn test_stream_solutions_in_chunks() {
+    let (mut client, mut server) = tokio::io::duplex(1024);
+    let reader = tokio::spawn(async move {
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        String::from_utf8(response).unwrap()
+    });
+
+    let rows = 20_000;
+    let solutions = (0..rows).map(|i| -> Result<Solution, StreamError> {
+        Ok(vec![Some(RdfTerm::iri(&format!("http://example.org/{}", i)))])
+    });
+    stream_solutions(&mut server, ResponseType::JSON, &["s".to_string()], futures::stream::iter(solutions), HttpVersion::Http11, false)
+        .await
+        .unwrap();
+    drop(server);
+
+    let response = reader.await.unwrap();
+    let (head, mut body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Transfer-Encoding: chunked"));
+
+    let mut chunks = 0;
+    let mut document = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n").unwrap();
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            assert_eq!(rest, "\r\n");
+            break;
+        }
+        document.push_str(&rest[..size]);
+        body = &rest[size + 2..];
+        chunks += 1;
+    }
+    assert!(chunks > 1);
+    assert!(document.ends_with("]}}\n"));
+    assert_eq!(document.matches("\"type\":\"uri\"").count(), rows);
+}
@@ -0,0 +1,367 @@
+use milleniumdb_rs::query::algebra::*;
+use milleniumdb_rs::query::query_forms::QueryForm;
+use milleniumdb_rs::query::rdf_terms::{RdfTerm, RDF_FIRST, RDF_NIL, RDF_REST, RDF_TYPE, XSD_DECIMAL, XSD_INTEGER};
+use milleniumdb_rs::query::sparql_parser::parse_query;
+
+fn var(name: &str) -> TermPattern {
+    TermPattern::Variable(name.to_string())
+}
+
+fn iri(value: &str) -> TermPattern {
+    TermPattern::Term(RdfTerm::iri(value))
+}
+
+// Relative IRIs of the queries are resolved against this base
+const BASE: &str = "BASE <http://ex.org/> ";
+
+fn ex(local: &str) -> String {
+    format!("http://ex.org/{}", local)
+}
+
+fn parse(query: &str) -> Query {
+    parse_query(&format!("{}{}", BASE, query)).unwrap()
+}
+
+fn triple(subject: TermPattern, predicate: TermPattern, object: TermPattern) -> TriplePattern {
+    TriplePattern::new(subject, predicate, object)
+}
+
+fn expression_var(name: &str) -> Box<Expression> {
+    Box::new(Expression::Variable(name.to_string()))
+}
+
+fn integer(value: &str) -> Box<Expression> {
+    Box::new(Expression::Constant(RdfTerm::typed_literal(value, XSD_INTEGER)))
+}
+
+fn project(pattern: GraphPattern, variables: &[&str]) -> GraphPattern {
+    GraphPattern::Project {
+        inner: Box::new(pattern),
+        variables: variables.iter().map(|name| name.to_string()).collect(),
+    }
+}
+
+#[test]
+fn test_prologue_and_select() {
+    let query = parse_query(
+        "# people\n\
+         BASE <http://ex.org/>\n\
+         PREFIX foaf: <http://xmlns.com/foaf/0.1/>\n\
+         SELECT ?name WHERE { ?p a foaf:Person ; foaf:name ?name . ?p <knows> <#bob> }",
+    )
+    .unwrap();
+
+    assert_eq!(query.form, QueryForm::Select);
+    assert_eq!(query.variables, ["name"]);
+    assert_eq!(query.prefixes, [("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string())]);
+    let bgp = GraphPattern::Bgp(vec![
+        triple(var("p"), iri(RDF_TYPE), iri("http://xmlns.com/foaf/0.1/Person")),
+        triple(var("p"), iri("http://xmlns.com/foaf/0.1/name"), var("name")),
+        triple(var("p"), iri("http://ex.org/knows"), iri("http://ex.org/#bob")),
+    ]);
+    assert_eq!(query.pattern, project(bgp, &["name"]));
+
+    // SELECT * projects the variables in scope, in the order they appear
+    let query = parse_query("SELECT * { ?s ?p ?o OPTIONAL { ?o ?q ?r } }").unwrap();
+    assert_eq!(query.variables, ["s", "p", "o", "q", "r"]);
+}
+
+#[test]
+fn test_query_forms() {
+    let query = parse_query("ASK { ?s ?p 1.5 }").unwrap();
+    assert_eq!(query.form, QueryForm::Ask);
+    assert_eq!(
+        query.pattern,
+        GraphPattern::Bgp(vec![triple(var("s"), var("p"), TermPattern::Term(RdfTerm::typed_literal("1.5", XSD_DECIMAL)))])
+    );
+
+    let query = parse_query("PREFIX : <http://ex.org/> CONSTRUCT { ?s :q _:b . _:b :r ?o } WHERE { ?s :p ?o }").unwrap();
+    assert_eq!(query.form, QueryForm::Construct);
+    assert_eq!(
+        query.template,
+        vec![
+            triple(var("s"), iri("http://ex.org/q"), TermPattern::BlankNode("b".to_string())),
+            triple(TermPattern::BlankNode("b".to_string()), iri("http://ex.org/r"), var("o")),
+        ]
+    );
+
+    // CONSTRUCT WHERE uses the pattern as template
+    let query = parse_query("CONSTRUCT WHERE { ?s <http://ex.org/p> ?o }").unwrap();
+    assert_eq!(query.template, vec![triple(var("s"), iri("http://ex.org/p"), var("o"))]);
+
+    let query = parse_query("DESCRIBE <http://ex.org/a> ?x WHERE { ?x ?p ?o }").unwrap();
+    assert_eq!(query.form, QueryForm::Describe);
+    assert_eq!(query.describe, vec![iri("http://ex.org/a"), var("x")]);
+
+    let query = parse_query("SELECT ?s FROM <http://ex.org/g> FROM NAMED <http://ex.org/h> { ?s ?p ?o }").unwrap();
+    assert_eq!(query.dataset.default_graphs, ["http://ex.org/g"]);
+    assert_eq!(query.dataset.named_graphs, ["http://ex.org/h"]);
+}
+
+#[test]
+fn test_graph_patterns() {
+    let query = parse(
+        "SELECT * {\n\
+           ?s <p> ?o .\n\
+           OPTIONAL { ?o <q> ?x FILTER(?x > 1) }\n\
+           { ?s <r> ?y } UNION { ?s <t> ?y }\n\
+           MINUS { ?s <u> ?o }\n\
+           BIND(?o + 1 AS ?z)\n\
+           FILTER(?y != ?z)\n\
+         }",
+    );
+
+    let bgp = |predicate: &str, object: &str| {
+        GraphPattern::Bgp(vec![triple(var(if predicate == "q" { "o" } else { "s" }), iri(&ex(predicate)), var(object))])
+    };
+    let optional = GraphPattern::LeftJoin {
+        left: Box::new(bgp("p", "o")),
+        right: Box::new(bgp("q", "x")),
+        condition: Some(Expression::Greater(expression_var("x"), integer("1"))),
+    };
+    let union = GraphPattern::Union(Box::new(bgp("r", "y")), Box::new(bgp("t", "y")));
+    let minus = GraphPattern::Minus(Box::new(GraphPattern::Join(Box::new(optional), Box::new(union))), Box::new(bgp("u", "o")));
+    let extend = GraphPattern::Extend {
+        inner: Box::new(minus),
+        variable: "z".to_string(),
+        expression: Expression::Add(expression_var("o"), integer("1")),
+    };
+    // The filters of a group apply to all of it
+    let filter = GraphPattern::Filter {
+        condition: Expression::NotEqual(expression_var("y"), expression_var("z")),
+        inner: Box::new(extend),
+    };
+    assert_eq!(query.pattern, project(filter, &["s", "o", "x", "y", "z"]));
+}
+
+#[test]
+fn test_values_and_subqueries() {
+    let query = parse("SELECT ?x { VALUES (?x ?y) { (1 UNDEF) (<a> \"b\"@en) } }");
+    assert_eq!(
+        query.pattern,
+        project(
+            GraphPattern::Values {
+                variables: vec!["x".to_string(), "y".to_string()],
+                rows: vec![
+                    vec![Some(RdfTerm::typed_literal("1", XSD_INTEGER)), None],
+                    vec![Some(RdfTerm::iri(&ex("a"))), Some(RdfTerm::lang_literal("b", "en"))],
+                ],
+            },
+            &["x"]
+        )
+    );
+
+    let query = parse("SELECT ?s { ?s <p> ?o { SELECT ?o { ?o <q> ?r } LIMIT 2 } }");
+    let subquery = GraphPattern::Slice {
+        inner: Box::new(project(GraphPattern::Bgp(vec![triple(var("o"), iri(&ex("q")), var("r"))]), &["o"])),
+        offset: 0,
+        limit: Some(2),
+    };
+    let join = GraphPattern::Join(Box::new(GraphPattern::Bgp(vec![triple(var("s"), iri(&ex("p")), var("o"))])), Box::new(subquery));
+    assert_eq!(query.pattern, project(join, &["s"]));
+}
+
+#[test]
+fn test_solution_modifiers() {
+    let query = parse("SELECT DISTINCT ?s (?o * 2 AS ?d) { ?s <p> ?o } ORDER BY DESC(?o) ?s LIMIT 10 OFFSET 5");
+    let extend = GraphPattern::Extend {
+        inner: Box::new(GraphPattern::Bgp(vec![triple(var("s"), iri(&ex("p")), var("o"))])),
+        variable: "d".to_string(),
+        expression: Expression::Multiply(expression_var("o"), integer("2")),
+    };
+    let order = GraphPattern::OrderBy {
+        inner: Box::new(extend),
+        conditions: vec![
+            OrderCondition { expression: Expression::Variable("o".to_string()), ascending: false },
+            OrderCondition { expression: Expression::Variable("s".to_string()), ascending: true },
+        ],
+    };
+    let expected = GraphPattern::Slice {
+        inner: Box::new(GraphPattern::Distinct(Box::new(project(order, &["s", "d"])))),
+        offset: 5,
+        limit: Some(10),
+    };
+    assert_eq!(query.variables, ["s", "d"]);
+    assert_eq!(query.pattern, expected);
+}
+
+#[test]
+fn test_aggregates() {
+    let query = parse_query(
+        "SELECT ?s (COUNT(DISTINCT ?o) AS ?n) { ?s ?p ?o } GROUP BY ?s HAVING (SUM(?o) > 10)",
+    )
+    .unwrap();
+    let (by, aggregates) = match &query.pattern {
+        GraphPattern::Project { inner, .. } => match inner.as_ref() {
+            GraphPattern::Extend { inner, variable, expression } => {
+                assert_eq!(variable, "n");
+                let aggregate = match expression {
+                    Expression::Variable(name) => name.clone(),
+                    other => panic!("unexpected {:?}", other),
+                };
+                assert!(is_internal_variable(&aggregate));
+                match inner.as_ref() {
+                    GraphPattern::Filter { inner, .. } => match inner.as_ref() {
+                        GraphPattern::Group { by, aggregates, .. } => (by.clone(), aggregates.clone()),
+                        other => panic!("unexpected {:?}", other),
+                    },
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            other => panic!("unexpected {:?}", other),
+        },
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(by, vec![(Expression::Variable("s".to_string()), "s".to_string())]);
+    let functions: Vec<(AggregateFunction, bool)> =
+        aggregates.iter().map(|(_, aggregate)| (aggregate.function, aggregate.distinct)).collect();
+    assert_eq!(functions, [(AggregateFunction::Count, true), (AggregateFunction::Sum, false)]);
+
+    // Variables outside GROUP BY can not be projected
+    let error = parse_query("SELECT ?o { ?s ?p ?o } GROUP BY ?s").unwrap_err();
+    assert!(error.to_string().contains("?o"), "{}", error);
+    assert!(parse_query("SELECT * { ?s ?p ?o } GROUP BY ?s").is_err());
+}
+
+#[test]
+fn test_property_paths() {
+    // Sequences and inverses are triples joined by new variables
+    let query = parse("ASK { ?s <p>/^<q> ?o }");
+    let triples = match &query.pattern {
+        GraphPattern::Bgp(triples) => triples.clone(),
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(triples.len(), 2);
+    assert_eq!(triples[0].subject, var("s"));
+    assert_eq!(triples[0].predicate, iri(&ex("p")));
+    assert_eq!(triples[1].subject, var("o"));
+    assert_eq!(triples[1].predicate, iri(&ex("q")));
+    assert_eq!(triples[0].object, triples[1].object);
+    assert!(is_internal_variable(&triples[0].object.variable().unwrap()));
+
+    let query = parse("ASK { ?s (<p>|<q>)+/!(<r>|^<t>)* <o> }");
+    let paths: Vec<PropertyPath> = match &query.pattern {
+        GraphPattern::Join(left, right) => [left, right]
+            .iter()
+            .map(|pattern| match pattern.as_ref() {
+                GraphPattern::Path { path, .. } => path.clone(),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect(),
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(
+        paths[0],
+        PropertyPath::OneOrMore(Box::new(PropertyPath::Alternative(vec![
+            PropertyPath::Iri(ex("p")),
+            PropertyPath::Iri(ex("q")),
+        ])))
+    );
+    assert_eq!(
+        paths[1],
+        PropertyPath::ZeroOrMore(Box::new(PropertyPath::NegatedSet(vec![
+            NegatedIri { iri: ex("r"), inverse: false },
+            NegatedIri { iri: ex("t"), inverse: true },
+        ])))
+    );
+}
+
+#[test]
+fn test_path_modes() {
+    let query = parse("SELECT ?p { ?s ALL SHORTEST ?p <p>+ <o> }");
+    assert_eq!(
+        query.pattern,
+        project(
+            GraphPattern::Path {
+                subject: var("s"),
+                path: PropertyPath::OneOrMore(Box::new(PropertyPath::Iri(ex("p")))),
+                object: iri(&ex("o")),
+                mode: Some(PathMode::AllShortest),
+                variable: Some("p".to_string()),
+            },
+            &["p"]
+        )
+    );
+
+    // A path with a mode is kept whole, even a single IRI, and the variable is optional
+    let query = parse("ASK { ?s any <p> ?o ; TRAIL (<q>/<r>)* ?o }");
+    let modes: Vec<(Option<PathMode>, Option<String>)> = match &query.pattern {
+        GraphPattern::Join(left, right) => [left, right]
+            .iter()
+            .map(|pattern| match pattern.as_ref() {
+                GraphPattern::Path { mode, variable, .. } => (*mode, variable.clone()),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect(),
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(modes, vec![(Some(PathMode::Any), None), (Some(PathMode::Trail), None)]);
+
+    for (mode, expected) in [
+        ("ANY SHORTEST", PathMode::AnyShortest),
+        ("SIMPLE", PathMode::Simple),
+        ("ACYCLIC", PathMode::Acyclic),
+    ] {
+        let query = parse(&format!("SELECT * {{ ?s {} ?path <p>* ?o }}", mode));
+        assert!(query.variables.contains(&"path".to_string()));
+        assert!(matches!(query.pattern, GraphPattern::Project { ref inner, .. }
+            if matches!(**inner, GraphPattern::Path { mode: Some(found), .. } if found == expected)));
+    }
+
+    assert!(parse_query("ASK { ?s ALL <p>* ?o }").is_err());
+    // Names that start like a mode are prefixed names
+    assert!(parse_query("PREFIX any: <http://ex.org/> ASK { ?s any:p ?o }").is_ok());
+}
+
+#[test]
+fn test_collections_and_blank_nodes() {
+    let query = parse("ASK { ?s <p> (1 [ <q> ?x ]) }");
+    let triples = match &query.pattern {
+        GraphPattern::Bgp(triples) => triples.clone(),
+        other => panic!("unexpected {:?}", other),
+    };
+    let count = |predicate: &str| triples.iter().filter(|triple| triple.predicate == iri(predicate)).count();
+    assert_eq!(count(RDF_FIRST), 2);
+    assert_eq!(count(RDF_REST), 2);
+    assert_eq!(count(&ex("q")), 1);
+    assert!(triples.iter().any(|triple| triple.object == iri(RDF_NIL)));
+}
+
+#[test]
+fn test_exists_and_functions() {
+    let query = parse_query(
+        "PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+         SELECT ?s { ?s ?p ?o FILTER(NOT EXISTS { ?o ?q ?s } && regex(str(?o), \"^a\", \"i\") || xsd:integer(?o) IN (1, 2)) }",
+    )
+    .unwrap();
+    let condition = match &query.pattern {
+        GraphPattern::Project { inner, .. } => match inner.as_ref() {
+            GraphPattern::Filter { condition, .. } => condition.clone(),
+            other => panic!("unexpected {:?}", other),
+        },
+        other => panic!("unexpected {:?}", other),
+    };
+    match condition {
+        Expression::Or(left, right) => {
+            assert!(matches!(*left, Expression::And(ref exists, _) if matches!(**exists, Expression::NotExists(_))));
+            assert!(matches!(*right, Expression::In(ref cast, _)
+                if matches!(**cast, Expression::Function(ref iri, _) if iri == XSD_INTEGER)));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = parse_query("SELECT ?s\nWHERE { ?s ?p }").unwrap_err();
+    assert!(error.to_string().starts_with("Parse error at line 2, column 15"), "{}", error);
+
+    let error = parse_query("SELECT ?s { ?s foo:p ?o }").unwrap_err();
+    assert!(error.to_string().contains("foo"), "{}", error);
+
+    // A variable can not be bound twice in the same group
+    assert!(parse_query("SELECT * { ?s ?p ?o BIND(1 AS ?o) }").is_err());
+    assert!(parse_query("SELECT (1 AS ?s) { ?s ?p ?o }").is_err());
+    assert!(parse_query("SELECT * { ?s ?p ?o } LIMIT").is_err());
+    assert!(parse_query("SELECT * { ?s ?p ?o } extra").is_err());
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use milleniumdb_rs::import::bulk_loader::BulkLoader;
+use milleniumdb_rs::network::http::{HttpRequest, RequestReader};
+use milleniumdb_rs::network::session::Session;
+use milleniumdb_rs::network::sparql_protocol::parse_sparql_request;
+use milleniumdb_rs::network::sparql_servers::Server;
+use milleniumdb_rs::storage::buffer_manager::BufferManager;
+use milleniumdb_rs::storage::catalog::Catalog;
+use milleniumdb_rs::storage::string_manager::StringManager;
+
+mod common;
+use common::temp_db_folder;
+
+async fn request_from(raw: &str) -> HttpRequest {
+    RequestReader::new(raw.as_bytes()).read_request().await.unwrap().unwrap()
+}
+
+async fn response_to(raw: &str) -> String {
+    response_from(&Server::new(), raw, Duration::from_secs(5)).await
+}
+
+async fn response_from(server: &Arc<Mutex<Server>>, raw: &str, timeout: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_weak = Arc::downgrade(server);
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        Session::new(server_weak, socket, timeout).run().await;
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(raw.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+// A server over a database with the TriG `data`
+async fn server_with_data(db_folder: &Path, data: &str) -> Arc<Mutex<Server>> {
+    let data_file = db_folder.join("data.trig");
+    fs::write(&data_file, data).unwrap();
+    let mut loader = BulkLoader::new(&db_folder.join("db"), 64).unwrap();
+    loader.load_file(&data_file).unwrap();
+    loader.finish().unwrap();
+
+    let buffer_manager = Arc::new(BufferManager::new(&db_folder.join("db"), 64, 1, 1));
+    let string_manager = StringManager::open(&buffer_manager, 0).unwrap();
+    let catalog = Catalog::load(&db_folder.join("db")).unwrap();
+    let server = Server::new();
+    {
+        let mut server = server.lock().await;
+        server.buffer_manager = Some(buffer_manager);
+        server.string_manager = Some(Arc::new(string_manager));
+        server.iri_prefixes = Some(Arc::new(catalog.prefixes));
+    }
+    server
+}
+
+#[tokio::test]
+async fn test_query_via_get_with_dataset() {
+    let request = request_from(
+        "GET /sparql?query=SELECT%20*%20WHERE%20%7B%3Fs%20%3Fp%20%3Fo%7D&default-graph-uri=http%3A%2F%2Fa\
+         &named-graph-uri=http%3A%2F%2Fb&named-graph-uri=http%3A%2F%2Fc HTTP/1.1\r\nHost: x\r\n\r\n",
+    )
+    .await;
+    let sparql_request = parse_sparql_request(&request).unwrap();
+    assert_eq!(sparql_request.query, "SELECT * WHERE {?s ?p ?o}");
+    assert_eq!(sparql_request.dataset.default_graph_uris, vec!["http://a"]);
+    assert_eq!(sparql_request.dataset.named_graph_uris, vec!["http://b", "http://c"]);
+}
+
+#[tokio::test]
+async fn test_query_via_urlencoded_post() {
+    let body = "query=ASK+%7B%7D&format=json";
+    let request = request_from(&format!(
+        "POST /sparql HTTP/1.1\r\nHost: x\r\nContent-Type: application/x-www-form-urlencoded; charset=UTF-8\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    ))
+    .await;
+    let sparql_request = parse_sparql_request(&request).unwrap();
+    assert_eq!(sparql_request.query, "ASK {}");
+    assert_eq!(sparql_request.parameter("format"), Some("json"));
+}
+
+#[tokio::test]
+async fn test_query_via_direct_post() {
+    let body = "SELECT ?x WHERE { ?x ?y ?z }";
+    let request = request_from(&format!(
+        "POST /sparql?default-graph-uri=http%3A%2F%2Fg HTTP/1.1\r\nHost: x\r\nContent-Type: application/sparql-query\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    ))
+    .await;
+    let sparql_request = parse_sparql_request(&request).unwrap();
+    assert_eq!(sparql_request.query, body);
+    assert_eq!(sparql_request.dataset.default_graph_uris, vec!["http://g"]);
+}
+
+#[tokio::test]
+async fn test_protocol_errors() {
+    let missing_query = request_from("GET /sparql HTTP/1.1\r\nHost: x\r\n\r\n").await;
+    assert_eq!(parse_sparql_request(&missing_query).unwrap_err().status(), 400);
+
+    let repeated_query = request_from("GET /sparql?query=ASK{}&query=ASK{} HTTP/1.1\r\nHost: x\r\n\r\n").await;
+    assert_eq!(parse_sparql_request(&repeated_query).unwrap_err().status(), 400);
+
+    let wrong_type =
+        request_from("POST /sparql HTTP/1.1\r\nHost: x\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\nab").await;
+    assert_eq!(parse_sparql_request(&wrong_type).unwrap_err().status(), 415);
+
+    let wrong_method = request_from("DELETE /sparql HTTP/1.1\r\nHost: x\r\n\r\n").await;
+    assert_eq!(parse_sparql_request(&wrong_method).unwrap_err().status(), 405);
+}
+
+#[tokio::test]
+async fn test_session_routes_sparql_requests() {
+    let response = response_to("PUT /sparql HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    assert!(response.contains("Allow: GET, POST\r\n"));
+
+    let response = response_to("GET /other HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    let response = response_to("GET /sparql?query= HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+    let response = response_to("GET /sparql?query=ASK%20%7B%7D&format=bogus HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 406 Not Acceptable\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_protocol_dataset() {
+    let db_folder = temp_db_folder("protocol_dataset");
+    let server = server_with_data(
+        &db_folder,
+        "@prefix : <http://ex.org/> .\n\
+         :alice :knows :bob .\n\
+         :g1 { :alice :knows :carol . }\n\
+         :g2 { :alice :knows :dave . }\n",
+    )
+    .await;
+    let get = |parameters: &str| {
+        format!(
+            "GET /sparql?query=SELECT%20%3Fy%20%7B%20%3Chttp%3A%2F%2Fex.org%2Falice%3E%20%3Fp%20%3Fy%20%7D{} HTTP/1.1\r\n\
+             Host: x\r\nAccept: text/csv\r\nConnection: close\r\n\r\n",
+            parameters
+        )
+    };
+
+    let response = response_from(&server, &get(""), Duration::from_secs(5)).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("http://ex.org/bob") && !response.contains("http://ex.org/carol"));
+
+    // HTTP/1.0 clients get the body without chunks, ended by closing the connection
+    let request = get("").replace("HTTP/1.1", "HTTP/1.0").replace("Connection: close\r\n", "");
+    let response = response_from(&server, &request, Duration::from_secs(5)).await;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n") && head.contains("Connection: close"), "{}", head);
+    assert!(!head.contains("Transfer-Encoding"));
+    assert_eq!(body, "y\r\nhttp://ex.org/bob\r\n");
+
+    // The default graph is the merge of the graphs of default-graph-uri
+    let response = response_from(
+        &server,
+        &get("&default-graph-uri=http%3A%2F%2Fex.org%2Fg1&default-graph-uri=http%3A%2F%2Fex.org%2Fg2"),
+        Duration::from_secs(5),
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("http://ex.org/carol") && response.contains("http://ex.org/dave"));
+    assert!(!response.contains("http://ex.org/bob"));
+
+    // GRAPH only sees the graphs of named-graph-uri, which replace FROM NAMED
+    let response = response_from(
+        &server,
+        "GET /sparql?query=SELECT%20%3Fg%20FROM%20NAMED%20%3Chttp%3A%2F%2Fex.org%2Fg1%3E%20%7B%20GRAPH%20%3Fg%20%7B%20%3Fs%20%3Fp%20%3Fo%20%7D%20%7D\
+         &named-graph-uri=http%3A%2F%2Fex.org%2Fg2 HTTP/1.1\r\nHost: x\r\nAccept: text/csv\r\nConnection: close\r\n\r\n",
+        Duration::from_secs(5),
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("http://ex.org/g2") && !response.contains("http://ex.org/g1"));
+
+    drop(server);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[tokio::test]
+async fn test_query_timeout() {
+    let db_folder = temp_db_folder("protocol_timeout");
+    let mut data = String::from("@prefix : <http://ex.org/> .\n");
+    for i in 0..400 {
+        data.push_str(&format!(":s{} :p {} .\n", i, i));
+    }
+    let server = server_with_data(&db_folder, &data).await;
+    server.lock().await.execute_timeouts().await;
+
+    // Counting the cross product takes much longer than the timeout
+    let response = response_from(
+        &server,
+        "GET /sparql?query=SELECT%20(COUNT(*)%20AS%20%3Fn)%20%7B%20%3Fa%20%3Fb%20%3Fc%20.%20%3Fd%20%3Fe%20%3Ff%20.%20%3Fg%20%3Fh%20%3Fi%20%7D \
+         HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n",
+        Duration::from_secs(1),
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{}", response);
+
+    *server.lock().await.shutdown_server.lock().await = true;
+    drop(server);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
@@ -0,0 +1,526 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use milleniumdb_rs::storage::bplus_tree::BPlusTree;
+use milleniumdb_rs::storage::catalog::{Catalog, DataModel, CATALOG_FILE, CATALOG_FILE_MAGIC, FORMAT_VERSION};
+use milleniumdb_rs::storage::buffer_manager::{pages_in_megabytes, BufferManager};
+use milleniumdb_rs::storage::exceptions::StorageError;
+use milleniumdb_rs::storage::quad_indexes::QuadIndexes;
+use milleniumdb_rs::storage::string_manager::{StringManager, STRINGS_HASH_FILE};
+use milleniumdb_rs::storage::iri_prefixes::IriPrefixes;
+use milleniumdb_rs::storage::file_manager::{PageId, PAGE_SIZE, TEMP_FOLDER};
+use milleniumdb_rs::storage::wal::{WalSyncPolicy, DEFAULT_CHECKPOINT_SIZE, WAL_FILE, WAL_FILE_MAGIC};
+
+mod common;
+use common::temp_db_folder;
+
+#[test]
+fn test_pin_and_write_back() {
+    let db_folder = temp_db_folder("buffer_write_back");
+    {
+        let buffer = BufferManager::new(&db_folder, 2, 1, 1);
+        let file_id = buffer.get_file_id("pages.dat").unwrap();
+
+        // Five pages through a pool of two: the evicted ones have to be written back
+        for i in 0..5u8 {
+            let page = buffer.append_page(file_id).unwrap();
+            assert_eq!(page.page_id().page_number, i as u64);
+            page.write()[0] = i + 1;
+            page.write()[PAGE_SIZE - 1] = i + 1;
+        }
+        for i in 0..5u8 {
+            let page = buffer.pin(PageId::new(file_id, i as u64)).unwrap();
+            assert_eq!(page.read()[0], i + 1);
+            assert_eq!(page.read()[PAGE_SIZE - 1], i + 1);
+        }
+
+        // Pages past the end of the file are zeroed
+        let page = buffer.pin(PageId::new(file_id, 10)).unwrap();
+        assert!(page.read().iter().all(|&b| b == 0));
+    }
+
+    // Dropping the buffer flushes the pages still in memory
+    let content = fs::read(db_folder.join("pages.dat")).unwrap();
+    assert_eq!(content.len(), 5 * PAGE_SIZE);
+    for i in 0..5 {
+        assert_eq!(content[i * PAGE_SIZE], i as u8 + 1);
+    }
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_pinned_pages_are_not_evicted() {
+    let db_folder = temp_db_folder("buffer_pinned");
+    let buffer = BufferManager::new(&db_folder, 2, 1, 1);
+    let file_id = buffer.get_file_id("pages.dat").unwrap();
+
+    let first = buffer.pin(PageId::new(file_id, 0)).unwrap();
+    let second = buffer.pin(PageId::new(file_id, 1)).unwrap();
+    let error = buffer.pin(PageId::new(file_id, 2)).err().unwrap();
+    assert_eq!(error.to_string(), "All the 2 pages of the buffer are pinned");
+
+    // Pinning a page already in the pool does not need a frame
+    let again = buffer.pin(PageId::new(file_id, 1)).unwrap();
+    drop(second);
+    assert!(buffer.pin(PageId::new(file_id, 2)).is_err());
+
+    // Once unpinned the frame can be reused, the pinned page stays in memory
+    first.write()[0] = 7;
+    first.unpin();
+    buffer.pin(PageId::new(file_id, 2)).unwrap();
+    again.write()[0] = 9;
+    assert_eq!(buffer.pin(PageId::new(file_id, 1)).unwrap().read()[0], 9);
+    drop(again);
+    assert_eq!(buffer.pin(PageId::new(file_id, 0)).unwrap().read()[0], 7);
+
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_private_buffer_pool() {
+    let db_folder = temp_db_folder("buffer_private");
+    let buffer = BufferManager::new(&db_folder, pages_in_megabytes(1), 2, 2);
+    assert_eq!(buffer.shared_capacity(), 1024 * 1024 / PAGE_SIZE);
+    assert!(buffer.private_pool(2).is_err());
+
+    {
+        let mut pool = buffer.private_pool(1).unwrap();
+        for i in 0..10u8 {
+            let page = pool.new_page().unwrap();
+            page.write()[100] = i;
+        }
+        assert_eq!(pool.page_count(), 10);
+        for i in 0..10u8 {
+            assert_eq!(pool.pin(i as u64).unwrap().read()[100], i);
+        }
+        assert!(pool.pin(10).is_err());
+        assert_eq!(fs::read_dir(db_folder.join(TEMP_FOLDER)).unwrap().count(), 1);
+
+        pool.clear().unwrap();
+        assert_eq!(pool.page_count(), 0);
+        assert!(pool.pin(0).is_err());
+        assert_eq!(fs::read_dir(db_folder.join(TEMP_FOLDER)).unwrap().count(), 0);
+
+        // The pool can be used again after being cleared
+        let page = pool.new_page().unwrap();
+        assert_eq!(page.page_id().page_number, 0);
+        assert!(page.read().iter().all(|&b| b == 0));
+    }
+
+    // The temporary files are deleted with the buffer
+    drop(buffer);
+    assert_eq!(fs::read_dir(db_folder.join(TEMP_FOLDER)).unwrap().count(), 0);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+// Deterministic pseudo-random numbers for the tests
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % bound
+    }
+}
+
+#[test]
+fn test_bplus_tree_bulk_load_and_scan() {
+    let db_folder = temp_db_folder("bplus_tree_bulk");
+    let buffer = Arc::new(BufferManager::new(&db_folder, 16, 1, 1));
+    let tuples: Vec<[u64; 3]> = (0..20_000u64).map(|i| [i / 100, i % 100, i]).collect();
+    {
+        let tree = BPlusTree::bulk_load(&buffer, "test.bpt", tuples.iter().map(|&t| Ok::<_, StorageError>(t))).unwrap();
+        assert_eq!(tree.len(), 20_000);
+    }
+
+    let tree = BPlusTree::<3>::open(&buffer, "test.bpt").unwrap();
+    assert_eq!(tree.len(), 20_000);
+    let all: Vec<_> = tree.scan_prefix(&[]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(all, tuples);
+
+    let prefix: Vec<_> = tree.scan_prefix(&[42]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(prefix, tuples[4200..4300]);
+    let prefix: Vec<_> = tree.scan_prefix(&[199, 99]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(prefix, vec![[199, 99, 19_999]]);
+    assert_eq!(tree.scan_prefix(&[200]).unwrap().count(), 0);
+
+    let range: Vec<_> = tree.range(&[10, 50, 0], &[11, 5, u64::MAX]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(range, tuples[1050..1106]);
+    assert!(tree.contains(&[3, 4, 304]).unwrap());
+    assert!(!tree.contains(&[3, 4, 305]).unwrap());
+
+    // The input of a bulk load has to be sorted and the file new
+    let unsorted = [[2u64, 0], [1, 0]].map(Ok::<_, StorageError>);
+    assert!(BPlusTree::bulk_load(&buffer, "unsorted.bpt", unsorted).is_err());
+    assert!(BPlusTree::<3>::create(&buffer, "test.bpt").is_err());
+    assert!(BPlusTree::<4>::open(&buffer, "test.bpt").is_err());
+
+    drop(tree);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_bplus_tree_seek() {
+    let db_folder = temp_db_folder("bplus_tree_seek");
+    let buffer = Arc::new(BufferManager::new(&db_folder, 16, 1, 1));
+    let tuples: Vec<[u64; 2]> = (0..10_000u64).map(|i| [i * 3, i]).collect();
+    let tree = BPlusTree::bulk_load(&buffer, "seek.bpt", tuples.iter().map(|&t| Ok::<_, StorageError>(t))).unwrap();
+
+    let mut iter = tree.scan_prefix(&[]).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap(), [0, 0]);
+    // Within the current leaf, to a key between two tuples and to one far away
+    iter.seek(&[7, 0]).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap(), [9, 3]);
+    iter.seek(&[24_000, 0]).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap(), [24_000, 8000]);
+    // Seeking to a key already passed does not move back
+    iter.seek(&[3, 1]).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap(), [24_003, 8001]);
+    iter.seek(&[29_997, 9999]).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap(), [29_997, 9999]);
+    assert!(iter.next().is_none());
+
+    // The end of a range is kept after a seek
+    let mut range = tree.range(&[0, 0], &[600, u64::MAX]).unwrap();
+    range.seek(&[590, 0]).unwrap();
+    let rest: Vec<_> = range.map(Result::unwrap).collect();
+    assert_eq!(rest, vec![[591, 197], [594, 198], [597, 199], [600, 200]]);
+    let mut range = tree.range(&[0, 0], &[600, u64::MAX]).unwrap();
+    range.seek(&[5000, 0]).unwrap();
+    assert!(range.next().is_none());
+
+    drop(tree);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_bplus_tree_insert_and_delete() {
+    let db_folder = temp_db_folder("bplus_tree_update");
+    let buffer = Arc::new(BufferManager::new(&db_folder, 8, 1, 1));
+    let mut tree = BPlusTree::<4>::create(&buffer, "test.bpt").unwrap();
+    // Wide tuples, so internal nodes are split too
+    let mut expected = BTreeSet::new();
+    let mut random = Lcg(7);
+
+    for _ in 0..30_000 {
+        let tuple = [random.next(500), random.next(500), random.next(3), 0];
+        assert_eq!(tree.insert(&tuple).unwrap(), expected.insert(tuple));
+    }
+    for _ in 0..20_000 {
+        let tuple = [random.next(500), random.next(500), random.next(3), 0];
+        assert_eq!(tree.delete(&tuple).unwrap(), expected.remove(&tuple));
+    }
+    assert_eq!(tree.len(), expected.len() as u64);
+    let all: Vec<_> = tree.scan_prefix(&[]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(all, expected.iter().copied().collect::<Vec<_>>());
+    let prefix: Vec<_> = tree.scan_prefix(&[250]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(prefix, expected.range([250, 0, 0, 0]..=[250, u64::MAX, u64::MAX, u64::MAX]).copied().collect::<Vec<_>>());
+
+    // Changes are kept when the tree is opened again
+    tree.flush().unwrap();
+    drop(tree);
+    let tree = BPlusTree::<4>::open(&buffer, "test.bpt").unwrap();
+    assert_eq!(tree.len(), expected.len() as u64);
+    assert_eq!(tree.scan_prefix(&[]).unwrap().count(), expected.len());
+
+    drop(tree);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_quad_indexes_patterns() {
+    let db_folder = temp_db_folder("quad_indexes");
+    let buffer = Arc::new(BufferManager::new(&db_folder, 64, 1, 1));
+    let mut indexes = QuadIndexes::create(&buffer).unwrap();
+    let mut random = Lcg(11);
+    let mut quads = BTreeSet::new();
+    for _ in 0..2_000 {
+        let quad = [random.next(10), random.next(5), random.next(10), random.next(3)];
+        assert_eq!(indexes.insert_quad(&quad).unwrap(), quads.insert(quad));
+    }
+    let quad = *quads.iter().next().unwrap();
+    assert!(indexes.delete_quad(&quad).unwrap());
+    assert!(!indexes.delete_quad(&quad).unwrap());
+    quads.remove(&quad);
+    assert_eq!(indexes.quad_count(), quads.len() as u64);
+
+    // Every combination of bound terms gives exactly the matching quads
+    let sample = *quads.iter().nth(100).unwrap();
+    for mask in 0..16 {
+        let mut pattern = [None; 4];
+        for (position, term) in pattern.iter_mut().enumerate() {
+            if mask & (1 << position) != 0 {
+                *term = Some(sample[position]);
+            }
+        }
+        let mut found: Vec<_> = indexes.scan_quads(&pattern).unwrap().map(Result::unwrap).collect();
+        found.sort();
+        let expected: Vec<_> = quads
+            .iter()
+            .filter(|quad| pattern.iter().zip(quad.iter()).all(|(term, value)| term.is_none_or(|term| term == *value)))
+            .copied()
+            .collect();
+        assert_eq!(found, expected, "pattern {:?}", pattern);
+    }
+
+    assert!(indexes.insert_triple(&[1, 2, 3]).unwrap());
+    assert!(indexes.insert_triple(&[3, 2, 1]).unwrap());
+    let by_object: Vec<_> = indexes.scan_triples(&[None, None, Some(1)]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(by_object, vec![[3, 2, 1]]);
+    let by_subject_object: Vec<_> = indexes.scan_triples(&[Some(1), None, Some(3)]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(by_subject_object, vec![[1, 2, 3]]);
+
+    drop(indexes);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_string_manager() {
+    let db_folder = temp_db_folder("string_manager");
+    let strings: Vec<String> = (0..2_000).map(|i| format!("<http://example.org/resource/{}>", i)).collect();
+    let mut ids = Vec::new();
+    {
+        let buffer = Arc::new(BufferManager::new(&db_folder, 4, 1, 1));
+        let manager = StringManager::open(&buffer, 0).unwrap();
+        assert!(manager.is_empty());
+        for string in &strings {
+            ids.push(manager.get_or_insert(string).unwrap());
+        }
+        // Inserting again gives the same id
+        assert_eq!(manager.get_or_insert(&strings[10]).unwrap(), ids[10]);
+        assert_eq!(manager.len(), 2_000);
+        assert_eq!(manager.get_id("<http://example.org/missing>").unwrap(), None);
+        manager.flush().unwrap();
+    }
+
+    // Reopened with the whole file in memory
+    {
+        let buffer = Arc::new(BufferManager::new(&db_folder, 4, 1, 1));
+        let manager = StringManager::open(&buffer, 1 << 30).unwrap();
+        assert_eq!(manager.len(), 2_000);
+        assert_eq!(manager.populated_size(), fs::metadata(db_folder.join("strings.dat")).unwrap().len());
+        for (string, &id) in strings.iter().zip(&ids) {
+            assert_eq!(manager.get_id(string).unwrap(), Some(id));
+            assert_eq!(&manager.get_string(id).unwrap(), string);
+        }
+    }
+
+    // Without its index, the index is rebuilt from the strings file
+    fs::remove_file(db_folder.join(STRINGS_HASH_FILE)).unwrap();
+    {
+        let buffer = Arc::new(BufferManager::new(&db_folder, 4, 1, 1));
+        let manager = StringManager::open(&buffer, 100).unwrap();
+        assert_eq!(manager.len(), 2_000);
+        assert_eq!(manager.populated_size(), 100);
+        assert_eq!(manager.get_id(&strings[1_999]).unwrap(), Some(ids[1_999]));
+        assert_eq!(manager.get_string(ids[1]).unwrap(), strings[1]);
+        let id = manager.get_or_insert("new").unwrap();
+        assert_eq!(manager.get_string(id).unwrap(), "new");
+    }
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+fn open_with_wal(db_folder: &Path) -> Arc<BufferManager> {
+    Arc::new(BufferManager::with_wal(db_folder, 24, 1, 1, WalSyncPolicy::Commit, DEFAULT_CHECKPOINT_SIZE).unwrap())
+}
+
+// Transactions of the crash tests: each one adds a string and the triples having it as
+// subject, the last one also deletes a triple. The log is checkpointed after the third.
+// `committed` counts the transactions whose commit returned.
+const WAL_TRANSACTIONS: u64 = 5;
+
+fn run_transactions(
+    buffer: &BufferManager,
+    indexes: &mut QuadIndexes,
+    strings: &StringManager,
+    committed: &mut u64,
+) -> Result<(), StorageError> {
+    for i in 0..WAL_TRANSACTIONS {
+        let id = strings.get_or_insert(&format!("<http://example.org/term/{}>", i))?;
+        for j in 0..40 {
+            indexes.insert_triple(&[id, i, j])?;
+        }
+        if i == WAL_TRANSACTIONS - 1 {
+            let first = strings.get_id("<http://example.org/term/0>")?.unwrap();
+            indexes.delete_triple(&[first, 0, 0])?;
+        }
+        buffer.commit()?;
+        *committed += 1;
+        if i == 2 {
+            buffer.checkpoint()?;
+        }
+    }
+    Ok(())
+}
+
+// Triples after the first `committed` transactions
+fn expected_triples(strings: &StringManager, committed: u64) -> BTreeSet<[u64; 3]> {
+    let mut triples = BTreeSet::new();
+    for i in 0..committed {
+        let id = strings.get_id(&format!("<http://example.org/term/{}>", i)).unwrap().unwrap();
+        triples.extend((0..40).map(|j| [id, i, j]));
+    }
+    if committed == WAL_TRANSACTIONS {
+        let first = strings.get_id("<http://example.org/term/0>").unwrap().unwrap();
+        triples.remove(&[first, 0, 0]);
+    }
+    triples
+}
+
+// Opens the folder after a crash and checks it holds exactly the committed transactions
+fn check_recovered(db_folder: &Path, committed: u64) {
+    let buffer = open_with_wal(db_folder);
+    let indexes = QuadIndexes::open(&buffer).unwrap();
+    let strings = StringManager::open(&buffer, 0).unwrap();
+    assert_eq!(strings.len(), committed);
+    let expected = expected_triples(&strings, committed);
+    assert_eq!(indexes.triple_count(), expected.len() as u64);
+    let triples: BTreeSet<[u64; 3]> = indexes.scan_triples(&[None, None, None]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(triples, expected);
+    // The other permutations agree
+    for triple in &expected {
+        let by_object: Vec<_> = indexes.scan_triples(&[None, Some(triple[1]), Some(triple[2])]).unwrap().map(Result::unwrap).collect();
+        assert!(by_object.contains(triple));
+    }
+}
+
+#[test]
+fn test_wal_redo_committed_transactions() {
+    let db_folder = temp_db_folder("wal_redo");
+    {
+        let buffer = open_with_wal(&db_folder);
+        let mut indexes = QuadIndexes::create(&buffer).unwrap();
+        let strings = StringManager::open(&buffer, 0).unwrap();
+        buffer.commit().unwrap();
+        let mut committed = 0;
+        run_transactions(&buffer, &mut indexes, &strings, &mut committed).unwrap();
+
+        // Changes that are not committed are lost in the crash
+        let id = strings.get_or_insert("<http://example.org/uncommitted>").unwrap();
+        indexes.insert_triple(&[id, 0, 0]).unwrap();
+        let wal = buffer.wal().unwrap();
+        assert!(wal.size() > WAL_FILE_MAGIC.len() as u64);
+        wal.simulate_crash_after(wal.record_count());
+        assert!(buffer.commit().is_err());
+    }
+    {
+        let buffer = open_with_wal(&db_folder);
+        // The two transactions after the checkpoint
+        assert_eq!(buffer.wal().unwrap().recovered_transactions(), 2);
+        assert_eq!(fs::metadata(db_folder.join(WAL_FILE)).unwrap().len(), WAL_FILE_MAGIC.len() as u64);
+        let strings = StringManager::open(&buffer, 0).unwrap();
+        assert_eq!(strings.get_id("<http://example.org/uncommitted>").unwrap(), None);
+    }
+    check_recovered(&db_folder, WAL_TRANSACTIONS);
+
+    // A clean shutdown leaves nothing to redo
+    let buffer = open_with_wal(&db_folder);
+    assert_eq!(buffer.wal().unwrap().recovered_transactions(), 0);
+    drop(buffer);
+
+    // A torn record at the end of the log is ignored
+    let mut log = fs::read(db_folder.join(WAL_FILE)).unwrap();
+    log.extend_from_slice(&[100, 0, 0, 0, 1, 2, 3, 4, 1]);
+    fs::write(db_folder.join(WAL_FILE), log).unwrap();
+    let buffer = open_with_wal(&db_folder);
+    assert_eq!(buffer.wal().unwrap().recovered_transactions(), 0);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_wal_checkpoint_keeps_committed_pages() {
+    let db_folder = temp_db_folder("wal_checkpoint_uncommitted");
+    {
+        let buffer = open_with_wal(&db_folder);
+        let file_id = buffer.get_file_id("pages.dat").unwrap();
+        let page = buffer.append_page(file_id).unwrap();
+        page.write()[0] = 1;
+        page.unpin();
+        buffer.commit().unwrap();
+        // Changed again but not committed when the log is emptied by the shutdown
+        let page = buffer.pin(PageId::new(file_id, 0)).unwrap();
+        page.write()[0] = 2;
+        page.unpin();
+    }
+    let buffer = open_with_wal(&db_folder);
+    assert_eq!(buffer.wal().unwrap().recovered_transactions(), 0);
+    let file_id = buffer.get_file_id("pages.dat").unwrap();
+    assert_eq!(buffer.file_manager().count_pages(file_id).unwrap(), 1);
+    assert_eq!(buffer.pin(PageId::new(file_id, 0)).unwrap().read()[0], 1);
+    drop(buffer);
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_wal_crash_at_every_record() {
+    let db_folder = temp_db_folder("wal_crash");
+
+    // Records written by the transactions when nothing fails
+    let total_records = {
+        let buffer = open_with_wal(&db_folder);
+        let mut indexes = QuadIndexes::create(&buffer).unwrap();
+        let strings = StringManager::open(&buffer, 0).unwrap();
+        buffer.commit().unwrap();
+        let start = buffer.wal().unwrap().record_count();
+        run_transactions(&buffer, &mut indexes, &strings, &mut 0).unwrap();
+        buffer.wal().unwrap().record_count() - start
+    };
+    assert!(total_records > 2 * WAL_TRANSACTIONS);
+
+    for crash_point in 0..=total_records {
+        fs::remove_dir_all(&db_folder).unwrap();
+        fs::create_dir_all(&db_folder).unwrap();
+        let mut committed = 0;
+        {
+            let buffer = open_with_wal(&db_folder);
+            let mut indexes = QuadIndexes::create(&buffer).unwrap();
+            let strings = StringManager::open(&buffer, 0).unwrap();
+            buffer.commit().unwrap();
+            let wal = buffer.wal().unwrap();
+            wal.simulate_crash_after(wal.record_count() + crash_point);
+            let result = run_transactions(&buffer, &mut indexes, &strings, &mut committed);
+            assert_eq!(result.is_ok(), crash_point == total_records, "crash at record {}", crash_point);
+        }
+        check_recovered(&db_folder, committed);
+    }
+    fs::remove_dir_all(&db_folder).unwrap();
+}
+
+#[test]
+fn test_catalog() {
+    let db_folder = temp_db_folder("catalog");
+    let error = Catalog::load(&db_folder).unwrap_err();
+    assert!(error.to_string().contains("does not contain a database"), "{}", error);
+
+    let mut catalog = Catalog::new(DataModel::Rdf);
+    catalog.indexes = vec!["spo.bpt".to_string(), "pos.bpt".to_string()];
+    catalog.counts.insert("default_graph_triples".to_string(), 42);
+    catalog.prefixes = IriPrefixes::new(["http://www.wikidata.org/entity/".to_string()]);
+    catalog.parameters.insert("memory_budget".to_string(), "1024".to_string());
+    catalog.save(&db_folder).unwrap();
+    let loaded = Catalog::load(&db_folder).unwrap();
+    assert_eq!(loaded, catalog);
+    assert_eq!(loaded.format_version, FORMAT_VERSION);
+    assert_eq!(loaded.count("default_graph_triples"), 42);
+    assert_eq!(loaded.count("missing"), 0);
+    assert!(!db_folder.join(format!("{}.tmp", CATALOG_FILE)).exists());
+
+    // Another version of the format is refused before reading the rest of the file
+    let mut bytes = fs::read(db_folder.join(CATALOG_FILE)).unwrap();
+    bytes[CATALOG_FILE_MAGIC.len()..CATALOG_FILE_MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    fs::write(db_folder.join(CATALOG_FILE), &bytes).unwrap();
+    let error = Catalog::load(&db_folder).unwrap_err();
+    assert!(error.to_string().contains(&format!("uses version {} of the format", FORMAT_VERSION + 1)), "{}", error);
+
+    fs::write(db_folder.join(CATALOG_FILE), b"not a catalog").unwrap();
+    assert!(Catalog::load(&db_folder).unwrap_err().to_string().contains("is not a catalog"));
+    fs::remove_dir_all(&db_folder).unwrap();
+}